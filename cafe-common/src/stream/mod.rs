@@ -15,7 +15,7 @@ fn calculate_position(position: usize, length: usize, loc: SeekOrigin, offset: i
                 return Err(SeekError::BeforeBegin)
             };
 
-            if new_position >= length {
+            if new_position > length {
                 return Err(SeekError::AfterEnd)
             }
 
@@ -33,7 +33,7 @@ fn calculate_position(position: usize, length: usize, loc: SeekOrigin, offset: i
                 position - u_offset
             };
 
-            if new_position >= length {
+            if new_position > length {
                 return Err(SeekError::AfterEnd)
             }
 
@@ -62,6 +62,13 @@ mod tests {
     #[test]
     fn seek_begin() {
         assert_eq!(calculate_position(0, 3, SeekOrigin::Begin, 1).unwrap(), 1);
+        assert_eq!(calculate_position(0, 3, SeekOrigin::Begin, 3).unwrap(), 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn seek_after_end() {
+        calculate_position(0, 3, SeekOrigin::Begin, 4).unwrap();
     }
 
     #[test]
@@ -1,7 +1,10 @@
 use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
 
 #[repr(u16)]
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum QClass {
     IN = 1
 }
@@ -22,3 +25,39 @@ impl TryFrom<u16> for QClass {
         }
     }
 }
+
+impl fmt::Display for QClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", class_name(*self as u16))
+    }
+}
+
+impl FromStr for QClass {
+    type Err = ();
+
+    /// Parses the mnemonic or CLASS followed by the code, case-insensitively.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        QClass::try_from(class_code(s).ok_or(())?)
+    }
+}
+
+/// Mnemonics of the record classes.
+const CLASS_NAMES: &[(u16, &str)] = &[(1, "IN"), (3, "CH"), (4, "HS"), (254, "NONE"), (255, "ANY")];
+
+/// Mnemonic of the record class, CLASS followed by the code for classes without one (RFC 3597).
+pub(crate) fn class_name(code: u16) -> String {
+    match CLASS_NAMES.iter().find(|(known, _)| *known == code) {
+        Some((_, name)) => name.to_string(),
+        None => format!("CLASS{}", code)
+    }
+}
+
+/// Code of the record class given by its mnemonic or as CLASS followed by the code, case-insensitively.
+pub(crate) fn class_code(name: &str) -> Option<u16> {
+    let name = name.to_ascii_uppercase();
+    if let Some((code, _)) = CLASS_NAMES.iter().find(|(_, known)| *known == name) {
+        return Some(*code);
+    }
+
+    name.strip_prefix("CLASS")?.parse().ok()
+}
@@ -0,0 +1,290 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use data_encoding::BASE64;
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{EcdsaKeyPair, EcdsaSigningAlgorithm, Ed25519KeyPair, KeyPair};
+use ring::signature::{ECDSA_P256_SHA256_FIXED_SIGNING, ECDSA_P384_SHA384_FIXED_SIGNING};
+
+use super::{Algorithm, DNSKEY_PROTOCOL, SECURE_ENTRY_POINT_FLAG, ZONE_KEY_FLAG};
+use crate::{absolute_name, QClass, ResourceRecord, Type};
+
+enum Pair {
+    Ed25519(Ed25519KeyPair),
+    Ecdsa(EcdsaKeyPair)
+}
+
+/// Key pair of a zone signing its RRsets, either a zone signing key or a key signing key,
+/// which carries the SEP flag. Keys are stored as BIND `dnssec-keygen` does: the DNSKEY record
+/// in `K<zone>.+<algorithm>+<key tag>.key` and the private key in the `.private` file.
+pub struct SigningKey {
+    zone: String,
+    algorithm: Algorithm,
+    dnskey: Type,
+    pair: Pair,
+    /// The Ed25519 seed or the ECDSA private scalar.
+    private_key: Vec<u8>
+}
+
+impl SigningKey {
+    /// Generates a key of `zone`, a key signing key if `ksk`. Only Ed25519 and ECDSA keys are supported.
+    pub fn generate(zone: &str, algorithm: Algorithm, ksk: bool) -> Result<SigningKey, KeyError> {
+        let rng = SystemRandom::new();
+        let (private_key, public_key) = match algorithm {
+            Algorithm::Ed25519 => {
+                let mut seed = vec![0; 32];
+                rng.fill(&mut seed).map_err(|_| KeyError::InvalidKey)?;
+                let pair = Ed25519KeyPair::from_seed_unchecked(&seed).map_err(|_| KeyError::InvalidKey)?;
+                (seed, pair.public_key().as_ref().to_vec())
+            },
+            Algorithm::EcdsaP256Sha256 | Algorithm::EcdsaP384Sha384 => {
+                let signing = ecdsa_signing(algorithm);
+                let pkcs8 = EcdsaKeyPair::generate_pkcs8(signing, &rng).map_err(|_| KeyError::InvalidKey)?;
+                let pair = EcdsaKeyPair::from_pkcs8(signing, pkcs8.as_ref(), &rng).map_err(|_| KeyError::InvalidKey)?;
+                let scalar = ecdsa_scalar(pkcs8.as_ref()).ok_or(KeyError::InvalidKey)?;
+                (scalar, pair.public_key().as_ref()[1 ..].to_vec())
+            },
+            Algorithm::RsaSha256 => return Err(KeyError::UnsupportedAlgorithm)
+        };
+
+        let flags = match ksk {
+            true => ZONE_KEY_FLAG | SECURE_ENTRY_POINT_FLAG,
+            false => ZONE_KEY_FLAG
+        };
+
+        SigningKey::from_private_key(zone, algorithm, flags, &private_key, &public_key)
+    }
+
+    /// Key made of `private_key` as BIND stores it and `public_key` as DNSKEY records carry it,
+    /// which must match.
+    pub fn from_private_key(
+        zone: &str,
+        algorithm: Algorithm,
+        flags: u16,
+        private_key: &[u8],
+        public_key: &[u8]
+    ) -> Result<SigningKey, KeyError> {
+        let pair = match algorithm {
+            Algorithm::Ed25519 => {
+                let pair = Ed25519KeyPair::from_seed_and_public_key(private_key, public_key);
+                Pair::Ed25519(pair.map_err(|_| KeyError::InvalidKey)?)
+            },
+            Algorithm::EcdsaP256Sha256 | Algorithm::EcdsaP384Sha384 => {
+                // The uncompressed point is prefixed with 0x04 unlike in DNSKEY records (RFC 6605, section 4).
+                let mut point = vec![0x04];
+                point.extend_from_slice(public_key);
+                let pair = EcdsaKeyPair::from_private_key_and_public_key(
+                    ecdsa_signing(algorithm),
+                    private_key,
+                    &point,
+                    &SystemRandom::new()
+                );
+
+                Pair::Ecdsa(pair.map_err(|_| KeyError::InvalidKey)?)
+            },
+            Algorithm::RsaSha256 => return Err(KeyError::UnsupportedAlgorithm)
+        };
+
+        Ok(SigningKey {
+            zone: zone.trim_end_matches('.').to_string(),
+            algorithm,
+            dnskey: Type::DNSKEY { flags, protocol: DNSKEY_PROTOCOL, algorithm: algorithm as u8, public_key: public_key.to_vec() },
+            pair,
+            private_key: private_key.to_vec()
+        })
+    }
+
+    pub fn zone(&self) -> &str {
+        &self.zone
+    }
+
+    pub fn dnskey(&self) -> &Type {
+        &self.dnskey
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn key_tag(&self) -> u16 {
+        self.dnskey.key_tag().unwrap_or_default()
+    }
+
+    /// Whether the key carries the SEP flag and so signs the DNSKEY RRset.
+    pub fn is_ksk(&self) -> bool {
+        match self.dnskey {
+            Type::DNSKEY { flags, .. } => flags & SECURE_ENTRY_POINT_FLAG != 0,
+            _ => false
+        }
+    }
+
+    /// DNSKEY record of the key.
+    pub fn record(&self, ttl: u32) -> ResourceRecord {
+        ResourceRecord::new(&self.zone, QClass::IN as u16, ttl, self.dnskey.clone())
+    }
+
+    pub fn sign(&self, data: &[u8]) -> Vec<u8> {
+        match &self.pair {
+            Pair::Ed25519(pair) => pair.sign(data).as_ref().to_vec(),
+            // Signing fails only if the system random generator does.
+            Pair::Ecdsa(pair) => pair
+                .sign(&SystemRandom::new(), data)
+                .map(|signature| signature.as_ref().to_vec())
+                .unwrap_or_default()
+        }
+    }
+
+    /// Name of the key files without the extension, e.g. `Kjabber.ru.+013+01234`.
+    pub fn file_name(&self) -> String {
+        format!("K{}+{:03}+{:05}", absolute_name(&self.zone), self.algorithm() as u8, self.key_tag())
+    }
+
+    /// Contents of the `.key` file.
+    pub fn to_bind_public(&self) -> String {
+        let kind = match self.is_ksk() {
+            true => "key-signing",
+            false => "zone-signing"
+        };
+
+        format!(
+            "; This is a {} key, keyid {}, for {}\n{} IN DNSKEY {}\n",
+            kind, self.key_tag(), absolute_name(&self.zone), absolute_name(&self.zone), self.dnskey
+        )
+    }
+
+    /// Contents of the `.private` file.
+    pub fn to_bind_private(&self) -> String {
+        let algorithm = self.algorithm;
+        format!(
+            "Private-key-format: v1.3\nAlgorithm: {} ({})\nPrivateKey: {}\n",
+            algorithm as u8, algorithm.name(), BASE64.encode(&self.private_key)
+        )
+    }
+
+    /// Key made of the contents of its `.key` and `.private` files.
+    pub fn parse_bind(public: &str, private: &str) -> Result<SigningKey, KeyError> {
+        let fields: Vec<&str> = public
+            .lines()
+            .map(|line| line.split(';').next().unwrap_or_default())
+            .flat_map(str::split_whitespace)
+            .collect();
+
+        let position = fields.iter().position(|field| field.eq_ignore_ascii_case("DNSKEY")).ok_or(KeyError::Syntax)?;
+        let (zone, dnskey) = match (fields.first(), Type::parse(48, &fields[position + 1 ..], None)) {
+            (Some(zone), Some(dnskey)) if position > 0 => (zone, dnskey),
+            _ => return Err(KeyError::Syntax)
+        };
+
+        let (flags, algorithm, public_key) = match &dnskey {
+            Type::DNSKEY { flags, algorithm, public_key, .. } => (*flags, *algorithm, public_key),
+            _ => return Err(KeyError::Syntax)
+        };
+
+        let mut private_key = None;
+        let mut private_algorithm = None;
+        for line in private.lines() {
+            let (field, value) = match line.find(':') {
+                Some(colon) => (line[.. colon].trim(), line[colon + 1 ..].trim()),
+                None => continue
+            };
+
+            match field {
+                "Algorithm" => {
+                    let code = value.split_whitespace().next().and_then(|code| code.parse::<u8>().ok());
+                    private_algorithm = Some(code.ok_or(KeyError::Syntax)?);
+                },
+                "PrivateKey" => {
+                    private_key = Some(BASE64.decode(value.as_bytes()).map_err(|_| KeyError::InvalidKey)?);
+                },
+                _ => ()
+            }
+        }
+
+        let private_key = private_key.ok_or(KeyError::Syntax)?;
+        if private_algorithm != Some(algorithm) {
+            return Err(KeyError::InvalidKey);
+        }
+
+        let algorithm = Algorithm::try_from(algorithm).map_err(|_| KeyError::UnsupportedAlgorithm)?;
+        SigningKey::from_private_key(zone, algorithm, flags, &private_key, public_key)
+    }
+
+    /// Loads the key from its files, `path` may point to either of them or omit the extension.
+    pub fn load_bind<P: AsRef<Path>>(path: P) -> Result<SigningKey, KeyError> {
+        let path = path.as_ref();
+        let path = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("key") | Some("private") => path.with_extension(""),
+            _ => path.to_path_buf()
+        };
+
+        let public = fs::read_to_string(with_suffix(&path, ".key")).map_err(KeyError::Io)?;
+        let private = fs::read_to_string(with_suffix(&path, ".private")).map_err(KeyError::Io)?;
+        SigningKey::parse_bind(&public, &private)
+    }
+
+    /// Writes both key files into `directory`, returns the path of the `.key` one.
+    pub fn save_bind<P: AsRef<Path>>(&self, directory: P) -> Result<PathBuf, KeyError> {
+        let path = directory.as_ref().join(self.file_name());
+        fs::write(with_suffix(&path, ".private"), self.to_bind_private()).map_err(KeyError::Io)?;
+
+        let public = with_suffix(&path, ".key");
+        fs::write(&public, self.to_bind_public()).map_err(KeyError::Io)?;
+        Ok(public)
+    }
+}
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigningKey")
+            .field("zone", &self.zone)
+            .field("dnskey", &self.dnskey)
+            .finish()
+    }
+}
+
+/// Appends `suffix` to the file name, which may contain dots of its own.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_os_string();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+fn ecdsa_signing(algorithm: Algorithm) -> &'static EcdsaSigningAlgorithm {
+    match algorithm {
+        Algorithm::EcdsaP384Sha384 => &ECDSA_P384_SHA384_FIXED_SIGNING,
+        _ => &ECDSA_P256_SHA256_FIXED_SIGNING
+    }
+}
+
+/// The private scalar of the ECPrivateKey (RFC 5915) wrapped into PKCS#8: the octet string
+/// following its version 1.
+fn ecdsa_scalar(pkcs8: &[u8]) -> Option<Vec<u8>> {
+    let start = pkcs8.windows(4).position(|window| window[.. 3] == [0x02, 0x01, 0x01] && window[3] == 0x04)?;
+    let length = *pkcs8.get(start + 4)? as usize;
+    pkcs8.get(start + 5 .. start + 5 + length).map(<[u8]>::to_vec)
+}
+
+#[derive(Debug)]
+/// Failure to generate or load a signing key.
+pub enum KeyError {
+    Io(io::Error),
+    /// Key files are malformed.
+    Syntax,
+    UnsupportedAlgorithm,
+    /// The private key is malformed or doesn't match the public one.
+    InvalidKey
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::Io(err) => write!(f, "{}", err),
+            KeyError::Syntax => write!(f, "malformed key file"),
+            KeyError::UnsupportedAlgorithm => write!(f, "unsupported algorithm"),
+            KeyError::InvalidKey => write!(f, "invalid key")
+        }
+    }
+}
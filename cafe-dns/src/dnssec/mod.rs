@@ -0,0 +1,276 @@
+//! Helpers shared by the DNSSEC record types (RFC 4034, RFC 5155) and their validation.
+
+mod key;
+mod signature;
+mod signer;
+
+pub use self::key::{KeyError, SigningKey};
+pub use self::signature::{label_count, signed_data, verify, Algorithm};
+pub use self::signer::{Denial, SignError, ZoneSigner};
+
+use std::cmp::Ordering;
+
+use ring::digest;
+
+use crate::Type;
+
+/// DNSKEY flag of keys used to sign zone data.
+pub const ZONE_KEY_FLAG: u16 = 0x0100;
+/// DNSKEY flag of key signing keys (RFC 3757).
+pub const SECURE_ENTRY_POINT_FLAG: u16 = 0x0001;
+/// The only protocol value DNSKEY records may carry.
+pub const DNSKEY_PROTOCOL: u8 = 3;
+
+/// RSA/MD5, the only algorithm whose key tag is not a checksum of RDATA.
+const RSAMD5: u8 = 1;
+
+/// Key tag of DNSKEY with RDATA `rdata` (RFC 4034, appendix B).
+pub fn key_tag(rdata: &[u8]) -> u16 {
+    if rdata.len() > 3 && rdata[3] == RSAMD5 {
+        // The most significant 16 of the least significant 24 bits of the modulus.
+        let length = rdata.len();
+        if length < 7 {
+            return 0;
+        }
+
+        return u16::from_be_bytes([rdata[length - 3], rdata[length - 2]]);
+    }
+
+    let mut accumulator: u32 = 0;
+    for (i, byte) in rdata.iter().enumerate() {
+        accumulator += match i & 1 {
+            0 => (*byte as u32) << 8,
+            _ => *byte as u32,
+        };
+    }
+
+    accumulator += (accumulator >> 16) & 0xFFFF;
+    (accumulator & 0xFFFF) as u16
+}
+
+/// Digest of DNSKEY owned by `owner` as in DS records (RFC 4034, section 5.1.4),
+/// `None` for unsupported digest types.
+pub fn ds_digest(owner: &str, dnskey: &Type, digest_type: u8) -> Option<Vec<u8>> {
+    let algorithm = match digest_type {
+        1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        2 => &digest::SHA256,
+        4 => &digest::SHA384,
+        _ => return None
+    };
+
+    dnskey.key_tag()?;
+
+    let mut data = canonical_name(owner);
+    data.extend_from_slice(&dnskey.rdata());
+    Some(digest::digest(algorithm, &data).as_ref().to_vec())
+}
+
+/// Hash of the owner name of NSEC3 records (RFC 5155, section 5), SHA-1 is the only algorithm.
+pub fn nsec3_hash(name: &str, salt: &[u8], iterations: u16) -> Vec<u8> {
+    let mut data = canonical_name(name);
+    for _ in 0 ..= iterations {
+        data.extend_from_slice(salt);
+        data = digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, &data).as_ref().to_vec();
+    }
+
+    data
+}
+
+/// Uncompressed wire format of the lowercased name.
+fn canonical_name(name: &str) -> Vec<u8> {
+    let mut result = crate::encode_qname(&name.to_ascii_lowercase());
+    result.push(0);
+    result
+}
+
+/// Canonical order of names (RFC 4034, section 6.1): by labels from the rightmost one,
+/// each compared as lowercased octets.
+pub fn compare_names(left: &str, right: &str) -> Ordering {
+    let left = left.to_ascii_lowercase();
+    let right = right.to_ascii_lowercase();
+    let left = left.split('.').rev().filter(|label| !label.is_empty());
+    let right = right.split('.').rev().filter(|label| !label.is_empty());
+
+    left.map(str::as_bytes).cmp(right.map(str::as_bytes))
+}
+
+/// Type bitmap of NSEC and NSEC3 records (RFC 4034, section 4.1.2), `types` must be sorted.
+pub(crate) fn encode_type_bitmap(types: &[u16]) -> Vec<u8> {
+    let mut result = Vec::new();
+    let mut window: Option<(u8, [u8; 32], usize)> = None;
+    for ttype in types {
+        let [number, low] = ttype.to_be_bytes();
+        match &mut window {
+            Some((current, _, _)) if *current == number => (),
+            _ => {
+                if let Some((current, bitmap, length)) = window.take() {
+                    append_window(&mut result, current, &bitmap[.. length]);
+                }
+
+                window = Some((number, [0; 32], 0));
+            }
+        }
+
+        if let Some((_, bitmap, length)) = &mut window {
+            bitmap[low as usize / 8] |= 0x80 >> (low % 8);
+            *length = (*length).max(low as usize / 8 + 1);
+        }
+    }
+
+    if let Some((current, bitmap, length)) = window {
+        append_window(&mut result, current, &bitmap[.. length]);
+    }
+
+    result
+}
+
+fn append_window(result: &mut Vec<u8>, number: u8, bitmap: &[u8]) {
+    result.push(number);
+    result.push(bitmap.len() as u8);
+    result.extend_from_slice(bitmap);
+}
+
+/// Types listed in the bitmap, `None` if windows are out of order or of invalid length.
+pub(crate) fn decode_type_bitmap(data: &[u8]) -> Option<Vec<u16>> {
+    let mut result = Vec::new();
+    let mut previous: Option<u8> = None;
+    let mut position = 0;
+    while position < data.len() {
+        let number = data[position];
+        let length = *data.get(position + 1)? as usize;
+        if length == 0 || length > 32 || previous.is_some_and(|previous| previous >= number) {
+            return None;
+        }
+
+        let bitmap = data.get(position + 2 .. position + 2 + length)?;
+        for (i, byte) in bitmap.iter().enumerate() {
+            for bit in 0 .. 8 {
+                if byte & (0x80 >> bit) != 0 {
+                    result.push(u16::from_be_bytes([number, (i * 8 + bit) as u8]));
+                }
+            }
+        }
+
+        previous = Some(number);
+        position += 2 + length;
+    }
+
+    Some(result)
+}
+
+/// Signature time as YYYYMMDDHHmmSS in UTC (RFC 4034, section 3.2).
+pub(crate) fn format_time(time: u32) -> String {
+    let days = (time / 86400) as i64;
+    let seconds = time % 86400;
+
+    // Civil date from the number of days since 1970-01-01, proleptic Gregorian calendar.
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year, month, day, seconds / 3600, seconds / 60 % 60, seconds % 60
+    )
+}
+
+/// Signature time given either as YYYYMMDDHHmmSS in UTC or as seconds (RFC 4034, section 3.2).
+pub(crate) fn parse_time(text: &str) -> Option<u32> {
+    if !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    if text.len() != 14 {
+        return text.parse().ok();
+    }
+
+    let field = |range: std::ops::Range<usize>| text[range].parse::<i64>().ok();
+    let (year, month, day) = (field(0 .. 4)?, field(4 .. 6)?, field(6 .. 8)?);
+    let (hours, minutes, seconds) = (field(8 .. 10)?, field(10 .. 12)?, field(12 .. 14)?);
+    if !(1 ..= 12).contains(&month) || !(1 ..= 31).contains(&day) || hours > 23 || minutes > 59 || seconds > 59 {
+        return None;
+    }
+
+    // Number of days since 1970-01-01 of the civil date, the inverse of `format_time`.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * mp + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    // Serial number arithmetic: times past 2106 wrap around.
+    Some((days * 86400 + hours * 3600 + minutes * 60 + seconds) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn type_bitmap() {
+        // RFC 4034, section 4.3: A MX RRSIG NSEC TYPE1234
+        let bitmap = [
+            0x00, 0x06, 0x40, 0x01, 0x00, 0x00, 0x00, 0x03,
+            0x04, 0x1b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x20
+        ];
+
+        assert_eq!(decode_type_bitmap(&bitmap).unwrap(), [1, 15, 46, 47, 1234]);
+        assert_eq!(encode_type_bitmap(&[1, 15, 46, 47, 1234]), bitmap);
+        assert_eq!(encode_type_bitmap(&[]), []);
+    }
+
+    #[test]
+    fn invalid_type_bitmap() {
+        assert!(decode_type_bitmap(&[0x00, 0x00]).is_none());
+        assert!(decode_type_bitmap(&[0x00, 0x01]).is_none());
+        assert!(decode_type_bitmap(&[0x01, 0x01, 0x40, 0x00, 0x01, 0x40]).is_none());
+    }
+
+    #[test]
+    fn canonical_order() {
+        // RFC 4034, section 6.1
+        let names = ["example", "a.example", "yljkjljk.a.example", "Z.a.example", "zABC.a.EXAMPLE", "z.example"];
+        for pair in names.windows(2) {
+            assert_eq!(compare_names(pair[0], pair[1]), Ordering::Less, "{} < {}", pair[0], pair[1]);
+        }
+
+        assert_eq!(compare_names("Example.COM", "example.com"), Ordering::Equal);
+        assert_eq!(compare_names("", "com"), Ordering::Less);
+    }
+
+    #[test]
+    fn nsec3_hashes() {
+        // RFC 5155, appendix A
+        let salt = [0xaa, 0xbb, 0xcc, 0xdd];
+        let hash = |name| data_encoding::BASE32HEX_NOPAD.encode(&nsec3_hash(name, &salt, 12)).to_ascii_lowercase();
+
+        assert_eq!(hash("example"), "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom");
+        assert_eq!(hash("a.example"), "35mthgpgcu1qg68fab165klnsnk3dpvl");
+        assert_eq!(hash("*.w.example"), "r53bq7cc2uvmubfu5ocmm6pers9tk9en");
+    }
+
+    #[test]
+    fn signature_time() {
+        assert_eq!(format_time(0), "19700101000000");
+        assert_eq!(format_time(1_600_000_000), "20200913122640");
+        assert_eq!(format_time(u32::MAX), "21060207062815");
+
+        assert_eq!(parse_time("20200913122640"), Some(1_600_000_000));
+        assert_eq!(parse_time("21060207062815"), Some(u32::MAX));
+        assert_eq!(parse_time("19700101000000"), Some(0));
+        assert_eq!(parse_time("1600000000"), Some(1_600_000_000));
+        assert_eq!(parse_time("20201313122640"), None);
+        assert_eq!(parse_time("2020-09-13"), None);
+    }
+}
@@ -0,0 +1,137 @@
+use std::convert::TryFrom;
+
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+
+use crate::{ResourceRecord, Type};
+
+/// DNSSEC algorithms signatures can be verified with (RFC 8624).
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Algorithm {
+    RsaSha256 = 8,
+    EcdsaP256Sha256 = 13,
+    EcdsaP384Sha384 = 14,
+    Ed25519 = 15
+}
+
+impl Algorithm {
+    /// Mnemonic of the algorithm in the IANA "DNS Security Algorithm Numbers" registry.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::RsaSha256 => "RSASHA256",
+            Algorithm::EcdsaP256Sha256 => "ECDSAP256SHA256",
+            Algorithm::EcdsaP384Sha384 => "ECDSAP384SHA384",
+            Algorithm::Ed25519 => "ED25519"
+        }
+    }
+}
+
+impl TryFrom<u8> for Algorithm {
+    type Error = ();
+
+    fn try_from(v: u8) -> Result<Self, Self::Error> {
+        match v {
+            x if x == Algorithm::RsaSha256 as u8 => Ok(Algorithm::RsaSha256),
+            x if x == Algorithm::EcdsaP256Sha256 as u8 => Ok(Algorithm::EcdsaP256Sha256),
+            x if x == Algorithm::EcdsaP384Sha384 as u8 => Ok(Algorithm::EcdsaP384Sha384),
+            x if x == Algorithm::Ed25519 as u8 => Ok(Algorithm::Ed25519),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Number of labels of the name, the root label excluded.
+pub fn label_count(name: &str) -> usize {
+    name.split('.').filter(|label| !label.is_empty()).count()
+}
+
+/// Data covered by the signature `rrsig` over `rrset` (RFC 4034, section 3.1.8.1): RRSIG RDATA
+/// without the signature followed by the records in canonical form and order. Owner names
+/// expanded from a wildcard are restored to it. `None` if the records do not match the RRSIG.
+pub fn signed_data(rrsig: &Type, rrset: &[ResourceRecord]) -> Option<Vec<u8>> {
+    let (type_covered, labels, original_ttl) = match rrsig {
+        Type::RRSIG { type_covered, labels, original_ttl, .. } => (*type_covered, *labels as usize, *original_ttl),
+        _ => return None
+    };
+
+    let mut data = rrsig.canonical().rrsig_data()?;
+    let mut records = Vec::with_capacity(rrset.len());
+    for record in rrset {
+        if record.ttype().code() != type_covered {
+            return None;
+        }
+
+        let mut record = record.canonical(original_ttl);
+        let count = label_count(record.name());
+        if count < labels {
+            return None;
+        }
+
+        if count > labels {
+            let suffix: Vec<&str> = record.name().split('.').skip(count - labels).collect();
+            let name = match labels {
+                0 => "*".to_string(),
+                _ => format!("*.{}", suffix.join("."))
+            };
+
+            record = ResourceRecord::new(&name, record.class(), original_ttl, record.ttype().clone());
+        }
+
+        records.push((record.ttype().rdata(), record));
+    }
+
+    records.sort_by(|a, b| a.0.cmp(&b.0));
+    records.dedup_by(|a, b| a.0 == b.0);
+    for (_, record) in records {
+        record.encode(&mut cafe_common::stream::Output::new(&mut data));
+    }
+
+    Some(data)
+}
+
+/// Verifies `signature` of `data` made with the private part of `dnskey`.
+/// Keys of unsupported algorithms never verify.
+pub fn verify(dnskey: &Type, data: &[u8], signature: &[u8]) -> bool {
+    let (algorithm, public_key) = match dnskey {
+        Type::DNSKEY { algorithm, public_key, .. } => (*algorithm, public_key),
+        _ => return false
+    };
+
+    match Algorithm::try_from(algorithm) {
+        Ok(Algorithm::RsaSha256) => {
+            // RFC 3110, section 2: exponent length, exponent and modulus.
+            let (exponent_length, offset) = match public_key.first() {
+                Some(0) if public_key.len() > 3 => (u16::from_be_bytes([public_key[1], public_key[2]]) as usize, 3),
+                Some(length) => (*length as usize, 1),
+                None => return false
+            };
+
+            if public_key.len() <= offset + exponent_length {
+                return false;
+            }
+
+            let components = RsaPublicKeyComponents {
+                e: &public_key[offset .. offset + exponent_length],
+                n: &public_key[offset + exponent_length ..]
+            };
+
+            components.verify(&signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY, data, signature).is_ok()
+        },
+        Ok(Algorithm::EcdsaP256Sha256) | Ok(Algorithm::EcdsaP384Sha384) => {
+            // The key is made of both point coordinates, uncompressed form adds the prefix.
+            let mut point = vec![0x04];
+            point.extend_from_slice(public_key);
+
+            let parameters = match algorithm == Algorithm::EcdsaP256Sha256 as u8 {
+                true => &signature::ECDSA_P256_SHA256_FIXED,
+                false => &signature::ECDSA_P384_SHA384_FIXED
+            };
+
+            UnparsedPublicKey::new(parameters, point).verify(data, signature).is_ok()
+        },
+        Ok(Algorithm::Ed25519) => {
+            UnparsedPublicKey::new(&signature::ED25519, public_key).verify(data, signature).is_ok()
+        },
+        Err(_) => false
+    }
+}
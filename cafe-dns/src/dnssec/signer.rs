@@ -0,0 +1,324 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use data_encoding::BASE32HEX_NOPAD;
+
+use super::{compare_names, label_count, nsec3_hash, signed_data, SigningKey};
+use crate::rrset::{self, RRset};
+use crate::{ResourceRecord, Type};
+
+const NS_TYPE: u16 = 2;
+const SOA_TYPE: u16 = 6;
+const DS_TYPE: u16 = 43;
+const RRSIG_TYPE: u16 = 46;
+const NSEC_TYPE: u16 = 47;
+const DNSKEY_TYPE: u16 = 48;
+const NSEC3_TYPE: u16 = 50;
+const NSEC3PARAM_TYPE: u16 = 51;
+
+/// SHA-1, the only NSEC3 hash algorithm.
+const NSEC3_SHA1: u8 = 1;
+/// NSEC3 flag of spans that may contain unsigned delegations (RFC 5155, section 3.1.2.1).
+const OPT_OUT_FLAG: u8 = 0x01;
+
+/// Signatures are valid for 30 days by default.
+const DEFAULT_VALIDITY: u32 = 30 * 86400;
+/// Inception is an hour in the past, so validators with clocks behind accept the signatures.
+const INCEPTION_OFFSET: u32 = 3600;
+
+/// How the signed zone proves names and types don't exist.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Denial {
+    /// NSEC records chaining the owner names (RFC 4034, section 4).
+    Nsec,
+    /// NSEC3 records chaining hashes of the owner names (RFC 5155). With `opt_out`, delegations
+    /// without DS records are left out of the chain.
+    Nsec3 {
+        iterations: u16,
+        salt: Vec<u8>,
+        opt_out: bool
+    }
+}
+
+/// Signs zones with the given keys: the DNSKEY RRset with the key signing keys, the rest
+/// with the zone signing keys. A single key of either kind signs everything.
+pub struct ZoneSigner {
+    keys: Vec<SigningKey>,
+    denial: Denial,
+    /// Validity period of signatures in seconds since the epoch.
+    inception: u32,
+    expiration: u32
+}
+
+impl ZoneSigner {
+    /// Signer proving denial with NSEC, its signatures are valid for 30 days.
+    pub fn new(keys: Vec<SigningKey>) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as u32)
+            .unwrap_or_default();
+
+        Self {
+            keys,
+            denial: Denial::Nsec,
+            inception: now.wrapping_sub(INCEPTION_OFFSET),
+            expiration: now.wrapping_add(DEFAULT_VALIDITY)
+        }
+    }
+
+    pub fn keys(&self) -> &[SigningKey] {
+        &self.keys
+    }
+
+    pub fn denial(&self) -> &Denial {
+        &self.denial
+    }
+
+    pub fn set_denial(&mut self, denial: Denial) {
+        self.denial = denial
+    }
+
+    pub fn inception(&self) -> u32 {
+        self.inception
+    }
+
+    pub fn expiration(&self) -> u32 {
+        self.expiration
+    }
+
+    pub fn set_validity(&mut self, inception: u32, expiration: u32) {
+        self.inception = inception;
+        self.expiration = expiration;
+    }
+
+    /// Signs the zone made of `records`, the apex is the owner of its SOA record. DNSKEY records
+    /// of the keys are added, RRSIG, NSEC and NSEC3 records present are replaced. The signed zone
+    /// is returned in the canonical order, SOA first, with signatures following the RRsets they cover.
+    /// Delegations are not signed, glue records and records occluded by delegations are left as is.
+    pub fn sign(&self, records: &[ResourceRecord]) -> Result<Vec<ResourceRecord>, SignError> {
+        let soa = records.iter().find(|record| record.ttype().code() == SOA_TYPE).ok_or(SignError::NoSoa)?;
+        let zone = soa.name().to_ascii_lowercase();
+        if let Some(record) = records.iter().find(|record| !is_subdomain(record.name(), &zone)) {
+            return Err(SignError::OutOfZone(record.name().to_string()));
+        }
+
+        let keys: Vec<&SigningKey> = self.keys.iter().filter(|key| key.zone().eq_ignore_ascii_case(&zone)).collect();
+        if keys.is_empty() {
+            return Err(SignError::NoKeys);
+        }
+
+        // RFC 9077: denial records live no longer than the negative answers they prove.
+        let denial_ttl = match soa.ttype() {
+            Type::SOA { minimum, .. } => soa.ttl().min(*minimum),
+            _ => soa.ttl()
+        };
+
+        let mut zone_records: Vec<ResourceRecord> = records
+            .iter()
+            .filter(|record| ![RRSIG_TYPE, NSEC_TYPE, NSEC3_TYPE, NSEC3PARAM_TYPE].contains(&record.ttype().code()))
+            .cloned()
+            .collect();
+
+        let dnskey_ttl = records
+            .iter()
+            .find(|record| record.ttype().code() == DNSKEY_TYPE)
+            .map_or(soa.ttl(), ResourceRecord::ttl);
+        for key in &keys {
+            zone_records.push(key.record(dnskey_ttl));
+        }
+
+        if let Denial::Nsec3 { iterations, salt, .. } = &self.denial {
+            let parameters = Type::NSEC3PARAM {
+                hash_algorithm: NSEC3_SHA1,
+                flags: 0,
+                iterations: *iterations,
+                salt: salt.clone()
+            };
+
+            zone_records.push(ResourceRecord::new(&zone, soa.class(), 0, parameters));
+        }
+
+        let sets = rrset::group(&zone_records);
+        let cuts: Vec<&str> = sets
+            .iter()
+            .filter(|set| set.code() == NS_TYPE && !set.name().eq_ignore_ascii_case(&zone))
+            .map(RRset::name)
+            .collect();
+        let is_glue = |name: &str| cuts.iter().any(|cut| is_subdomain(name, cut) && !name.eq_ignore_ascii_case(cut));
+        let is_cut = |name: &str| cuts.iter().any(|cut| name.eq_ignore_ascii_case(cut));
+
+        // Authoritative owner names in the canonical order with the types they own. At a delegation
+        // only NS and DS are, the rest is occluded by it (RFC 4035, section 2.3).
+        let mut owners: Vec<(String, Vec<u16>)> = Vec::new();
+        let is_authoritative = |set: &RRset| match is_cut(set.name()) {
+            true => set.code() == NS_TYPE || set.code() == DS_TYPE,
+            false => !is_glue(set.name())
+        };
+        for set in sets.iter().filter(|set| is_authoritative(set)) {
+            let name = set.name().to_ascii_lowercase();
+            match owners.iter_mut().find(|(owner, _)| *owner == name) {
+                Some((_, types)) => types.push(set.code()),
+                None => owners.push((name, vec![set.code()]))
+            }
+        }
+        owners.sort_by(|a, b| compare_names(&a.0, &b.0));
+
+        let mut denial_records = Vec::new();
+        match &self.denial {
+            Denial::Nsec => {
+                for (i, (owner, types)) in owners.iter().enumerate() {
+                    let mut types = types.clone();
+                    types.extend_from_slice(&[RRSIG_TYPE, NSEC_TYPE]);
+                    types.sort_unstable();
+
+                    let nsec = Type::NSEC { next_domain_name: owners[(i + 1) % owners.len()].0.clone(), types };
+                    denial_records.push(ResourceRecord::new(owner, soa.class(), denial_ttl, nsec));
+                }
+            },
+            Denial::Nsec3 { iterations, salt, opt_out } => {
+                let mut hashes: Vec<(Vec<u8>, Vec<u16>)> = Vec::new();
+                for (owner, types) in &owners {
+                    let insecure_cut = is_cut(owner) && !types.contains(&DS_TYPE);
+                    if *opt_out && insecure_cut {
+                        continue;
+                    }
+
+                    let mut types = types.clone();
+                    if !insecure_cut {
+                        types.push(RRSIG_TYPE);
+                    }
+                    types.sort_unstable();
+                    hashes.push((nsec3_hash(owner, salt, *iterations), types));
+
+                    // Empty non-terminals between the name and the apex own hashes too.
+                    let labels: Vec<&str> = owner.split('.').collect();
+                    for i in 1 .. labels.len() {
+                        let ancestor = labels[i ..].join(".");
+                        if label_count(&ancestor) <= label_count(&zone) {
+                            break;
+                        }
+
+                        if !owners.iter().any(|(owner, _)| *owner == ancestor) {
+                            hashes.push((nsec3_hash(&ancestor, salt, *iterations), Vec::new()));
+                        }
+                    }
+                }
+
+                hashes.sort();
+                hashes.dedup_by(|a, b| a.0 == b.0);
+
+                let flags = match opt_out {
+                    true => OPT_OUT_FLAG,
+                    false => 0
+                };
+
+                for (i, (hash, types)) in hashes.iter().enumerate() {
+                    let nsec3 = Type::NSEC3 {
+                        hash_algorithm: NSEC3_SHA1,
+                        flags,
+                        iterations: *iterations,
+                        salt: salt.clone(),
+                        next_hashed_owner: hashes[(i + 1) % hashes.len()].0.clone(),
+                        types: types.clone()
+                    };
+
+                    let owner = format!("{}.{}", BASE32HEX_NOPAD.encode(hash).to_ascii_lowercase(), zone);
+                    denial_records.push(ResourceRecord::new(owner.trim_end_matches('.'), soa.class(), denial_ttl, nsec3));
+                }
+            }
+        }
+
+        zone_records.extend(denial_records);
+        let mut sets = rrset::group(&zone_records);
+        sets.sort_by(RRset::canonical_cmp);
+        // Master files start with the SOA record by convention.
+        if let Some(position) = sets.iter().position(|set| set.code() == SOA_TYPE) {
+            let soa = sets.remove(position);
+            sets.insert(0, soa);
+        }
+
+        let ksks: Vec<&SigningKey> = match keys.iter().any(|key| key.is_ksk()) {
+            true => keys.iter().copied().filter(|key| key.is_ksk()).collect(),
+            false => keys.clone()
+        };
+        let zsks: Vec<&SigningKey> = match keys.iter().any(|key| !key.is_ksk()) {
+            true => keys.iter().copied().filter(|key| !key.is_ksk()).collect(),
+            false => keys.clone()
+        };
+
+        let mut result = Vec::new();
+        for set in &sets {
+            let records = set.records();
+            result.extend(records.iter().cloned());
+
+            // Only DS and NSEC records at delegations belong to the zone (RFC 4035, section 2.2).
+            let authoritative = match is_cut(set.name()) {
+                true => set.code() == DS_TYPE || set.code() == NSEC_TYPE,
+                false => !is_glue(set.name())
+            };
+            if !authoritative {
+                continue;
+            }
+
+            let signers = match set.code() {
+                DNSKEY_TYPE => &ksks,
+                _ => &zsks
+            };
+
+            for key in signers {
+                result.push(self.rrsig(key, set, &records));
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn rrsig(&self, key: &SigningKey, set: &RRset, records: &[ResourceRecord]) -> ResourceRecord {
+        let wildcard = set.name().starts_with("*.") || set.name() == "*";
+        let mut rrsig = Type::RRSIG {
+            type_covered: set.code(),
+            algorithm: key.algorithm() as u8,
+            labels: (label_count(set.name()) - wildcard as usize) as u8,
+            original_ttl: set.ttl(),
+            expiration: self.expiration,
+            inception: self.inception,
+            key_tag: key.key_tag(),
+            signer_name: key.zone().to_string(),
+            signature: Vec::new()
+        };
+
+        let data = signed_data(&rrsig, records).unwrap_or_default();
+        if let Type::RRSIG { signature, .. } = &mut rrsig {
+            *signature = key.sign(&data);
+        }
+
+        ResourceRecord::new(set.name(), set.class(), set.ttl(), rrsig)
+    }
+}
+
+/// Whether `name` is `zone` itself or a name beneath it.
+fn is_subdomain(name: &str, zone: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    zone.is_empty() || name == zone || name.ends_with(&format!(".{}", zone))
+}
+
+#[derive(Debug, PartialEq)]
+/// Failure to sign a zone.
+pub enum SignError {
+    /// The zone has no SOA record, so its apex is unknown.
+    NoSoa,
+    /// None of the keys belongs to the zone.
+    NoKeys,
+    /// A record is owned by a name outside of the zone.
+    OutOfZone(String)
+}
+
+impl fmt::Display for SignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignError::NoSoa => write!(f, "the zone has no SOA record"),
+            SignError::NoKeys => write!(f, "no keys of the zone"),
+            SignError::OutOfZone(name) => write!(f, "{} is outside of the zone", name)
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use cafe_common::BinaryWriter;
+use cafe_common::stream::Output as OutputStream;
+
+/// Address family numbers assigned by IANA.
+const FAMILY_IPV4: u16 = 1;
+const FAMILY_IPV6: u16 = 2;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// EDNS Client Subnet option data (RFC 7871):
+///                 +0 (MSB)                            +1 (LSB)
+///     +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///  0: |                            FAMILY                             |
+///     +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///  2: |     SOURCE PREFIX-LENGTH      |     SCOPE PREFIX-LENGTH       |
+///     +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///  4: |                           ADDRESS...                          /
+///     +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+pub struct ClientSubnet {
+    /// The leftmost number of significant bits of ADDRESS to be used for the lookup.
+    /// Zero asks the server not to use client's address at all.
+    source_prefix: u8,
+    /// The leftmost number of significant bits of ADDRESS that the response covers.
+    /// In queries it MUST be set to 0.
+    scope_prefix: u8,
+    /// Client's address with all the bits beyond the source prefix set to zero.
+    address: IpAddr
+}
+
+impl ClientSubnet {
+    /// Subnet of the `address` limited to the `source_prefix` leftmost bits.
+    /// Prefix longer than the address is truncated to the address length.
+    pub fn new(address: IpAddr, source_prefix: u8) -> Self {
+        let source_prefix = source_prefix.min(max_prefix(&address));
+        Self {
+            source_prefix,
+            scope_prefix: 0,
+            address: truncate(&address, source_prefix)
+        }
+    }
+
+    /// Explicitly asks the server not to reveal anything about the client's subnet
+    /// (RFC 7871, section 7.1.2).
+    pub fn opt_out() -> Self {
+        Self::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)
+    }
+
+    pub fn family(&self) -> u16 {
+        match self.address {
+            IpAddr::V4(_) => FAMILY_IPV4,
+            IpAddr::V6(_) => FAMILY_IPV6
+        }
+    }
+
+    pub fn source_prefix(&self) -> u8 {
+        self.source_prefix
+    }
+
+    pub fn scope_prefix(&self) -> u8 {
+        self.scope_prefix
+    }
+
+    pub fn set_scope_prefix(&mut self, value: u8) {
+        self.scope_prefix = value.min(max_prefix(&self.address))
+    }
+
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// Checks whether an answer carrying this option is applicable to the client with `address`,
+    /// i.e. `address` matches the subnet in the scope prefix leftmost bits.
+    pub fn matches(&self, address: &IpAddr) -> bool {
+        match (self.address, address) {
+            (IpAddr::V4(_), IpAddr::V4(_)) | (IpAddr::V6(_), IpAddr::V6(_)) => {
+                let prefix = self.scope_prefix.min(self.source_prefix);
+                truncate(&self.address, prefix) == truncate(address, prefix)
+            },
+            _ => self.scope_prefix == 0
+        }
+    }
+
+    pub fn encode(&self, stream: &mut OutputStream) {
+        let octets = address_octets(&self.address);
+        let length = prefix_octets(self.source_prefix);
+
+        let mut writer = BinaryWriter::new(stream);
+        writer.write_u16(self.family().to_be());
+        writer.write_u8(self.source_prefix);
+        writer.write_u8(self.scope_prefix);
+        stream.write(&octets, 0, length);
+    }
+
+    pub fn decode(data: &[u8]) -> Option<ClientSubnet> {
+        if data.len() < 4 {
+            return None;
+        }
+
+        let family = u16::from_be_bytes([data[0], data[1]]);
+        let source_prefix = data[2];
+        let scope_prefix = data[3];
+        let octets = &data[4 ..];
+
+        // ADDRESS must not contain any octets beyond the source prefix.
+        if octets.len() != prefix_octets(source_prefix) {
+            return None;
+        }
+
+        let address = match family {
+            FAMILY_IPV4 if octets.len() <= 4 => {
+                let mut full = [0; 4];
+                full[.. octets.len()].copy_from_slice(octets);
+                IpAddr::V4(Ipv4Addr::from(full))
+            },
+            FAMILY_IPV6 if octets.len() <= 16 => {
+                let mut full = [0; 16];
+                full[.. octets.len()].copy_from_slice(octets);
+                IpAddr::V6(Ipv6Addr::from(full))
+            },
+            _ => return None
+        };
+
+        let max = max_prefix(&address);
+        if source_prefix > max || scope_prefix > max {
+            return None;
+        }
+
+        // The bits beyond the source prefix must be zero.
+        if truncate(&address, source_prefix) != address {
+            return None;
+        }
+
+        Some(
+            ClientSubnet {
+                source_prefix,
+                scope_prefix,
+                address
+            }
+        )
+    }
+}
+
+impl FromStr for ClientSubnet {
+    type Err = ();
+
+    /// Parses `address/prefix`, a bare address means the full-length prefix.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (address, prefix) = match s.find('/') {
+            Some(i) => (&s[.. i], Some(&s[i + 1 ..])),
+            None => (s, None)
+        };
+
+        let address: IpAddr = address.parse().map_err(|_| ())?;
+        let prefix = match prefix {
+            Some(p) => p.parse::<u8>().map_err(|_| ())?,
+            None => max_prefix(&address)
+        };
+
+        if prefix > max_prefix(&address) {
+            return Err(());
+        }
+
+        Ok(ClientSubnet::new(address, prefix))
+    }
+}
+
+fn max_prefix(address: &IpAddr) -> u8 {
+    match address {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128
+    }
+}
+
+fn prefix_octets(prefix: u8) -> usize {
+    (prefix as usize).div_ceil(8)
+}
+
+fn address_octets(address: &IpAddr) -> Vec<u8> {
+    match address {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec()
+    }
+}
+
+fn truncate(address: &IpAddr, prefix: u8) -> IpAddr {
+    match address {
+        IpAddr::V4(ip) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(u32::from(*ip) & mask))
+        },
+        IpAddr::V6(ip) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(u128::from(*ip) & mask))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_address() {
+        let ip: IpAddr = "192.0.2.129".parse().unwrap();
+        assert_eq!(truncate(&ip, 32), ip);
+        assert_eq!(truncate(&ip, 25), "192.0.2.128".parse::<IpAddr>().unwrap());
+        assert_eq!(truncate(&ip, 24), "192.0.2.0".parse::<IpAddr>().unwrap());
+        assert_eq!(truncate(&ip, 0), "0.0.0.0".parse::<IpAddr>().unwrap());
+
+        let ip: IpAddr = "2001:db8:ffff::1".parse().unwrap();
+        assert_eq!(truncate(&ip, 40), "2001:db8:ff00::".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn encode_decode() {
+        let subnet: ClientSubnet = "192.0.2.77/20".parse().unwrap();
+        assert_eq!(subnet.address(), "192.0.0.0".parse::<IpAddr>().unwrap());
+
+        let mut data = Vec::new();
+        subnet.encode(&mut OutputStream::new(&mut data));
+        assert_eq!(data, [0, 1, 20, 0, 192, 0, 0]);
+        assert_eq!(ClientSubnet::decode(&data).unwrap(), subnet);
+
+        let mut data = Vec::new();
+        ClientSubnet::opt_out().encode(&mut OutputStream::new(&mut data));
+        assert_eq!(data, [0, 1, 0, 0]);
+
+        let subnet: ClientSubnet = "2001:db8:1234::/48".parse().unwrap();
+        let mut data = Vec::new();
+        subnet.encode(&mut OutputStream::new(&mut data));
+        assert_eq!(data, [0, 2, 48, 0, 0x20, 0x01, 0x0d, 0xb8, 0x12, 0x34]);
+        assert_eq!(ClientSubnet::decode(&data).unwrap(), subnet);
+    }
+
+    #[test]
+    fn decode_malformed() {
+        // address longer than the source prefix
+        assert!(ClientSubnet::decode(&[0, 1, 16, 0, 192, 0, 2]).is_none());
+        // bits beyond the source prefix are not zero
+        assert!(ClientSubnet::decode(&[0, 1, 20, 0, 192, 0, 2]).is_none());
+        // unknown family
+        assert!(ClientSubnet::decode(&[0, 3, 8, 0, 192]).is_none());
+        // scope prefix longer than the address
+        assert!(ClientSubnet::decode(&[0, 1, 8, 33, 192]).is_none());
+    }
+
+    #[test]
+    fn matches_scope() {
+        let mut subnet: ClientSubnet = "192.0.2.0/24".parse().unwrap();
+        subnet.set_scope_prefix(16);
+        assert!(subnet.matches(&"192.0.200.1".parse().unwrap()));
+        assert!(!subnet.matches(&"192.1.2.1".parse().unwrap()));
+        assert!(!subnet.matches(&"2001:db8::1".parse().unwrap()));
+
+        subnet.set_scope_prefix(0);
+        assert!(subnet.matches(&"10.0.0.1".parse().unwrap()));
+        assert!(subnet.matches(&"2001:db8::1".parse().unwrap()));
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt;
+
+use cafe_common::BinaryWriter;
+use cafe_common::stream::Output as OutputStream;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Extended DNS Error option data (RFC 8914):
+///                                              1   1   1   1   1   1
+///      0   1   2   3   4   5   6   7   8   9   0   1   2   3   4   5
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// 0: |                            INFO-CODE                          |
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+/// 2: / EXTRA-TEXT ...                                                /
+///    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+pub struct ExtendedError {
+    /// A code from IANA "Extended DNS Error Codes" registry.
+    info_code: u16,
+    /// Free-form UTF-8 text intended for humans, may be empty.
+    extra_text: String
+}
+
+impl ExtendedError {
+    pub fn new(info_code: u16, extra_text: &str) -> Self {
+        Self {
+            info_code,
+            extra_text: extra_text.to_string()
+        }
+    }
+
+    pub fn info_code(&self) -> u16 {
+        self.info_code
+    }
+
+    pub fn extra_text(&self) -> &str {
+        &self.extra_text
+    }
+
+    /// Purpose of the info code as registered in RFC 8914.
+    pub fn description(&self) -> Option<&'static str> {
+        let description = match self.info_code {
+            0 => "Other Error",
+            1 => "Unsupported DNSKEY Algorithm",
+            2 => "Unsupported DS Digest Type",
+            3 => "Stale Answer",
+            4 => "Forged Answer",
+            5 => "DNSSEC Indeterminate",
+            6 => "DNSSEC Bogus",
+            7 => "Signature Expired",
+            8 => "Signature Not Yet Valid",
+            9 => "DNSKEY Missing",
+            10 => "RRSIGs Missing",
+            11 => "No Zone Key Bit Set",
+            12 => "NSEC Missing",
+            13 => "Cached Error",
+            14 => "Not Ready",
+            15 => "Blocked",
+            16 => "Censored",
+            17 => "Filtered",
+            18 => "Prohibited",
+            19 => "Stale NXDomain Answer",
+            20 => "Not Authoritative",
+            21 => "Not Supported",
+            22 => "No Reachable Authority",
+            23 => "Network Error",
+            24 => "Invalid Data",
+            _ => return None
+        };
+
+        Some(description)
+    }
+
+    pub fn encode(&self, stream: &mut OutputStream) {
+        let mut writer = BinaryWriter::new(stream);
+        writer.write_u16(self.info_code.to_be());
+
+        let text = self.extra_text.as_bytes();
+        stream.write(text, 0, text.len());
+    }
+
+    pub fn decode(data: &[u8]) -> Option<ExtendedError> {
+        if data.len() < 2 {
+            return None;
+        }
+
+        // Some implementations null-terminate EXTRA-TEXT though RFC 8914 says they shouldn't.
+        let text = &data[2 ..];
+        let text = text.strip_suffix(&[0]).unwrap_or(text);
+
+        Some(
+            ExtendedError {
+                info_code: u16::from_be_bytes([data[0], data[1]]),
+                extra_text: String::from_utf8_lossy(text).into_owned()
+            }
+        )
+    }
+}
+
+impl fmt::Display for ExtendedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.description() {
+            Some(description) => write!(f, "{} ({})", description, self.info_code)?,
+            None => write!(f, "Unknown ({})", self.info_code)?
+        }
+
+        if !self.extra_text.is_empty() {
+            write!(f, ": {}", self.extra_text)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode() {
+        let error = ExtendedError::new(6, "no valid RRSIG");
+        let mut data = Vec::new();
+        error.encode(&mut OutputStream::new(&mut data));
+        assert_eq!(&data[.. 2], &[0, 6]);
+        assert_eq!(&data[2 ..], b"no valid RRSIG");
+        assert_eq!(ExtendedError::decode(&data).unwrap(), error);
+
+        let error = ExtendedError::decode(&[0, 15, b'a', b'd', b's', 0]).unwrap();
+        assert_eq!(error.info_code(), 15);
+        assert_eq!(error.extra_text(), "ads");
+
+        assert!(ExtendedError::decode(&[0]).is_none());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(ExtendedError::new(6, "").to_string(), "DNSSEC Bogus (6)");
+        assert_eq!(ExtendedError::new(22, "at delegation ru.").to_string(), "No Reachable Authority (22): at delegation ru.");
+        assert_eq!(ExtendedError::new(4000, "").to_string(), "Unknown (4000)");
+    }
+}
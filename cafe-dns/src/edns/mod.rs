@@ -0,0 +1,262 @@
+mod client_subnet;
+mod extended_error;
+
+pub use self::client_subnet::ClientSubnet;
+pub use self::extended_error::ExtendedError;
+
+use cafe_common::{BinaryReader, BinaryWriter, BitVector64};
+use cafe_common::stream::{SeekOrigin, Output as OutputStream, Input as InputStream};
+
+use crate::read_bytes;
+
+/// TYPE value of the OPT pseudo-record.
+const OPT_TYPE: u16 = 41;
+
+/// Option codes assigned by IANA "DNS EDNS0 Option Codes (OPT)" registry.
+const CLIENT_SUBNET_CODE: u16 = 8;
+const TCP_KEEPALIVE_CODE: u16 = 11;
+const PADDING_CODE: u16 = 12;
+const EXTENDED_ERROR_CODE: u16 = 15;
+
+#[derive(Debug, Clone, PartialEq)]
+/// EDNS(0) pseudo-record (RFC 6891). It is carried in the additional section
+/// as an OPT RR with the following layout:
+/// +------------+--------------+------------------------------+
+/// | Field Name | Field Type   | Description                  |
+/// +------------+--------------+------------------------------+
+/// | NAME       | domain name  | MUST be 0 (root domain)      |
+/// | TYPE       | u_int16_t    | OPT (41)                     |
+/// | CLASS      | u_int16_t    | requestor's UDP payload size |
+/// | TTL        | u_int32_t    | extended RCODE and flags     |
+/// | RDLEN      | u_int16_t    | length of all RDATA          |
+/// | RDATA      | octet stream | {attribute,value} pairs      |
+/// +------------+--------------+------------------------------+
+pub struct Edns {
+    /// The number of octets of the largest UDP payload that can be
+    /// reassembled and delivered in the requestor's network stack.
+    udp_payload_size: u16,
+    /// Upper 8 bits of the 12-bit RCODE, lower 4 bits are in the header.
+    extended_rcode: u8,
+    /// Implementation level of the setter.
+    version: u8,
+    /// DNSSEC OK bit (RFC 3225).
+    dnssec_ok: bool,
+    options: Vec<EdnsOption>
+}
+
+impl Edns {
+    pub fn new() -> Self {
+        Self {
+            udp_payload_size: 1232,
+            extended_rcode: 0,
+            version: 0,
+            dnssec_ok: false,
+            options: Vec::new()
+        }
+    }
+
+    pub fn udp_payload_size(&self) -> u16 {
+        self.udp_payload_size
+    }
+
+    pub fn set_udp_payload_size(&mut self, value: u16) {
+        self.udp_payload_size = value
+    }
+
+    pub fn extended_rcode(&self) -> u8 {
+        self.extended_rcode
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn dnssec_ok(&self) -> bool {
+        self.dnssec_ok
+    }
+
+    pub fn set_dnssec_ok(&mut self, value: bool) {
+        self.dnssec_ok = value
+    }
+
+    pub fn options(&self) -> &[EdnsOption] {
+        &self.options
+    }
+
+    pub fn add_option(&mut self, option: EdnsOption) {
+        self.options.push(option)
+    }
+
+    pub fn client_subnet(&self) -> Option<&ClientSubnet> {
+        self.options.iter().find_map(|option| match option {
+            EdnsOption::ClientSubnet(subnet) => Some(subnet),
+            _ => None
+        })
+    }
+
+    /// Idle timeout the server announced for the connection, in units of 100 milliseconds.
+    pub fn tcp_keepalive(&self) -> Option<u16> {
+        self.options.iter().find_map(|option| match option {
+            EdnsOption::TcpKeepalive(timeout) => *timeout,
+            _ => None
+        })
+    }
+
+    /// A response may carry several extended errors, e.g. one per failed upstream.
+    pub fn extended_errors(&self) -> impl Iterator<Item = &ExtendedError> {
+        self.options.iter().filter_map(|option| match option {
+            EdnsOption::ExtendedError(error) => Some(error),
+            _ => None
+        })
+    }
+
+    pub fn encode(&self, stream: &mut OutputStream) {
+        let mut data = Vec::new();
+        let mut data_stream = OutputStream::new(&mut data);
+        for option in &self.options {
+            option.encode(&mut data_stream);
+        }
+
+        let mut flags = BitVector64::new();
+        flags.set_part(24, 8, self.extended_rcode.into());
+        flags.set_part(16, 8, self.version.into());
+        flags.set_part(15, 1, u64::from(self.dnssec_ok));
+
+        let mut writer = BinaryWriter::new(stream);
+        writer.write_u8(0);
+        writer.write_u16(OPT_TYPE.to_be());
+        writer.write_u16(self.udp_payload_size.to_be());
+        writer.write_u32((flags.data() as u32).to_be());
+        writer.write_u16((data.len() as u16).to_be());
+        stream.write(&data, 0, data.len());
+    }
+
+    /// Checks without consuming anything whether the next record in the stream is OPT.
+    pub fn is_next(stream: &mut InputStream) -> bool {
+        let position = stream.position();
+
+        let mut reader = BinaryReader::new(stream);
+        let result = match (reader.read_u8(), reader.read_u16()) {
+            (Some(0), Some(ttype)) => u16::from_be(ttype) == OPT_TYPE,
+            _ => false
+        };
+
+        let _ = stream.seek(SeekOrigin::Begin, position as i64);
+        result
+    }
+
+    pub fn decode(stream: &mut InputStream) -> Option<Edns> {
+        let mut reader = BinaryReader::new(stream);
+        if reader.read_u8()? != 0 || u16::from_be(reader.read_u16()?) != OPT_TYPE {
+            return None;
+        }
+
+        let udp_payload_size = u16::from_be(reader.read_u16()?);
+        let flags = BitVector64::from(u32::from_be(reader.read_u32()?) as u64);
+        let data_length = u16::from_be(reader.read_u16()?) as usize;
+        let data = read_bytes(stream, data_length)?;
+
+        let mut options = Vec::new();
+        let mut data_stream = InputStream::new(&data);
+        while data_stream.position() < data.len() {
+            options.push(EdnsOption::decode(&mut data_stream)?);
+        }
+
+        Some(
+            Edns {
+                udp_payload_size,
+                extended_rcode: flags.get_part(24, 8) as u8,
+                version: flags.get_part(16, 8) as u8,
+                dnssec_ok: flags.get(15),
+                options
+            }
+        )
+    }
+}
+
+impl Default for Edns {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// A single {attribute,value} pair of the OPT RDATA:
+///                +0 (MSB)                            +1 (LSB)
+///     +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///  0: |                          OPTION-CODE                          |
+///     +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///  2: |                         OPTION-LENGTH                         |
+///     +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+///  4: |                                                               |
+///     /                          OPTION-DATA                          /
+///     /                                                               /
+///     +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+pub enum EdnsOption {
+    /// Client Subnet (RFC 7871).
+    ClientSubnet(ClientSubnet),
+    /// edns-tcp-keepalive (RFC 7828): the idle timeout in units of 100 milliseconds,
+    /// which only servers send, clients leave it out.
+    TcpKeepalive(Option<u16>),
+    /// Padding (RFC 7830) of the given number of zero octets.
+    Padding(u16),
+    /// Extended DNS Error (RFC 8914).
+    ExtendedError(ExtendedError),
+    /// An option this crate does not interpret, OPTION-DATA is kept as is.
+    Unknown {
+        code: u16,
+        data: Vec<u8>
+    }
+}
+
+impl EdnsOption {
+    pub fn code(&self) -> u16 {
+        match self {
+            EdnsOption::ClientSubnet(_) => CLIENT_SUBNET_CODE,
+            EdnsOption::TcpKeepalive(_) => TCP_KEEPALIVE_CODE,
+            EdnsOption::Padding(_) => PADDING_CODE,
+            EdnsOption::ExtendedError(_) => EXTENDED_ERROR_CODE,
+            EdnsOption::Unknown { code, data: _ } => *code
+        }
+    }
+
+    pub fn encode(&self, stream: &mut OutputStream) {
+        let mut data = Vec::new();
+        let mut data_stream = OutputStream::new(&mut data);
+        match self {
+            EdnsOption::ClientSubnet(subnet) => subnet.encode(&mut data_stream),
+            EdnsOption::TcpKeepalive(None) => (),
+            EdnsOption::TcpKeepalive(Some(timeout)) => BinaryWriter::new(&mut data_stream).write_u16(timeout.to_be()),
+            EdnsOption::Padding(length) => data_stream.write(&vec![0; *length as usize], 0, *length as usize),
+            EdnsOption::ExtendedError(error) => error.encode(&mut data_stream),
+            EdnsOption::Unknown { code: _, data: raw } => data_stream.write(raw, 0, raw.len())
+        }
+
+        let mut writer = BinaryWriter::new(stream);
+        writer.write_u16(self.code().to_be());
+        writer.write_u16((data.len() as u16).to_be());
+        stream.write(&data, 0, data.len());
+    }
+
+    pub fn decode(stream: &mut InputStream) -> Option<EdnsOption> {
+        let mut reader = BinaryReader::new(stream);
+        let code = u16::from_be(reader.read_u16()?);
+        let data_length = u16::from_be(reader.read_u16()?) as usize;
+        let data = read_bytes(stream, data_length)?;
+
+        let option = match code {
+            CLIENT_SUBNET_CODE => EdnsOption::ClientSubnet(ClientSubnet::decode(&data)?),
+            TCP_KEEPALIVE_CODE => match data.as_slice() {
+                [] => EdnsOption::TcpKeepalive(None),
+                [high, low] => EdnsOption::TcpKeepalive(Some(u16::from_be_bytes([*high, *low]))),
+                _ => return None
+            },
+            // Senders should use zeros, but receivers must accept anything (RFC 7830, section 4).
+            PADDING_CODE => EdnsOption::Padding(data.len() as u16),
+            EXTENDED_ERROR_CODE => EdnsOption::ExtendedError(ExtendedError::decode(&data)?),
+            code => EdnsOption::Unknown { code, data }
+        };
+
+        Some(option)
+    }
+}
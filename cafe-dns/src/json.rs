@@ -0,0 +1,218 @@
+//! JSON representation of DNS messages (RFC 8427), available with the `serde` feature.
+//!
+//! `Message` serializes to the member names of the RFC with any serde format:
+//!
+//! ```text
+//! {"ID":1,"QR":1,"Opcode":0,"AA":0,"TC":0,"RD":1,"RA":1,"AD":0,"CD":0,"RCODE":0,
+//!  "QDCOUNT":1,"ANCOUNT":1,"NSCOUNT":0,"ARCOUNT":0,
+//!  "QNAME":"_xmpp-client._tcp.jabber.ru.","QTYPE":33,"QTYPEname":"SRV","QCLASS":1,"QCLASSname":"IN",
+//!  "answerRRs":[{"NAME":"_xmpp-client._tcp.jabber.ru.","TYPE":33,"TYPEname":"SRV","CLASS":1,
+//!                "CLASSname":"IN","TTL":300,"rdataSRV":"5 0 5222 xmpp.jabber.ru."}]}
+//! ```
+
+use std::collections::BTreeMap;
+
+use data_encoding::HEXUPPER;
+use serde::{Deserialize, Serialize};
+
+use crate::{classes, types, absolute_name, QType, ResourceRecord, Response, Type};
+
+/// DNS message as a JSON object of RFC 8427, section 2.1. Flags are 0 or 1 as in the examples
+/// of the RFC. EDNS and TSIG records are not listed in `additionalRRs`, only counted in `ARCOUNT`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    #[serde(rename = "ID")]
+    id: u16,
+    #[serde(rename = "QR")]
+    qr: u8,
+    #[serde(rename = "Opcode")]
+    opcode: u8,
+    #[serde(rename = "AA")]
+    aa: u8,
+    #[serde(rename = "TC")]
+    tc: u8,
+    #[serde(rename = "RD")]
+    rd: u8,
+    #[serde(rename = "RA")]
+    ra: u8,
+    #[serde(rename = "AD")]
+    ad: u8,
+    #[serde(rename = "CD")]
+    cd: u8,
+    #[serde(rename = "RCODE")]
+    rcode: u8,
+    #[serde(rename = "QDCOUNT")]
+    qdcount: u16,
+    #[serde(rename = "ANCOUNT")]
+    ancount: u16,
+    #[serde(rename = "NSCOUNT")]
+    nscount: u16,
+    #[serde(rename = "ARCOUNT")]
+    arcount: u16,
+    /// The first question, the only one in practice.
+    #[serde(rename = "QNAME", default, skip_serializing_if = "Option::is_none")]
+    qname: Option<String>,
+    #[serde(rename = "QTYPE", default, skip_serializing_if = "Option::is_none")]
+    qtype: Option<u16>,
+    #[serde(rename = "QTYPEname", default, skip_serializing_if = "Option::is_none")]
+    qtype_name: Option<String>,
+    #[serde(rename = "QCLASS", default, skip_serializing_if = "Option::is_none")]
+    qclass: Option<u16>,
+    #[serde(rename = "QCLASSname", default, skip_serializing_if = "Option::is_none")]
+    qclass_name: Option<String>,
+    #[serde(rename = "answerRRs", default, skip_serializing_if = "Vec::is_empty")]
+    answers: Vec<Record>,
+    #[serde(rename = "authorityRRs", default, skip_serializing_if = "Vec::is_empty")]
+    authorities: Vec<Record>,
+    #[serde(rename = "additionalRRs", default, skip_serializing_if = "Vec::is_empty")]
+    additionals: Vec<Record>,
+    /// The whole message in wire format, the fields above are informational when it is present.
+    #[serde(rename = "messageOctetsHEX", default, skip_serializing_if = "Option::is_none")]
+    octets: Option<String>
+}
+
+fn flag(value: bool) -> u8 {
+    match value {
+        true => 1,
+        false => 0
+    }
+}
+
+impl Message {
+    /// Message of the wire format `data`, kept as `messageOctetsHEX` if `keep_octets` is set.
+    pub fn decode(data: &[u8], keep_octets: bool) -> Option<Message> {
+        let response = Response::decode(data)?;
+        let header = response.header();
+        let question = response.questions().first();
+        let records = |records: &[ResourceRecord]| records.iter().map(Record::new).collect();
+
+        Some(Message {
+            id: header.id(),
+            qr: flag(header.is_response()),
+            opcode: header.opcode(),
+            aa: flag(header.aa()),
+            tc: flag(header.tc()),
+            rd: flag(header.rd()),
+            ra: flag(header.ra()),
+            ad: flag(header.ad()),
+            cd: flag(header.cd()),
+            rcode: header.rcode() as u8,
+            qdcount: header.qdcount(),
+            ancount: header.ancount(),
+            nscount: header.nscount(),
+            arcount: header.arcount(),
+            qname: question.map(|question| absolute_name(question.host_name())),
+            qtype: question.map(|question| question.qtype() as u16),
+            qtype_name: question.map(|question| question.qtype().to_string()),
+            qclass: question.map(|question| question.qclass() as u16),
+            qclass_name: question.map(|question| question.qclass().to_string()),
+            answers: records(response.answers()),
+            authorities: records(response.authorities()),
+            additionals: records(response.additionals()),
+            octets: match keep_octets {
+                true => Some(HEXUPPER.encode(data)),
+                false => None
+            }
+        })
+    }
+
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    pub fn qname(&self) -> Option<&str> {
+        self.qname.as_deref()
+    }
+
+    /// Type of the question, `None` for types `QType` doesn't cover.
+    pub fn qtype(&self) -> Option<QType> {
+        types::type_name(self.qtype?).parse().ok()
+    }
+
+    pub fn answers(&self) -> &[Record] {
+        &self.answers
+    }
+
+    pub fn authorities(&self) -> &[Record] {
+        &self.authorities
+    }
+
+    pub fn additionals(&self) -> &[Record] {
+        &self.additionals
+    }
+
+    /// Wire format of the message given as `messageOctetsHEX`.
+    pub fn octets(&self) -> Option<Vec<u8>> {
+        HEXUPPER.decode(self.octets.as_ref()?.to_ascii_uppercase().as_bytes()).ok()
+    }
+
+    /// The message decoded from `messageOctetsHEX`, exactly as it was sent.
+    pub fn response(&self) -> Option<Response> {
+        Response::decode(&self.octets()?)
+    }
+}
+
+/// Resource record as a JSON object of RFC 8427, section 2.2. RDATA of types this crate
+/// interprets is given in the presentation format as `rdata` followed by the type mnemonic
+/// (section 2.3), that of other types as `RDATAHEX`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    #[serde(rename = "NAME")]
+    name: String,
+    #[serde(rename = "TYPE")]
+    code: u16,
+    #[serde(rename = "TYPEname", default, skip_serializing_if = "Option::is_none")]
+    type_name: Option<String>,
+    #[serde(rename = "CLASS")]
+    class: u16,
+    #[serde(rename = "CLASSname", default, skip_serializing_if = "Option::is_none")]
+    class_name: Option<String>,
+    #[serde(rename = "TTL")]
+    ttl: u32,
+    #[serde(rename = "RDATAHEX", default, skip_serializing_if = "Option::is_none")]
+    rdata_hex: Option<String>,
+    /// The `rdata` member named after the type.
+    #[serde(flatten)]
+    rdata: BTreeMap<String, String>
+}
+
+impl Record {
+    pub fn new(record: &ResourceRecord) -> Record {
+        let code = record.ttype().code();
+        let type_name = types::type_name(code);
+        let mut rdata = BTreeMap::new();
+        let rdata_hex = match record.ttype() {
+            Type::Unknown { data, .. } => Some(HEXUPPER.encode(data)),
+            ttype => {
+                rdata.insert(format!("rdata{}", type_name), ttype.to_string());
+                None
+            }
+        };
+
+        Record {
+            name: absolute_name(record.name()),
+            code,
+            type_name: Some(type_name),
+            class: record.class(),
+            class_name: Some(classes::class_name(record.class())),
+            ttl: record.ttl(),
+            rdata_hex,
+            rdata
+        }
+    }
+
+    /// The record the object describes, `None` if its RDATA is missing or invalid.
+    pub fn record(&self) -> Option<ResourceRecord> {
+        let rdata = match (&self.rdata_hex, self.rdata.get(&format!("rdata{}", types::type_name(self.code)))) {
+            (_, Some(text)) => Type::parse_rdata(self.code, text)?,
+            (Some(hex), None) => {
+                let length = hex.len() / 2;
+                Type::parse_rdata(self.code, &format!("\\# {} {}", length, hex))?
+            },
+            (None, None) => return None
+        };
+
+        let name = types::parse_name(&self.name, None)?;
+        Some(ResourceRecord::new(&name, self.class, self.ttl, rdata))
+    }
+}
@@ -1,16 +1,30 @@
+pub mod dnssec;
+pub mod edns;
+#[cfg(feature = "serde")]
+pub mod json;
 pub mod rcode;
+pub mod rrset;
+pub mod tsig;
 pub mod types;
+pub mod update;
 pub mod classes;
+pub mod zone;
 
 pub use self::classes::QClass;
+pub use self::edns::{ClientSubnet, Edns, EdnsOption, ExtendedError};
 pub use self::rcode::ResponseCode;
+pub use self::rrset::RRset;
+pub use self::tsig::Tsig;
 pub use self::types::{QType, Type};
+pub use self::update::Update;
 
 use cafe_common::{BinaryReader, BinaryWriter, BitVector64};
-use cafe_common::stream::{SeekOrigin, SeekError, Output as OutputStream, Input as InputStream};
+use cafe_common::stream::{SeekOrigin, Output as OutputStream, Input as InputStream};
 
-use std::net::Ipv4Addr;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
 use std::convert::TryInto;
+use std::str::FromStr;
 
 fn to_u64(value: bool) -> u64 {
     match value {
@@ -25,7 +39,7 @@ fn to_bool(value: u64) -> bool {
 
 fn encode_qname(qname: &str) -> Vec<u8> {
     let mut result: Vec<u8> = Vec::with_capacity(qname.len());
-    for part in qname.split('.') {
+    for part in qname.split('.').filter(|part| !part.is_empty()) {
         result.push(part.len() as u8);
         result.extend_from_slice(part.as_bytes());
     }
@@ -33,43 +47,82 @@ fn encode_qname(qname: &str) -> Vec<u8> {
     result
 }
 
-fn decode_qname(bytes: &[u8]) -> Option<String> {
-    if bytes.len() == 0 {
-        return None
-    }
+/// Name with the trailing dot as written in master files.
+fn absolute_name(name: &str) -> String {
+    format!("{}.", name.trim_end_matches('.'))
+}
+
+/// Writes uncompressed name terminated with the root label.
+fn encode_name(stream: &mut OutputStream, name: &str) {
+    let name = encode_qname(name);
+    stream.write(&name, 0, name.len());
+    stream.write_byte(0);
+}
+
+/// Upper bound of compression pointers followed while decoding a single name,
+/// protects from pointer loops in malformed messages.
+const MAX_NAME_POINTERS: usize = 64;
 
-    let mut current = bytes;
+fn decode_name(stream: &mut InputStream) -> Option<String> {
     let mut result = String::new();
+    let mut resume_position = None;
+    let mut pointers = 0;
 
     loop {
-        let len = current[0] as usize;
-        if len > current.len() {
-            return None;
-        }
+        let len = stream.read_byte()?;
+        match len & 0xC0 {
+            0x00 => {
+                if len == 0 {
+                    break;
+                }
 
-        current = &current[1 ..];
-        if !current[.. len].is_ascii() {
-            return None;
-        }
+                let label = read_bytes(stream, len as usize)?;
+                if !label.is_ascii() {
+                    return None;
+                }
 
-        for ch in current[.. len].iter().map(|byte| *byte as char) {
-            result.push(ch);
-        }
+                if !result.is_empty() {
+                    result.push('.');
+                }
 
-        current = &current[len ..];
-        if current.is_empty() {
-            return Some(result);
-        }
+                result.extend(label.iter().map(|byte| *byte as char));
+            },
+            0xC0 => {
+                let offset = ((len & 0x3F) as usize) << 8 | stream.read_byte()? as usize;
+                if resume_position.is_none() {
+                    resume_position = Some(stream.position());
+                }
 
-        if current[0] as usize == 0 {
-            return Some(result);
+                pointers += 1;
+                if pointers > MAX_NAME_POINTERS {
+                    return None;
+                }
+
+                stream.seek(SeekOrigin::Begin, offset as i64).ok()?;
+            },
+            _ => return None
         }
+    }
 
-        result.push('.')
+    if let Some(position) = resume_position {
+        stream.seek(SeekOrigin::Begin, position as i64).ok()?;
     }
+
+    Some(result)
+}
+
+fn read_bytes(stream: &mut InputStream, count: usize) -> Option<Vec<u8>> {
+    if stream.position() + count > stream.length() {
+        return None;
+    }
+
+    let mut result = vec![0; count];
+    stream.read(&mut result, 0, count);
+    Some(result)
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The header contains the following fields (RFC 1035):
 ///                                 1  1  1  1  1  1
 ///   0  1  2  3  4  5  6  7  8  9  0  1  2  3  4  5
@@ -157,6 +210,10 @@ impl Header {
         self.id
     }
 
+    pub fn set_id(&mut self, value: u16) {
+        self.id = value
+    }
+
     pub fn is_response(&self) -> bool {
         self.qr
     }
@@ -165,6 +222,10 @@ impl Header {
         self.opcode
     }
 
+    pub fn set_opcode(&mut self, value: u8) {
+        self.opcode = value
+    }
+
     pub fn aa(&self) -> bool {
         self.aa
     }
@@ -189,6 +250,23 @@ impl Header {
         self.z
     }
 
+    /// Authentic Data - the server considers the answer validated with DNSSEC (RFC 4035).
+    pub fn ad(&self) -> bool {
+        self.z & 0b010 != 0
+    }
+
+    /// Checking Disabled - asks the server to return data it failed to validate (RFC 4035).
+    pub fn cd(&self) -> bool {
+        self.z & 0b001 != 0
+    }
+
+    pub fn set_cd(&mut self, value: bool) {
+        match value {
+            true => self.z |= 0b001,
+            false => self.z &= !0b001
+        }
+    }
+
     pub fn rcode(&self) -> ResponseCode {
         self.rcode
     }
@@ -252,6 +330,11 @@ impl Header {
             Ok(ResponseCode::NameError) => ResponseCode::NameError,
             Ok(ResponseCode::NotImplemented) => ResponseCode::NotImplemented,
             Ok(ResponseCode::Refused) => ResponseCode::Refused,
+            Ok(ResponseCode::YXDomain) => ResponseCode::YXDomain,
+            Ok(ResponseCode::YXRRSet) => ResponseCode::YXRRSet,
+            Ok(ResponseCode::NXRRSet) => ResponseCode::NXRRSet,
+            Ok(ResponseCode::NotAuth) => ResponseCode::NotAuth,
+            Ok(ResponseCode::NotZone) => ResponseCode::NotZone,
             Err(_) => return None
         };
         
@@ -284,6 +367,7 @@ impl Header {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The question section is used to carry the "question" in most queries,
 /// i.e., the parameters that define what is being asked
 ///                               1  1  1  1  1  1
@@ -328,6 +412,14 @@ impl Question {
         &self.qname
     }
 
+    pub fn qtype(&self) -> QType {
+        self.qtype
+    }
+
+    pub fn qclass(&self) -> QClass {
+        self.qclass
+    }
+
     pub fn encode(&self, stream: &mut OutputStream) {
         let qname = encode_qname(&self.qname);
         stream.write(&qname, 0, qname.len());
@@ -339,16 +431,24 @@ impl Question {
     }
 
     pub fn decode(stream: &mut InputStream) -> Option<Question> {
-        let qname = decode_qname(stream.data()?)?;
-        if let Err(_) = stream.seek(SeekOrigin::Current, (qname.as_bytes().len() + 2) as i64) {
-            return None
-        }
+        let qname = decode_name(stream)?;
 
         let mut reader = BinaryReader::new(stream);
         let qtype = reader.read_u16()?;
         let qtype = match u16::from_be(qtype).try_into() {
             Ok(QType::A) => QType::A,
+            Ok(QType::SOA) => QType::SOA,
+            Ok(QType::AAAA) => QType::AAAA,
             Ok(QType::SRV) => QType::SRV,
+            Ok(QType::DS) => QType::DS,
+            Ok(QType::RRSIG) => QType::RRSIG,
+            Ok(QType::NSEC) => QType::NSEC,
+            Ok(QType::DNSKEY) => QType::DNSKEY,
+            Ok(QType::NSEC3) => QType::NSEC3,
+            Ok(QType::NSEC3PARAM) => QType::NSEC3PARAM,
+            Ok(QType::IXFR) => QType::IXFR,
+            Ok(QType::AXFR) => QType::AXFR,
+            Ok(QType::ANY) => QType::ANY,
             Err(_) => return None
         };
 
@@ -368,7 +468,32 @@ impl Question {
     }
 }
 
-#[derive(Debug)]
+impl fmt::Display for Question {
+    /// The question as `name CLASS TYPE`, the way master files order them.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t{}\t{}", absolute_name(&self.qname), self.qclass, self.qtype)
+    }
+}
+
+impl FromStr for Question {
+    type Err = ();
+
+    /// Parses `name CLASS TYPE` with an absolute name, the class may be omitted for IN.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let (name, qclass, qtype) = match fields.as_slice() {
+            [name, qclass, qtype] => (name, qclass.parse()?, qtype.parse()?),
+            [name, qtype] => (name, QClass::IN, qtype.parse()?),
+            _ => return Err(())
+        };
+
+        let qname = types::parse_name(name, None).ok_or(())?;
+        Ok(Question { qname, qtype, qclass })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The answer, authority, and additional sections all share the same
 /// format: a variable number of resource records, where the number of
 /// records is specified in the corresponding count field in the header.
@@ -411,25 +536,45 @@ pub struct ResourceRecord {
 }
 
 impl ResourceRecord {
+    pub fn new(name: &str, class: u16, ttl: u32, ttype: Type) -> Self {
+        Self {
+            name: name.to_string(),
+            ttype,
+            class,
+            ttl
+        }
+    }
+
     pub fn decode(stream: &mut InputStream) -> Option<ResourceRecord> {
-        let first_byte = stream.read_byte()?;
-        let name = match ResourceRecord::is_compressed_name(first_byte) {
-            true => {
-                if let Err(_) = stream.seek(SeekOrigin::Current, -1) {
-                    return None;
-                }
-                ResourceRecord::extract_name(stream)?
-            }
-            _ => panic!("not implemented yet")
-        };
+        Self::decode_with(stream, false)
+    }
+
+    /// Decodes a record, one of the prerequisite or update section of a dynamic update (RFC 2136)
+    /// if `in_update` is set: records of class ANY and NONE carry no RDATA there whatever their type is.
+    pub(crate) fn decode_with(stream: &mut InputStream, in_update: bool) -> Option<ResourceRecord> {
+        let name = decode_name(stream)?;
 
         let mut reader = BinaryReader::new(stream);
         let ttype = u16::from_be(reader.read_u16()?);
         let class = u16::from_be(reader.read_u16()?);
         let ttl = u32::from_be(reader.read_u32()?);
-        let _data_length = u16::from_be(reader.read_u16()?);
+        let data_length = u16::from_be(reader.read_u16()?) as usize;
 
+        let data_start = stream.position();
+        let data_end = data_start + data_length;
+        if data_end > stream.length() {
+            return None;
+        }
+
+        let mut reader = BinaryReader::new(stream);
         let ttype = match ttype {
+            // Prerequisites and deletions of RFC 2136 carry no RDATA whatever the type is.
+            code if in_update && data_length == 0 && [update::CLASS_ANY, update::CLASS_NONE].contains(&class) => {
+                Type::Unknown {
+                    code,
+                    data: Vec::new()
+                }
+            },
             1 => {
                 let octet0 = reader.read_u8()?;
                 let octet1 = reader.read_u8()?;
@@ -440,18 +585,71 @@ impl ResourceRecord {
                     ip: Ipv4Addr::new(octet0, octet1, octet2, octet3)
                 }
             },
+            28 => {
+                let mut octets = [0; 16];
+                for octet in octets.iter_mut() {
+                    *octet = reader.read_u8()?;
+                }
+
+                Type::AAAA {
+                    ip: Ipv6Addr::from(octets)
+                }
+            },
+            2 => {
+                Type::NS {
+                    nsdname: decode_name(stream)?
+                }
+            },
+            5 => {
+                Type::CNAME {
+                    cname: decode_name(stream)?
+                }
+            },
+            12 => {
+                Type::PTR {
+                    ptrdname: decode_name(stream)?
+                }
+            },
+            15 => {
+                let preference = u16::from_be(reader.read_u16()?);
+                let exchange = decode_name(stream)?;
+
+                Type::MX {
+                    preference,
+                    exchange
+                }
+            },
+            16 => {
+                let mut strings = Vec::new();
+                while stream.position() < data_end {
+                    let length = BinaryReader::new(stream).read_u8()? as usize;
+                    strings.push(read_bytes(stream, length)?);
+                }
+
+                Type::TXT {
+                    strings
+                }
+            },
+            6 => {
+                let mname = decode_name(stream)?;
+                let rname = decode_name(stream)?;
+
+                let mut reader = BinaryReader::new(stream);
+                Type::SOA {
+                    mname,
+                    rname,
+                    serial: u32::from_be(reader.read_u32()?),
+                    refresh: u32::from_be(reader.read_u32()?),
+                    retry: u32::from_be(reader.read_u32()?),
+                    expire: u32::from_be(reader.read_u32()?),
+                    minimum: u32::from_be(reader.read_u32()?)
+                }
+            },
             33 => {
                 let priority = u16::from_be(reader.read_u16()?);
                 let weight = u16::from_be(reader.read_u16()?);
                 let port = u16::from_be(reader.read_u16()?);
-
-                let target = decode_qname(stream.data()?)?;
-                match stream.seek(SeekOrigin::Current, (target.as_bytes().len() + 2) as i64) {
-                    Ok(_) => { },
-                    // Might appears in the case of the last RR. Just ignore.
-                    Err(SeekError::AfterEnd) => { },
-                    Err(_) => return None
-                }
+                let target = decode_name(stream)?;
 
                 Type::SRV {
                     priority,
@@ -460,9 +658,120 @@ impl ResourceRecord {
                     target
                 }
             },
-            _ => return None
+            43 => {
+                let key_tag = u16::from_be(reader.read_u16()?);
+                let algorithm = reader.read_u8()?;
+                let digest_type = reader.read_u8()?;
+
+                Type::DS {
+                    key_tag,
+                    algorithm,
+                    digest_type,
+                    digest: read_bytes(stream, data_end.checked_sub(stream.position())?)?
+                }
+            },
+            46 => {
+                let type_covered = u16::from_be(reader.read_u16()?);
+                let algorithm = reader.read_u8()?;
+                let labels = reader.read_u8()?;
+                let original_ttl = u32::from_be(reader.read_u32()?);
+                let expiration = u32::from_be(reader.read_u32()?);
+                let inception = u32::from_be(reader.read_u32()?);
+                let key_tag = u16::from_be(reader.read_u16()?);
+                let signer_name = decode_name(stream)?;
+
+                Type::RRSIG {
+                    type_covered,
+                    algorithm,
+                    labels,
+                    original_ttl,
+                    expiration,
+                    inception,
+                    key_tag,
+                    signer_name,
+                    signature: read_bytes(stream, data_end.checked_sub(stream.position())?)?
+                }
+            },
+            47 => {
+                let next_domain_name = decode_name(stream)?;
+                let bitmap = read_bytes(stream, data_end.checked_sub(stream.position())?)?;
+
+                Type::NSEC {
+                    next_domain_name,
+                    types: dnssec::decode_type_bitmap(&bitmap)?
+                }
+            },
+            48 => {
+                let flags = u16::from_be(reader.read_u16()?);
+                let protocol = reader.read_u8()?;
+                let algorithm = reader.read_u8()?;
+
+                Type::DNSKEY {
+                    flags,
+                    protocol,
+                    algorithm,
+                    public_key: read_bytes(stream, data_end.checked_sub(stream.position())?)?
+                }
+            },
+            50 => {
+                let hash_algorithm = reader.read_u8()?;
+                let flags = reader.read_u8()?;
+                let iterations = u16::from_be(reader.read_u16()?);
+                let salt_length = reader.read_u8()? as usize;
+                let salt = read_bytes(stream, salt_length)?;
+                let hash_length = BinaryReader::new(stream).read_u8()? as usize;
+                let next_hashed_owner = read_bytes(stream, hash_length)?;
+                let bitmap = read_bytes(stream, data_end.checked_sub(stream.position())?)?;
+
+                Type::NSEC3 {
+                    hash_algorithm,
+                    flags,
+                    iterations,
+                    salt,
+                    next_hashed_owner,
+                    types: dnssec::decode_type_bitmap(&bitmap)?
+                }
+            },
+            51 => {
+                let hash_algorithm = reader.read_u8()?;
+                let flags = reader.read_u8()?;
+                let iterations = u16::from_be(reader.read_u16()?);
+                let salt_length = reader.read_u8()? as usize;
+
+                Type::NSEC3PARAM {
+                    hash_algorithm,
+                    flags,
+                    iterations,
+                    salt: read_bytes(stream, salt_length)?
+                }
+            },
+            // Names in RDATA of the other types of RFC 1035 may be compressed (RFC 3597, section 4),
+            // they are kept uncompressed.
+            code @ (3 | 4 | 7 | 8 | 9 | 14) => {
+                let names = if code == 14 { 2 } else { 1 };
+                let mut data = Vec::new();
+                let mut output = OutputStream::new(&mut data);
+                for _ in 0 .. names {
+                    encode_name(&mut output, &decode_name(stream)?);
+                }
+
+                Type::Unknown {
+                    code,
+                    data
+                }
+            },
+            code => {
+                Type::Unknown {
+                    code,
+                    data: read_bytes(stream, data_length)?
+                }
+            }
         };
 
+        if stream.position() != data_end {
+            return None;
+        }
+
         Some(
             ResourceRecord {
                 name,
@@ -473,6 +782,30 @@ impl ResourceRecord {
         )
     }
 
+    /// Encodes the record without name compression.
+    pub fn encode(&self, stream: &mut OutputStream) {
+        let data = self.ttype.rdata();
+
+        encode_name(stream, &self.name);
+        let mut writer = BinaryWriter::new(stream);
+        writer.write_u16(self.ttype.code().to_be());
+        writer.write_u16(self.class.to_be());
+        writer.write_u32(self.ttl.to_be());
+        writer.write_u16((data.len() as u16).to_be());
+        stream.write(&data, 0, data.len());
+    }
+
+    /// The record in the canonical form of RFC 4034, section 6.2 with `ttl` as TTL, which is
+    /// the original TTL of the covering RRSIG when the record is being validated.
+    pub fn canonical(&self, ttl: u32) -> ResourceRecord {
+        ResourceRecord {
+            name: self.name.to_ascii_lowercase(),
+            ttype: self.ttype.canonical(),
+            class: self.class,
+            ttl
+        }
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -488,39 +821,42 @@ impl ResourceRecord {
     pub fn class(&self) -> u16 {
         self.class
     }
+}
 
-    fn is_compressed_name(byte: u8) -> bool {
-        match byte == 0xC0 {
-            true => true,
-            _ => false
-        }
+impl fmt::Display for ResourceRecord {
+    /// The record as a line of a master file.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}\t{}",
+            absolute_name(&self.name),
+            self.ttl,
+            classes::class_name(self.class),
+            types::type_name(self.ttype.code()),
+            self.ttype
+        )
     }
+}
 
-    fn extract_name(stream: &mut InputStream) -> Option<String> {
-        let current = stream.position();
-
-        let mut reader = BinaryReader::new(stream);
-        let mut bits = BitVector64::from(reader.read_u16()? as u64);
-        bits.set_part(6, 2, 0);
-        let offset = u16::from_be(bits.get_part(0, 14) as u16);
-        if let Err(_) = stream.seek(SeekOrigin::Begin, offset as i64) {
-            return None;
-        }
-
-        let qname = decode_qname(stream.data()?)?;
+impl FromStr for ResourceRecord {
+    type Err = ();
 
-        if let Err(_) = stream.seek(SeekOrigin::Begin, (current + 2) as i64) {
-            return None;
-        }
-
-        Some(qname)
+    /// Parses the record as a line of a master file with absolute names. Parentheses and
+    /// comments are allowed, the class may be omitted for IN.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        zone::parse_record(s).ok_or(())
     }
 }
 
+#[derive(Debug)]
 pub struct Response {
     header: Header,
     questions: Vec<Question>,
-    answers: Vec<ResourceRecord>
+    answers: Vec<ResourceRecord>,
+    authorities: Vec<ResourceRecord>,
+    additionals: Vec<ResourceRecord>,
+    edns: Option<Edns>,
+    tsig: Option<Tsig>
 }
 
 impl Response {
@@ -537,20 +873,54 @@ impl Response {
             return None;
         }
 
+        // Answer and authority sections of a dynamic update hold its prerequisites and updates.
+        let in_update = header.opcode() == update::UPDATE_OPCODE;
         let mut answers = Vec::new();
         for _ in 0 .. header.ancount() {
-            answers.push(ResourceRecord::decode(&mut stream)?);
+            answers.push(ResourceRecord::decode_with(&mut stream, in_update)?);
         }
         
         if answers.len() != header.ancount() as usize {
             return None;
         }
 
+        let mut authorities = Vec::new();
+        for _ in 0 .. header.nscount() {
+            authorities.push(ResourceRecord::decode_with(&mut stream, in_update)?);
+        }
+
+        let mut additionals = Vec::new();
+        let mut edns = None;
+        let mut tsig = None;
+        for i in 0 .. header.arcount() {
+            if Tsig::is_next(&mut stream) {
+                // RFC 8945: TSIG must be the last record of the message.
+                if i + 1 != header.arcount() {
+                    return None;
+                }
+
+                tsig = Some(Tsig::decode(&mut stream)?);
+            } else if Edns::is_next(&mut stream) {
+                // RFC 6891: a message with more than one OPT RR is malformed.
+                if edns.is_some() {
+                    return None;
+                }
+
+                edns = Some(Edns::decode(&mut stream)?);
+            } else {
+                additionals.push(ResourceRecord::decode(&mut stream)?);
+            }
+        }
+
         Some(
             Response {
                 header,
                 questions,
-                answers
+                answers,
+                authorities,
+                additionals,
+                edns,
+                tsig
         })
     }
 
@@ -569,18 +939,45 @@ impl Response {
     pub fn answers(&self) -> &[ResourceRecord] {
         &self.answers
     }
+
+    /// Records of the answer section grouped into RRsets.
+    pub fn answer_rrsets(&self) -> Vec<RRset> {
+        rrset::group(&self.answers)
+    }
+
+    pub fn authorities(&self) -> &[ResourceRecord] {
+        &self.authorities
+    }
+
+    /// Additional records except OPT and TSIG, which are available via `edns` and `tsig`.
+    pub fn additionals(&self) -> &[ResourceRecord] {
+        &self.additionals
+    }
+
+    pub fn edns(&self) -> Option<&Edns> {
+        self.edns.as_ref()
+    }
+
+    /// Transaction signature, use `tsig::Verifier` to check it.
+    pub fn tsig(&self) -> Option<&Tsig> {
+        self.tsig.as_ref()
+    }
 }
 
 pub struct Request {
     header: Header,
-    questions: Vec<Question>
+    questions: Vec<Question>,
+    authorities: Vec<ResourceRecord>,
+    edns: Option<Edns>
 }
 
 impl Request {
     pub fn new(id: u16) -> Self {
         Self {
             header: Header::new(id),
-            questions: Vec::new()
+            questions: Vec::new(),
+            authorities: Vec::new(),
+            edns: None
         }
     }
 
@@ -608,11 +1005,44 @@ impl Request {
         self.header.qdcount += 1;
     }
 
+    pub fn authorities(&self) -> &[ResourceRecord] {
+        &self.authorities
+    }
+
+    /// Adds a record to the authority section, e.g. the current SOA of an IXFR request.
+    pub fn add_authority(&mut self, record: ResourceRecord) {
+        self.authorities.push(record);
+        self.header.nscount += 1;
+    }
+
+    pub fn edns(&self) -> Option<&Edns> {
+        self.edns.as_ref()
+    }
+
+    /// Attaches OPT pseudo-record to the additional section.
+    pub fn set_edns(&mut self, edns: Option<Edns>) {
+        match (self.edns.is_some(), edns.is_some()) {
+            (false, true) => self.header.arcount += 1,
+            (true, false) => self.header.arcount -= 1,
+            _ => { }
+        }
+
+        self.edns = edns;
+    }
+
     pub fn encode(&self, stream: &mut OutputStream) {
         self.header.encode(stream);
         for q in &self.questions {
             q.encode(stream);
         }
+
+        for record in &self.authorities {
+            record.encode(stream);
+        }
+
+        if let Some(edns) = &self.edns {
+            edns.encode(stream);
+        }
     }
 }
 
@@ -629,25 +1059,46 @@ mod tests {
         assert_eq!(
             encode_qname("mail.ru"), 
             [4, 109, 97, 105, 108, 2, 114, 117]);
+
+        assert_eq!(
+            encode_qname("mail.ru."), 
+            [4, 109, 97, 105, 108, 2, 114, 117]);
+
+        assert_eq!(encode_qname(""), []);
     }
 
     #[test]
     fn decode_addreass() {
         assert_eq!( 
-            decode_qname(&[3, 119, 119, 119, 7, 101, 120, 97, 109, 112, 108, 101, 3, 99, 111, 109]).unwrap(),
+            decode_name(&mut InputStream::new(&[3, 119, 119, 119, 7, 101, 120, 97, 109, 112, 108, 101, 3, 99, 111, 109, 0])).unwrap(),
             "www.example.com");
 
         assert_eq!(
-            decode_qname(&[4, 109, 97, 105, 108, 2, 114, 117]).unwrap(),
+            decode_name(&mut InputStream::new(&[4, 109, 97, 105, 108, 2, 114, 117, 0])).unwrap(),
             "mail.ru");
 
-        // zero end
-        assert_eq!(
-            decode_qname(&[4, 109, 97, 105, 108, 2, 114, 117, 0]).unwrap(),
-            "mail.ru");
+        // trailing data
+        let mut stream = InputStream::new(&[4, 109, 97, 105, 108, 2, 114, 117, 0, 23, 32, 99]);
+        assert_eq!(decode_name(&mut stream).unwrap(), "mail.ru");
+        assert_eq!(stream.position(), 9);
 
-        assert_eq!(
-            decode_qname(&[4, 109, 97, 105, 108, 2, 114, 117, 0, 23, 32, 99]).unwrap(),
-            "mail.ru");
+        // root
+        assert_eq!(decode_name(&mut InputStream::new(&[0])).unwrap(), "");
+
+        // unterminated
+        assert!(decode_name(&mut InputStream::new(&[4, 109, 97, 105, 108, 2, 114, 117])).is_none());
+    }
+
+    #[test]
+    fn decode_compressed_address() {
+        // "mail.ru" followed by "www" + pointer to offset 0
+        let data = [4, 109, 97, 105, 108, 2, 114, 117, 0, 3, 119, 119, 119, 0xC0, 0x00];
+        let mut stream = InputStream::new(&data);
+        stream.seek(SeekOrigin::Begin, 9).unwrap();
+        assert_eq!(decode_name(&mut stream).unwrap(), "www.mail.ru");
+        assert_eq!(stream.position(), data.len());
+
+        // pointer to itself
+        assert!(decode_name(&mut InputStream::new(&[0xC0, 0x00])).is_none());
     }
 }
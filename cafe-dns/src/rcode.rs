@@ -2,6 +2,7 @@ use std::convert::TryFrom;
 
 #[repr(u8)]
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ResponseCode {
     /// No error condition
     NoError = 0,
@@ -19,7 +20,19 @@ pub enum ResponseCode {
     /// policy reasons.  For example, a name server may not wish to provide the
     /// information to the particular requester, or a name server may not wish to perform
     /// a particular operation (e.g., zone transfer) for particular data.
-    Refused = 5
+    Refused = 5,
+    /// Some name that ought not to exist, does exist (RFC 2136).
+    YXDomain = 6,
+    /// Some RRset that ought not to exist, does exist (RFC 2136).
+    YXRRSet = 7,
+    /// Some RRset that ought to exist, does not exist (RFC 2136).
+    NXRRSet = 8,
+    /// The server is not authoritative for the zone or the request is not
+    /// authorized, e.g. a TSIG signature failed to verify (RFC 8945).
+    NotAuth = 9,
+    /// A name used in the prerequisite or update section is not
+    /// within the zone denoted by the zone section (RFC 2136).
+    NotZone = 10
 }
 
 impl Default for ResponseCode {
@@ -39,6 +52,11 @@ impl TryFrom<u8> for ResponseCode {
             x if x == ResponseCode::NameError as u8 => Ok(ResponseCode::NameError),
             x if x == ResponseCode::NotImplemented as u8 => Ok(ResponseCode::NotImplemented),
             x if x == ResponseCode::Refused as u8 => Ok(ResponseCode::Refused),
+            x if x == ResponseCode::YXDomain as u8 => Ok(ResponseCode::YXDomain),
+            x if x == ResponseCode::YXRRSet as u8 => Ok(ResponseCode::YXRRSet),
+            x if x == ResponseCode::NXRRSet as u8 => Ok(ResponseCode::NXRRSet),
+            x if x == ResponseCode::NotAuth as u8 => Ok(ResponseCode::NotAuth),
+            x if x == ResponseCode::NotZone as u8 => Ok(ResponseCode::NotZone),
             _ => Err(()),
         }
     }
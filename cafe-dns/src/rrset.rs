@@ -0,0 +1,181 @@
+use std::cmp::Ordering;
+
+use crate::dnssec::compare_names;
+use crate::{ResourceRecord, Type};
+
+/// Records of the same owner name, class and type (RFC 2181, section 5). RDATA is kept
+/// in the order records were added, duplicates are dropped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RRset {
+    name: String,
+    class: u16,
+    code: u16,
+    /// The lowest TTL of the records.
+    ttl: u32,
+    rdatas: Vec<Type>,
+    /// Whether the records were given with different TTLs, which RFC 2181, section 5.2 forbids.
+    ttl_mismatch: bool
+}
+
+impl RRset {
+    pub fn new(record: &ResourceRecord) -> Self {
+        Self {
+            name: record.name().to_string(),
+            class: record.class(),
+            code: record.ttype().code(),
+            ttl: record.ttl(),
+            rdatas: vec![record.ttype().clone()],
+            ttl_mismatch: false
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn class(&self) -> u16 {
+        self.class
+    }
+
+    /// Type code of the records.
+    pub fn code(&self) -> u16 {
+        self.code
+    }
+
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
+
+    pub fn rdatas(&self) -> &[Type] {
+        &self.rdatas
+    }
+
+    pub fn has_ttl_mismatch(&self) -> bool {
+        self.ttl_mismatch
+    }
+
+    /// Whether `record` belongs to the set, owner names are compared case-insensitively.
+    pub fn matches(&self, record: &ResourceRecord) -> bool {
+        self.class == record.class()
+            && self.code == record.ttype().code()
+            && self.name.eq_ignore_ascii_case(record.name())
+    }
+
+    /// Adds RDATA of `record`, `false` if the record doesn't belong to the set. The set takes
+    /// the lowest TTL of its records as RFC 2181, section 5.2 suggests for mismatching ones.
+    pub fn add(&mut self, record: &ResourceRecord) -> bool {
+        if !self.matches(record) {
+            return false;
+        }
+
+        if record.ttl() != self.ttl {
+            self.ttl_mismatch = true;
+            self.ttl = self.ttl.min(record.ttl());
+        }
+
+        let rdata = record.ttype().canonical().rdata();
+        if !self.rdatas.iter().any(|known| known.canonical().rdata() == rdata) {
+            self.rdatas.push(record.ttype().clone());
+        }
+
+        true
+    }
+
+    /// Records of the set, all with its TTL.
+    pub fn records(&self) -> Vec<ResourceRecord> {
+        self.rdatas
+            .iter()
+            .map(|rdata| ResourceRecord::new(&self.name, self.class, self.ttl, rdata.clone()))
+            .collect()
+    }
+
+    /// The set in the canonical form of RFC 4034, section 6.2 with RDATA in the canonical
+    /// order of section 6.3, by the uncompressed wire form.
+    pub fn canonical(&self) -> RRset {
+        let mut rdatas: Vec<(Vec<u8>, Type)> = self
+            .rdatas
+            .iter()
+            .map(|rdata| {
+                let rdata = rdata.canonical();
+                (rdata.rdata(), rdata)
+            })
+            .collect();
+        rdatas.sort_by(|a, b| a.0.cmp(&b.0));
+
+        RRset {
+            name: self.name.to_ascii_lowercase(),
+            rdatas: rdatas.into_iter().map(|(_, rdata)| rdata).collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Canonical order of sets: by owner name as in RFC 4034, section 6.1, then by class and type.
+    pub fn canonical_cmp(&self, other: &RRset) -> Ordering {
+        compare_names(&self.name, &other.name)
+            .then(self.class.cmp(&other.class))
+            .then(self.code.cmp(&other.code))
+    }
+}
+
+/// Groups `records` into sets in the order their first records appear.
+pub fn group(records: &[ResourceRecord]) -> Vec<RRset> {
+    let mut result: Vec<RRset> = Vec::new();
+    for record in records {
+        match result.iter_mut().find(|set| set.matches(record)) {
+            Some(set) => {
+                set.add(record);
+            },
+            None => result.push(RRset::new(record))
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn a(name: &str, ttl: u32, ip: [u8; 4]) -> ResourceRecord {
+        ResourceRecord::new(name, 1, ttl, Type::A { ip: Ipv4Addr::from(ip) })
+    }
+
+    #[test]
+    fn grouping() {
+        let records = [
+            a("www.jabber.ru", 300, [192, 0, 2, 2]),
+            a("jabber.ru", 300, [192, 0, 2, 1]),
+            a("WWW.jabber.ru", 60, [192, 0, 2, 1]),
+            a("www.jabber.ru", 300, [192, 0, 2, 2]),
+        ];
+
+        let sets = group(&records);
+        assert_eq!(sets.len(), 2);
+        assert_eq!(sets[0].name(), "www.jabber.ru");
+        assert_eq!(sets[0].rdatas().len(), 2);
+        assert_eq!(sets[0].ttl(), 60);
+        assert!(sets[0].has_ttl_mismatch());
+        assert!(!sets[1].has_ttl_mismatch());
+        assert_eq!(sets[0].records()[1].ttl(), 60);
+
+        let canonical = sets[0].canonical();
+        assert_eq!(canonical.rdatas()[0], Type::A { ip: Ipv4Addr::new(192, 0, 2, 1) });
+    }
+
+    #[test]
+    fn canonical_order() {
+        let mut sets: Vec<RRset> = ["z.example", "*.z.example", "example", "a.example", "Z.a.example"]
+            .iter()
+            .map(|name| RRset::new(&a(name, 300, [192, 0, 2, 1])))
+            .collect();
+        sets.push(RRset::new(&ResourceRecord::new("a.example", 1, 300, Type::TXT { strings: vec![Vec::new()] })));
+        sets.sort_by(RRset::canonical_cmp);
+
+        let order: Vec<(&str, u16)> = sets.iter().map(|set| (set.name(), set.code())).collect();
+        assert_eq!(
+            order,
+            [("example", 1), ("a.example", 1), ("a.example", 16), ("Z.a.example", 1), ("z.example", 1), ("*.z.example", 1)]
+        );
+    }
+}
@@ -0,0 +1,335 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use data_encoding::BASE64;
+use ring::hmac;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// MAC algorithms this crate supports out of the IANA "TSIG Algorithm Names" registry.
+pub enum Algorithm {
+    HmacSha256,
+    HmacSha384,
+    HmacSha512
+}
+
+impl Algorithm {
+    /// Algorithm name as it is carried in the TSIG record and BIND configuration.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::HmacSha256 => "hmac-sha256",
+            Algorithm::HmacSha384 => "hmac-sha384",
+            Algorithm::HmacSha512 => "hmac-sha512"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Algorithm> {
+        let name = name.trim_end_matches('.');
+        [Algorithm::HmacSha256, Algorithm::HmacSha384, Algorithm::HmacSha512]
+            .iter()
+            .find(|algorithm| algorithm.name().eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    /// Length of the untruncated MAC.
+    pub fn mac_length(&self) -> usize {
+        self.hmac().digest_algorithm().output_len()
+    }
+
+    fn hmac(&self) -> hmac::Algorithm {
+        match self {
+            Algorithm::HmacSha256 => hmac::HMAC_SHA256,
+            Algorithm::HmacSha384 => hmac::HMAC_SHA384,
+            Algorithm::HmacSha512 => hmac::HMAC_SHA512
+        }
+    }
+}
+
+#[derive(Clone)]
+/// A shared secret both sides of a transaction know by the same name.
+pub struct Key {
+    name: String,
+    algorithm: Algorithm,
+    secret: Vec<u8>
+}
+
+impl Key {
+    pub fn new(name: &str, algorithm: Algorithm, secret: &[u8]) -> Self {
+        Self {
+            name: name.trim_end_matches('.').to_ascii_lowercase(),
+            algorithm,
+            secret: secret.to_vec()
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub(crate) fn sign(&self, data: &[u8]) -> Vec<u8> {
+        let key = hmac::Key::new(self.algorithm.hmac(), &self.secret);
+        hmac::sign(&key, data).as_ref().to_vec()
+    }
+
+    /// Parses `key` statements of a BIND configuration, e.g. the output of `tsig-keygen`:
+    ///
+    /// ```text
+    /// key "update.example.org" {
+    ///     algorithm hmac-sha256;
+    ///     secret "MmQxYjY2ZTI1ZmY0NjVkZWNkMmYzZTIwNDhkMDg0ZjM=";
+    /// };
+    /// ```
+    pub fn parse_bind(text: &str) -> Result<Vec<Key>, KeyFileError> {
+        let tokens = tokenize(text)?;
+        let mut tokens = tokens.iter().peekable();
+        let mut keys = Vec::new();
+
+        while let Some((line, token)) = tokens.next() {
+            if *token != Token::Word("key".to_string()) {
+                return Err(KeyFileError::Syntax(*line));
+            }
+
+            let name = match tokens.next() {
+                Some((_, Token::Word(name))) | Some((_, Token::Quoted(name))) => name,
+                _ => return Err(KeyFileError::Syntax(*line))
+            };
+
+            expect(&mut tokens, Token::Symbol('{'), *line)?;
+
+            let mut algorithm = None;
+            let mut secret = None;
+            loop {
+                let (line, token) = tokens.next().ok_or(KeyFileError::Syntax(*line))?;
+                let clause = match token {
+                    Token::Symbol('}') => break,
+                    Token::Word(clause) => clause,
+                    _ => return Err(KeyFileError::Syntax(*line))
+                };
+
+                let value = match tokens.next() {
+                    Some((_, Token::Word(value))) | Some((_, Token::Quoted(value))) => value,
+                    _ => return Err(KeyFileError::Syntax(*line))
+                };
+
+                match clause.as_str() {
+                    "algorithm" => {
+                        let value = Algorithm::from_name(value).ok_or(KeyFileError::UnknownAlgorithm(*line))?;
+                        algorithm = Some(value);
+                    },
+                    "secret" => {
+                        let value: String = value.chars().filter(|ch| !ch.is_whitespace()).collect();
+                        let value = BASE64.decode(value.as_bytes()).map_err(|_| KeyFileError::InvalidSecret(*line))?;
+                        secret = Some(value);
+                    },
+                    _ => return Err(KeyFileError::Syntax(*line))
+                }
+
+                expect(&mut tokens, Token::Symbol(';'), *line)?;
+            }
+
+            expect(&mut tokens, Token::Symbol(';'), *line)?;
+
+            match (algorithm, secret) {
+                (Some(algorithm), Some(secret)) => keys.push(Key::new(name, algorithm, &secret)),
+                _ => return Err(KeyFileError::Incomplete(*line))
+            }
+        }
+
+        Ok(keys)
+    }
+
+    pub fn load_bind<P: AsRef<Path>>(path: P) -> Result<Vec<Key>, KeyFileError> {
+        let text = fs::read_to_string(path).map_err(KeyFileError::Io)?;
+        Key::parse_bind(&text)
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Key")
+            .field("name", &self.name)
+            .field("algorithm", &self.algorithm)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+/// Failure to load keys, line numbers are 1-based.
+pub enum KeyFileError {
+    Io(io::Error),
+    Syntax(usize),
+    UnknownAlgorithm(usize),
+    InvalidSecret(usize),
+    /// A key statement misses either algorithm or secret.
+    Incomplete(usize)
+}
+
+impl fmt::Display for KeyFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyFileError::Io(err) => write!(f, "{}", err),
+            KeyFileError::Syntax(line) => write!(f, "line {}: syntax error", line),
+            KeyFileError::UnknownAlgorithm(line) => write!(f, "line {}: unsupported algorithm", line),
+            KeyFileError::InvalidSecret(line) => write!(f, "line {}: secret is not valid base64", line),
+            KeyFileError::Incomplete(line) => write!(f, "line {}: key must have algorithm and secret", line)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Symbol(char)
+}
+
+fn expect<'a, I>(tokens: &mut I, expected: Token, line: usize) -> Result<(), KeyFileError>
+where
+    I: Iterator<Item = &'a (usize, Token)>
+{
+    match tokens.next() {
+        Some((_, token)) if *token == expected => Ok(()),
+        Some((line, _)) => Err(KeyFileError::Syntax(*line)),
+        None => Err(KeyFileError::Syntax(line))
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>, KeyFileError> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\n' => line += 1,
+            ch if ch.is_whitespace() => { },
+            '#' => {
+                while chars.peek().is_some_and(|ch| *ch != '\n') {
+                    chars.next();
+                }
+            },
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.peek().is_some_and(|ch| *ch != '\n') {
+                    chars.next();
+                }
+            },
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let start = line;
+                let mut previous = ' ';
+                loop {
+                    let ch = chars.next().ok_or(KeyFileError::Syntax(start))?;
+                    if ch == '\n' {
+                        line += 1;
+                    }
+
+                    if previous == '*' && ch == '/' {
+                        break;
+                    }
+
+                    previous = ch;
+                }
+            },
+            '"' => {
+                let start = line;
+                let mut value = String::new();
+                loop {
+                    match chars.next().ok_or(KeyFileError::Syntax(start))? {
+                        '"' => break,
+                        '\n' => {
+                            line += 1;
+                            value.push('\n');
+                        },
+                        ch => value.push(ch)
+                    }
+                }
+
+                tokens.push((start, Token::Quoted(value)));
+            },
+            '{' | '}' | ';' => tokens.push((line, Token::Symbol(ch))),
+            ch => {
+                let mut value = ch.to_string();
+                while let Some(next) = chars.peek() {
+                    if next.is_whitespace() || "{};\"".contains(*next) {
+                        break;
+                    }
+
+                    value.push(*next);
+                    chars.next();
+                }
+
+                tokens.push((line, Token::Word(value)));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tsig_keygen_output() {
+        let text = r#"
+            # generated by tsig-keygen
+            key "update.example.org." {
+                algorithm hmac-sha256;
+                secret "MmQxYjY2ZTI1ZmY0NjVkZWNkMmYzZTIwNDhkMDg0ZjM=";
+            };
+
+            /* transfers */
+            key xfr {
+                algorithm HMAC-SHA512; // upper case is fine too
+                secret "c2VjcmV0";
+            };
+        "#;
+
+        let keys = Key::parse_bind(text).unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].name(), "update.example.org");
+        assert_eq!(keys[0].algorithm(), Algorithm::HmacSha256);
+        assert_eq!(keys[0].secret, b"2d1b66e25ff465decd2f3e2048d084f3");
+        assert_eq!(keys[1].name(), "xfr");
+        assert_eq!(keys[1].algorithm(), Algorithm::HmacSha512);
+        assert_eq!(keys[1].secret, b"secret");
+    }
+
+    #[test]
+    fn parse_errors() {
+        match Key::parse_bind("key a {\n algorithm hmac-md5;\n secret \"c2VjcmV0\";\n};") {
+            Err(KeyFileError::UnknownAlgorithm(2)) => { },
+            other => panic!("Unexpected result: {:?}", other)
+        }
+
+        match Key::parse_bind("key a {\n algorithm hmac-sha256;\n secret \"!!\";\n};") {
+            Err(KeyFileError::InvalidSecret(3)) => { },
+            other => panic!("Unexpected result: {:?}", other)
+        }
+
+        match Key::parse_bind("key a {\n algorithm hmac-sha256;\n};") {
+            Err(KeyFileError::Incomplete(1)) => { },
+            other => panic!("Unexpected result: {:?}", other)
+        }
+
+        match Key::parse_bind("key a {\n algorithm hmac-sha256\n secret \"c2VjcmV0\";\n};") {
+            Err(KeyFileError::Syntax(3)) => { },
+            other => panic!("Unexpected result: {:?}", other)
+        }
+    }
+
+    #[test]
+    fn hmac_sha256() {
+        // RFC 4231, test case 2
+        let key = Key::new("test", Algorithm::HmacSha256, b"Jefe");
+        assert_eq!(
+            key.sign(b"what do ya want for nothing?"),
+            data_encoding::HEXLOWER.decode(b"5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843").unwrap());
+    }
+}
@@ -0,0 +1,533 @@
+mod key;
+
+pub use self::key::{Algorithm, Key, KeyFileError};
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use cafe_common::{BinaryReader, BinaryWriter};
+use cafe_common::stream::{SeekOrigin, Output as OutputStream, Input as InputStream};
+
+use crate::{decode_name, encode_name, read_bytes, Edns, Header, ResourceRecord};
+use crate::update::UPDATE_OPCODE;
+
+/// TYPE value of the TSIG meta-record.
+const TSIG_TYPE: u16 = 250;
+/// TSIG records are always of class ANY.
+const TSIG_CLASS: u16 = 255;
+/// RFC 8945 recommends 300 seconds.
+const DEFAULT_FUDGE: u16 = 300;
+/// RFC 8945, section 5.3.1: no more than 99 unsigned messages in a row within a stream.
+const MAX_UNSIGNED_MESSAGES: usize = 99;
+
+/// Seconds since the UNIX epoch as TSIG counts the time.
+pub fn current_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Transaction signature (RFC 8945). It is carried as the last record of the additional section,
+/// the record's owner is the key name and RDATA is the following:
+/// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+/// /                 Algorithm Name                /
+/// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+/// |                                               |
+/// |          Time Signed                          |
+/// |                       +--+--+--+--+--+--+--+--+
+/// |                       |      Fudge            |
+/// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+/// |          MAC Size     |                       /
+/// +--+--+--+--+--+--+--+--+          MAC          /
+/// /                                               /
+/// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+/// |          Original ID  |      Error            |
+/// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+/// |          Other Len    |                       /
+/// +--+--+--+--+--+--+--+--+      Other Data       /
+/// /                                               /
+/// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+pub struct Tsig {
+    key_name: String,
+    algorithm: String,
+    /// Seconds since the UNIX epoch, 48 bits.
+    time_signed: u64,
+    /// Permitted error in Time Signed in seconds.
+    fudge: u16,
+    mac: Vec<u8>,
+    /// Message ID at the time the message was signed, forwarders may change the header's one.
+    original_id: u16,
+    /// Extended RCODE covering TSIG processing.
+    error: u16,
+    other: Vec<u8>
+}
+
+impl Tsig {
+    pub fn key_name(&self) -> &str {
+        &self.key_name
+    }
+
+    pub fn algorithm(&self) -> &str {
+        &self.algorithm
+    }
+
+    pub fn time_signed(&self) -> u64 {
+        self.time_signed
+    }
+
+    pub fn fudge(&self) -> u16 {
+        self.fudge
+    }
+
+    pub fn mac(&self) -> &[u8] {
+        &self.mac
+    }
+
+    pub fn original_id(&self) -> u16 {
+        self.original_id
+    }
+
+    pub fn error(&self) -> u16 {
+        self.error
+    }
+
+    pub fn other(&self) -> &[u8] {
+        &self.other
+    }
+
+    /// Checks without consuming anything whether the next record in the stream is TSIG.
+    pub fn is_next(stream: &mut InputStream) -> bool {
+        let position = stream.position();
+        let result = match decode_name(stream) {
+            Some(_) => BinaryReader::new(stream).read_u16().map(u16::from_be) == Some(TSIG_TYPE),
+            None => false
+        };
+
+        let _ = stream.seek(SeekOrigin::Begin, position as i64);
+        result
+    }
+
+    pub fn encode(&self, stream: &mut OutputStream) {
+        let mut data = Vec::new();
+        let mut data_stream = OutputStream::new(&mut data);
+        encode_name(&mut data_stream, &self.algorithm);
+
+        let mut writer = BinaryWriter::new(&mut data_stream);
+        writer.write_u16(((self.time_signed >> 32) as u16).to_be());
+        writer.write_u32((self.time_signed as u32).to_be());
+        writer.write_u16(self.fudge.to_be());
+        writer.write_u16((self.mac.len() as u16).to_be());
+        data_stream.write(&self.mac, 0, self.mac.len());
+
+        let mut writer = BinaryWriter::new(&mut data_stream);
+        writer.write_u16(self.original_id.to_be());
+        writer.write_u16(self.error.to_be());
+        writer.write_u16((self.other.len() as u16).to_be());
+        data_stream.write(&self.other, 0, self.other.len());
+
+        encode_name(stream, &self.key_name);
+        let mut writer = BinaryWriter::new(stream);
+        writer.write_u16(TSIG_TYPE.to_be());
+        writer.write_u16(TSIG_CLASS.to_be());
+        writer.write_u32(0);
+        writer.write_u16((data.len() as u16).to_be());
+        stream.write(&data, 0, data.len());
+    }
+
+    pub fn decode(stream: &mut InputStream) -> Option<Tsig> {
+        let key_name = decode_name(stream)?;
+
+        let mut reader = BinaryReader::new(stream);
+        if u16::from_be(reader.read_u16()?) != TSIG_TYPE || u16::from_be(reader.read_u16()?) != TSIG_CLASS {
+            return None;
+        }
+
+        let _ttl = reader.read_u32()?;
+        let data_length = u16::from_be(reader.read_u16()?) as usize;
+        let data_end = stream.position() + data_length;
+
+        let algorithm = decode_name(stream)?;
+        let mut reader = BinaryReader::new(stream);
+        let time_high = u16::from_be(reader.read_u16()?) as u64;
+        let time_low = u32::from_be(reader.read_u32()?) as u64;
+        let fudge = u16::from_be(reader.read_u16()?);
+        let mac_size = u16::from_be(reader.read_u16()?) as usize;
+        let mac = read_bytes(stream, mac_size)?;
+
+        let mut reader = BinaryReader::new(stream);
+        let original_id = u16::from_be(reader.read_u16()?);
+        let error = u16::from_be(reader.read_u16()?);
+        let other_length = u16::from_be(reader.read_u16()?) as usize;
+        let other = read_bytes(stream, other_length)?;
+
+        if stream.position() != data_end {
+            return None;
+        }
+
+        Some(
+            Tsig {
+                key_name,
+                algorithm,
+                time_signed: time_high << 32 | time_low,
+                fudge,
+                mac,
+                original_id,
+                error,
+                other
+            }
+        )
+    }
+
+    /// TSIG Variables digested along with the message (RFC 8945, section 4.3.3).
+    /// Only Time Signed and Fudge are digested for subsequent messages of a stream.
+    fn variables(&self, timers_only: bool) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut stream = OutputStream::new(&mut data);
+        if !timers_only {
+            encode_name(&mut stream, &self.key_name.to_ascii_lowercase());
+            let mut writer = BinaryWriter::new(&mut stream);
+            writer.write_u16(TSIG_CLASS.to_be());
+            writer.write_u32(0);
+            encode_name(&mut stream, &self.algorithm.to_ascii_lowercase());
+        }
+
+        let mut writer = BinaryWriter::new(&mut stream);
+        writer.write_u16(((self.time_signed >> 32) as u16).to_be());
+        writer.write_u32((self.time_signed as u32).to_be());
+        writer.write_u16(self.fudge.to_be());
+        if !timers_only {
+            writer.write_u16(self.error.to_be());
+            writer.write_u16((self.other.len() as u16).to_be());
+            stream.write(&self.other, 0, self.other.len());
+        }
+
+        data
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TsigError {
+    /// The message or its TSIG record is malformed.
+    FormatError,
+    /// The message is expected to be signed but it is not.
+    Unsigned,
+    /// MAC doesn't match (BADSIG).
+    BadSig,
+    /// Unknown key or algorithm (BADKEY).
+    BadKey,
+    /// Time Signed is out of the fudge window (BADTIME).
+    BadTime,
+    /// MAC is truncated more than the local policy allows (BADTRUNC), see `Verifier::set_min_mac_size`.
+    BadTrunc,
+    /// The other side reported the error code in the TSIG record.
+    Rejected(u16)
+}
+
+impl TsigError {
+    /// TSIG RR error code reported back to the other side.
+    pub fn code(&self) -> u16 {
+        match self {
+            TsigError::FormatError | TsigError::Unsigned => 1,
+            TsigError::BadSig => 16,
+            TsigError::BadKey => 17,
+            TsigError::BadTime => 18,
+            TsigError::BadTrunc => 22,
+            TsigError::Rejected(code) => *code
+        }
+    }
+}
+
+impl fmt::Display for TsigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TsigError::FormatError => write!(f, "malformed TSIG"),
+            TsigError::Unsigned => write!(f, "message is not signed"),
+            TsigError::BadSig => write!(f, "BADSIG"),
+            TsigError::BadKey => write!(f, "BADKEY"),
+            TsigError::BadTime => write!(f, "BADTIME"),
+            TsigError::BadTrunc => write!(f, "BADTRUNC"),
+            TsigError::Rejected(code) => write!(f, "rejected by peer with TSIG error {}", code)
+        }
+    }
+}
+
+/// Appends TSIG to encoded messages: a request, a single response or every message
+/// of a multi-message response stream (AXFR/IXFR over TCP).
+pub struct Signer<'a> {
+    key: &'a Key,
+    fudge: u16,
+    /// MAC of the request for the first response, then MAC of the previous signed message.
+    previous_mac: Option<Vec<u8>>,
+    /// Whether a message of the same stream has been signed already.
+    continuation: bool,
+    /// Unsigned messages sent since the last signed one.
+    unsigned: Vec<u8>,
+    unsigned_count: usize
+}
+
+impl<'a> Signer<'a> {
+    /// Signer of a request.
+    pub fn new(key: &'a Key) -> Self {
+        Self {
+            key,
+            fudge: DEFAULT_FUDGE,
+            previous_mac: None,
+            continuation: false,
+            unsigned: Vec::new(),
+            unsigned_count: 0
+        }
+    }
+
+    /// Signer of responses to the request signed with `request_mac`.
+    pub fn for_response(key: &'a Key, request_mac: &[u8]) -> Self {
+        Self {
+            previous_mac: Some(request_mac.to_vec()),
+            ..Self::new(key)
+        }
+    }
+
+    pub fn set_fudge(&mut self, fudge: u16) {
+        self.fudge = fudge
+    }
+
+    /// MAC of the last signed message.
+    pub fn mac(&self) -> Option<&[u8]> {
+        match self.continuation {
+            true => self.previous_mac.as_deref(),
+            false => None
+        }
+    }
+
+    /// Appends TSIG record to the encoded `message` and increments its ARCOUNT.
+    pub fn sign(&mut self, message: &mut Vec<u8>, time_signed: u64) -> Result<(), TsigError> {
+        self.sign_with(message, time_signed, 0, Vec::new())
+    }
+
+    /// Appends TSIG with the BADTIME error to the encoded error response, for a request that
+    /// failed verification with `TsigError::BadTime` (RFC 8945, section 5.2.3). Time Signed is the one
+    /// of the request, `request_time`, so that the client verifies the response with its own clock;
+    /// the server's time `now` goes in Other Data. The signer has to be made with `for_response`.
+    pub fn sign_bad_time(&mut self, message: &mut Vec<u8>, request_time: u64, now: u64) -> Result<(), TsigError> {
+        let other = now.to_be_bytes()[2 ..].to_vec();
+        self.sign_with(message, request_time, TsigError::BadTime.code(), other)
+    }
+
+    fn sign_with(&mut self, message: &mut Vec<u8>, time_signed: u64, error: u16, other: Vec<u8>) -> Result<(), TsigError> {
+        if message.len() < 12 {
+            return Err(TsigError::FormatError);
+        }
+
+        let mut tsig = Tsig {
+            key_name: self.key.name().to_string(),
+            algorithm: self.key.algorithm().name().to_string(),
+            time_signed,
+            fudge: self.fudge,
+            mac: Vec::new(),
+            original_id: u16::from_be_bytes([message[0], message[1]]),
+            error,
+            other
+        };
+
+        let mut data = Vec::new();
+        append_mac(&mut data, self.previous_mac.as_deref());
+        data.extend_from_slice(&self.unsigned);
+        data.extend_from_slice(message);
+        data.extend_from_slice(&tsig.variables(self.continuation));
+        tsig.mac = self.key.sign(&data);
+
+        tsig.encode(&mut OutputStream::new(message));
+        let arcount = u16::from_be_bytes([message[10], message[11]]).wrapping_add(1);
+        message[10 .. 12].copy_from_slice(&arcount.to_be_bytes());
+
+        self.previous_mac = Some(tsig.mac);
+        self.continuation = true;
+        self.unsigned.clear();
+        self.unsigned_count = 0;
+        Ok(())
+    }
+
+    /// Records a message of a stream sent without TSIG, the next signed message covers it.
+    /// The first message of a stream and every hundredth one must be signed.
+    pub fn add_unsigned(&mut self, message: &[u8]) -> Result<(), TsigError> {
+        if !self.continuation || self.unsigned_count >= MAX_UNSIGNED_MESSAGES {
+            return Err(TsigError::Unsigned);
+        }
+
+        self.unsigned.extend_from_slice(message);
+        self.unsigned_count += 1;
+        Ok(())
+    }
+}
+
+/// Verifies TSIG of a request, a single response or a multi-message response stream.
+pub struct Verifier<'a> {
+    key: &'a Key,
+    /// Shortest truncated MAC accepted, on top of the minimum of RFC 8945.
+    min_mac_size: usize,
+    /// MAC of the request for the first response, then MAC of the previous signed message.
+    previous_mac: Option<Vec<u8>>,
+    /// Whether a message of the same stream has been verified already.
+    continuation: bool,
+    /// Unsigned messages received since the last signed one.
+    unsigned: Vec<u8>,
+    unsigned_count: usize
+}
+
+impl<'a> Verifier<'a> {
+    /// Verifier of a request.
+    pub fn new(key: &'a Key) -> Self {
+        Self {
+            key,
+            min_mac_size: 0,
+            previous_mac: None,
+            continuation: false,
+            unsigned: Vec::new(),
+            unsigned_count: 0
+        }
+    }
+
+    /// Verifier of responses to the request signed with `request_mac`.
+    pub fn for_response(key: &'a Key, request_mac: &[u8]) -> Self {
+        Self {
+            previous_mac: Some(request_mac.to_vec()),
+            ..Self::new(key)
+        }
+    }
+
+    /// Local policy on truncation: MACs shorter than `size` octets fail with `TsigError::BadTrunc`.
+    /// Any truncation RFC 8945 allows is accepted by default.
+    pub fn set_min_mac_size(&mut self, size: usize) {
+        self.min_mac_size = size
+    }
+
+    /// MAC of the last verified message.
+    pub fn mac(&self) -> Option<&[u8]> {
+        match self.continuation {
+            true => self.previous_mac.as_deref(),
+            false => None
+        }
+    }
+
+    /// Verifies the next message at the time `now`. Within a stream, unsigned messages are
+    /// accepted after the first signed one and yield `None`; their content is covered by
+    /// the MAC of the next signed message.
+    pub fn verify(&mut self, message: &[u8], now: u64) -> Result<Option<Tsig>, TsigError> {
+        let (position, tsig) = match locate(message).ok_or(TsigError::FormatError)? {
+            Some(located) => located,
+            None => {
+                if !self.continuation || self.unsigned_count >= MAX_UNSIGNED_MESSAGES {
+                    return Err(TsigError::Unsigned);
+                }
+
+                self.unsigned.extend_from_slice(message);
+                self.unsigned_count += 1;
+                return Ok(None);
+            }
+        };
+
+        if !tsig.key_name.eq_ignore_ascii_case(self.key.name())
+            || Algorithm::from_name(&tsig.algorithm) != Some(self.key.algorithm()) {
+            return Err(TsigError::BadKey);
+        }
+
+        if tsig.error != 0 && tsig.mac.is_empty() {
+            return Err(TsigError::Rejected(tsig.error));
+        }
+
+        // The message as it was before signing: without TSIG and with the original ID.
+        let mut unsigned = message[.. position].to_vec();
+        unsigned[0 .. 2].copy_from_slice(&tsig.original_id.to_be_bytes());
+        let arcount = u16::from_be_bytes([unsigned[10], unsigned[11]]) - 1;
+        unsigned[10 .. 12].copy_from_slice(&arcount.to_be_bytes());
+
+        let mut data = Vec::new();
+        append_mac(&mut data, self.previous_mac.as_deref());
+        data.extend_from_slice(&self.unsigned);
+        data.extend_from_slice(&unsigned);
+        data.extend_from_slice(&tsig.variables(self.continuation));
+
+        // RFC 8945, section 5.2.2.1: a MAC longer than the hash, or truncated below half of it
+        // or 10 octets, is malformed. Truncation the protocol allows may still be refused by policy.
+        let expected = self.key.sign(&data);
+        let length = tsig.mac.len();
+        if length > expected.len() || (length < expected.len() && (length < 10 || length < expected.len() / 2)) {
+            return Err(TsigError::FormatError);
+        }
+
+        if length < expected.len().min(self.min_mac_size) {
+            return Err(TsigError::BadTrunc);
+        }
+
+        if !constant_time_eq(&expected[.. length], &tsig.mac) {
+            return Err(TsigError::BadSig);
+        }
+
+        if now.max(tsig.time_signed) - now.min(tsig.time_signed) > tsig.fudge as u64 {
+            return Err(TsigError::BadTime);
+        }
+
+        if tsig.error != 0 {
+            return Err(TsigError::Rejected(tsig.error));
+        }
+
+        self.previous_mac = Some(tsig.mac.clone());
+        self.continuation = true;
+        self.unsigned.clear();
+        self.unsigned_count = 0;
+        Ok(Some(tsig))
+    }
+
+    /// Must be called once the stream is over: its last message is required to be signed.
+    pub fn finish(&self) -> Result<(), TsigError> {
+        match (self.continuation, self.unsigned_count) {
+            (true, 0) => Ok(()),
+            _ => Err(TsigError::Unsigned)
+        }
+    }
+}
+
+/// Finds TSIG at the end of the additional section, returns its offset in the message.
+/// `None` is returned for malformed messages, `Some(None)` for unsigned ones.
+pub fn locate(message: &[u8]) -> Option<Option<(usize, Tsig)>> {
+    let mut stream = InputStream::new(message);
+    let header = Header::decode(&mut stream)?;
+    // Questions are skipped without interpretation, any QTYPE may be signed.
+    for _ in 0 .. header.qdcount() {
+        decode_name(&mut stream)?;
+        read_bytes(&mut stream, 4)?;
+    }
+
+    let in_update = header.opcode() == UPDATE_OPCODE;
+    for _ in 0 .. header.ancount() as usize + header.nscount() as usize {
+        ResourceRecord::decode_with(&mut stream, in_update)?;
+    }
+
+    for i in 0 .. header.arcount() {
+        let position = stream.position();
+        if Tsig::is_next(&mut stream) {
+            // TSIG must be the very last record.
+            if i + 1 != header.arcount() {
+                return None;
+            }
+
+            let tsig = Tsig::decode(&mut stream)?;
+            return Some(Some((position, tsig)));
+        } else if Edns::is_next(&mut stream) {
+            Edns::decode(&mut stream)?;
+        } else {
+            ResourceRecord::decode(&mut stream)?;
+        }
+    }
+
+    Some(None)
+}
+
+fn append_mac(data: &mut Vec<u8>, mac: Option<&[u8]>) {
+    if let Some(mac) = mac {
+        data.extend_from_slice(&(mac.len() as u16).to_be_bytes());
+        data.extend_from_slice(mac);
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -1,12 +1,35 @@
-pub use std::net::Ipv4Addr;
+pub use std::net::{Ipv4Addr, Ipv6Addr};
 
 use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use cafe_common::stream::{Input as InputStream, Output as OutputStream};
+use cafe_common::BinaryWriter;
+use data_encoding::{BASE32HEX_NOPAD, BASE64, HEXLOWER_PERMISSIVE, HEXUPPER};
+
+use crate::dnssec;
 
 #[repr(u16)]
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum QType {
     A = 1,
-    SRV = 33
+    SOA = 6,
+    AAAA = 28,
+    SRV = 33,
+    DS = 43,
+    RRSIG = 46,
+    NSEC = 47,
+    DNSKEY = 48,
+    NSEC3 = 50,
+    NSEC3PARAM = 51,
+    /// Incremental zone transfer (RFC 1995).
+    IXFR = 251,
+    /// Full zone transfer (RFC 5936).
+    AXFR = 252,
+    /// A request for all records (RFC 1035 "*").
+    ANY = 255
 }
 
 impl Default for QType {
@@ -21,21 +44,703 @@ impl TryFrom<u16> for QType {
     fn try_from(v: u16) -> Result<Self, Self::Error> {
         match v {
             x if x == QType::A as u16 => Ok(QType::A),
+            x if x == QType::SOA as u16 => Ok(QType::SOA),
+            x if x == QType::AAAA as u16 => Ok(QType::AAAA),
             x if x == QType::SRV as u16 => Ok(QType::SRV),
+            x if x == QType::DS as u16 => Ok(QType::DS),
+            x if x == QType::RRSIG as u16 => Ok(QType::RRSIG),
+            x if x == QType::NSEC as u16 => Ok(QType::NSEC),
+            x if x == QType::DNSKEY as u16 => Ok(QType::DNSKEY),
+            x if x == QType::NSEC3 as u16 => Ok(QType::NSEC3),
+            x if x == QType::NSEC3PARAM as u16 => Ok(QType::NSEC3PARAM),
+            x if x == QType::IXFR as u16 => Ok(QType::IXFR),
+            x if x == QType::AXFR as u16 => Ok(QType::AXFR),
+            x if x == QType::ANY as u16 => Ok(QType::ANY),
             _ => Err(()),
         }
     }
 }
 
-#[derive(Debug)]
+impl fmt::Display for QType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", type_name(*self as u16))
+    }
+}
+
+impl FromStr for QType {
+    type Err = ();
+
+    /// Parses the mnemonic or TYPE followed by the code, case-insensitively.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        QType::try_from(type_code(s).ok_or(())?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Type {
     A {
         ip: Ipv4Addr
     }, 
+    /// Authoritative name server of the zone.
+    NS {
+        nsdname: String
+    },
+    /// Canonical name the owner name is an alias for.
+    CNAME {
+        cname: String
+    },
+    /// Start of a zone of authority.
+    SOA {
+        /// Name server that was the original or primary source of data for the zone.
+        mname: String,
+        /// Mailbox of the person responsible for the zone.
+        rname: String,
+        /// Version number of the zone, compared with sequence space arithmetic.
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        /// TTL of negative responses (RFC 2308).
+        minimum: u32
+    },
+    /// Name the owner name points to, as in the reverse mapping of addresses.
+    PTR {
+        ptrdname: String
+    },
+    /// Mail exchange, lower preferences are tried first.
+    MX {
+        preference: u16,
+        exchange: String
+    },
+    /// Character strings of up to 255 bytes each, at least one.
+    TXT {
+        strings: Vec<Vec<u8>>
+    },
+    /// IPv6 address of the host (RFC 3596).
+    AAAA {
+        ip: Ipv6Addr
+    },
     SRV {
         priority: u16,
         weight: u16,
         port: u16,
         target: String
+    },
+    /// Delegation signer, digest of a DNSKEY of the child zone (RFC 4034, section 5).
+    DS {
+        key_tag: u16,
+        algorithm: u8,
+        digest_type: u8,
+        digest: Vec<u8>
+    },
+    /// Signature of an RRset (RFC 4034, section 3).
+    RRSIG {
+        type_covered: u16,
+        algorithm: u8,
+        /// Number of labels of the original owner name, wildcard and root labels excluded.
+        labels: u8,
+        original_ttl: u32,
+        /// Seconds since 1970-01-01 UTC modulo 2^32, compared with serial number arithmetic.
+        expiration: u32,
+        inception: u32,
+        key_tag: u16,
+        signer_name: String,
+        signature: Vec<u8>
+    },
+    /// Authenticated denial of existence (RFC 4034, section 4).
+    NSEC {
+        next_domain_name: String,
+        /// Types present at the owner name, in ascending order.
+        types: Vec<u16>
+    },
+    /// Public key of a zone (RFC 4034, section 2).
+    DNSKEY {
+        flags: u16,
+        protocol: u8,
+        algorithm: u8,
+        public_key: Vec<u8>
+    },
+    /// Hashed authenticated denial of existence (RFC 5155, section 3).
+    NSEC3 {
+        hash_algorithm: u8,
+        flags: u8,
+        iterations: u16,
+        salt: Vec<u8>,
+        next_hashed_owner: Vec<u8>,
+        /// Types present at the original owner name, in ascending order.
+        types: Vec<u16>
+    },
+    /// Parameters an authoritative server uses to compute NSEC3 hashes (RFC 5155, section 4).
+    NSEC3PARAM {
+        hash_algorithm: u8,
+        flags: u8,
+        iterations: u16,
+        salt: Vec<u8>
+    },
+    /// A record of a type this crate does not interpret, RDATA is kept as is.
+    Unknown {
+        code: u16,
+        data: Vec<u8>
+    }
+}
+
+impl Type {
+    /// TYPE value of the record.
+    pub fn code(&self) -> u16 {
+        match self {
+            Type::A { ip: _ } => QType::A as u16,
+            Type::NS { .. } => 2,
+            Type::CNAME { .. } => 5,
+            Type::SOA { .. } => QType::SOA as u16,
+            Type::PTR { .. } => 12,
+            Type::MX { .. } => 15,
+            Type::TXT { .. } => 16,
+            Type::AAAA { .. } => QType::AAAA as u16,
+            Type::SRV { priority: _, weight: _, port: _, target: _ } => QType::SRV as u16,
+            Type::DS { .. } => QType::DS as u16,
+            Type::RRSIG { .. } => QType::RRSIG as u16,
+            Type::NSEC { .. } => QType::NSEC as u16,
+            Type::DNSKEY { .. } => QType::DNSKEY as u16,
+            Type::NSEC3 { .. } => QType::NSEC3 as u16,
+            Type::NSEC3PARAM { .. } => QType::NSEC3PARAM as u16,
+            Type::Unknown { code, data: _ } => *code
+        }
+    }
+
+    /// Key tag of DNSKEY, `None` for other types.
+    pub fn key_tag(&self) -> Option<u16> {
+        match self {
+            Type::DNSKEY { .. } => Some(dnssec::key_tag(&self.rdata())),
+            _ => None
+        }
+    }
+
+    /// RDATA in the wire format, names are not compressed.
+    pub fn rdata(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut stream = OutputStream::new(&mut data);
+        match self {
+            Type::A { ip } => {
+                stream.write(&ip.octets(), 0, 4);
+            },
+            Type::AAAA { ip } => {
+                stream.write(&ip.octets(), 0, 16);
+            },
+            Type::NS { nsdname: name } | Type::CNAME { cname: name } | Type::PTR { ptrdname: name } => {
+                crate::encode_name(&mut stream, name);
+            },
+            Type::MX { preference, exchange } => {
+                BinaryWriter::new(&mut stream).write_u16(preference.to_be());
+                crate::encode_name(&mut stream, exchange);
+            },
+            Type::TXT { strings } => {
+                for string in strings {
+                    BinaryWriter::new(&mut stream).write_u8(string.len() as u8);
+                    stream.write(string, 0, string.len());
+                }
+            },
+            Type::SOA { mname, rname, serial, refresh, retry, expire, minimum } => {
+                crate::encode_name(&mut stream, mname);
+                crate::encode_name(&mut stream, rname);
+                let mut writer = BinaryWriter::new(&mut stream);
+                for value in &[serial, refresh, retry, expire, minimum] {
+                    writer.write_u32(value.to_be());
+                }
+            },
+            Type::SRV { priority, weight, port, target } => {
+                let mut writer = BinaryWriter::new(&mut stream);
+                writer.write_u16(priority.to_be());
+                writer.write_u16(weight.to_be());
+                writer.write_u16(port.to_be());
+                crate::encode_name(&mut stream, target);
+            },
+            Type::DS { key_tag, algorithm, digest_type, digest } => {
+                let mut writer = BinaryWriter::new(&mut stream);
+                writer.write_u16(key_tag.to_be());
+                writer.write_u8(*algorithm);
+                writer.write_u8(*digest_type);
+                stream.write(digest, 0, digest.len());
+            },
+            Type::RRSIG { signature, .. } => {
+                data = self.rrsig_data().unwrap_or_default();
+                data.extend_from_slice(signature);
+            },
+            Type::NSEC { next_domain_name, types } => {
+                crate::encode_name(&mut stream, next_domain_name);
+                let bitmap = dnssec::encode_type_bitmap(types);
+                stream.write(&bitmap, 0, bitmap.len());
+            },
+            Type::DNSKEY { flags, protocol, algorithm, public_key } => {
+                let mut writer = BinaryWriter::new(&mut stream);
+                writer.write_u16(flags.to_be());
+                writer.write_u8(*protocol);
+                writer.write_u8(*algorithm);
+                stream.write(public_key, 0, public_key.len());
+            },
+            Type::NSEC3 { hash_algorithm, flags, iterations, salt, next_hashed_owner, types } => {
+                let mut writer = BinaryWriter::new(&mut stream);
+                writer.write_u8(*hash_algorithm);
+                writer.write_u8(*flags);
+                writer.write_u16(iterations.to_be());
+                writer.write_u8(salt.len() as u8);
+                stream.write(salt, 0, salt.len());
+                BinaryWriter::new(&mut stream).write_u8(next_hashed_owner.len() as u8);
+                stream.write(next_hashed_owner, 0, next_hashed_owner.len());
+                let bitmap = dnssec::encode_type_bitmap(types);
+                stream.write(&bitmap, 0, bitmap.len());
+            },
+            Type::NSEC3PARAM { hash_algorithm, flags, iterations, salt } => {
+                let mut writer = BinaryWriter::new(&mut stream);
+                writer.write_u8(*hash_algorithm);
+                writer.write_u8(*flags);
+                writer.write_u16(iterations.to_be());
+                writer.write_u8(salt.len() as u8);
+                stream.write(salt, 0, salt.len());
+            },
+            Type::Unknown { code: _, data: raw } => {
+                stream.write(raw, 0, raw.len());
+            }
+        }
+
+        data
+    }
+
+    /// RRSIG RDATA without the signature, the part covered by the signature itself
+    /// (RFC 4034, section 3.1.8.1) once the record is in canonical form. `None` for other types.
+    pub fn rrsig_data(&self) -> Option<Vec<u8>> {
+        let (type_covered, algorithm, labels, original_ttl, expiration, inception, key_tag, signer_name) = match self {
+            Type::RRSIG { type_covered, algorithm, labels, original_ttl, expiration, inception, key_tag, signer_name, signature: _ } =>
+                (type_covered, algorithm, labels, original_ttl, expiration, inception, key_tag, signer_name),
+            _ => return None
+        };
+
+        let mut data = Vec::new();
+        let mut stream = OutputStream::new(&mut data);
+        let mut writer = BinaryWriter::new(&mut stream);
+        writer.write_u16(type_covered.to_be());
+        writer.write_u8(*algorithm);
+        writer.write_u8(*labels);
+        writer.write_u32(original_ttl.to_be());
+        writer.write_u32(expiration.to_be());
+        writer.write_u32(inception.to_be());
+        writer.write_u16(key_tag.to_be());
+        crate::encode_name(&mut stream, signer_name);
+        Some(data)
+    }
+
+    /// RDATA in the canonical form of RFC 4034, section 6.2: names embedded into RDATA of
+    /// the types listed there are lowercased, except the next name of NSEC (RFC 6840, section 5.1).
+    pub fn canonical(&self) -> Type {
+        match self {
+            Type::NS { nsdname } => Type::NS { nsdname: nsdname.to_ascii_lowercase() },
+            Type::CNAME { cname } => Type::CNAME { cname: cname.to_ascii_lowercase() },
+            Type::PTR { ptrdname } => Type::PTR { ptrdname: ptrdname.to_ascii_lowercase() },
+            Type::MX { preference, exchange } => Type::MX { preference: *preference, exchange: exchange.to_ascii_lowercase() },
+            Type::SOA { mname, rname, serial, refresh, retry, expire, minimum } => Type::SOA {
+                mname: mname.to_ascii_lowercase(),
+                rname: rname.to_ascii_lowercase(),
+                serial: *serial,
+                refresh: *refresh,
+                retry: *retry,
+                expire: *expire,
+                minimum: *minimum
+            },
+            Type::SRV { priority, weight, port, target } => Type::SRV {
+                priority: *priority,
+                weight: *weight,
+                port: *port,
+                target: target.to_ascii_lowercase()
+            },
+            Type::RRSIG { signer_name, .. } => {
+                let mut canonical = self.clone();
+                if let Type::RRSIG { signer_name: name, .. } = &mut canonical {
+                    *name = signer_name.to_ascii_lowercase();
+                }
+
+                canonical
+            },
+            // Other types of the list are kept as wire data, made of names only or of a 16-bit
+            // value followed by names. Length octets of labels never fall into A-Z.
+            Type::Unknown { code: code @ (3 | 4 | 7 | 8 | 9 | 14 | 17 | 39), data } => Type::Unknown {
+                code: *code,
+                data: data.to_ascii_lowercase()
+            },
+            Type::Unknown { code: code @ (18 | 21 | 26 | 36), data } if data.len() > 2 => {
+                let mut data = data.clone();
+                data[2 ..].make_ascii_lowercase();
+                Type::Unknown { code: *code, data }
+            },
+            other => other.clone()
+        }
+    }
+}
+
+/// Mnemonics of the record types this crate prints by name.
+const TYPE_NAMES: &[(u16, &str)] = &[
+    (1, "A"),
+    (2, "NS"),
+    (5, "CNAME"),
+    (6, "SOA"),
+    (12, "PTR"),
+    (15, "MX"),
+    (16, "TXT"),
+    (28, "AAAA"),
+    (33, "SRV"),
+    (35, "NAPTR"),
+    (39, "DNAME"),
+    (43, "DS"),
+    (46, "RRSIG"),
+    (47, "NSEC"),
+    (48, "DNSKEY"),
+    (50, "NSEC3"),
+    (51, "NSEC3PARAM"),
+    (52, "TLSA"),
+    (59, "CDS"),
+    (60, "CDNSKEY"),
+    (64, "SVCB"),
+    (65, "HTTPS"),
+    (251, "IXFR"),
+    (252, "AXFR"),
+    (255, "ANY"),
+    (257, "CAA")
+];
+
+/// Mnemonic of the record type, TYPE followed by the code for types without one (RFC 3597).
+pub(crate) fn type_name(code: u16) -> String {
+    match TYPE_NAMES.iter().find(|(known, _)| *known == code) {
+        Some((_, name)) => name.to_string(),
+        None => format!("TYPE{}", code)
+    }
+}
+
+/// Code of the record type given by its mnemonic or as TYPE followed by the code, case-insensitively.
+pub(crate) fn type_code(name: &str) -> Option<u16> {
+    let name = name.to_ascii_uppercase();
+    if let Some((code, _)) = TYPE_NAMES.iter().find(|(_, known)| *known == name) {
+        return Some(*code);
+    }
+
+    name.strip_prefix("TYPE")?.parse().ok()
+}
+
+fn write_types(f: &mut fmt::Formatter<'_>, types: &[u16]) -> fmt::Result {
+    for ttype in types {
+        write!(f, " {}", type_name(*ttype))?;
+    }
+
+    Ok(())
+}
+
+fn salt_text(salt: &[u8]) -> String {
+    match salt.is_empty() {
+        true => "-".to_string(),
+        false => HEXUPPER.encode(salt)
+    }
+}
+
+/// Character string quoted, with quotes and backslashes escaped and other bytes
+/// outside of printable ASCII written as `\DDD` (RFC 1035, section 5.1).
+fn write_string(f: &mut fmt::Formatter<'_>, string: &[u8]) -> fmt::Result {
+    write!(f, "\"")?;
+    for byte in string {
+        match byte {
+            b'"' | b'\\' => write!(f, "\\{}", *byte as char)?,
+            0x20 ..= 0x7e => write!(f, "{}", *byte as char)?,
+            _ => write!(f, "\\{:03}", byte)?
+        }
+    }
+
+    write!(f, "\"")
+}
+
+/// Character string either quoted or given as a single field, with `\X` and `\DDD` escapes resolved.
+fn parse_string(text: &str) -> Option<Vec<u8>> {
+    let text = match text.strip_prefix('"') {
+        Some(quoted) => quoted.strip_suffix('"')?,
+        None => text
+    };
+
+    let mut string = Vec::new();
+    let mut bytes = text.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            string.push(byte);
+            continue;
+        }
+
+        let escaped = bytes.next()?;
+        if !escaped.is_ascii_digit() {
+            string.push(escaped);
+            continue;
+        }
+
+        let mut value = u32::from(escaped - b'0');
+        for _ in 0 .. 2 {
+            let digit = bytes.next().filter(u8::is_ascii_digit)?;
+            value = value * 10 + u32::from(digit - b'0');
+        }
+
+        string.push(u8::try_from(value).ok()?);
+    }
+
+    match string.len() {
+        0 ..= 255 => Some(string),
+        _ => None
+    }
+}
+
+/// Domain name in the presentation format with the trailing dot dropped. Relative names and `@`
+/// are completed with `origin`, they are invalid without one.
+pub(crate) fn parse_name(text: &str, origin: Option<&str>) -> Option<String> {
+    if text == "@" {
+        return origin.map(str::to_string);
+    }
+
+    let (name, absolute) = match text.strip_suffix('.') {
+        Some(name) if !name.ends_with('.') || name.ends_with("\\.") => (name, true),
+        Some(_) => return None,
+        None => (text, false)
+    };
+
+    if name.is_empty() {
+        return match absolute {
+            true => Some(String::new()),
+            false => None
+        };
+    }
+
+    if name.starts_with('.') || name.contains("..") {
+        return None;
+    }
+
+    match (absolute, origin) {
+        (true, _) => Some(name.to_string()),
+        (false, Some("")) => Some(name.to_string()),
+        (false, Some(origin)) => Some(format!("{}.{}", name, origin)),
+        (false, None) => None
+    }
+}
+
+fn parse_salt(text: &str) -> Option<Vec<u8>> {
+    match text {
+        "-" => Some(Vec::new()),
+        _ => HEXLOWER_PERMISSIVE.decode(text.as_bytes()).ok()
+    }
+}
+
+fn parse_types(fields: &[&str]) -> Option<Vec<u16>> {
+    let mut types = fields.iter().map(|field| type_code(field)).collect::<Option<Vec<u16>>>()?;
+    types.sort_unstable();
+    types.dedup();
+    Some(types)
+}
+
+impl Type {
+    /// RDATA of the type `code` from its presentation format, as `Type` is displayed.
+    /// Names must be absolute.
+    pub fn parse_rdata(code: u16, text: &str) -> Option<Type> {
+        let fields = crate::zone::split_fields(text)?;
+        let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+        Type::parse(code, &fields, None)
+    }
+
+    /// RDATA of the type `code` from its presentation format split into whitespace separated
+    /// `fields`, quoted strings being single fields. Relative names are completed with `origin`, they must be absolute without one.
+    /// The generic syntax of RFC 3597 is accepted for any type.
+    pub(crate) fn parse(code: u16, fields: &[&str], origin: Option<&str>) -> Option<Type> {
+        if fields.first() == Some(&"\\#") {
+            let length: usize = fields.get(1)?.parse().ok()?;
+            let data = HEXLOWER_PERMISSIVE.decode(fields[2 ..].concat().as_bytes()).ok()?;
+            if data.len() != length {
+                return None;
+            }
+
+            // Known types are decoded from their wire form as RFC 3597, section 5 suggests.
+            let mut record = vec![0];
+            record.extend_from_slice(&code.to_be_bytes());
+            record.extend_from_slice(&[0, 1, 0, 0, 0, 0]);
+            record.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            record.extend_from_slice(&data);
+            let record = crate::ResourceRecord::decode(&mut InputStream::new(&record))?;
+            return Some(record.ttype().clone());
+        }
+
+        let number = |index: usize| fields.get(index).and_then(|field| field.parse::<u32>().ok());
+        let byte = |index: usize| number(index).and_then(|value| u8::try_from(value).ok());
+        let short = |index: usize| number(index).and_then(|value| u16::try_from(value).ok());
+        let count = |expected: usize| match fields.len() == expected {
+            true => Some(()),
+            false => None
+        };
+
+        let rdata = match code {
+            1 => {
+                count(1)?;
+                Type::A { ip: fields[0].parse().ok()? }
+            },
+            2 => {
+                count(1)?;
+                Type::NS { nsdname: parse_name(fields[0], origin)? }
+            },
+            5 => {
+                count(1)?;
+                Type::CNAME { cname: parse_name(fields[0], origin)? }
+            },
+            12 => {
+                count(1)?;
+                Type::PTR { ptrdname: parse_name(fields[0], origin)? }
+            },
+            15 => {
+                count(2)?;
+                Type::MX { preference: short(0)?, exchange: parse_name(fields[1], origin)? }
+            },
+            16 if !fields.is_empty() => Type::TXT {
+                strings: fields.iter().map(|field| parse_string(field)).collect::<Option<Vec<Vec<u8>>>>()?
+            },
+            28 => {
+                count(1)?;
+                Type::AAAA { ip: fields[0].parse().ok()? }
+            },
+            6 => {
+                count(7)?;
+                Type::SOA {
+                    mname: parse_name(fields[0], origin)?,
+                    rname: parse_name(fields[1], origin)?,
+                    serial: number(2)?,
+                    refresh: number(3)?,
+                    retry: number(4)?,
+                    expire: number(5)?,
+                    minimum: number(6)?
+                }
+            },
+            33 => {
+                count(4)?;
+                Type::SRV { priority: short(0)?, weight: short(1)?, port: short(2)?, target: parse_name(fields[3], origin)? }
+            },
+            43 if fields.len() >= 4 => Type::DS {
+                key_tag: short(0)?,
+                algorithm: byte(1)?,
+                digest_type: byte(2)?,
+                digest: HEXLOWER_PERMISSIVE.decode(fields[3 ..].concat().as_bytes()).ok()?
+            },
+            46 if fields.len() >= 9 => Type::RRSIG {
+                type_covered: type_code(fields[0])?,
+                algorithm: byte(1)?,
+                labels: byte(2)?,
+                original_ttl: number(3)?,
+                expiration: dnssec::parse_time(fields[4])?,
+                inception: dnssec::parse_time(fields[5])?,
+                key_tag: short(6)?,
+                signer_name: parse_name(fields[7], origin)?,
+                signature: BASE64.decode(fields[8 ..].concat().as_bytes()).ok()?
+            },
+            47 if !fields.is_empty() => Type::NSEC {
+                next_domain_name: parse_name(fields[0], origin)?,
+                types: parse_types(&fields[1 ..])?
+            },
+            48 if fields.len() >= 4 => Type::DNSKEY {
+                flags: short(0)?,
+                protocol: byte(1)?,
+                algorithm: byte(2)?,
+                public_key: BASE64.decode(fields[3 ..].concat().as_bytes()).ok()?
+            },
+            50 if fields.len() >= 5 => Type::NSEC3 {
+                hash_algorithm: byte(0)?,
+                flags: byte(1)?,
+                iterations: short(2)?,
+                salt: parse_salt(fields[3])?,
+                next_hashed_owner: BASE32HEX_NOPAD.decode(fields[4].to_ascii_uppercase().as_bytes()).ok()?,
+                types: parse_types(&fields[5 ..])?
+            },
+            51 => {
+                count(4)?;
+                Type::NSEC3PARAM { hash_algorithm: byte(0)?, flags: byte(1)?, iterations: short(2)?, salt: parse_salt(fields[3])? }
+            },
+            _ => return None
+        };
+
+        Some(rdata)
+    }
+}
+
+impl fmt::Display for Type {
+    /// RDATA in the presentation format of master files, unknown types as of RFC 3597.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::A { ip } => write!(f, "{}", ip),
+            Type::AAAA { ip } => write!(f, "{}", ip),
+            Type::NS { nsdname: name } | Type::CNAME { cname: name } | Type::PTR { ptrdname: name } => {
+                write!(f, "{}", crate::absolute_name(name))
+            },
+            Type::MX { preference, exchange } => write!(f, "{} {}", preference, crate::absolute_name(exchange)),
+            Type::TXT { strings } => {
+                for (index, string) in strings.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, " ")?;
+                    }
+
+                    write_string(f, string)?;
+                }
+
+                Ok(())
+            },
+            Type::SOA { mname, rname, serial, refresh, retry, expire, minimum } => write!(
+                f,
+                "{} {} {} {} {} {} {}",
+                crate::absolute_name(mname), crate::absolute_name(rname), serial, refresh, retry, expire, minimum
+            ),
+            Type::SRV { priority, weight, port, target } => write!(
+                f,
+                "{} {} {} {}",
+                priority, weight, port, crate::absolute_name(target)
+            ),
+            Type::DS { key_tag, algorithm, digest_type, digest } => write!(
+                f,
+                "{} {} {} {}",
+                key_tag, algorithm, digest_type, HEXUPPER.encode(digest)
+            ),
+            Type::RRSIG { type_covered, algorithm, labels, original_ttl, expiration, inception, key_tag, signer_name, signature } => write!(
+                f,
+                "{} {} {} {} {} {} {} {} {}",
+                type_name(*type_covered), algorithm, labels, original_ttl, dnssec::format_time(*expiration),
+                dnssec::format_time(*inception), key_tag, crate::absolute_name(signer_name), BASE64.encode(signature)
+            ),
+            Type::NSEC { next_domain_name, types } => {
+                write!(f, "{}", crate::absolute_name(next_domain_name))?;
+                write_types(f, types)
+            },
+            Type::DNSKEY { flags, protocol, algorithm, public_key } => write!(
+                f,
+                "{} {} {} {}",
+                flags, protocol, algorithm, BASE64.encode(public_key)
+            ),
+            Type::NSEC3 { hash_algorithm, flags, iterations, salt, next_hashed_owner, types } => {
+                write!(
+                    f,
+                    "{} {} {} {} {}",
+                    hash_algorithm, flags, iterations, salt_text(salt),
+                    BASE32HEX_NOPAD.encode(next_hashed_owner).to_ascii_lowercase()
+                )?;
+                write_types(f, types)
+            },
+            Type::NSEC3PARAM { hash_algorithm, flags, iterations, salt } => write!(
+                f,
+                "{} {} {} {}",
+                hash_algorithm, flags, iterations, salt_text(salt)
+            ),
+            Type::Unknown { code: _, data } => {
+                write!(f, "\\# {}", data.len())?;
+                if !data.is_empty() {
+                    write!(f, " ")?;
+                }
+
+                for byte in data {
+                    write!(f, "{:02x}", byte)?;
+                }
+
+                Ok(())
+            }
+        }
     }
 }
@@ -0,0 +1,140 @@
+use cafe_common::stream::Output as OutputStream;
+
+use crate::{Header, QClass, QType, Question, ResourceRecord, Type};
+
+/// OPCODE of the dynamic update message.
+pub const UPDATE_OPCODE: u8 = 5;
+
+/// CLASS values with special meaning in prerequisite and update sections.
+pub(crate) const CLASS_NONE: u16 = 254;
+pub(crate) const CLASS_ANY: u16 = 255;
+
+/// Dynamic update message (RFC 2136). It reuses the sections of a query:
+/// +---------------------+
+/// |        Header       |
+/// +---------------------+
+/// |         Zone        | specifies the zone to be updated
+/// +---------------------+
+/// |     Prerequisite    | RRs or RRsets which must (not) preexist
+/// +---------------------+
+/// |        Update       | RRs or RRsets to be added or deleted
+/// +---------------------+
+/// |   Additional Data   | additional data
+/// +---------------------+
+pub struct Update {
+    header: Header,
+    zone: Question,
+    prerequisites: Vec<ResourceRecord>,
+    updates: Vec<ResourceRecord>
+}
+
+impl Update {
+    pub fn new(id: u16, zone: &str) -> Self {
+        let mut header = Header::new(id);
+        header.set_opcode(UPDATE_OPCODE);
+        header.qdcount = 1;
+
+        Self {
+            header,
+            zone: Question::new(zone, QType::SOA, QClass::IN),
+            prerequisites: Vec::new(),
+            updates: Vec::new()
+        }
+    }
+
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    pub fn header_mut(&mut self) -> &mut Header {
+        &mut self.header
+    }
+
+    pub fn zone(&self) -> &str {
+        self.zone.host_name()
+    }
+
+    pub fn prerequisites(&self) -> &[ResourceRecord] {
+        &self.prerequisites
+    }
+
+    pub fn updates(&self) -> &[ResourceRecord] {
+        &self.updates
+    }
+
+    /// RRset of `rtype` exists, whatever its records are (RFC 2136, section 2.4.1).
+    pub fn require_rrset_exists(&mut self, name: &str, rtype: QType) {
+        self.add_prerequisite(ResourceRecord::new(name, CLASS_ANY, 0, no_data(rtype)));
+    }
+
+    /// RRset exists and consists of exactly these records (RFC 2136, section 2.4.2).
+    /// Call it for every record of the RRset.
+    pub fn require_rrset_matches(&mut self, name: &str, ttype: Type) {
+        self.add_prerequisite(ResourceRecord::new(name, self.zone_class(), 0, ttype));
+    }
+
+    /// There is no RRset of `rtype` (RFC 2136, section 2.4.3).
+    pub fn require_rrset_absent(&mut self, name: &str, rtype: QType) {
+        self.add_prerequisite(ResourceRecord::new(name, CLASS_NONE, 0, no_data(rtype)));
+    }
+
+    /// The name owns at least one RR (RFC 2136, section 2.4.4).
+    pub fn require_name_in_use(&mut self, name: &str) {
+        self.add_prerequisite(ResourceRecord::new(name, CLASS_ANY, 0, no_data(QType::ANY)));
+    }
+
+    /// The name owns no RRs (RFC 2136, section 2.4.5).
+    pub fn require_name_absent(&mut self, name: &str) {
+        self.add_prerequisite(ResourceRecord::new(name, CLASS_NONE, 0, no_data(QType::ANY)));
+    }
+
+    /// Adds the record to an RRset (RFC 2136, section 2.5.1).
+    pub fn add(&mut self, name: &str, ttl: u32, ttype: Type) {
+        self.add_update(ResourceRecord::new(name, self.zone_class(), ttl, ttype));
+    }
+
+    /// Deletes the whole RRset of `rtype` (RFC 2136, section 2.5.2).
+    pub fn delete_rrset(&mut self, name: &str, rtype: QType) {
+        self.add_update(ResourceRecord::new(name, CLASS_ANY, 0, no_data(rtype)));
+    }
+
+    /// Deletes all RRsets of the name (RFC 2136, section 2.5.3).
+    pub fn delete_name(&mut self, name: &str) {
+        self.add_update(ResourceRecord::new(name, CLASS_ANY, 0, no_data(QType::ANY)));
+    }
+
+    /// Deletes the single record from an RRset (RFC 2136, section 2.5.4).
+    pub fn delete_record(&mut self, name: &str, ttype: Type) {
+        self.add_update(ResourceRecord::new(name, CLASS_NONE, 0, ttype));
+    }
+
+    pub fn encode(&self, stream: &mut OutputStream) {
+        self.header.encode(stream);
+        self.zone.encode(stream);
+        for record in self.prerequisites.iter().chain(self.updates.iter()) {
+            record.encode(stream);
+        }
+    }
+
+    fn zone_class(&self) -> u16 {
+        self.zone.qclass() as u16
+    }
+
+    fn add_prerequisite(&mut self, record: ResourceRecord) {
+        self.prerequisites.push(record);
+        self.header.ancount += 1;
+    }
+
+    fn add_update(&mut self, record: ResourceRecord) {
+        self.updates.push(record);
+        self.header.nscount += 1;
+    }
+}
+
+/// Record of the type with empty RDATA as prerequisites and deletions require.
+fn no_data(rtype: QType) -> Type {
+    Type::Unknown {
+        code: rtype as u16,
+        data: Vec::new()
+    }
+}
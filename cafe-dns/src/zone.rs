@@ -0,0 +1,436 @@
+//! Master files (RFC 1035, section 5).
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{classes, types, QClass, ResourceRecord, Type};
+
+/// Nesting of `$INCLUDE` directives beyond which files are assumed to include each other.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Reads records of a master file (RFC 1035, section 5.1):
+///
+/// ```text
+/// $ORIGIN jabber.ru.
+/// $TTL 1h
+/// @           IN  SOA ns1 hostmaster (
+///                     1       ; serial
+///                     7200 3600 1209600 300 )
+///                 NS  ns1
+///                 MX  10 xmpp
+///                 TXT "v=spf1 mx -all"
+/// _xmpp-client._tcp   300 SRV 5 0 5222 xmpp
+/// $INCLUDE cluster.zone
+/// ```
+///
+/// Omitted owner names, TTLs and classes are those of the previous record; without a previous
+/// TTL or `$TTL` the SOA record must come first and its minimum is used. TTLs may be written
+/// with the w, d, h, m and s units. Relative names are invalid until `$ORIGIN` is given, included
+/// files are looked for in the current directory.
+pub fn parse(text: &str) -> Result<Vec<ResourceRecord>, ZoneError> {
+    let mut records = Vec::new();
+    Reader::new(None, None).read(text, &mut records)?;
+    Ok(records)
+}
+
+/// Reads records of a master file with relative names completed with `origin` until a `$ORIGIN`.
+pub fn parse_with_origin(text: &str, origin: &str) -> Result<Vec<ResourceRecord>, ZoneError> {
+    let mut records = Vec::new();
+    Reader::new(Some(origin), None).read(text, &mut records)?;
+    Ok(records)
+}
+
+/// Reads records of a master file, included files are looked for next to it.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<ResourceRecord>, ZoneError> {
+    load_file(path.as_ref(), None)
+}
+
+/// Reads records of a master file with relative names completed with `origin` until a `$ORIGIN`.
+pub fn load_with_origin<P: AsRef<Path>>(path: P, origin: &str) -> Result<Vec<ResourceRecord>, ZoneError> {
+    load_file(path.as_ref(), Some(origin))
+}
+
+fn load_file(path: &Path, origin: Option<&str>) -> Result<Vec<ResourceRecord>, ZoneError> {
+    let text = fs::read_to_string(path).map_err(ZoneError::Io)?;
+    let mut records = Vec::new();
+    Reader::new(origin, path.parent()).read(&text, &mut records)?;
+    Ok(records)
+}
+
+/// Record written on its own as a line of a master file with absolute names, `None` if the text
+/// holds anything else.
+pub(crate) fn parse_record(text: &str) -> Option<ResourceRecord> {
+    let entries = split_entries(text).ok()?;
+    match entries.as_slice() {
+        [entry] if !entry.continued && !entry.fields[0].starts_with('$') => {
+            let fields: Vec<&str> = entry.fields.iter().map(String::as_str).collect();
+            Reader::new(None, None).record(entry, &fields).ok()
+        },
+        _ => None
+    }
+}
+
+/// Fields of RDATA written on its own, `None` if the text spans several entries.
+pub(crate) fn split_fields(text: &str) -> Option<Vec<String>> {
+    let mut entries = split_entries(text).ok()?;
+    match entries.len() {
+        0 => Some(Vec::new()),
+        1 => entries.pop().map(|entry| entry.fields),
+        _ => None
+    }
+}
+
+/// Entry of a master file, records and directives span several lines within parentheses.
+struct Entry {
+    /// Line the entry starts at.
+    line: usize,
+    /// The entry starts with a blank, the owner name is that of the previous record.
+    continued: bool,
+    fields: Vec<String>
+}
+
+/// Splits the text into entries of whitespace separated fields. Comments are dropped, quoted
+/// strings and escaped characters are kept as is.
+fn split_entries(text: &str) -> Result<Vec<Entry>, ZoneError> {
+    let mut entries = Vec::new();
+    let mut entry = Entry { line: 1, continued: false, fields: Vec::new() };
+    let mut field: Option<String> = None;
+    let mut line = 1;
+    let mut depth = 0;
+    let mut chars = text.chars().peekable();
+    let mut line_start = true;
+    while let Some(c) = chars.next() {
+        if line_start && depth == 0 {
+            entry = Entry { line, continued: c == ' ' || c == '\t', fields: Vec::new() };
+        }
+
+        line_start = false;
+        match c {
+            '\\' => {
+                let field = field.get_or_insert_with(String::new);
+                field.push(c);
+                match chars.next() {
+                    Some('\n') | None => return Err(ZoneError::Syntax(line)),
+                    Some(escaped) => field.push(escaped)
+                }
+            },
+            '"' => {
+                let field = field.get_or_insert_with(String::new);
+                field.push(c);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => {
+                            field.push('\\');
+                            match chars.next() {
+                                Some('\n') | None => return Err(ZoneError::Syntax(line)),
+                                Some(escaped) => field.push(escaped)
+                            }
+                        },
+                        Some('\n') | None => return Err(ZoneError::Syntax(line)),
+                        Some(other) => field.push(other)
+                    }
+                }
+
+                field.push('"');
+            },
+            ';' | '(' | ')' | ' ' | '\t' | '\r' | '\n' => {
+                if let Some(field) = field.take() {
+                    entry.fields.push(field);
+                }
+
+                match c {
+                    ';' => {
+                        while chars.peek().is_some_and(|next| *next != '\n') {
+                            chars.next();
+                        }
+                    },
+                    '(' => depth += 1,
+                    ')' if depth == 0 => return Err(ZoneError::Syntax(line)),
+                    ')' => depth -= 1,
+                    '\n' => {
+                        if depth == 0 && !entry.fields.is_empty() {
+                            entries.push(std::mem::replace(&mut entry, Entry { line, continued: false, fields: Vec::new() }));
+                        }
+
+                        line += 1;
+                        line_start = true;
+                    },
+                    _ => ()
+                }
+            },
+            _ => field.get_or_insert_with(String::new).push(c)
+        }
+    }
+
+    if let Some(field) = field.take() {
+        entry.fields.push(field);
+    }
+
+    if depth > 0 {
+        return Err(ZoneError::Syntax(entry.line));
+    }
+
+    if !entry.fields.is_empty() {
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// TTL in seconds, either as a number or with units as in `1h30m`.
+fn parse_ttl(text: &str) -> Option<u32> {
+    if let Ok(ttl) = text.parse() {
+        return Some(ttl);
+    }
+
+    let mut total: u32 = 0;
+    let mut value: Option<u32> = None;
+    for c in text.chars() {
+        match c.to_digit(10) {
+            Some(digit) => value = Some(value.unwrap_or(0).checked_mul(10)?.checked_add(digit)?),
+            None => {
+                let unit = match c.to_ascii_lowercase() {
+                    'w' => 604800,
+                    'd' => 86400,
+                    'h' => 3600,
+                    'm' => 60,
+                    's' => 1,
+                    _ => return None
+                };
+
+                total = total.checked_add(value.take()?.checked_mul(unit)?)?;
+            }
+        }
+    }
+
+    match value {
+        Some(_) => None,
+        None => Some(total)
+    }
+}
+
+/// State carried from one entry of a master file to the next.
+#[derive(Clone)]
+struct Reader {
+    origin: Option<String>,
+    /// Directory included files are looked for in.
+    directory: Option<PathBuf>,
+    default_ttl: Option<u32>,
+    owner: Option<String>,
+    ttl: Option<u32>,
+    class: u16,
+    depth: usize
+}
+
+impl Reader {
+    fn new(origin: Option<&str>, directory: Option<&Path>) -> Reader {
+        Reader {
+            origin: origin.map(|origin| origin.trim_end_matches('.').to_string()),
+            directory: directory.map(Path::to_path_buf),
+            default_ttl: None,
+            owner: None,
+            ttl: None,
+            class: QClass::IN as u16,
+            depth: 0
+        }
+    }
+
+    fn read(&mut self, text: &str, records: &mut Vec<ResourceRecord>) -> Result<(), ZoneError> {
+        for entry in split_entries(text)? {
+            let line = entry.line;
+            let fields: Vec<&str> = entry.fields.iter().map(String::as_str).collect();
+            match fields[0].to_ascii_uppercase().as_str() {
+                "$ORIGIN" if fields.len() == 2 && !entry.continued => {
+                    self.origin = Some(self.name(fields[1]).ok_or(ZoneError::Syntax(line))?);
+                },
+                "$TTL" if fields.len() == 2 && !entry.continued => {
+                    self.default_ttl = Some(parse_ttl(fields[1]).ok_or(ZoneError::Syntax(line))?);
+                },
+                "$INCLUDE" if (2 ..= 3).contains(&fields.len()) && !entry.continued => {
+                    let origin = match fields.get(2) {
+                        Some(origin) => Some(self.name(origin).ok_or(ZoneError::Syntax(line))?),
+                        None => self.origin.clone()
+                    };
+
+                    self.include(fields[1], origin, records).map_err(|err| ZoneError::Include(line, Box::new(err)))?;
+                },
+                directive if directive.starts_with('$') && !entry.continued => return Err(ZoneError::Syntax(line)),
+                _ => records.push(self.record(&entry, &fields)?)
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the file of `$INCLUDE` into `records`, the origin and other state of the including
+    /// file are left as they were.
+    fn include(&self, file: &str, origin: Option<String>, records: &mut Vec<ResourceRecord>) -> Result<(), ZoneError> {
+        let mut reader = self.clone();
+        reader.origin = origin;
+        reader.depth += 1;
+        if reader.depth > MAX_INCLUDE_DEPTH {
+            return Err(ZoneError::Io(io::Error::other("too many nested $INCLUDE")));
+        }
+
+        let path = match &self.directory {
+            Some(directory) => directory.join(file),
+            None => PathBuf::from(file)
+        };
+
+        let text = fs::read_to_string(&path).map_err(ZoneError::Io)?;
+        reader.directory = path.parent().map(Path::to_path_buf);
+        reader.read(&text, records)
+    }
+
+    fn name(&self, text: &str) -> Option<String> {
+        types::parse_name(text, self.origin.as_deref())
+    }
+
+    fn record(&mut self, entry: &Entry, fields: &[&str]) -> Result<ResourceRecord, ZoneError> {
+        let line = entry.line;
+        let (owner, mut position) = match entry.continued {
+            true => (self.owner.clone().ok_or(ZoneError::Syntax(line))?, 0),
+            false => (self.name(fields[0]).ok_or(ZoneError::Syntax(line))?, 1)
+        };
+
+        // TTL and class come in either order before the type, both may be omitted.
+        let mut ttl = None;
+        let mut class = None;
+        while let Some(field) = fields.get(position) {
+            match (parse_ttl(field), classes::class_code(field)) {
+                (Some(value), _) if ttl.is_none() => ttl = Some(value),
+                (_, Some(code)) if class.is_none() => class = Some(code),
+                (_, None) if field.to_ascii_uppercase().starts_with("CLASS") => return Err(ZoneError::UnknownClass(line)),
+                _ => break
+            }
+
+            position += 1;
+        }
+
+        let code = fields.get(position).ok_or(ZoneError::Syntax(line))?;
+        let code = types::type_code(code).ok_or(ZoneError::UnknownType(line))?;
+        let rdata = Type::parse(code, &fields[position + 1 ..], self.origin.as_deref()).ok_or(ZoneError::InvalidRdata(line))?;
+
+        // Before $TTL (RFC 2308, section 4) the SOA minimum was the default TTL.
+        let ttl = match (ttl.or(self.default_ttl).or(self.ttl), &rdata) {
+            (Some(ttl), _) => ttl,
+            (None, Type::SOA { minimum, .. }) => *minimum,
+            (None, _) => return Err(ZoneError::Syntax(line))
+        };
+
+        let class = class.unwrap_or(self.class);
+        self.owner = Some(owner.clone());
+        self.ttl = Some(ttl);
+        self.class = class;
+        Ok(ResourceRecord::new(&owner, class, ttl, rdata))
+    }
+}
+
+#[derive(Debug)]
+/// Failure to read a master file, line numbers are 1-based.
+pub enum ZoneError {
+    Io(io::Error),
+    Syntax(usize),
+    UnknownClass(usize),
+    /// The type has neither a known mnemonic nor the TYPE prefix of RFC 3597.
+    UnknownType(usize),
+    /// RDATA doesn't match its type or the type is only supported in the generic syntax.
+    InvalidRdata(usize),
+    /// Failure to read the file of the `$INCLUDE` directive at the line.
+    Include(usize, Box<ZoneError>)
+}
+
+impl fmt::Display for ZoneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZoneError::Io(err) => write!(f, "{}", err),
+            ZoneError::Syntax(line) => write!(f, "line {}: syntax error", line),
+            ZoneError::UnknownClass(line) => write!(f, "line {}: unknown class", line),
+            ZoneError::UnknownType(line) => write!(f, "line {}: unknown type", line),
+            ZoneError::InvalidRdata(line) => write!(f, "line {}: invalid record data", line),
+            ZoneError::Include(line, err) => write!(f, "line {}: included file: {}", line, err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn displayed_records() {
+        let text = "\
+            ; jabber.ru zone\n\
+            jabber.ru.\t3600\tIN\tSOA\tns1.jabber.ru. hostmaster.jabber.ru. 1 7200 3600 1209600 3600\n\
+            \n\
+            jabber.ru. 3600 IN NS ns1.jabber.ru. ; primary\n\
+            _xmpp-client._tcp.jabber.ru. 300 in TYPE33 5 0 5222 xmpp.jabber.ru.\n\
+            . 0 CLASS3 A 192.0.2.1\n";
+
+        let records = parse(text).unwrap();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0].to_string(), text.lines().nth(1).unwrap());
+        assert_eq!(records[1].ttype(), &Type::NS { nsdname: "ns1.jabber.ru".to_string() });
+        assert_eq!(records[2].ttype(), &Type::SRV { priority: 5, weight: 0, port: 5222, target: "xmpp.jabber.ru".to_string() });
+        assert_eq!(records[3].name(), "");
+        assert_eq!(records[3].class(), 3);
+    }
+
+    #[test]
+    fn entries() {
+        let entries = split_entries("a  ( b ; c )\n  d\n)\n\n\te \"f ; (g)\" \\; h\n").unwrap();
+        let fields: Vec<(usize, bool, Vec<&str>)> = entries
+            .iter()
+            .map(|entry| (entry.line, entry.continued, entry.fields.iter().map(String::as_str).collect()))
+            .collect();
+
+        assert_eq!(fields, [
+            (1, false, vec!["a", "b", "d"]),
+            (5, true, vec!["e", "\"f ; (g)\"", "\\;", "h"])
+        ]);
+
+        assert!(split_entries("a ( b\n").is_err());
+        assert!(split_entries("a ) b\n").is_err());
+        assert!(split_entries("a \"b\nc\"\n").is_err());
+    }
+
+    #[test]
+    fn ttls() {
+        assert_eq!(parse_ttl("3600"), Some(3600));
+        assert_eq!(parse_ttl("1h30m"), Some(5400));
+        assert_eq!(parse_ttl("1W2d3H4m5S"), Some(788645));
+        assert_eq!(parse_ttl("1h30"), None);
+        assert_eq!(parse_ttl("h"), None);
+        assert_eq!(parse_ttl("IN"), None);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |text| match parse(text) {
+            Err(ZoneError::Syntax(line)) => format!("syntax {}", line),
+            Err(ZoneError::UnknownClass(line)) => format!("class {}", line),
+            Err(ZoneError::UnknownType(line)) => format!("type {}", line),
+            Err(ZoneError::InvalidRdata(line)) => format!("rdata {}", line),
+            Err(ZoneError::Include(line, _)) => format!("include {}", line),
+            other => format!("{:?}", other)
+        };
+
+        assert_eq!(error("\njabber.ru 3600 IN A 192.0.2.1"), "syntax 2");
+        assert_eq!(error("jabber.ru. 3600 XX A 192.0.2.1"), "type 1");
+        assert_eq!(error("jabber.ru. 3600 CLASS65536 A 192.0.2.1"), "class 1");
+        assert_eq!(error("jabber.ru. 3600 IN BOGUS 192.0.2.1"), "type 1");
+        assert_eq!(error("jabber.ru. 3600 IN A 192.0.2"), "rdata 1");
+        assert_eq!(error("jabber.ru. 3600 IN MX ns1.jabber.ru."), "rdata 1");
+        assert_eq!(error("jabber.ru. 3600 IN TXT \"v=spf1\\300\""), "rdata 1");
+        assert_eq!(error("jabber.ru. 3600 IN A \\# 3 c00002"), "rdata 1");
+        assert_eq!(error("jabber.ru. IN A 192.0.2.1"), "syntax 1");
+        assert_eq!(error(" 3600 IN A 192.0.2.1"), "syntax 1");
+        assert_eq!(error("$ORIGIN jabber.ru.\n@ 3600 IN SOA ns1 hostmaster (\n1 2 3 4\n"), "syntax 2");
+        assert_eq!(error("$GENERATE 1-2 a$ A 192.0.2.$"), "syntax 1");
+        assert_eq!(error("$TTL 1x"), "syntax 1");
+        assert_eq!(error("$INCLUDE /nonexistent/jabber.ru.zone"), "include 1");
+    }
+}
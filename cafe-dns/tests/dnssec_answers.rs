@@ -0,0 +1,329 @@
+use cafe_common::stream::Output as OutputStream;
+use cafe_dns::{dnssec, QType, ResourceRecord, Response as DnsResponse, Type};
+
+/*
+Domain Name System (response)
+    Transaction ID: 0x1234
+    Flags: 0x8180 Standard query response, No error
+    Questions: 1
+    Answer RRs: 4
+    Authority RRs: 2
+    Additional RRs: 1
+    Queries
+        example.com: type DNSKEY, class IN
+    Answers
+        example.com: type DNSKEY, class IN
+            Time to live: 3600 (1 hour)
+            Data length: 36
+            Flags: 0x0101
+            Protocol: 3
+            Algorithm: Ed25519 (15)
+            Public Key: 974d96a22d224bc01adb915091477d44ccd91c9a41a114300101...
+            [Key id: 3613]
+        example.com: type RRSIG, class IN
+            Time to live: 3600 (1 hour)
+            Data length: 95
+            Type Covered: MX (15)
+            Algorithm: Ed25519 (15)
+            Labels: 2
+            Original TTL: 3600 (1 hour)
+            Signature Expiration: Aug 19, 2015 22:00:00.000000000 UTC
+            Signature Inception: Jul 29, 2015 22:00:00.000000000 UTC
+            Key Tag: 3613
+            Signer's name: example.com
+            Signature: a0bf64ac9ba7ef17c138859c1878bb99a839fe1759aca5b0d798...
+        example.com: type DS, class IN
+            Time to live: 3600 (1 hour)
+            Data length: 36
+            Key id: 3613
+            Algorithm: Ed25519 (15)
+            Digest Type: SHA-256 (2)
+            Public Key: 3aa5ab37efce57f737fc1627013fee07bdf241bd10f3b1964ab5...
+        example.com: type NSEC3PARAM, class IN
+            Time to live: 0
+            Data length: 9
+            Algorithm: SHA-1 (1)
+            Flags: 0x00
+            Iterations: 12
+            Salt Length: 4
+            Salt Value: aabbccdd
+    Authoritative nameservers
+        0p9mhaveqvm6t7vbl5lop2u3t2rp3tom.example.com: type NSEC3, class IN
+            Time to live: 3600 (1 hour)
+            Data length: 39
+            Hash algorithm: SHA-1 (1)
+            NSEC3 flags: 0x01, Opt-Out
+            Iterations: 12
+            Salt length: 4
+            Salt value: aabbccdd
+            Hash length: 20
+            Next hashed owner: 2t7b4g4vsa5smi47k61mv5bv1a22bojr
+            Type Bitmap: NS SOA MX RRSIG DNSKEY NSEC3PARAM
+        alfa.example.com: type NSEC, class IN
+            Time to live: 86400 (1 day)
+            Data length: 55
+            Next Domain Name: host.example.com
+            Type Bitmap: A MX RRSIG NSEC TYPE1234
+    Additional records
+        <Root>: type OPT
+            UDP payload size: 1232
+            Higher bits in extended RCODE: 0x00
+            EDNS0 version: 0
+            Z: 0x8000
+                1... .... .... .... = DO bit: Accepts DNSSEC security RRs
+            Data length: 0
+*/
+const RESPONSE: [u8; 486] = [
+    0x12, 0x34, 0x81, 0x80, 0x00, 0x01, 0x00, 0x04,
+    0x00, 0x02, 0x00, 0x01, 0x07, 0x65, 0x78, 0x61,
+    0x6d, 0x70, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d,
+    0x00, 0x00, 0x30, 0x00, 0x01, 0x07, 0x65, 0x78,
+    0x61, 0x6d, 0x70, 0x6c, 0x65, 0x03, 0x63, 0x6f,
+    0x6d, 0x00, 0x00, 0x30, 0x00, 0x01, 0x00, 0x00,
+    0x0e, 0x10, 0x00, 0x24, 0x01, 0x01, 0x03, 0x0f,
+    0x97, 0x4d, 0x96, 0xa2, 0x2d, 0x22, 0x4b, 0xc0,
+    0x1a, 0xdb, 0x91, 0x50, 0x91, 0x47, 0x7d, 0x44,
+    0xcc, 0xd9, 0x1c, 0x9a, 0x41, 0xa1, 0x14, 0x30,
+    0x01, 0x01, 0x17, 0xd5, 0x2c, 0x59, 0x24, 0x0e,
+    0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65,
+    0x03, 0x63, 0x6f, 0x6d, 0x00, 0x00, 0x2e, 0x00,
+    0x01, 0x00, 0x00, 0x0e, 0x10, 0x00, 0x5f, 0x00,
+    0x0f, 0x0f, 0x02, 0x00, 0x00, 0x0e, 0x10, 0x55,
+    0xd4, 0xfc, 0x60, 0x55, 0xb9, 0x4c, 0xe0, 0x0e,
+    0x1d, 0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c,
+    0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00, 0xa0, 0xbf,
+    0x64, 0xac, 0x9b, 0xa7, 0xef, 0x17, 0xc1, 0x38,
+    0x85, 0x9c, 0x18, 0x78, 0xbb, 0x99, 0xa8, 0x39,
+    0xfe, 0x17, 0x59, 0xac, 0xa5, 0xb0, 0xd7, 0x98,
+    0xcf, 0x1a, 0xb1, 0xe9, 0x8d, 0x07, 0x91, 0x02,
+    0xf4, 0xdd, 0xb3, 0x36, 0x8f, 0x0f, 0xe4, 0x0b,
+    0xb3, 0x77, 0xf1, 0xf0, 0x0e, 0x0c, 0xdd, 0xed,
+    0xb7, 0x99, 0x16, 0x7d, 0x56, 0xb6, 0xe9, 0x32,
+    0x78, 0x30, 0x72, 0xba, 0x8d, 0x02, 0x07, 0x65,
+    0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x03, 0x63,
+    0x6f, 0x6d, 0x00, 0x00, 0x2b, 0x00, 0x01, 0x00,
+    0x00, 0x0e, 0x10, 0x00, 0x24, 0x0e, 0x1d, 0x0f,
+    0x02, 0x3a, 0xa5, 0xab, 0x37, 0xef, 0xce, 0x57,
+    0xf7, 0x37, 0xfc, 0x16, 0x27, 0x01, 0x3f, 0xee,
+    0x07, 0xbd, 0xf2, 0x41, 0xbd, 0x10, 0xf3, 0xb1,
+    0x96, 0x4a, 0xb5, 0x5c, 0x78, 0xe7, 0x9a, 0x30,
+    0x4b, 0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c,
+    0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00, 0x00, 0x33,
+    0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x09,
+    0x01, 0x00, 0x00, 0x0c, 0x04, 0xaa, 0xbb, 0xcc,
+    0xdd, 0x20, 0x30, 0x70, 0x39, 0x6d, 0x68, 0x61,
+    0x76, 0x65, 0x71, 0x76, 0x6d, 0x36, 0x74, 0x37,
+    0x76, 0x62, 0x6c, 0x35, 0x6c, 0x6f, 0x70, 0x32,
+    0x75, 0x33, 0x74, 0x32, 0x72, 0x70, 0x33, 0x74,
+    0x6f, 0x6d, 0x07, 0x65, 0x78, 0x61, 0x6d, 0x70,
+    0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00, 0x00,
+    0x32, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10, 0x00,
+    0x27, 0x01, 0x01, 0x00, 0x0c, 0x04, 0xaa, 0xbb,
+    0xcc, 0xdd, 0x14, 0x17, 0x4e, 0xb2, 0x40, 0x9f,
+    0xe2, 0x8b, 0xcb, 0x48, 0x87, 0xa1, 0x83, 0x6f,
+    0x95, 0x7f, 0x0a, 0x84, 0x25, 0xe2, 0x7b, 0x00,
+    0x07, 0x22, 0x01, 0x00, 0x00, 0x00, 0x02, 0x90,
+    0x04, 0x61, 0x6c, 0x66, 0x61, 0x07, 0x65, 0x78,
+    0x61, 0x6d, 0x70, 0x6c, 0x65, 0x03, 0x63, 0x6f,
+    0x6d, 0x00, 0x00, 0x2f, 0x00, 0x01, 0x00, 0x01,
+    0x51, 0x80, 0x00, 0x37, 0x04, 0x68, 0x6f, 0x73,
+    0x74, 0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c,
+    0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00, 0x00, 0x06,
+    0x40, 0x01, 0x00, 0x00, 0x00, 0x03, 0x04, 0x1b,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x20, 0x00, 0x00, 0x29, 0x04, 0xd0,
+    0x00, 0x00, 0x80, 0x00, 0x00, 0x00
+];
+
+/// Offset of the answer section, all the records are encoded without compression.
+const ANSWERS_START: usize = 29;
+/// Length of the OPT record ending the message.
+const OPT_LENGTH: usize = 11;
+
+#[test]
+fn decode_response() {
+    let response = DnsResponse::decode(&RESPONSE).unwrap();
+    assert!(matches!(response.questions()[0].qtype(), QType::DNSKEY));
+    assert!(response.edns().unwrap().dnssec_ok());
+
+    let answers = response.answers();
+    assert_eq!(answers.len(), 4);
+    match answers[0].ttype() {
+        Type::DNSKEY { flags, protocol, algorithm, public_key } => {
+            assert_eq!(*flags, 257);
+            assert_eq!(*protocol, 3);
+            assert_eq!(*algorithm, 15);
+            assert_eq!(public_key.len(), 32);
+        }
+        other => panic!("Unexpected record: {:?}", other),
+    }
+
+    assert_eq!(answers[0].ttype().key_tag(), Some(3613));
+    assert_eq!(answers[1].ttype().key_tag(), None);
+
+    match answers[1].ttype() {
+        Type::RRSIG { type_covered, labels, original_ttl, expiration, inception, key_tag, signer_name, signature, .. } => {
+            assert_eq!(*type_covered, 15);
+            assert_eq!(*labels, 2);
+            assert_eq!(*original_ttl, 3600);
+            assert_eq!(*expiration, 1440021600);
+            assert_eq!(*inception, 1438207200);
+            assert_eq!(*key_tag, 3613);
+            assert_eq!(signer_name, "example.com");
+            assert_eq!(signature.len(), 64);
+        }
+        other => panic!("Unexpected record: {:?}", other),
+    }
+
+    match response.authorities()[0].ttype() {
+        Type::NSEC3 { hash_algorithm, flags, iterations, salt, next_hashed_owner, types } => {
+            assert_eq!(*hash_algorithm, 1);
+            assert_eq!(*flags, 1);
+            assert_eq!(*iterations, 12);
+            assert_eq!(salt, &[0xaa, 0xbb, 0xcc, 0xdd]);
+            assert_eq!(next_hashed_owner.len(), 20);
+            assert_eq!(types, &[2, 6, 15, 46, 48, 51]);
+        }
+        other => panic!("Unexpected record: {:?}", other),
+    }
+
+    match response.authorities()[1].ttype() {
+        Type::NSEC { next_domain_name, types } => {
+            assert_eq!(next_domain_name, "host.example.com");
+            assert_eq!(types, &[1, 15, 46, 47, 1234]);
+        }
+        other => panic!("Unexpected record: {:?}", other),
+    }
+}
+
+#[test]
+fn presentation_format() {
+    let response = DnsResponse::decode(&RESPONSE).unwrap();
+    let lines: Vec<String> = response.answers().iter().chain(response.authorities()).map(ResourceRecord::to_string).collect();
+
+    assert_eq!(lines, [
+        "example.com.\t3600\tIN\tDNSKEY\t257 3 15 l02Woi0iS8Aa25FQkUd9RMzZHJpBoRQwAQEX1SxZJA4=",
+        "example.com.\t3600\tIN\tRRSIG\tMX 15 2 3600 20150819220000 20150729220000 3613 example.com. \
+            oL9krJun7xfBOIWcGHi7mag5/hdZrKWw15jPGrHpjQeRAvTdszaPD+QLs3fx8A4M3e23mRZ9VrbpMngwcrqNAg==",
+        "example.com.\t3600\tIN\tDS\t3613 15 2 3AA5AB37EFCE57F737FC1627013FEE07BDF241BD10F3B1964AB55C78E79A304B",
+        "example.com.\t0\tIN\tNSEC3PARAM\t1 0 12 AABBCCDD",
+        "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom.example.com.\t3600\tIN\tNSEC3\t1 1 12 AABBCCDD \
+            2t7b4g4vsa5smi47k61mv5bv1a22bojr NS SOA MX RRSIG DNSKEY NSEC3PARAM",
+        "alfa.example.com.\t86400\tIN\tNSEC\thost.example.com. A MX RRSIG NSEC TYPE1234",
+    ]);
+}
+
+#[test]
+fn encode_records() {
+    let response = DnsResponse::decode(&RESPONSE).unwrap();
+
+    let mut data = Vec::new();
+    let mut stream = OutputStream::new(&mut data);
+    for record in response.answers().iter().chain(response.authorities()) {
+        record.encode(&mut stream);
+    }
+
+    assert_eq!(data[..], RESPONSE[ANSWERS_START .. RESPONSE.len() - OPT_LENGTH]);
+}
+
+#[test]
+fn rsasha1_key_tag() {
+    // RFC 4034, section 5.4
+    let public_key = data_encoding::BASE64.decode(
+        b"AQOeiiR0GOMYkDshWoSKz9XzfwJr1AYtsmx3TGkJaNXVbfi/2pHm822aJ5iI9BMzNXxeYCmZDRD99WYwYqUSdjMmmAphXdvx\
+          egXd/M5+X7OrzKBaMbCVdFLUUh6DhweJBjEVv5f2wwjM9XzcnOf+EPbtG9DMBmADjFDc2w/rljwvFw==").unwrap();
+
+    let dnskey = Type::DNSKEY { flags: 256, protocol: 3, algorithm: 5, public_key };
+    assert_eq!(dnskey.key_tag(), Some(60485));
+}
+
+#[test]
+fn canonical_form() {
+    let srv = Type::SRV { priority: 0, weight: 5, port: 5222, target: "XMPP.Jabber.RU".to_string() };
+    let record = ResourceRecord::new("_xmpp-client._tcp.Jabber.RU", 1, 300, srv).canonical(3600);
+    assert_eq!(record.name(), "_xmpp-client._tcp.jabber.ru");
+    assert_eq!(record.ttl(), 3600);
+    match record.ttype() {
+        Type::SRV { target, .. } => assert_eq!(target, "xmpp.jabber.ru"),
+        other => panic!("Unexpected record: {:?}", other),
+    }
+
+    // RFC 6840, section 5.1: the next name of NSEC keeps its case.
+    let nsec = Type::NSEC { next_domain_name: "Host.Example.COM".to_string(), types: vec![1] };
+    assert_eq!(nsec.canonical(), nsec);
+
+    let response = DnsResponse::decode(&RESPONSE).unwrap();
+    let rrsig = match response.answers()[1].ttype() {
+        Type::RRSIG { signer_name, .. } => {
+            let mut rrsig = response.answers()[1].ttype().clone();
+            if let Type::RRSIG { signer_name: name, .. } = &mut rrsig {
+                *name = signer_name.to_ascii_uppercase();
+            }
+
+            rrsig
+        }
+        other => panic!("Unexpected record: {:?}", other),
+    };
+
+    let covered = rrsig.canonical().rrsig_data().unwrap();
+    assert_eq!(covered[..], RESPONSE[ANSWERS_START + 82 .. ANSWERS_START + 82 + 31]);
+}
+
+#[test]
+fn verify_ed25519_signature() {
+    // RFC 8080, section 6.1
+    let response = DnsResponse::decode(&RESPONSE).unwrap();
+    let dnskey = response.answers()[0].ttype();
+    let rrsig = response.answers()[1].ttype();
+
+    let mx = Type::MX { preference: 10, exchange: "mail.example.com".to_string() };
+    let rrset = [ResourceRecord::new("example.com", 1, 3600, mx)];
+
+    let signature = match rrsig {
+        Type::RRSIG { signature, .. } => signature.clone(),
+        other => panic!("Unexpected record: {:?}", other),
+    };
+
+    let data = dnssec::signed_data(rrsig, &rrset).unwrap();
+    assert!(dnssec::verify(dnskey, &data, &signature));
+
+    let mut forged = signature.clone();
+    forged[0] ^= 1;
+    assert!(!dnssec::verify(dnskey, &data, &forged));
+
+    // The signature covers the original TTL rather than the one of the record.
+    let expired = [ResourceRecord::new("EXAMPLE.com", 1, 17, rrset[0].ttype().clone())];
+    assert_eq!(dnssec::signed_data(rrsig, &expired).unwrap(), data);
+
+    // Names in RDATA are lowercased too.
+    let mx = Type::MX { preference: 10, exchange: "Mail.EXAMPLE.com".to_string() };
+    let uppercase = [ResourceRecord::new("example.com", 1, 3600, mx)];
+    assert_eq!(dnssec::signed_data(rrsig, &uppercase).unwrap(), data);
+}
+
+#[test]
+fn ds_digest() {
+    let response = DnsResponse::decode(&RESPONSE).unwrap();
+    let digest = match response.answers()[2].ttype() {
+        Type::DS { digest, .. } => digest.clone(),
+        other => panic!("Unexpected record: {:?}", other),
+    };
+
+    assert_eq!(dnssec::ds_digest("example.com", response.answers()[0].ttype(), 2), Some(digest));
+    assert_eq!(dnssec::ds_digest("example.com", response.answers()[0].ttype(), 3), None);
+}
+
+#[test]
+fn canonical_rdata() {
+    let dname = Type::Unknown { code: 39, data: b"\x04XMPP\x06Jabber\x02ru\x00".to_vec() };
+    assert_eq!(dname.canonical(), Type::Unknown { code: 39, data: b"\x04xmpp\x06jabber\x02ru\x00".to_vec() });
+
+    // The preference of AFSDB is left alone.
+    let afsdb = Type::Unknown { code: 18, data: b"\x00\x41\x03AFS\x00".to_vec() };
+    assert_eq!(afsdb.canonical(), Type::Unknown { code: 18, data: b"\x00\x41\x03afs\x00".to_vec() });
+
+    let unknown = Type::Unknown { code: 65280, data: b"ABC".to_vec() };
+    assert_eq!(unknown.canonical(), unknown);
+}
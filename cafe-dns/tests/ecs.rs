@@ -0,0 +1,127 @@
+use cafe_common::stream::Output as OutputStream;
+use cafe_dns::{ClientSubnet, Edns, EdnsOption, QClass, QType, Request as DnsRequest, Response as DnsResponse, Type};
+
+/*
+Domain Name System (query)
+    Transaction ID: 0x0001
+    Flags: 0x0100 Standard query
+    Questions: 1
+    Answer RRs: 0
+    Authority RRs: 0
+    Additional RRs: 1
+    Queries
+        www.mail.ru: type A, class IN
+    Additional records
+        <Root>: type OPT
+            Name: <Root>
+            Type: OPT (41)
+            UDP payload size: 1232
+            Higher bits in extended RCODE: 0x00
+            EDNS0 version: 0
+            Z: 0x0000
+            Data length: 11
+            Option: CSUBNET - Client subnet
+                Option Code: CSUBNET - Client subnet (8)
+                Option Length: 7
+                Family: IPv4 (1)
+                Source Netmask: 24
+                Scope Netmask: 0
+                Client Subnet: 192.0.2.0
+*/
+const REQUEST: [u8; 51] = [
+    0x00, 0x01, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x01, 0x03, 0x77, 0x77, 0x77,
+    0x04, 0x6d, 0x61, 0x69, 0x6c, 0x02, 0x72, 0x75,
+    0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x29,
+    0x04, 0xd0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0b,
+    0x00, 0x08, 0x00, 0x07, 0x00, 0x01, 0x18, 0x00,
+    0xc0, 0x00, 0x02
+];
+
+/*
+Domain Name System (response)
+    Transaction ID: 0x0001
+    Flags: 0x8180 Standard query response, No error
+    Questions: 1
+    Answer RRs: 1
+    Authority RRs: 0
+    Additional RRs: 1
+    Queries
+        www.mail.ru: type A, class IN
+    Answers
+        www.mail.ru: type A, class IN, addr 217.69.139.70
+    Additional records
+        <Root>: type OPT
+            UDP payload size: 512
+            Data length: 11
+            Option: CSUBNET - Client subnet
+                Family: IPv4 (1)
+                Source Netmask: 24
+                Scope Netmask: 16
+                Client Subnet: 192.0.2.0
+*/
+const RESPONSE: [u8; 67] = [
+    0x00, 0x01, 0x81, 0x80, 0x00, 0x01, 0x00, 0x01,
+    0x00, 0x00, 0x00, 0x01, 0x03, 0x77, 0x77, 0x77,
+    0x04, 0x6d, 0x61, 0x69, 0x6c, 0x02, 0x72, 0x75,
+    0x00, 0x00, 0x01, 0x00, 0x01, 0xc0, 0x0c, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3c, 0x00,
+    0x04, 0xd9, 0x45, 0x8b, 0x46, 0x00, 0x00, 0x29,
+    0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0b,
+    0x00, 0x08, 0x00, 0x07, 0x00, 0x01, 0x18, 0x10,
+    0xc0, 0x00, 0x02
+];
+
+#[test]
+fn encode_request() {
+    let mut edns = Edns::new();
+    edns.add_option(EdnsOption::ClientSubnet("192.0.2.0/24".parse().unwrap()));
+
+    let mut request = DnsRequest::new(1);
+    request.header_mut().set_rd(true);
+    request.add_question("www.mail.ru", QType::A, QClass::IN);
+    request.set_edns(Some(edns));
+    assert_eq!(request.header().arcount(), 1);
+
+    let mut result: Vec<u8> = Vec::new();
+    let mut stream = OutputStream::new(&mut result);
+    request.encode(&mut stream);
+    assert_eq!(&result[..], &REQUEST[..]);
+}
+
+#[test]
+fn decode_response() {
+    let response = DnsResponse::decode(&RESPONSE).unwrap();
+    assert_eq!(response.answers().len(), 1);
+    assert!(response.additionals().is_empty());
+    match response.answers()[0].ttype() {
+        Type::A { ip } => assert_eq!(*ip, std::net::Ipv4Addr::new(217, 69, 139, 70)),
+        _ => panic!("Unexpected type!")
+    }
+
+    let edns = response.edns().unwrap();
+    assert_eq!(edns.udp_payload_size(), 512);
+    assert_eq!(edns.version(), 0);
+    assert!(!edns.dnssec_ok());
+
+    let subnet = edns.client_subnet().unwrap();
+    assert_eq!(subnet.family(), 1);
+    assert_eq!(subnet.source_prefix(), 24);
+    assert_eq!(subnet.scope_prefix(), 16);
+    assert_eq!(subnet.address(), "192.0.2.0".parse::<std::net::IpAddr>().unwrap());
+    assert!(subnet.matches(&"192.0.99.1".parse().unwrap()));
+}
+
+#[test]
+fn decode_opt_out() {
+    let mut edns = Edns::new();
+    edns.add_option(EdnsOption::ClientSubnet(ClientSubnet::opt_out()));
+
+    let mut data = Vec::new();
+    edns.encode(&mut OutputStream::new(&mut data));
+
+    let mut stream = cafe_common::stream::Input::new(&data);
+    let decoded = Edns::decode(&mut stream).unwrap();
+    assert_eq!(decoded, edns);
+    assert_eq!(decoded.client_subnet().unwrap().source_prefix(), 0);
+}
@@ -0,0 +1,93 @@
+#![cfg(feature = "serde")]
+
+use cafe_dns::json::Message;
+use cafe_dns::{QClass, QType, Question, ResourceRecord, ResponseCode, Type};
+use serde_json::json;
+
+/// SRV response for _xmpp-client._tcp.jabber.ru, as in srv_answers.rs.
+const RESPONSE: [u8; 112] = [
+    0x00, 0x01, 0x81, 0x80, 0x00, 0x01, 0x00, 0x02,
+    0x00, 0x00, 0x00, 0x00, 0x0c, 0x5f, 0x78, 0x6d,
+    0x70, 0x70, 0x2d, 0x63, 0x6c, 0x69, 0x65, 0x6e,
+    0x74, 0x04, 0x5f, 0x74, 0x63, 0x70, 0x06, 0x6a,
+    0x61, 0x62, 0x62, 0x65, 0x72, 0x02, 0x72, 0x75,
+    0x00, 0x00, 0x21, 0x00, 0x01, 0xc0, 0x0c, 0x00,
+    0x21, 0x00, 0x01, 0x00, 0x00, 0x53, 0x1e, 0x00,
+    0x11, 0x00, 0x00, 0x00, 0x00, 0x14, 0x66, 0x06,
+    0x6a, 0x61, 0x62, 0x62, 0x65, 0x72, 0x02, 0x72,
+    0x75, 0x00, 0xc0, 0x0c, 0x00, 0x21, 0x00, 0x01,
+    0x00, 0x00, 0x53, 0x1e, 0x00, 0x1a, 0x00, 0x0a,
+    0x00, 0x00, 0x01, 0xbb, 0x08, 0x61, 0x6c, 0x6c,
+    0x70, 0x6f, 0x72, 0x74, 0x73, 0x06, 0x6a, 0x61,
+    0x62, 0x62, 0x65, 0x72, 0x02, 0x72, 0x75, 0x00
+];
+
+#[test]
+fn rfc8427_message() {
+    let message = Message::decode(&RESPONSE, false).unwrap();
+    assert_eq!(serde_json::to_value(&message).unwrap(), json!({
+        "ID": 1, "QR": 1, "Opcode": 0, "AA": 0, "TC": 0, "RD": 1, "RA": 1, "AD": 0, "CD": 0, "RCODE": 0,
+        "QDCOUNT": 1, "ANCOUNT": 2, "NSCOUNT": 0, "ARCOUNT": 0,
+        "QNAME": "_xmpp-client._tcp.jabber.ru.", "QTYPE": 33, "QTYPEname": "SRV", "QCLASS": 1, "QCLASSname": "IN",
+        "answerRRs": [
+            {
+                "NAME": "_xmpp-client._tcp.jabber.ru.", "TYPE": 33, "TYPEname": "SRV", "CLASS": 1, "CLASSname": "IN",
+                "TTL": 21278, "rdataSRV": "0 0 5222 jabber.ru."
+            },
+            {
+                "NAME": "_xmpp-client._tcp.jabber.ru.", "TYPE": 33, "TYPEname": "SRV", "CLASS": 1, "CLASSname": "IN",
+                "TTL": 21278, "rdataSRV": "10 0 443 allports.jabber.ru."
+            }
+        ]
+    }));
+
+    let text = serde_json::to_string(&message).unwrap();
+    let parsed: Message = serde_json::from_str(&text).unwrap();
+    assert_eq!(parsed, message);
+    assert!(matches!(parsed.qtype(), Some(QType::SRV)));
+    assert_eq!(parsed.qname(), Some("_xmpp-client._tcp.jabber.ru."));
+    assert_eq!(parsed.answers()[1].record().unwrap().to_string(), "_xmpp-client._tcp.jabber.ru.\t21278\tIN\tSRV\t10 0 443 allports.jabber.ru.");
+    assert!(parsed.octets().is_none());
+}
+
+#[test]
+fn message_octets() {
+    let message = Message::decode(&RESPONSE, true).unwrap();
+    let value = serde_json::to_value(&message).unwrap();
+    assert_eq!(value["messageOctetsHEX"].as_str().unwrap(), data_encoding::HEXUPPER.encode(&RESPONSE));
+
+    let parsed: Message = serde_json::from_value(value).unwrap();
+    assert_eq!(parsed.octets().unwrap(), RESPONSE);
+    assert_eq!(parsed.response().unwrap().answers().len(), 2);
+}
+
+#[test]
+fn unknown_rdata() {
+    let record: ResourceRecord = "jabber.ru. 3600 IN CAA \\# 15 036e7331066a616262657202727500".parse().unwrap();
+    let value = serde_json::to_value(cafe_dns::json::Record::new(&record)).unwrap();
+    assert_eq!(value, json!({
+        "NAME": "jabber.ru.", "TYPE": 257, "TYPEname": "CAA", "CLASS": 1, "CLASSname": "IN", "TTL": 3600,
+        "RDATAHEX": "036E7331066A616262657202727500"
+    }));
+
+    let parsed: cafe_dns::json::Record = serde_json::from_value(value).unwrap();
+    assert_eq!(parsed.record().unwrap(), record);
+
+    let parsed: cafe_dns::json::Record = serde_json::from_value(json!({ "NAME": "jabber.ru.", "TYPE": 1, "CLASS": 1, "TTL": 60 })).unwrap();
+    assert!(parsed.record().is_none());
+}
+
+#[test]
+fn derived() {
+    let record = ResourceRecord::new("jabber.ru", 1, 300, Type::A { ip: "192.0.2.1".parse().unwrap() });
+    let text = serde_json::to_string(&record).unwrap();
+    assert_eq!(serde_json::from_str::<ResourceRecord>(&text).unwrap(), record);
+
+    let question: Question = "jabber.ru. IN A".parse().unwrap();
+    let question: Question = serde_json::from_str(&serde_json::to_string(&question).unwrap()).unwrap();
+    assert_eq!(question.to_string(), "jabber.ru.\tIN\tA");
+
+    assert_eq!(serde_json::to_string(&QClass::IN).unwrap(), "\"IN\"");
+    assert!(matches!(serde_json::from_str("\"SRV\""), Ok(QType::SRV)));
+    assert!(matches!(serde_json::from_str("\"NameError\""), Ok(ResponseCode::NameError)));
+}
@@ -0,0 +1,22 @@
+use cafe_common::stream::{Input as InputStream, Output as OutputStream};
+use cafe_dns::{Edns, EdnsOption};
+
+#[test]
+fn encode_padding() {
+    let mut edns = Edns::new();
+    edns.add_option(EdnsOption::Padding(5));
+
+    let mut data = Vec::new();
+    edns.encode(&mut OutputStream::new(&mut data));
+    assert_eq!(&data[data.len() - 9 ..], &[0x00, 0x0c, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+    let decoded = Edns::decode(&mut InputStream::new(&data)).unwrap();
+    assert_eq!(decoded, edns);
+}
+
+#[test]
+fn decode_nonzero_padding() {
+    let data = [0x00, 0x00, 0x29, 0x04, 0xd0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x06, 0x00, 0x0c, 0x00, 0x02, 0xff, 0x01];
+    let decoded = Edns::decode(&mut InputStream::new(&data)).unwrap();
+    assert_eq!(decoded.options(), &[EdnsOption::Padding(2)]);
+}
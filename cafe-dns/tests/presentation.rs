@@ -0,0 +1,112 @@
+use cafe_dns::{QClass, QType, Question, ResourceRecord, Type};
+
+const RECORDS: &[&str] = &[
+    "jabber.ru.\t300\tIN\tA\t192.0.2.1",
+    "jabber.ru.\t3600\tIN\tSOA\tns1.jabber.ru. hostmaster.jabber.ru. 2020091301 7200 3600 1209600 300",
+    "_xmpp-client._tcp.jabber.ru.\t300\tIN\tSRV\t5 0 5222 xmpp.jabber.ru.",
+    "jabber.ru.\t86400\tIN\tDS\t60485 13 2 D4B7D520E7BB5F0F67674A0CCEB1E3E0614B93C4F9E99B8383F6A1E4469DA50A",
+    "jabber.ru.\t3600\tIN\tRRSIG\tSRV 13 2 3600 20201013122640 20200913122640 60485 jabber.ru. AAECAwQFBgcICQ==",
+    "jabber.ru.\t300\tIN\tNSEC\t_xmpp-client._tcp.jabber.ru. A NS SOA RRSIG NSEC DNSKEY TYPE1234",
+    "jabber.ru.\t3600\tIN\tDNSKEY\t257 3 15 l02Woi0iS8Aa25FQkUd9RMzZHJpBoRQwAQEX1SxZJA4=",
+    "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom.jabber.ru.\t300\tIN\tNSEC3\t1 1 12 AABBCCDD 2t7b4g4vsa5smi47k61mv5bv1a22bojr MX DNSKEY NS SOA NSEC3PARAM RRSIG",
+    "jabber.ru.\t0\tIN\tNSEC3PARAM\t1 0 0 -",
+    "jabber.ru.\t3600\tIN\tNS\tns1.jabber.ru.",
+    "jabber.ru.\t3600\tCH\tTYPE65280\t\\# 0",
+    "xmpp.jabber.ru.\t300\tIN\tAAAA\t2001:db8::1",
+    "www.jabber.ru.\t300\tIN\tCNAME\txmpp.jabber.ru.",
+    "jabber.ru.\t300\tIN\tMX\t10 xmpp.jabber.ru.",
+    "jabber.ru.\t300\tIN\tTXT\t\"v=spf1 mx -all\" \"say \\\"hi\\\"\\009\"",
+    "1.2.0.192.in-addr.arpa.\t300\tIN\tPTR\txmpp.jabber.ru."
+];
+
+#[test]
+fn record_round_trip() {
+    for text in RECORDS {
+        let record: ResourceRecord = text.parse().unwrap_or_else(|_| panic!("{}", text));
+        let displayed = record.to_string();
+        let reparsed: ResourceRecord = displayed.parse().unwrap();
+        assert_eq!(reparsed, record, "{}", text);
+        assert_eq!(reparsed.to_string(), displayed);
+    }
+
+    // Types are sorted by code, names lowercased in hashes.
+    let nsec3: ResourceRecord = RECORDS[7].parse().unwrap();
+    assert!(nsec3.to_string().ends_with("2t7b4g4vsa5smi47k61mv5bv1a22bojr NS SOA MX RRSIG DNSKEY NSEC3PARAM"));
+}
+
+#[test]
+fn record_syntax() {
+    let record: ResourceRecord = "jabber.ru. 300 A 192.0.2.1 ; default class".parse().unwrap();
+    assert_eq!(record.to_string(), RECORDS[0]);
+
+    let record: ResourceRecord = "jabber.ru. IN 3600 SOA ns1.jabber.ru. hostmaster.jabber.ru. (\n2020091301 7200 3600 1209600 300 )"
+        .parse()
+        .unwrap();
+    assert_eq!(record.to_string(), RECORDS[1]);
+
+    let record: ResourceRecord = "jabber.ru. 3600 IN NS \\# 15 036e7331066a616262657202727500".parse().unwrap();
+    assert_eq!(record.to_string(), RECORDS[9]);
+
+    let record: ResourceRecord = "jabber.ru. 300 IN TXT v=spf1 \"say \\\"hi\\\"\\t\" ; unquoted".parse().unwrap();
+    assert_eq!(record.ttype(), &Type::TXT { strings: vec![b"v=spf1".to_vec(), b"say \"hi\"t".to_vec()] });
+
+    let record: ResourceRecord = "jabber.ru. 300 IN A \\# 4 c0000201".parse().unwrap();
+    assert_eq!(record.ttype(), &Type::A { ip: "192.0.2.1".parse().unwrap() });
+
+    for text in &[
+        "",
+        "jabber.ru 300 IN A 192.0.2.1",
+        "jabber.ru. IN A 192.0.2.1",
+        "jabber.ru. 300 IN A 192.0.2.1\njabber.ru. 300 IN A 192.0.2.2",
+        "$TTL 300",
+        " 300 IN A 192.0.2.1",
+        "jabber.ru. 300 IN SRV 5 0 5222 xmpp"
+    ] {
+        assert!(text.parse::<ResourceRecord>().is_err(), "{}", text);
+    }
+}
+
+#[test]
+fn rdata() {
+    assert_eq!(Type::parse_rdata(33, "5 0 5222 xmpp.jabber.ru.").unwrap().to_string(), "5 0 5222 xmpp.jabber.ru.");
+    assert_eq!(Type::parse_rdata(2, "\\# 1 00").unwrap(), Type::NS { nsdname: String::new() });
+    assert_eq!(Type::parse_rdata(2, "ns1.jabber.ru.").unwrap(), Type::NS { nsdname: "ns1.jabber.ru".to_string() });
+    assert!(Type::parse_rdata(2, "ns1.jabber.ru").is_none());
+    assert_eq!(Type::parse_rdata(65280, "\\# 1 00").unwrap(), Type::Unknown { code: 65280, data: vec![0] });
+
+    let txt = Type::parse_rdata(16, "\"v=spf1 mx\" a\\032b \"\\\"\\\\\\255\"").unwrap();
+    assert_eq!(txt, Type::TXT { strings: vec![b"v=spf1 mx".to_vec(), b"a b".to_vec(), b"\"\\\xff".to_vec()] });
+    assert_eq!(txt.to_string(), "\"v=spf1 mx\" \"a b\" \"\\\"\\\\\\255\"");
+    assert!(Type::parse_rdata(16, "\"unterminated").is_none());
+    assert!(Type::parse_rdata(16, &format!("\"{}\"", "a".repeat(256))).is_none());
+    assert!(Type::parse_rdata(1, "192.0.2.1 192.0.2.2").is_none());
+}
+
+#[test]
+fn question() {
+    let question: Question = "_xmpp-client._tcp.jabber.ru. IN SRV".parse().unwrap();
+    assert_eq!(question.host_name(), "_xmpp-client._tcp.jabber.ru");
+    assert_eq!(question.to_string(), "_xmpp-client._tcp.jabber.ru.\tIN\tSRV");
+    assert_eq!(question.to_string().parse::<Question>().unwrap().to_string(), question.to_string());
+
+    let question: Question = ". type6".parse().unwrap();
+    assert_eq!(question.to_string(), ".\tIN\tSOA");
+
+    assert!("jabber.ru. CH A".parse::<Question>().is_err());
+    assert!("jabber.ru. IN TYPE1234".parse::<Question>().is_err());
+    assert!("jabber.ru IN A".parse::<Question>().is_err());
+}
+
+#[test]
+fn mnemonics() {
+    assert!(matches!("srv".parse(), Ok(QType::SRV)));
+    assert!(matches!("TYPE252".parse(), Ok(QType::AXFR)));
+    assert!(matches!("IN".parse(), Ok(QClass::IN)));
+    assert!(matches!("class1".parse(), Ok(QClass::IN)));
+    assert!("MX".parse::<QType>().is_err());
+    assert!("CH".parse::<QClass>().is_err());
+
+    assert_eq!(QType::NSEC3PARAM.to_string(), "NSEC3PARAM");
+    assert_eq!(QType::ANY.to_string(), "ANY");
+    assert_eq!(QClass::IN.to_string(), "IN");
+}
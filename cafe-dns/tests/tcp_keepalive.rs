@@ -0,0 +1,36 @@
+use cafe_common::stream::{Input as InputStream, Output as OutputStream};
+use cafe_dns::{Edns, EdnsOption};
+
+fn round_trip(edns: &Edns) -> (Vec<u8>, Edns) {
+    let mut data = Vec::new();
+    edns.encode(&mut OutputStream::new(&mut data));
+    let decoded = Edns::decode(&mut InputStream::new(&data)).unwrap();
+    (data, decoded)
+}
+
+#[test]
+fn query_without_timeout() {
+    let mut edns = Edns::new();
+    edns.add_option(EdnsOption::TcpKeepalive(None));
+
+    let (data, decoded) = round_trip(&edns);
+    assert_eq!(&data[data.len() - 4 ..], &[0x00, 0x0b, 0x00, 0x00]);
+    assert_eq!(decoded, edns);
+    assert_eq!(decoded.tcp_keepalive(), None);
+}
+
+#[test]
+fn response_with_timeout() {
+    let mut edns = Edns::new();
+    edns.add_option(EdnsOption::TcpKeepalive(Some(1200)));
+
+    let (data, decoded) = round_trip(&edns);
+    assert_eq!(&data[data.len() - 6 ..], &[0x00, 0x0b, 0x00, 0x02, 0x04, 0xb0]);
+    assert_eq!(decoded.tcp_keepalive(), Some(1200));
+}
+
+#[test]
+fn malformed_timeout() {
+    let data = [0x00, 0x00, 0x29, 0x04, 0xd0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00, 0x0b, 0x00, 0x01, 0x04];
+    assert!(Edns::decode(&mut InputStream::new(&data)).is_none());
+}
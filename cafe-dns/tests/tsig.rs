@@ -0,0 +1,264 @@
+use cafe_common::stream::Output as OutputStream;
+use cafe_dns::tsig::{self, Algorithm, Key, Signer, TsigError, Verifier};
+use cafe_dns::{QClass, QType, Request as DnsRequest, Response as DnsResponse};
+
+/// Time Signed of both messages, 2020-09-13 12:26:40 UTC.
+const TIME: u64 = 1_600_000_000;
+
+const KEY: &str = r#"
+key "update.example.org." {
+    algorithm hmac-sha256;
+    secret "MmQxYjY2ZTI1ZmY0NjVkZWNkMmYzZTIwNDhkMDg0ZjM=";
+};
+"#;
+
+/*
+Domain Name System (query)
+    Transaction ID: 0x1234
+    Flags: 0x0100 Standard query
+    Questions: 1
+    Additional RRs: 1
+    Queries
+        www.example.org: type A, class IN
+    Additional records
+        update.example.org: type TSIG, class ANY
+            Type: TSIG (Transaction Signature) (250)
+            Class: ANY (0x00ff)
+            Time to live: 0
+            Data length: 61
+            Algorithm Name: hmac-sha256
+            Time Signed: Sep 13, 2020 12:26:40.000000000 UTC
+            Fudge: 300
+            MAC Size: 32
+            MAC
+            Original Id: 4660
+            Error: No error (0)
+            Other Len: 0
+*/
+const SIGNED_REQUEST: [u8; 124] = [
+    0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x01, 0x03, 0x77, 0x77, 0x77,
+    0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65,
+    0x03, 0x6f, 0x72, 0x67, 0x00, 0x00, 0x01, 0x00,
+    0x01, 0x06, 0x75, 0x70, 0x64, 0x61, 0x74, 0x65,
+    0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65,
+    0x03, 0x6f, 0x72, 0x67, 0x00, 0x00, 0xfa, 0x00,
+    0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3d, 0x0b,
+    0x68, 0x6d, 0x61, 0x63, 0x2d, 0x73, 0x68, 0x61,
+    0x32, 0x35, 0x36, 0x00, 0x00, 0x00, 0x5f, 0x5e,
+    0x10, 0x00, 0x01, 0x2c, 0x00, 0x20, 0x1a, 0x59,
+    0x41, 0xd5, 0x3e, 0x9e, 0x8f, 0xcd, 0xe6, 0x9f,
+    0xec, 0xc6, 0x1f, 0xe0, 0x57, 0x5a, 0x81, 0x7e,
+    0x1a, 0x9b, 0x50, 0x4a, 0x38, 0xc8, 0x34, 0x78,
+    0x56, 0xfc, 0x31, 0xc7, 0xb6, 0x79, 0x12, 0x34,
+    0x00, 0x00, 0x00, 0x00
+];
+
+/*
+Domain Name System (response)
+    Transaction ID: 0x1234
+    Flags: 0x8580 Standard query response, No error
+    Questions: 1
+    Answer RRs: 1
+    Additional RRs: 1
+    Queries
+        www.example.org: type A, class IN
+    Answers
+        www.example.org: type A, class IN, addr 192.0.2.1
+    Additional records
+        update.example.org: type TSIG, class ANY
+*/
+const SIGNED_RESPONSE: [u8; 140] = [
+    0x12, 0x34, 0x85, 0x80, 0x00, 0x01, 0x00, 0x01,
+    0x00, 0x00, 0x00, 0x01, 0x03, 0x77, 0x77, 0x77,
+    0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65,
+    0x03, 0x6f, 0x72, 0x67, 0x00, 0x00, 0x01, 0x00,
+    0x01, 0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00,
+    0x00, 0x0e, 0x10, 0x00, 0x04, 0xc0, 0x00, 0x02,
+    0x01, 0x06, 0x75, 0x70, 0x64, 0x61, 0x74, 0x65,
+    0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65,
+    0x03, 0x6f, 0x72, 0x67, 0x00, 0x00, 0xfa, 0x00,
+    0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3d, 0x0b,
+    0x68, 0x6d, 0x61, 0x63, 0x2d, 0x73, 0x68, 0x61,
+    0x32, 0x35, 0x36, 0x00, 0x00, 0x00, 0x5f, 0x5e,
+    0x10, 0x00, 0x01, 0x2c, 0x00, 0x20, 0x26, 0x74,
+    0x9b, 0x73, 0x77, 0xb6, 0x31, 0x3d, 0x94, 0x8d,
+    0x9b, 0xaf, 0xfe, 0x12, 0x58, 0xb7, 0xfe, 0xfc,
+    0xa9, 0x52, 0xbf, 0xbb, 0xa7, 0x1f, 0x4f, 0xf2,
+    0x79, 0xe6, 0x07, 0x77, 0x11, 0x31, 0x12, 0x34,
+    0x00, 0x00, 0x00, 0x00
+];
+
+/// Length of the response before TSIG was appended.
+const RESPONSE_LENGTH: usize = 49;
+
+fn key() -> Key {
+    Key::parse_bind(KEY).unwrap().remove(0)
+}
+
+fn response() -> Vec<u8> {
+    let mut response = SIGNED_RESPONSE[.. RESPONSE_LENGTH].to_vec();
+    response[11] = 0;
+    response
+}
+
+#[test]
+fn sign_request() {
+    let mut request = DnsRequest::new(0x1234);
+    request.header_mut().set_rd(true);
+    request.add_question("www.example.org", QType::A, QClass::IN);
+
+    let mut message = Vec::new();
+    request.encode(&mut OutputStream::new(&mut message));
+
+    let key = key();
+    let mut signer = Signer::new(&key);
+    signer.sign(&mut message, TIME).unwrap();
+    assert_eq!(&message[..], &SIGNED_REQUEST[..]);
+    assert_eq!(signer.mac().unwrap(), &SIGNED_REQUEST[86 .. 118]);
+}
+
+#[test]
+fn decode_signed_request() {
+    let request = DnsResponse::decode(&SIGNED_REQUEST).unwrap();
+    assert!(request.additionals().is_empty());
+
+    let tsig = request.tsig().unwrap();
+    assert_eq!(tsig.key_name(), "update.example.org");
+    assert_eq!(tsig.algorithm(), "hmac-sha256");
+    assert_eq!(tsig.time_signed(), TIME);
+    assert_eq!(tsig.fudge(), 300);
+    assert_eq!(tsig.mac().len(), 32);
+    assert_eq!(tsig.original_id(), 0x1234);
+    assert_eq!(tsig.error(), 0);
+}
+
+#[test]
+fn verify_request_and_response() {
+    let key = key();
+    let mut verifier = Verifier::new(&key);
+    let tsig = verifier.verify(&SIGNED_REQUEST, TIME + 299).unwrap().unwrap();
+
+    let mut verifier = Verifier::for_response(&key, tsig.mac());
+    assert!(verifier.verify(&SIGNED_RESPONSE, TIME - 299).unwrap().is_some());
+    assert!(verifier.finish().is_ok());
+
+    let mut response = response();
+    let mut signer = Signer::for_response(&key, tsig.mac());
+    signer.sign(&mut response, TIME).unwrap();
+    assert_eq!(&response[..], &SIGNED_RESPONSE[..]);
+}
+
+#[test]
+fn verify_failures() {
+    let key = key();
+    let request_mac = &SIGNED_REQUEST[86 .. 118];
+
+    let verify = |message: &[u8], key: &Key, now: u64| Verifier::for_response(key, request_mac).verify(message, now);
+    assert_eq!(verify(&SIGNED_RESPONSE, &key, TIME + 301).unwrap_err(), TsigError::BadTime);
+
+    let mut tampered = SIGNED_RESPONSE;
+    tampered[RESPONSE_LENGTH - 1] = 2;
+    assert_eq!(verify(&tampered, &key, TIME).unwrap_err(), TsigError::BadSig);
+
+    let other = Key::new("other.example.org", Algorithm::HmacSha256, b"secret");
+    assert_eq!(verify(&SIGNED_RESPONSE, &other, TIME).unwrap_err(), TsigError::BadKey);
+
+    let other = Key::new("update.example.org", Algorithm::HmacSha512, b"secret");
+    assert_eq!(verify(&SIGNED_RESPONSE, &other, TIME).unwrap_err(), TsigError::BadKey);
+
+    assert_eq!(verify(&response(), &key, TIME).unwrap_err(), TsigError::Unsigned);
+    assert!(Verifier::new(&key).finish().is_err());
+}
+
+#[test]
+fn verify_truncated_mac() {
+    let key = key();
+    let request_mac = &SIGNED_REQUEST[86 .. 118];
+
+    // MAC starts at offset 102, cutting it makes MAC Size and RDLENGTH smaller.
+    let truncate = |length: usize| {
+        let mut message = SIGNED_RESPONSE[.. 102 + length].to_vec();
+        message.extend_from_slice(&SIGNED_RESPONSE[134 ..]);
+        message[RESPONSE_LENGTH + 29] -= (32 - length) as u8;
+        message[RESPONSE_LENGTH + 52] = length as u8;
+        message
+    };
+
+    let message = truncate(16);
+    assert!(Verifier::for_response(&key, request_mac).verify(&message, TIME).unwrap().is_some());
+
+    // Below the minimum of RFC 8945 the record is malformed, above it local policy may still refuse it.
+    let message = truncate(8);
+    assert_eq!(Verifier::for_response(&key, request_mac).verify(&message, TIME).unwrap_err(), TsigError::FormatError);
+
+    let mut verifier = Verifier::for_response(&key, request_mac);
+    verifier.set_min_mac_size(20);
+    assert_eq!(verifier.verify(&truncate(16), TIME).unwrap_err(), TsigError::BadTrunc);
+    assert!(verifier.verify(&SIGNED_RESPONSE, TIME).unwrap().is_some());
+}
+
+#[test]
+fn bad_time_response() {
+    let key = key();
+    let server_time = TIME + 3600;
+    assert_eq!(Verifier::new(&key).verify(&SIGNED_REQUEST, server_time).unwrap_err(), TsigError::BadTime);
+
+    // The MAC of the request is good, the error response is signed with the time of the request.
+    let request = tsig::locate(&SIGNED_REQUEST).unwrap().unwrap().1;
+    let mut response = response();
+    response[3] = 0x89;
+    response[7] = 0;
+    response.truncate(33);
+    Signer::for_response(&key, request.mac()).sign_bad_time(&mut response, request.time_signed(), server_time).unwrap();
+
+    let tsig = tsig::locate(&response).unwrap().unwrap().1;
+    assert_eq!(tsig.time_signed(), TIME);
+    assert_eq!(tsig.error(), 18);
+    assert_eq!(tsig.other(), &server_time.to_be_bytes()[2 ..]);
+
+    // The client verifies it with its own clock and learns about the error.
+    let mut verifier = Verifier::for_response(&key, request.mac());
+    assert_eq!(verifier.verify(&response, TIME).unwrap_err(), TsigError::Rejected(18));
+}
+
+#[test]
+fn multi_message_stream() {
+    let key = key();
+    let request_mac = &SIGNED_REQUEST[86 .. 118];
+
+    // signed, unsigned, unsigned, signed
+    let mut messages = [response(), response(), response(), response()];
+    messages[1][1] = 0x35;
+    messages[2][1] = 0x36;
+
+    let mut signer = Signer::for_response(&key, request_mac);
+    assert_eq!(signer.add_unsigned(&messages[0]).unwrap_err(), TsigError::Unsigned);
+    signer.sign(&mut messages[0], TIME).unwrap();
+    let first_mac = signer.mac().unwrap().to_vec();
+    signer.add_unsigned(&messages[1]).unwrap();
+    signer.add_unsigned(&messages[2]).unwrap();
+    signer.sign(&mut messages[3], TIME + 1).unwrap();
+
+    let mut verifier = Verifier::for_response(&key, request_mac);
+    assert!(verifier.verify(&messages[0], TIME).unwrap().is_some());
+    assert_eq!(verifier.mac().unwrap(), &first_mac[..]);
+    assert!(verifier.verify(&messages[1], TIME).unwrap().is_none());
+    assert!(verifier.verify(&messages[2], TIME).unwrap().is_none());
+    assert!(verifier.finish().is_err());
+    assert!(verifier.verify(&messages[3], TIME).unwrap().is_some());
+    assert!(verifier.finish().is_ok());
+
+    // A tampered unsigned message breaks the MAC of the next signed one.
+    let mut verifier = Verifier::for_response(&key, request_mac);
+    let mut tampered = messages[2].clone();
+    tampered[RESPONSE_LENGTH - 1] = 2;
+    verifier.verify(&messages[0], TIME).unwrap();
+    verifier.verify(&messages[1], TIME).unwrap();
+    verifier.verify(&tampered, TIME).unwrap();
+    assert_eq!(verifier.verify(&messages[3], TIME).unwrap_err(), TsigError::BadSig);
+
+    // The first message of a stream must be signed.
+    let mut verifier = Verifier::for_response(&key, request_mac);
+    assert_eq!(verifier.verify(&messages[1], TIME).unwrap_err(), TsigError::Unsigned);
+}
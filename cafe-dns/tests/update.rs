@@ -0,0 +1,162 @@
+use cafe_common::stream::Output as OutputStream;
+use cafe_dns::{QType, Response as DnsResponse, Type, Update};
+
+/*
+Domain Name System (query)
+    Transaction ID: 0x00ab
+    Flags: 0x2800 Dynamic update
+        0... .... .... .... = Response: Message is a query
+        .010 1... .... .... = Opcode: Dynamic update (5)
+    Zones: 1
+    Prerequisites: 1
+    Updates: 5
+    Additional RRs: 0
+    Zone
+        example.org: type SOA, class IN
+    Prerequisites
+        xmpp1.example.org: type ANY, class NONE
+    Updates
+        xmpp1.example.org: type A, class IN, addr 192.0.2.10
+        _xmpp-server._tcp.example.org: type SRV, class IN, priority 0, weight 5, port 5269, target xmpp1.example.org
+        old.example.org: type A, class ANY
+        _xmpp-server._tcp.example.org: type SRV, class NONE, priority 0, weight 5, port 5269, target old.example.org
+        stale.example.org: type ANY, class ANY
+*/
+const UPDATE: [u8; 277] = [
+    0x00, 0xab, 0x28, 0x00, 0x00, 0x01, 0x00, 0x01,
+    0x00, 0x05, 0x00, 0x00, 0x07, 0x65, 0x78, 0x61,
+    0x6d, 0x70, 0x6c, 0x65, 0x03, 0x6f, 0x72, 0x67,
+    0x00, 0x00, 0x06, 0x00, 0x01, 0x05, 0x78, 0x6d,
+    0x70, 0x70, 0x31, 0x07, 0x65, 0x78, 0x61, 0x6d,
+    0x70, 0x6c, 0x65, 0x03, 0x6f, 0x72, 0x67, 0x00,
+    0x00, 0xff, 0x00, 0xfe, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x05, 0x78, 0x6d, 0x70, 0x70, 0x31,
+    0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65,
+    0x03, 0x6f, 0x72, 0x67, 0x00, 0x00, 0x01, 0x00,
+    0x01, 0x00, 0x00, 0x01, 0x2c, 0x00, 0x04, 0xc0,
+    0x00, 0x02, 0x0a, 0x0c, 0x5f, 0x78, 0x6d, 0x70,
+    0x70, 0x2d, 0x73, 0x65, 0x72, 0x76, 0x65, 0x72,
+    0x04, 0x5f, 0x74, 0x63, 0x70, 0x07, 0x65, 0x78,
+    0x61, 0x6d, 0x70, 0x6c, 0x65, 0x03, 0x6f, 0x72,
+    0x67, 0x00, 0x00, 0x21, 0x00, 0x01, 0x00, 0x00,
+    0x01, 0x2c, 0x00, 0x19, 0x00, 0x00, 0x00, 0x05,
+    0x14, 0x95, 0x05, 0x78, 0x6d, 0x70, 0x70, 0x31,
+    0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65,
+    0x03, 0x6f, 0x72, 0x67, 0x00, 0x03, 0x6f, 0x6c,
+    0x64, 0x07, 0x65, 0x78, 0x61, 0x6d, 0x70, 0x6c,
+    0x65, 0x03, 0x6f, 0x72, 0x67, 0x00, 0x00, 0x01,
+    0x00, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x0c, 0x5f, 0x78, 0x6d, 0x70, 0x70, 0x2d, 0x73,
+    0x65, 0x72, 0x76, 0x65, 0x72, 0x04, 0x5f, 0x74,
+    0x63, 0x70, 0x07, 0x65, 0x78, 0x61, 0x6d, 0x70,
+    0x6c, 0x65, 0x03, 0x6f, 0x72, 0x67, 0x00, 0x00,
+    0x21, 0x00, 0xfe, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x17, 0x00, 0x00, 0x00, 0x05, 0x14, 0x95, 0x03,
+    0x6f, 0x6c, 0x64, 0x07, 0x65, 0x78, 0x61, 0x6d,
+    0x70, 0x6c, 0x65, 0x03, 0x6f, 0x72, 0x67, 0x00,
+    0x05, 0x73, 0x74, 0x61, 0x6c, 0x65, 0x07, 0x65,
+    0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x03, 0x6f,
+    0x72, 0x67, 0x00, 0x00, 0xff, 0x00, 0xff, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00
+];
+
+fn update() -> Update {
+    let mut update = Update::new(0xab, "example.org");
+    update.require_name_absent("xmpp1.example.org");
+    update.add("xmpp1.example.org", 300, Type::A { ip: "192.0.2.10".parse().unwrap() });
+    update.add("_xmpp-server._tcp.example.org", 300, Type::SRV {
+        priority: 0,
+        weight: 5,
+        port: 5269,
+        target: "xmpp1.example.org".to_string()
+    });
+    update.delete_rrset("old.example.org", QType::A);
+    update.delete_record("_xmpp-server._tcp.example.org", Type::SRV {
+        priority: 0,
+        weight: 5,
+        port: 5269,
+        target: "old.example.org".to_string()
+    });
+    update.delete_name("stale.example.org");
+    update
+}
+
+#[test]
+fn encode_update() {
+    let update = update();
+    assert_eq!(update.header().opcode(), 5);
+    assert_eq!(update.header().qdcount(), 1);
+    assert_eq!(update.header().ancount(), 1);
+    assert_eq!(update.header().nscount(), 5);
+
+    let mut result: Vec<u8> = Vec::new();
+    update.encode(&mut OutputStream::new(&mut result));
+    assert_eq!(&result[..], &UPDATE[..]);
+}
+
+#[test]
+fn decode_update() {
+    // Zone, prerequisite and update sections share the layout of question, answer and authority.
+    let message = DnsResponse::decode(&UPDATE).unwrap();
+    assert_eq!(message.header().opcode(), 5);
+    assert_eq!(message.questions()[0].host_name(), "example.org");
+
+    let prerequisite = &message.answers()[0];
+    assert_eq!(prerequisite.class(), 254);
+    assert_eq!(prerequisite.ttype().code(), 255);
+
+    let updates = message.authorities();
+    assert_eq!(updates.len(), 5);
+    assert_eq!(updates[0].class(), 1);
+    assert_eq!(updates[0].ttl(), 300);
+    match updates[1].ttype() {
+        Type::SRV { priority, weight, port, target } => {
+            assert_eq!(*priority, 0);
+            assert_eq!(*weight, 5);
+            assert_eq!(*port, 5269);
+            assert_eq!(*target, "xmpp1.example.org");
+        }
+        _ => panic!("Unexpected type!")
+    }
+
+    assert_eq!(updates[2].class(), 255);
+    assert_eq!(updates[2].ttype().code(), 1);
+    assert_eq!(updates[3].class(), 254);
+    assert_eq!(updates[3].ttl(), 0);
+    assert_eq!(updates[4].ttype().code(), 255);
+}
+
+#[test]
+fn empty_rdata() {
+    // Only records of class ANY and NONE of a dynamic update may have no RDATA.
+    let mut message = UPDATE;
+    message[2] = 0x80;
+    assert!(DnsResponse::decode(&message).is_none());
+
+    let mut message = UPDATE;
+    message[176 .. 178].copy_from_slice(&[0x00, 0x01]);
+    assert!(DnsResponse::decode(&message).is_none());
+
+    // Response with the A record of example.org with RDLENGTH 0.
+    let response = [
+        0x00, 0xab, 0x81, 0x80, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x07, 0x65, 0x78, 0x61,
+        0x6d, 0x70, 0x6c, 0x65, 0x03, 0x6f, 0x72, 0x67,
+        0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x01,
+        0x2c, 0x00, 0x00
+    ];
+    assert!(DnsResponse::decode(&response).is_none());
+}
+
+#[test]
+fn prerequisites() {
+    let mut update = Update::new(1, "example.org");
+    update.require_rrset_exists("a.example.org", QType::A);
+    update.require_rrset_matches("a.example.org", Type::A { ip: "192.0.2.1".parse().unwrap() });
+    update.require_rrset_absent("a.example.org", QType::SRV);
+    update.require_name_in_use("a.example.org");
+
+    let classes: Vec<_> = update.prerequisites().iter().map(|r| (r.class(), r.ttype().code())).collect();
+    assert_eq!(classes, [(255, 1), (1, 1), (254, 33), (255, 255)]);
+    assert!(update.prerequisites().iter().all(|r| r.ttl() == 0));
+}
@@ -0,0 +1,103 @@
+use std::fs;
+
+use cafe_dns::zone::{self, ZoneError};
+use cafe_dns::{ResourceRecord, Type};
+
+const ZONE: &str = "\
+$ORIGIN jabber.ru.
+@               IN  SOA ns1 hostmaster (
+                        2020091301  ; serial
+                        7200        ; refresh
+                        3600 1209600
+                        300 )
+                    NS  ns1
+                    MX  10 xmpp
+                    TXT \"v=spf1 mx -all\"
+$TTL 1h
+ns1                 A   192.0.2.1
+xmpp            600 IN  A 192.0.2.2
+                    A   192.0.2.3   ; owner and class of the previous record, TTL of $TTL
+$ORIGIN _tcp.jabber.ru.
+_xmpp-client    IN 300 SRV 5 0 5222 xmpp.jabber.ru.
+_xmpp-server        SRV (5 0 5269
+                         xmpp.jabber.ru.)
+$INCLUDE cluster.zone conference.jabber.ru.
+*.jabber.ru.        A   192.0.2.4
+";
+
+const CLUSTER: &str = "\
+_xmpp-server._tcp   SRV 5 0 5269 @
+@                   A   192.0.2.5
+";
+
+fn record(name: &str, ttl: u32, ttype: Type) -> String {
+    ResourceRecord::new(name, 1, ttl, ttype).to_string()
+}
+
+fn a(name: &str, ttl: u32, ip: &str) -> String {
+    record(name, ttl, Type::A { ip: ip.parse().unwrap() })
+}
+
+fn srv(name: &str, ttl: u32, port: u16, target: &str) -> String {
+    record(name, ttl, Type::SRV { priority: 5, weight: 0, port, target: target.to_string() })
+}
+
+#[test]
+fn master_file() {
+    let directory = std::env::temp_dir().join(format!("cafe-dns-zone-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("jabber.ru.zone"), ZONE).unwrap();
+    fs::write(directory.join("cluster.zone"), CLUSTER).unwrap();
+    let records = zone::load(directory.join("jabber.ru.zone"));
+    fs::remove_dir_all(&directory).unwrap();
+
+    let records: Vec<String> = records.unwrap().iter().map(ResourceRecord::to_string).collect();
+    let soa = Type::SOA {
+        mname: "ns1.jabber.ru".to_string(),
+        rname: "hostmaster.jabber.ru".to_string(),
+        serial: 2020091301,
+        refresh: 7200,
+        retry: 3600,
+        expire: 1209600,
+        minimum: 300
+    };
+
+    assert_eq!(records, [
+        record("jabber.ru", 300, soa),
+        record("jabber.ru", 300, Type::NS { nsdname: "ns1.jabber.ru".to_string() }),
+        record("jabber.ru", 300, Type::MX { preference: 10, exchange: "xmpp.jabber.ru".to_string() }),
+        record("jabber.ru", 300, Type::TXT { strings: vec![b"v=spf1 mx -all".to_vec()] }),
+        a("ns1.jabber.ru", 3600, "192.0.2.1"),
+        a("xmpp.jabber.ru", 600, "192.0.2.2"),
+        a("xmpp.jabber.ru", 3600, "192.0.2.3"),
+        srv("_xmpp-client._tcp.jabber.ru", 300, 5222, "xmpp.jabber.ru"),
+        srv("_xmpp-server._tcp.jabber.ru", 3600, 5269, "xmpp.jabber.ru"),
+        srv("_xmpp-server._tcp.conference.jabber.ru", 3600, 5269, "conference.jabber.ru"),
+        a("conference.jabber.ru", 3600, "192.0.2.5"),
+        a("*.jabber.ru", 3600, "192.0.2.4")
+    ]);
+}
+
+#[test]
+fn origin() {
+    let records = zone::parse_with_origin("@ 60 A 192.0.2.1\nxmpp 60 A 192.0.2.2\n", "jabber.ru.").unwrap();
+    assert_eq!(records[0].name(), "jabber.ru");
+    assert_eq!(records[1].name(), "xmpp.jabber.ru");
+
+    let records = zone::parse_with_origin("xmpp 60 A 192.0.2.2\n", ".").unwrap();
+    assert_eq!(records[0].name(), "xmpp");
+
+    assert!(matches!(zone::parse("xmpp 60 A 192.0.2.2\n"), Err(ZoneError::Syntax(1))));
+}
+
+#[test]
+fn line_numbers() {
+    let text = "$ORIGIN jabber.ru.\n$TTL 300\n\nxmpp A (\n192.0.2.1\n)\nxmpp SRV 5 0 5222\n";
+    assert!(matches!(zone::parse(text), Err(ZoneError::InvalidRdata(7))));
+
+    let text = "$ORIGIN jabber.ru.\n$TTL 300\n; comment\n$INCLUDE /nonexistent/cluster.zone\n";
+    match zone::parse(text) {
+        Err(ZoneError::Include(4, err)) => assert!(matches!(*err, ZoneError::Io(_))),
+        other => panic!("{:?}", other)
+    }
+}
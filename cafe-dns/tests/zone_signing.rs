@@ -0,0 +1,223 @@
+use cafe_dns::dnssec::{self, Algorithm, Denial, SignError, SigningKey, ZoneSigner};
+use cafe_dns::rrset;
+use cafe_dns::{zone, ResourceRecord, Type};
+
+const ZONE: &str = "\
+$ORIGIN jabber.ru.
+$TTL 3600
+@                   SOA ns1 hostmaster 1 7200 3600 1209600 300
+                    NS  ns1
+                    A   192.0.2.1
+                    MX  10 @
+ns1                 A   192.0.2.2
+_xmpp-client._tcp   300 SRV 5 0 5222 @
+secure              NS  ns.secure
+                    DS  60485 13 2 D4B7D520E7BB5F0F67674A0CCEB1E3E0614B93C4F9E99B8383F6A1E4469DA50A
+ns.secure           A   192.0.2.4   ; glue
+insecure            NS  ns.insecure
+                    A   192.0.2.5   ; occluded by the delegation
+ns.insecure         A   192.0.2.3
+";
+
+fn keys() -> Vec<SigningKey> {
+    vec![
+        SigningKey::generate("jabber.ru", Algorithm::Ed25519, true).unwrap(),
+        SigningKey::generate("jabber.ru.", Algorithm::EcdsaP256Sha256, false).unwrap(),
+    ]
+}
+
+/// Checks every signature of the zone with the keys, returns the number of signatures
+/// made by each of them.
+fn verify_signatures(signed: &[ResourceRecord], keys: &[SigningKey]) -> Vec<Vec<u16>> {
+    let sets = rrset::group(signed);
+    let mut covered_by_key = vec![Vec::new(); keys.len()];
+    for record in signed {
+        let (type_covered, key_tag, signature) = match record.ttype() {
+            Type::RRSIG { type_covered, key_tag, signature, .. } => (*type_covered, *key_tag, signature),
+            _ => continue,
+        };
+
+        let set = sets
+            .iter()
+            .find(|set| set.code() == type_covered && set.name() == record.name())
+            .unwrap();
+        let index = keys.iter().position(|key| key.key_tag() == key_tag).unwrap();
+        let data = dnssec::signed_data(record.ttype(), &set.records()).unwrap();
+        assert!(dnssec::verify(keys[index].dnskey(), &data, signature), "{}", record);
+
+        covered_by_key[index].push(type_covered);
+    }
+
+    covered_by_key
+}
+
+fn records_of<'a>(signed: &'a [ResourceRecord], name: &str, code: u16) -> Vec<&'a ResourceRecord> {
+    signed
+        .iter()
+        .filter(|record| record.name() == name && record.ttype().code() == code)
+        .collect()
+}
+
+#[test]
+fn nsec_signed_zone() {
+    let keys = keys();
+    let signer = ZoneSigner::new(keys);
+    let signed = signer.sign(&zone::parse(ZONE).unwrap()).unwrap();
+
+    let by_key = verify_signatures(&signed, signer.keys());
+    assert_eq!(by_key[0], [48]);
+    assert!(by_key[1].contains(&6) && by_key[1].contains(&43) && !by_key[1].contains(&48));
+
+    // Neither delegations, glue nor occluded records are signed, DS at a delegation is.
+    let covered = |name: &str| -> Vec<u16> {
+        records_of(&signed, name, 46)
+            .iter()
+            .map(|record| match record.ttype() {
+                Type::RRSIG { type_covered, .. } => *type_covered,
+                _ => 0,
+            })
+            .collect()
+    };
+    assert_eq!(covered("secure.jabber.ru"), [43, 47]);
+    assert_eq!(covered("insecure.jabber.ru"), [47]);
+    assert!(covered("ns.insecure.jabber.ru").is_empty());
+    assert!(covered("ns.secure.jabber.ru").is_empty());
+    assert_eq!(records_of(&signed, "ns.insecure.jabber.ru", 1).len(), 1);
+    assert_eq!(records_of(&signed, "insecure.jabber.ru", 1).len(), 1);
+
+    let chain: Vec<(String, String, Vec<u16>)> = signed
+        .iter()
+        .filter_map(|record| match record.ttype() {
+            Type::NSEC { next_domain_name, types } => Some((record.name().to_string(), next_domain_name.clone(), types.clone())),
+            _ => None,
+        })
+        .collect();
+    let expected = [
+        ("jabber.ru", "_xmpp-client._tcp.jabber.ru", vec![1, 2, 6, 15, 46, 47, 48]),
+        ("_xmpp-client._tcp.jabber.ru", "insecure.jabber.ru", vec![33, 46, 47]),
+        ("insecure.jabber.ru", "ns1.jabber.ru", vec![2, 46, 47]),
+        ("ns1.jabber.ru", "secure.jabber.ru", vec![1, 46, 47]),
+        ("secure.jabber.ru", "jabber.ru", vec![2, 43, 46, 47]),
+    ];
+    assert_eq!(chain.len(), expected.len());
+    for ((owner, next, types), (expected_owner, expected_next, expected_types)) in chain.iter().zip(&expected) {
+        assert_eq!((owner.as_str(), next.as_str(), types), (*expected_owner, *expected_next, expected_types));
+    }
+
+    // NSEC TTL is the SOA minimum (RFC 9077).
+    assert!(records_of(&signed, "jabber.ru", 47).iter().all(|record| record.ttl() == 300));
+
+    // The signed zone reads back from its text and signs again to the same records.
+    let text: String = signed.iter().map(|record| format!("{}\n", record)).collect();
+    let reread = zone::parse(&text).unwrap();
+    assert_eq!(reread, signed);
+    let mut resigner = ZoneSigner::new(keys_of(&signer));
+    resigner.set_validity(signer.inception(), signer.expiration());
+    let resigned = resigner.sign(&reread).unwrap();
+    assert_eq!(resigned.len(), signed.len());
+    verify_signatures(&resigned, resigner.keys());
+}
+
+fn keys_of(signer: &ZoneSigner) -> Vec<SigningKey> {
+    signer
+        .keys()
+        .iter()
+        .map(|key| SigningKey::parse_bind(&key.to_bind_public(), &key.to_bind_private()).unwrap())
+        .collect()
+}
+
+#[test]
+fn nsec3_signed_zone() {
+    let mut signer = ZoneSigner::new(keys());
+    signer.set_denial(Denial::Nsec3 { iterations: 0, salt: vec![0xab, 0xcd], opt_out: true });
+    let signed = signer.sign(&zone::parse(ZONE).unwrap()).unwrap();
+    verify_signatures(&signed, signer.keys());
+
+    assert!(signed.iter().all(|record| record.ttype().code() != 47));
+    let parameters = records_of(&signed, "jabber.ru", 51);
+    assert_eq!(
+        parameters[0].ttype(),
+        &Type::NSEC3PARAM { hash_algorithm: 1, flags: 0, iterations: 0, salt: vec![0xab, 0xcd] }
+    );
+
+    // The empty non-terminal _tcp.jabber.ru is hashed, the insecure delegation is opted out.
+    let hashed = |name: &str| {
+        let hash = dnssec::nsec3_hash(name, &[0xab, 0xcd], 0);
+        let owner = format!("{}.jabber.ru", data_encoding::BASE32HEX_NOPAD.encode(&hash).to_ascii_lowercase());
+        signed.iter().find(|record| record.name() == owner && record.ttype().code() == 50).cloned()
+    };
+
+    let types = |name: &str| match hashed(name).map(|record| record.ttype().clone()) {
+        Some(Type::NSEC3 { flags, types, .. }) => {
+            assert_eq!(flags, 1);
+            Some(types)
+        }
+        _ => None,
+    };
+
+    assert_eq!(types("jabber.ru").unwrap(), [1, 2, 6, 15, 46, 48, 51]);
+    assert_eq!(types("_tcp.jabber.ru").unwrap(), []);
+    assert_eq!(types("_xmpp-client._tcp.jabber.ru").unwrap(), [33, 46]);
+    assert_eq!(types("secure.jabber.ru").unwrap(), [2, 43, 46]);
+    assert_eq!(types("insecure.jabber.ru"), None);
+    assert_eq!(types("ns.insecure.jabber.ru"), None);
+    assert_eq!(signed.iter().filter(|record| record.ttype().code() == 50).count(), 5);
+
+    // Without opt-out the insecure delegation is in the chain with NS alone.
+    signer.set_denial(Denial::Nsec3 { iterations: 0, salt: vec![0xab, 0xcd], opt_out: false });
+    let signed = signer.sign(&zone::parse(ZONE).unwrap()).unwrap();
+    let hash = dnssec::nsec3_hash("insecure.jabber.ru", &[0xab, 0xcd], 0);
+    let owner = format!("{}.jabber.ru", data_encoding::BASE32HEX_NOPAD.encode(&hash).to_ascii_lowercase());
+    match records_of(&signed, &owner, 50)[0].ttype() {
+        Type::NSEC3 { flags, types, .. } => assert_eq!((*flags, types.as_slice()), (0, &[2][..])),
+        other => panic!("Unexpected record: {:?}", other),
+    }
+}
+
+#[test]
+fn bind_key_files() {
+    let key = SigningKey::generate("jabber.ru", Algorithm::EcdsaP256Sha256, true).unwrap();
+    let public = key.to_bind_public();
+    assert!(public.starts_with(&format!("; This is a key-signing key, keyid {}, for jabber.ru.\n", key.key_tag())));
+    assert!(key.to_bind_private().starts_with("Private-key-format: v1.3\nAlgorithm: 13 (ECDSAP256SHA256)\nPrivateKey: "));
+    assert!(key.file_name().starts_with("Kjabber.ru.+013+"));
+
+    let directory = std::env::temp_dir().join(format!("cafe-dns-keys-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = key.save_bind(&directory).unwrap();
+    let loaded = SigningKey::load_bind(&path).unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+
+    assert_eq!(loaded.dnskey(), key.dnskey());
+    assert!(loaded.is_ksk());
+    assert!(dnssec::verify(key.dnskey(), b"data", &loaded.sign(b"data")));
+
+    // RFC 8080, section 6.1. BIND writes the DNSKEY record without TTL.
+    let ed25519 = "\
+        ; This is a key-signing key, keyid 3613, for example.com.\n\
+        example.com. IN DNSKEY 257 3 15 l02Woi0iS8Aa25FQkUd9RMzZHJpBoRQwAQEX1SxZJA4=\n";
+    let private = "\
+        Private-key-format: v1.3\n\
+        Algorithm: 15 (ED25519)\n\
+        PrivateKey: ODIyNjAzODQ2MjgwODAxMjI2NDUxOTAyMDQxNDIyNjI=\n";
+    let key = SigningKey::parse_bind(ed25519, private).unwrap();
+    assert_eq!(key.key_tag(), 3613);
+    assert!(key.is_ksk());
+
+    // A private key of another pair doesn't match the public one.
+    let other = SigningKey::generate("example.com", Algorithm::Ed25519, false).unwrap();
+    assert!(SigningKey::parse_bind(ed25519, &other.to_bind_private()).is_err());
+}
+
+#[test]
+fn sign_errors() {
+    let records = zone::parse(ZONE).unwrap();
+    let signer = ZoneSigner::new(keys());
+
+    assert_eq!(signer.sign(&records[1 ..]).err(), Some(SignError::NoSoa));
+    assert_eq!(ZoneSigner::new(Vec::new()).sign(&records).err(), Some(SignError::NoKeys));
+
+    let mut outside = records.clone();
+    outside.push(ResourceRecord::new("jabber.org", 1, 300, Type::A { ip: [192, 0, 2, 4].into() }));
+    assert_eq!(signer.sign(&outside).err(), Some(SignError::OutOfZone("jabber.org".to_string())));
+}
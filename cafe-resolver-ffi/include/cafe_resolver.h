@@ -0,0 +1,214 @@
+#ifndef CAFE_RESOLVER_H
+#define CAFE_RESOLVER_H
+
+/* Generated with cbindgen from cafe-resolver-ffi, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Outcome of a call, mirrors `ResolveError`.
+ */
+typedef enum CafeStatus {
+  CAFE_STATUS_OK = 0,
+  /**
+   * A null pointer or a string that is not valid UTF-8 was passed.
+   */
+  CAFE_STATUS_INVALID_ARGUMENT = 1,
+  CAFE_STATUS_TRANSPORT_FAILED = 2,
+  CAFE_STATUS_DECODE_FAILED = 3,
+  /**
+   * The server answered with an error, see `cafe_resolver_last_rcode`.
+   */
+  CAFE_STATUS_DNS_ERROR = 4,
+  CAFE_STATUS_TSIG_FAILED = 5,
+  /**
+   * DNSSEC validation of the answer failed.
+   */
+  CAFE_STATUS_BOGUS = 6,
+  /**
+   * No response came in time.
+   */
+  CAFE_STATUS_TIMEOUT = 7,
+} CafeStatus;
+
+/**
+ * Opaque resolver handle.
+ */
+typedef struct CafeResolver CafeResolver;
+
+/**
+ * SRV record (RFC 2782), `target` is a NUL-terminated name without the trailing dot.
+ */
+typedef struct CafeSrvRecord {
+  char *target;
+  uint16_t port;
+  uint16_t priority;
+  uint16_t weight;
+  uint32_t ttl;
+} CafeSrvRecord;
+
+/**
+ * Address of A or AAAA record. `version` is 4 or 6, IPv4 addresses take the first 4 octets.
+ */
+typedef struct CafeAddress {
+  uint8_t version;
+  uint8_t octets[16];
+  uint32_t ttl;
+} CafeAddress;
+
+/**
+ * Opaque handle of the non-blocking resolver.
+ */
+typedef struct CafeEventResolver CafeEventResolver;
+
+/**
+ * Gets the outcome of `cafe_event_resolve_srv`. The records are only valid during the call.
+ */
+typedef void (*CafeSrvCallback)(void *context,
+                                CafeStatus status,
+                                uint8_t rcode,
+                                const CafeSrvRecord *records,
+                                size_t count);
+
+/**
+ * Gets the outcome of `cafe_event_resolve_a` and `cafe_event_resolve_aaaa`.
+ * The addresses are only valid during the call.
+ */
+typedef void (*CafeAddressCallback)(void *context,
+                                    CafeStatus status,
+                                    uint8_t rcode,
+                                    const CafeAddress *addresses,
+                                    size_t count);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Makes a resolver sending queries to `server`, given as "ip" or "ip:port",
+ * or to the default server if it is null. Returns null if the server is invalid.
+ */
+CafeResolver *cafe_resolver_new(const char *server);
+
+/**
+ * Releases the resolver, null is ignored.
+ */
+void cafe_resolver_free(CafeResolver *resolver);
+
+/**
+ * Enables DNSSEC validation of answers from the root trust anchors.
+ */
+CafeStatus cafe_resolver_set_dnssec_validation(CafeResolver *resolver, bool enabled);
+
+/**
+ * Response code of the last lookup that failed with `CAFE_STATUS_DNS_ERROR`, 0 otherwise.
+ */
+uint8_t cafe_resolver_last_rcode(const CafeResolver *resolver);
+
+/**
+ * Looks SRV records of `name` up, ordered by priority. On success `*records` points to `*count`
+ * records to be released with `cafe_srv_records_free`, null if there are none.
+ */
+CafeStatus cafe_resolve_srv(CafeResolver *resolver,
+                            const char *name,
+                            CafeSrvRecord **records,
+                            size_t *count);
+
+/**
+ * Releases records returned by `cafe_resolve_srv`.
+ */
+void cafe_srv_records_free(CafeSrvRecord *records, size_t count);
+
+/**
+ * Looks IPv4 addresses of `name` up. On success `*addresses` points to `*count` addresses
+ * to be released with `cafe_addresses_free`, null if there are none.
+ */
+CafeStatus cafe_resolve_a(CafeResolver *resolver,
+                          const char *name,
+                          CafeAddress **addresses,
+                          size_t *count);
+
+/**
+ * Looks IPv6 addresses of `name` up, as `cafe_resolve_a` does.
+ */
+CafeStatus cafe_resolve_aaaa(CafeResolver *resolver,
+                             const char *name,
+                             CafeAddress **addresses,
+                             size_t *count);
+
+/**
+ * Releases addresses returned by `cafe_resolve_a` and `cafe_resolve_aaaa`.
+ */
+void cafe_addresses_free(CafeAddress *addresses, size_t count);
+
+/**
+ * Static description of the status, values out of `CafeStatus` included.
+ */
+const char *cafe_status_string(int status);
+
+/**
+ * Makes a non-blocking resolver sending queries to `server`, as `cafe_resolver_new` does.
+ * Returns null if the server is invalid or the socket can't be set up. The answers are not
+ * validated with DNSSEC, use `cafe_resolver_set_dnssec_validation` with a blocking resolver for that.
+ */
+CafeEventResolver *cafe_event_resolver_new(const char *server);
+
+/**
+ * Releases the resolver, null is ignored. Callbacks of pending lookups are never invoked.
+ */
+void cafe_event_resolver_free(CafeEventResolver *resolver);
+
+/**
+ * Descriptor to watch for readability, -1 if the resolver is null. It stays the same for the
+ * lifetime of the resolver, while the sockets of the lookups behind it change.
+ */
+int cafe_event_resolver_fd(const CafeEventResolver *resolver);
+
+/**
+ * Reads the received responses and expires the lookups that timed out, invoking their callbacks.
+ * To be called when the descriptor is readable and when the timeout from
+ * `cafe_event_resolver_next_timeout` elapses.
+ */
+CafeStatus cafe_event_resolver_process_events(CafeEventResolver *resolver);
+
+/**
+ * Milliseconds until `cafe_event_resolver_process_events` has to be called even if nothing
+ * is received, -1 if there are no pending lookups.
+ */
+int64_t cafe_event_resolver_next_timeout(const CafeEventResolver *resolver);
+
+/**
+ * Starts looking SRV records of `name` up, `callback` gets them ordered by priority
+ * from `cafe_event_resolver_process_events` along with `context`.
+ * The callback is never invoked if the lookup fails to start.
+ */
+CafeStatus cafe_event_resolve_srv(CafeEventResolver *resolver,
+                                  const char *name,
+                                  CafeSrvCallback callback,
+                                  void *context);
+
+/**
+ * Starts looking IPv4 addresses of `name` up, as `cafe_event_resolve_srv` does.
+ */
+CafeStatus cafe_event_resolve_a(CafeEventResolver *resolver,
+                                const char *name,
+                                CafeAddressCallback callback,
+                                void *context);
+
+/**
+ * Starts looking IPv6 addresses of `name` up, as `cafe_event_resolve_srv` does.
+ */
+CafeStatus cafe_event_resolve_aaaa(CafeEventResolver *resolver,
+                                   const char *name,
+                                   CafeAddressCallback callback,
+                                   void *context);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* CAFE_RESOLVER_H */
@@ -0,0 +1,229 @@
+//! Non-blocking lookups for hosts running their own event loop, see `EventResolver`.
+
+use std::cell::RefCell;
+use std::ffi::c_void;
+use std::os::raw::c_char;
+use std::ptr;
+use std::rc::Rc;
+
+use cafe_resolver::event::Callback;
+use cafe_resolver::{EventResolver, RecordVariant, ResolveError};
+
+use super::{address_records, free_srv_records, server_config, srv_records, to_str, CafeAddress, CafeSrvRecord, CafeStatus};
+
+/// Gets the outcome of `cafe_event_resolve_srv`. The records are only valid during the call.
+pub type CafeSrvCallback =
+    Option<unsafe extern "C" fn(context: *mut c_void, status: CafeStatus, rcode: u8, records: *const CafeSrvRecord, count: usize)>;
+
+/// Gets the outcome of `cafe_event_resolve_a` and `cafe_event_resolve_aaaa`.
+/// The addresses are only valid during the call.
+pub type CafeAddressCallback =
+    Option<unsafe extern "C" fn(context: *mut c_void, status: CafeStatus, rcode: u8, addresses: *const CafeAddress, count: usize)>;
+
+/// Completed lookups are queued here and reported once the resolver isn't borrowed anymore,
+/// so callbacks are free to start new lookups or even release the resolver.
+type Completions = Rc<RefCell<Vec<Box<dyn FnOnce()>>>>;
+
+/// Opaque handle of the non-blocking resolver.
+pub struct CafeEventResolver {
+    resolver: EventResolver,
+    completions: Completions,
+}
+
+/// Status and response code of a failed lookup.
+fn failure(err: &ResolveError) -> (CafeStatus, u8) {
+    let rcode = match err {
+        ResolveError::DnsError(rcode, _) => *rcode as u8,
+        _ => 0,
+    };
+
+    return (CafeStatus::from(err), rcode);
+}
+
+/// Makes a non-blocking resolver sending queries to `server`, as `cafe_resolver_new` does.
+/// Returns null if the server is invalid or the socket can't be set up. The answers are not
+/// validated with DNSSEC, use `cafe_resolver_set_dnssec_validation` with a blocking resolver for that.
+#[no_mangle]
+pub unsafe extern "C" fn cafe_event_resolver_new(server: *const c_char) -> *mut CafeEventResolver {
+    let config = match server_config(server) {
+        Some(config) => config,
+        None => return ptr::null_mut(),
+    };
+
+    return match EventResolver::with_config(config) {
+        Ok(resolver) => {
+            let resolver = CafeEventResolver {
+                resolver,
+                completions: Default::default(),
+            };
+
+            Box::into_raw(Box::new(resolver))
+        }
+        Err(_) => ptr::null_mut(),
+    };
+}
+
+/// Releases the resolver, null is ignored. Callbacks of pending lookups are never invoked.
+#[no_mangle]
+pub unsafe extern "C" fn cafe_event_resolver_free(resolver: *mut CafeEventResolver) {
+    if !resolver.is_null() {
+        drop(Box::from_raw(resolver));
+    }
+}
+
+/// Descriptor to watch for readability, -1 if the resolver is null. It stays the same for the
+/// lifetime of the resolver, while the sockets of the lookups behind it change.
+#[cfg(unix)]
+#[no_mangle]
+pub unsafe extern "C" fn cafe_event_resolver_fd(resolver: *const CafeEventResolver) -> std::os::raw::c_int {
+    use std::os::unix::io::AsRawFd;
+
+    return match resolver.as_ref() {
+        Some(resolver) => resolver.resolver.as_raw_fd(),
+        None => -1,
+    };
+}
+
+/// Reads the received responses and expires the lookups that timed out, invoking their callbacks.
+/// To be called when the descriptor is readable and when the timeout from
+/// `cafe_event_resolver_next_timeout` elapses.
+#[no_mangle]
+pub unsafe extern "C" fn cafe_event_resolver_process_events(resolver: *mut CafeEventResolver) -> CafeStatus {
+    let completions = match resolver.as_mut() {
+        Some(resolver) => {
+            resolver.resolver.process_events();
+            resolver.completions.borrow_mut().split_off(0)
+        }
+        None => return CafeStatus::InvalidArgument,
+    };
+
+    for completion in completions {
+        completion();
+    }
+
+    return CafeStatus::Ok;
+}
+
+/// Milliseconds until `cafe_event_resolver_process_events` has to be called even if nothing
+/// is received, -1 if there are no pending lookups.
+#[no_mangle]
+pub unsafe extern "C" fn cafe_event_resolver_next_timeout(resolver: *const CafeEventResolver) -> i64 {
+    let timeout = match resolver.as_ref().and_then(|resolver| resolver.resolver.next_timeout()) {
+        Some(timeout) => timeout,
+        None => return -1,
+    };
+
+    // Rounded up, so the loop doesn't wake up just before the deadline.
+    let millis = timeout.as_micros().div_ceil(1000);
+    return millis.min(i64::MAX as u128) as i64;
+}
+
+/// Starts the lookup with `lookup`, which gets the resolver, the host name and
+/// the callback reporting the records through `report`.
+unsafe fn start<F, R>(resolver: *mut CafeEventResolver, name: *const c_char, lookup: F, report: R) -> CafeStatus
+where
+    F: FnOnce(&mut EventResolver, &str, Callback) -> Result<(), ResolveError>,
+    R: FnOnce(Result<Vec<RecordVariant>, ResolveError>) + 'static,
+{
+    let resolver = match resolver.as_mut() {
+        Some(resolver) => resolver,
+        None => return CafeStatus::InvalidArgument,
+    };
+
+    let name = match to_str(name) {
+        Some(name) => name,
+        None => return CafeStatus::InvalidArgument,
+    };
+
+    let completions = resolver.completions.clone();
+    let callback = Box::new(move |result| completions.borrow_mut().push(Box::new(move || report(result)) as Box<dyn FnOnce()>));
+
+    return match lookup(&mut resolver.resolver, name, callback) {
+        Ok(()) => CafeStatus::Ok,
+        Err(err) => CafeStatus::from(&err),
+    };
+}
+
+/// Starts looking SRV records of `name` up, `callback` gets them ordered by priority
+/// from `cafe_event_resolver_process_events` along with `context`.
+/// The callback is never invoked if the lookup fails to start.
+#[no_mangle]
+pub unsafe extern "C" fn cafe_event_resolve_srv(
+    resolver: *mut CafeEventResolver,
+    name: *const c_char,
+    callback: CafeSrvCallback,
+    context: *mut c_void,
+) -> CafeStatus {
+    let callback = match callback {
+        Some(callback) => callback,
+        None => return CafeStatus::InvalidArgument,
+    };
+
+    let report = move |result: Result<Vec<RecordVariant>, ResolveError>| {
+        match result.map_err(|err| failure(&err)).and_then(|found| srv_records(found).map_err(|status| (status, 0))) {
+            Ok(records) => {
+                callback(context, CafeStatus::Ok, 0, records.as_ptr(), records.len());
+                free_srv_records(records);
+            }
+            Err((status, rcode)) => callback(context, status, rcode, ptr::null(), 0),
+        }
+    };
+
+    return start(resolver, name, |resolver, name, callback| resolver.get_srv_records(name, callback), report);
+}
+
+unsafe fn resolve_addresses<F>(
+    resolver: *mut CafeEventResolver,
+    name: *const c_char,
+    callback: CafeAddressCallback,
+    context: *mut c_void,
+    lookup: F,
+) -> CafeStatus
+where
+    F: FnOnce(&mut EventResolver, &str, Callback) -> Result<(), ResolveError>,
+{
+    let callback = match callback {
+        Some(callback) => callback,
+        None => return CafeStatus::InvalidArgument,
+    };
+
+    let report = move |result: Result<Vec<RecordVariant>, ResolveError>| match result {
+        Ok(found) => {
+            let addresses = address_records(found);
+            callback(context, CafeStatus::Ok, 0, addresses.as_ptr(), addresses.len());
+        }
+        Err(err) => {
+            let (status, rcode) = failure(&err);
+            callback(context, status, rcode, ptr::null(), 0);
+        }
+    };
+
+    return start(resolver, name, lookup, report);
+}
+
+/// Starts looking IPv4 addresses of `name` up, as `cafe_event_resolve_srv` does.
+#[no_mangle]
+pub unsafe extern "C" fn cafe_event_resolve_a(
+    resolver: *mut CafeEventResolver,
+    name: *const c_char,
+    callback: CafeAddressCallback,
+    context: *mut c_void,
+) -> CafeStatus {
+    return resolve_addresses(resolver, name, callback, context, |resolver, name, callback| {
+        resolver.get_a_records(name, callback)
+    });
+}
+
+/// Starts looking IPv6 addresses of `name` up, as `cafe_event_resolve_srv` does.
+#[no_mangle]
+pub unsafe extern "C" fn cafe_event_resolve_aaaa(
+    resolver: *mut CafeEventResolver,
+    name: *const c_char,
+    callback: CafeAddressCallback,
+    context: *mut c_void,
+) -> CafeStatus {
+    return resolve_addresses(resolver, name, callback, context, |resolver, name, callback| {
+        resolver.get_aaaa_records(name, callback)
+    });
+}
+
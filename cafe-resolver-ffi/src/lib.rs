@@ -0,0 +1,351 @@
+//! C interface of cafe-resolver, declared in `include/cafe_resolver.h`.
+//!
+//! Resolvers are opaque handles made by `cafe_resolver_new` and released by `cafe_resolver_free`.
+//! Lookups fill arrays allocated here, the caller releases them with the matching free function.
+//! A handle must not be used from several threads at once.
+//!
+//! # Safety
+//!
+//! Every function takes pointers from C as they are: they must be null or valid, strings must be
+//! NUL-terminated and arrays must be released once, with the count they were returned with.
+
+// The contract above holds for every function, it isn't repeated on each of them.
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::{CStr, CString};
+use std::net::{IpAddr, SocketAddr};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use cafe_resolver::{Config, RecordVariant, ResolveError, Resolver};
+
+mod event;
+
+pub use self::event::*;
+
+/// Outcome of a call, mirrors `ResolveError`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CafeStatus {
+    Ok = 0,
+    /// A null pointer or a string that is not valid UTF-8 was passed.
+    InvalidArgument = 1,
+    TransportFailed = 2,
+    DecodeFailed = 3,
+    /// The server answered with an error, see `cafe_resolver_last_rcode`.
+    DnsError = 4,
+    TsigFailed = 5,
+    /// DNSSEC validation of the answer failed.
+    Bogus = 6,
+    /// No response came in time.
+    Timeout = 7,
+}
+
+impl From<&ResolveError> for CafeStatus {
+    fn from(err: &ResolveError) -> Self {
+        return match err {
+            ResolveError::TransportFailed => CafeStatus::TransportFailed,
+            ResolveError::DecodeFailed => CafeStatus::DecodeFailed,
+            ResolveError::DnsError(_, _) => CafeStatus::DnsError,
+            ResolveError::TsigFailed(_) => CafeStatus::TsigFailed,
+            ResolveError::Bogus => CafeStatus::Bogus,
+            ResolveError::Timeout => CafeStatus::Timeout,
+        };
+    }
+}
+
+/// Opaque resolver handle.
+pub struct CafeResolver {
+    resolver: Resolver,
+    /// Response code of the last lookup that failed with `CafeStatus::DnsError`.
+    last_rcode: u8,
+}
+
+/// SRV record (RFC 2782), `target` is a NUL-terminated name without the trailing dot.
+#[repr(C)]
+pub struct CafeSrvRecord {
+    pub target: *mut c_char,
+    pub port: u16,
+    pub priority: u16,
+    pub weight: u16,
+    pub ttl: u32,
+}
+
+/// Address of A or AAAA record. `version` is 4 or 6, IPv4 addresses take the first 4 octets.
+#[repr(C)]
+pub struct CafeAddress {
+    pub version: u8,
+    pub octets: [u8; 16],
+    pub ttl: u32,
+}
+
+unsafe fn to_str<'a>(text: *const c_char) -> Option<&'a str> {
+    if text.is_null() {
+        return None;
+    }
+
+    return CStr::from_ptr(text).to_str().ok();
+}
+
+/// Moves `items` to a heap array the caller gets through `records` and `count`.
+unsafe fn export<T>(items: Vec<T>, records: *mut *mut T, count: *mut usize) {
+    let items = items.into_boxed_slice();
+    *count = items.len();
+    *records = match items.is_empty() {
+        true => ptr::null_mut(),
+        false => Box::into_raw(items) as *mut T,
+    };
+}
+
+/// Takes back an array made by `export`.
+unsafe fn import<T>(records: *mut T, count: usize) -> Vec<T> {
+    if records.is_null() || count == 0 {
+        return Vec::new();
+    }
+
+    return Box::from_raw(ptr::slice_from_raw_parts_mut(records, count)).into_vec();
+}
+
+/// Runs `lookup` with the resolver and the host name, recording the response code of DNS errors.
+unsafe fn resolve<T, F>(resolver: *mut CafeResolver, name: *const c_char, lookup: F) -> Result<T, CafeStatus>
+where
+    F: FnOnce(&mut Resolver, &str) -> Result<T, ResolveError>,
+{
+    let resolver = match resolver.as_mut() {
+        Some(resolver) => resolver,
+        None => return Err(CafeStatus::InvalidArgument),
+    };
+
+    let name = match to_str(name) {
+        Some(name) => name,
+        None => return Err(CafeStatus::InvalidArgument),
+    };
+
+    resolver.last_rcode = 0;
+    return lookup(&mut resolver.resolver, name).map_err(|err| {
+        if let ResolveError::DnsError(rcode, _) = &err {
+            resolver.last_rcode = *rcode as u8;
+        }
+
+        return CafeStatus::from(&err);
+    });
+}
+
+/// Config with the server given as "ip" or "ip:port", the default one if it is null.
+unsafe fn server_config(server: *const c_char) -> Option<Config> {
+    let mut config = Config::new();
+    if !server.is_null() {
+        let server = to_str(server)?;
+        match server.parse::<SocketAddr>() {
+            Ok(addr) => config.set_server(addr),
+            Err(_) => config.set_server(SocketAddr::new(server.parse::<IpAddr>().ok()?, 53)),
+        }
+    }
+
+    return Some(config);
+}
+
+/// Makes a resolver sending queries to `server`, given as "ip" or "ip:port",
+/// or to the default server if it is null. Returns null if the server is invalid.
+#[no_mangle]
+pub unsafe extern "C" fn cafe_resolver_new(server: *const c_char) -> *mut CafeResolver {
+    let config = match server_config(server) {
+        Some(config) => config,
+        None => return ptr::null_mut(),
+    };
+
+    let resolver = CafeResolver {
+        resolver: Resolver::with_config(config),
+        last_rcode: 0,
+    };
+
+    return Box::into_raw(Box::new(resolver));
+}
+
+/// Releases the resolver, null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn cafe_resolver_free(resolver: *mut CafeResolver) {
+    if !resolver.is_null() {
+        drop(Box::from_raw(resolver));
+    }
+}
+
+/// Enables DNSSEC validation of answers from the root trust anchors.
+#[no_mangle]
+pub unsafe extern "C" fn cafe_resolver_set_dnssec_validation(resolver: *mut CafeResolver, enabled: bool) -> CafeStatus {
+    return match resolver.as_mut() {
+        Some(resolver) => {
+            resolver.resolver.config_mut().set_dnssec_validation(enabled);
+            CafeStatus::Ok
+        }
+        None => CafeStatus::InvalidArgument,
+    };
+}
+
+/// Response code of the last lookup that failed with `CAFE_STATUS_DNS_ERROR`, 0 otherwise.
+#[no_mangle]
+pub unsafe extern "C" fn cafe_resolver_last_rcode(resolver: *const CafeResolver) -> u8 {
+    return match resolver.as_ref() {
+        Some(resolver) => resolver.last_rcode,
+        None => 0,
+    };
+}
+
+/// Looks SRV records of `name` up, ordered by priority. On success `*records` points to `*count`
+/// records to be released with `cafe_srv_records_free`, null if there are none.
+#[no_mangle]
+pub unsafe extern "C" fn cafe_resolve_srv(
+    resolver: *mut CafeResolver,
+    name: *const c_char,
+    records: *mut *mut CafeSrvRecord,
+    count: *mut usize,
+) -> CafeStatus {
+    if records.is_null() || count.is_null() {
+        return CafeStatus::InvalidArgument;
+    }
+
+    let found = match resolve(resolver, name, |resolver, name| resolver.get_srv_records(name)) {
+        Ok(found) => found,
+        Err(status) => return status,
+    };
+
+    return match srv_records(found) {
+        Ok(result) => {
+            export(result, records, count);
+            CafeStatus::Ok
+        }
+        Err(status) => status,
+    };
+}
+
+unsafe fn srv_records(found: Vec<RecordVariant>) -> Result<Vec<CafeSrvRecord>, CafeStatus> {
+    let mut result = Vec::with_capacity(found.len());
+    for record in found {
+        if let RecordVariant::SRV { target, port, priority, weight, ttl, .. } = record {
+            let target = match CString::new(target) {
+                Ok(target) => target,
+                Err(_) => {
+                    free_srv_records(result);
+                    return Err(CafeStatus::DecodeFailed);
+                }
+            };
+
+            result.push(CafeSrvRecord {
+                target: target.into_raw(),
+                port,
+                priority,
+                weight,
+                ttl,
+            });
+        }
+    }
+
+    return Ok(result);
+}
+
+unsafe fn free_srv_records(records: Vec<CafeSrvRecord>) {
+    for record in records {
+        if !record.target.is_null() {
+            drop(CString::from_raw(record.target));
+        }
+    }
+}
+
+/// Releases records returned by `cafe_resolve_srv`.
+#[no_mangle]
+pub unsafe extern "C" fn cafe_srv_records_free(records: *mut CafeSrvRecord, count: usize) {
+    free_srv_records(import(records, count));
+}
+
+unsafe fn resolve_addresses<F>(
+    resolver: *mut CafeResolver,
+    name: *const c_char,
+    addresses: *mut *mut CafeAddress,
+    count: *mut usize,
+    lookup: F,
+) -> CafeStatus
+where
+    F: FnOnce(&mut Resolver, &str) -> Result<Vec<RecordVariant>, ResolveError>,
+{
+    if addresses.is_null() || count.is_null() {
+        return CafeStatus::InvalidArgument;
+    }
+
+    let found = match resolve(resolver, name, lookup) {
+        Ok(found) => found,
+        Err(status) => return status,
+    };
+
+    export(address_records(found), addresses, count);
+    return CafeStatus::Ok;
+}
+
+fn address_records(found: Vec<RecordVariant>) -> Vec<CafeAddress> {
+    let mut result = Vec::with_capacity(found.len());
+    for record in found {
+        if let RecordVariant::A { ip, ttl, .. } = record {
+            let mut octets = [0; 16];
+            let version = match ip {
+                IpAddr::V4(ip) => {
+                    octets[.. 4].copy_from_slice(&ip.octets());
+                    4
+                }
+                IpAddr::V6(ip) => {
+                    octets.copy_from_slice(&ip.octets());
+                    6
+                }
+            };
+
+            result.push(CafeAddress { version, octets, ttl });
+        }
+    }
+
+    return result;
+}
+
+/// Looks IPv4 addresses of `name` up. On success `*addresses` points to `*count` addresses
+/// to be released with `cafe_addresses_free`, null if there are none.
+#[no_mangle]
+pub unsafe extern "C" fn cafe_resolve_a(
+    resolver: *mut CafeResolver,
+    name: *const c_char,
+    addresses: *mut *mut CafeAddress,
+    count: *mut usize,
+) -> CafeStatus {
+    return resolve_addresses(resolver, name, addresses, count, |resolver, name| resolver.get_a_records(name));
+}
+
+/// Looks IPv6 addresses of `name` up, as `cafe_resolve_a` does.
+#[no_mangle]
+pub unsafe extern "C" fn cafe_resolve_aaaa(
+    resolver: *mut CafeResolver,
+    name: *const c_char,
+    addresses: *mut *mut CafeAddress,
+    count: *mut usize,
+) -> CafeStatus {
+    return resolve_addresses(resolver, name, addresses, count, |resolver, name| resolver.get_aaaa_records(name));
+}
+
+/// Releases addresses returned by `cafe_resolve_a` and `cafe_resolve_aaaa`.
+#[no_mangle]
+pub unsafe extern "C" fn cafe_addresses_free(addresses: *mut CafeAddress, count: usize) {
+    drop(import(addresses, count));
+}
+
+/// Static description of the status, values out of `CafeStatus` included.
+#[no_mangle]
+pub extern "C" fn cafe_status_string(status: c_int) -> *const c_char {
+    let text: &'static [u8] = match status {
+        x if x == CafeStatus::Ok as c_int => b"ok\0",
+        x if x == CafeStatus::InvalidArgument as c_int => b"invalid argument\0",
+        x if x == CafeStatus::TransportFailed as c_int => b"transport failed\0",
+        x if x == CafeStatus::DecodeFailed as c_int => b"unable to decode response\0",
+        x if x == CafeStatus::DnsError as c_int => b"server returned an error\0",
+        x if x == CafeStatus::TsigFailed as c_int => b"TSIG verification failed\0",
+        x if x == CafeStatus::Bogus as c_int => b"DNSSEC validation failed\0",
+        x if x == CafeStatus::Timeout as c_int => b"timed out\0",
+        _ => b"unknown status\0",
+    };
+
+    return text.as_ptr() as *const c_char;
+}
@@ -0,0 +1,142 @@
+/* Drives the non-blocking interface with poll() against the test server given as the only argument. */
+
+#define _POSIX_C_SOURCE 200809L
+
+#include <poll.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "cafe_resolver.h"
+
+#define CHECK(condition)                                                    \
+  do {                                                                      \
+    if (!(condition)) {                                                     \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition); \
+      return 1;                                                             \
+    }                                                                       \
+  } while (0)
+
+typedef struct Lookups {
+  CafeEventResolver *resolver;
+  int pending;
+  int srv_count;
+  uint16_t first_port;
+  int ipv4_count;
+  int ipv6_count;
+  uint8_t missing_rcode;
+  int failures;
+} Lookups;
+
+static void on_srv(void *context, CafeStatus status, uint8_t rcode, const CafeSrvRecord *records, size_t count) {
+  Lookups *lookups = context;
+  lookups->pending--;
+  if (status != CAFE_STATUS_OK || rcode != 0 || count != 2 || strcmp(records[0].target, "jabber.ru") != 0) {
+    lookups->failures++;
+    return;
+  }
+
+  lookups->srv_count = (int)count;
+  lookups->first_port = records[0].port;
+}
+
+static void on_ipv6(void *context, CafeStatus status, uint8_t rcode, const CafeAddress *addresses, size_t count) {
+  Lookups *lookups = context;
+  lookups->pending--;
+  if (status != CAFE_STATUS_OK || count != 1 || addresses[0].version != 6) {
+    lookups->failures++;
+    return;
+  }
+
+  lookups->ipv6_count = (int)count;
+}
+
+/* Starts the IPv6 lookup from within the callback, which the interface allows. */
+static void on_ipv4(void *context, CafeStatus status, uint8_t rcode, const CafeAddress *addresses, size_t count) {
+  Lookups *lookups = context;
+  lookups->pending--;
+  if (status != CAFE_STATUS_OK || count != 1 || addresses[0].version != 4 || addresses[0].octets[3] != 1) {
+    lookups->failures++;
+    return;
+  }
+
+  lookups->ipv4_count = (int)count;
+  if (cafe_event_resolve_aaaa(lookups->resolver, "jabber.ru", on_ipv6, lookups) == CAFE_STATUS_OK) {
+    lookups->pending++;
+  } else {
+    lookups->failures++;
+  }
+}
+
+static void on_missing(void *context, CafeStatus status, uint8_t rcode, const CafeAddress *addresses, size_t count) {
+  Lookups *lookups = context;
+  lookups->pending--;
+  if (status != CAFE_STATUS_DNS_ERROR || addresses != NULL || count != 0) {
+    lookups->failures++;
+    return;
+  }
+
+  lookups->missing_rcode = rcode;
+}
+
+/* Polls the descriptor, registered once, until the lookups are over. */
+static int run(Lookups *lookups, int descriptor) {
+  struct pollfd fd;
+  int rounds = 0;
+
+  fd.fd = descriptor;
+  fd.events = POLLIN;
+
+  while (lookups->pending > 0) {
+    int64_t timeout = cafe_event_resolver_next_timeout(lookups->resolver);
+    CHECK(timeout >= 0);
+    CHECK(++rounds < 100);
+    CHECK(poll(&fd, 1, (int)timeout) >= 0);
+    CHECK(cafe_event_resolver_process_events(lookups->resolver) == CAFE_STATUS_OK);
+  }
+
+  CHECK(cafe_event_resolver_next_timeout(lookups->resolver) == -1);
+  CHECK(cafe_event_resolver_fd(lookups->resolver) == descriptor);
+  return 0;
+}
+
+int main(int argc, char **argv) {
+  Lookups lookups;
+  int descriptor;
+  int result;
+
+  if (argc != 2) {
+    fprintf(stderr, "usage: %s server\n", argv[0]);
+    return 2;
+  }
+
+  memset(&lookups, 0, sizeof(lookups));
+  lookups.resolver = cafe_event_resolver_new(argv[1]);
+  CHECK(lookups.resolver != NULL);
+  CHECK(cafe_event_resolver_next_timeout(lookups.resolver) == -1);
+  descriptor = cafe_event_resolver_fd(lookups.resolver);
+  CHECK(descriptor >= 0);
+
+  CHECK(cafe_event_resolve_srv(lookups.resolver, "_xmpp-client._tcp.jabber.ru", on_srv, &lookups) == CAFE_STATUS_OK);
+  CHECK(cafe_event_resolve_a(lookups.resolver, "jabber.ru", on_ipv4, &lookups) == CAFE_STATUS_OK);
+  CHECK(cafe_event_resolve_a(lookups.resolver, "missing.jabber.ru", on_missing, &lookups) == CAFE_STATUS_OK);
+  CHECK(cafe_event_resolve_a(lookups.resolver, NULL, on_missing, &lookups) == CAFE_STATUS_INVALID_ARGUMENT);
+  CHECK(cafe_event_resolve_a(lookups.resolver, "jabber.ru", NULL, &lookups) == CAFE_STATUS_INVALID_ARGUMENT);
+  lookups.pending = 3;
+
+  result = run(&lookups, descriptor);
+
+  /* Nothing is pending, the next lookup goes from a new socket behind the same descriptor. */
+  if (result == 0 && cafe_event_resolve_a(lookups.resolver, "jabber.ru", on_ipv4, &lookups) == CAFE_STATUS_OK) {
+    lookups.pending = 1;
+    result = run(&lookups, descriptor);
+  }
+
+  cafe_event_resolver_free(lookups.resolver);
+  CHECK(result == 0);
+
+  CHECK(lookups.failures == 0);
+  CHECK(lookups.srv_count == 2 && lookups.first_port == 5222);
+  CHECK(lookups.ipv4_count == 1 && lookups.ipv6_count == 1);
+  CHECK(lookups.missing_rcode == 3);
+  return 0;
+}
@@ -0,0 +1,94 @@
+/* Exercises the C interface against the test server given as the only argument. */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "cafe_resolver.h"
+
+#define CHECK(condition)                                                    \
+  do {                                                                      \
+    if (!(condition)) {                                                     \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition); \
+      return 1;                                                             \
+    }                                                                       \
+  } while (0)
+
+static int check_srv(CafeResolver *resolver) {
+  CafeSrvRecord *records = NULL;
+  size_t count = 0;
+
+  CHECK(cafe_resolve_srv(resolver, "_xmpp-client._tcp.jabber.ru", &records, &count) == CAFE_STATUS_OK);
+  CHECK(count == 2);
+  CHECK(strcmp(records[0].target, "jabber.ru") == 0);
+  CHECK(records[0].port == 5222);
+  CHECK(records[0].priority == 0);
+  CHECK(records[0].ttl == 300);
+  CHECK(strcmp(records[1].target, "allports.jabber.ru") == 0);
+  CHECK(records[1].port == 443);
+  CHECK(records[1].priority == 10);
+  cafe_srv_records_free(records, count);
+  return 0;
+}
+
+static int check_addresses(CafeResolver *resolver) {
+  static const uint8_t ipv6[16] = {0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1};
+  CafeAddress *addresses = NULL;
+  size_t count = 0;
+
+  CHECK(cafe_resolve_a(resolver, "jabber.ru", &addresses, &count) == CAFE_STATUS_OK);
+  CHECK(count == 1);
+  CHECK(addresses[0].version == 4);
+  CHECK(addresses[0].octets[0] == 192 && addresses[0].octets[1] == 0);
+  CHECK(addresses[0].octets[2] == 2 && addresses[0].octets[3] == 1);
+  CHECK(addresses[0].ttl == 60);
+  cafe_addresses_free(addresses, count);
+
+  CHECK(cafe_resolve_aaaa(resolver, "jabber.ru", &addresses, &count) == CAFE_STATUS_OK);
+  CHECK(count == 1);
+  CHECK(addresses[0].version == 6);
+  CHECK(memcmp(addresses[0].octets, ipv6, sizeof(ipv6)) == 0);
+  cafe_addresses_free(addresses, count);
+
+  CHECK(cafe_resolve_aaaa(resolver, "empty.jabber.ru", &addresses, &count) == CAFE_STATUS_OK);
+  CHECK(count == 0);
+  CHECK(addresses == NULL);
+  cafe_addresses_free(addresses, count);
+  return 0;
+}
+
+static int check_errors(CafeResolver *resolver) {
+  CafeAddress *addresses = NULL;
+  size_t count = 0;
+
+  CHECK(cafe_resolve_a(resolver, "missing.jabber.ru", &addresses, &count) == CAFE_STATUS_DNS_ERROR);
+  CHECK(cafe_resolver_last_rcode(resolver) == 3);
+  CHECK(cafe_resolve_a(resolver, "jabber.ru", &addresses, &count) == CAFE_STATUS_OK);
+  CHECK(cafe_resolver_last_rcode(resolver) == 0);
+  cafe_addresses_free(addresses, count);
+
+  CHECK(cafe_resolve_a(resolver, NULL, &addresses, &count) == CAFE_STATUS_INVALID_ARGUMENT);
+  CHECK(cafe_resolve_a(NULL, "jabber.ru", &addresses, &count) == CAFE_STATUS_INVALID_ARGUMENT);
+  CHECK(cafe_resolve_srv(resolver, "jabber.ru", NULL, &count) == CAFE_STATUS_INVALID_ARGUMENT);
+  CHECK(cafe_resolver_new("not an address") == NULL);
+
+  CHECK(strcmp(cafe_status_string(CAFE_STATUS_DNS_ERROR), "server returned an error") == 0);
+  CHECK(strcmp(cafe_status_string(100), "unknown status") == 0);
+  return 0;
+}
+
+int main(int argc, char **argv) {
+  CafeResolver *resolver;
+  int result;
+
+  if (argc != 2) {
+    fprintf(stderr, "usage: %s server\n", argv[0]);
+    return 2;
+  }
+
+  resolver = cafe_resolver_new(argv[1]);
+  CHECK(resolver != NULL);
+
+  result = check_srv(resolver) || check_addresses(resolver) || check_errors(resolver);
+  cafe_resolver_free(resolver);
+  return result;
+}
@@ -0,0 +1,98 @@
+#![cfg(unix)]
+
+#[path = "../../cafe-resolver/tests/common/mod.rs"]
+mod common;
+
+use std::env;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::Command;
+
+use cafe_common::stream::Output as OutputStream;
+use cafe_dns::{QType, Response as DnsResponse, ResourceRecord, Type};
+
+fn encode(record: ResourceRecord) -> Vec<u8> {
+    let mut data = Vec::new();
+    record.encode(&mut OutputStream::new(&mut data));
+    data
+}
+
+/// Answers for jabber.ru, NXDOMAIN for missing.jabber.ru and no data for other names.
+fn spawn_server() -> SocketAddr {
+    common::spawn_udp_server(|query| {
+        let decoded = DnsResponse::decode(query).unwrap();
+        let question = &decoded.questions()[0];
+        let name = question.host_name();
+        let answers = match (name, question.qtype()) {
+            ("missing.jabber.ru", _) => return Some(common::reply(query, 3, &[])),
+            ("jabber.ru", QType::A) => vec![common::a_record([192, 0, 2, 1], 60)],
+            ("jabber.ru", QType::AAAA) => {
+                vec![encode(ResourceRecord::new(name, 1, 60, Type::AAAA { ip: "2001:db8::1".parse().unwrap() }))]
+            }
+            ("_xmpp-client._tcp.jabber.ru", QType::SRV) => {
+                let srv = |priority, port, target: &str| Type::SRV { priority, weight: 0, port, target: target.to_string() };
+                vec![
+                    encode(ResourceRecord::new(name, 1, 300, srv(10, 443, "allports.jabber.ru"))),
+                    encode(ResourceRecord::new(name, 1, 300, srv(0, 5222, "jabber.ru"))),
+                ]
+            }
+            _ => Vec::new(),
+        };
+
+        Some(common::reply(query, 0, &answers))
+    })
+}
+
+/// Directory the library is built to, the one above that of the test executable.
+fn library_directory() -> PathBuf {
+    let mut directory = env::current_exe().unwrap();
+    directory.pop();
+    if directory.ends_with("deps") {
+        directory.pop();
+    }
+
+    directory
+}
+
+/// Compiles the test program from `tests/c` against the library.
+fn compile(source: &str) -> PathBuf {
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let library = library_directory();
+    let program = library.join(format!("cafe_resolver_c_test_{}", source.trim_end_matches(".c")));
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+
+    let status = Command::new(compiler)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest.join("include"))
+        .arg(manifest.join("tests/c").join(source))
+        .arg("-o")
+        .arg(&program)
+        .arg("-L")
+        .arg(&library)
+        .arg("-lcafe_resolver_ffi")
+        .arg(format!("-Wl,-rpath,{}", library.display()))
+        .status()
+        .unwrap();
+    assert!(status.success(), "compilation failed");
+
+    program
+}
+
+fn run(program: PathBuf) {
+    let server = spawn_server();
+    let output = Command::new(&program).arg(server.to_string()).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn c_program() {
+    run(compile("resolve.c"));
+}
+
+#[test]
+fn c_event_loop() {
+    run(compile("event_loop.c"));
+}
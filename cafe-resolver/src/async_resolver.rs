@@ -0,0 +1,499 @@
+use std::collections::BTreeMap;
+use std::future;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::{mpsc, oneshot, Notify};
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsConnector;
+
+use cafe_dns::{QType, Response as DnsResponse};
+
+use crate::engine::{Action, Engine, QueryId};
+#[cfg(any(feature = "https", feature = "quic"))]
+use crate::multiplex;
+use crate::tcp::DOT_ALPN;
+use crate::{answer_records, cached_records, check_rcode, connect_udp, is_applicable, sort_by_priority};
+use crate::{Answer, Config, RecordsResult, ResolveError, ResolveRecord, ResolveResult, Security, Transport};
+
+struct State {
+    engine: Engine,
+    waiters: BTreeMap<QueryId, oneshot::Sender<Result<DnsResponse, ResolveError>>>,
+}
+
+impl State {
+    /// Hands the finished queries over to their waiters, returning the messages to send.
+    fn take_actions(&mut self) -> Vec<Action> {
+        let mut messages = Vec::new();
+        while let Some(action) = self.engine.poll_action() {
+            match action {
+                Action::Finished(query, result) => {
+                    if let Some(waiter) = self.waiters.remove(&query) {
+                        let _ = waiter.send(result);
+                    }
+                }
+                action => messages.push(action),
+            }
+        }
+
+        return messages;
+    }
+}
+
+/// Messages for the task running the connection to the server, with their queries.
+type Outgoing = mpsc::UnboundedSender<(QueryId, Vec<u8>)>;
+type Messages = mpsc::UnboundedReceiver<(QueryId, Vec<u8>)>;
+
+/// What becomes of the connection once it is idle for `Engine::idle_timeout`.
+enum Idle {
+    KeepOpen,
+    Close,
+    /// A message came in the meantime, the engine sends it again over a new connection.
+    Reopen,
+}
+
+struct Shared {
+    /// Sockets of the queries that went over UDP, each bound to a port of its own.
+    sockets: Mutex<BTreeMap<QueryId, Arc<UdpSocket>>>,
+    state: Mutex<State>,
+    /// Wakes the receiving task up when a query, maybe with an earlier deadline, is started,
+    /// or when a response came over the connection.
+    changed: Notify,
+    /// Messages for the task running the connection to the server, if there is one.
+    connection: Mutex<Option<Outgoing>>,
+    cache: Mutex<BTreeMap<String, Vec<ResolveRecord>>>,
+}
+
+impl Shared {
+    async fn send(self: &Arc<Self>, messages: Vec<Action>) {
+        for action in messages {
+            match action {
+                // A datagram that failed to go is no different from a lost one, the engine times it out.
+                Action::Send(query, data) => {
+                    let socket = self.sockets.lock().unwrap().get(&query).cloned();
+                    if let Some(socket) = socket {
+                        drop(socket.send(&data).await);
+                    }
+                }
+                Action::SendStream(query, data) => self.send_stream(query, data),
+                Action::Finished(_, _) => (),
+            }
+        }
+    }
+
+    /// Queues the message for the connection to the server, opening it if there is none.
+    fn send_stream(self: &Arc<Self>, query: QueryId, data: Vec<u8>) {
+        let mut connection = self.connection.lock().unwrap();
+        let message = match connection.as_ref() {
+            Some(sender) => match sender.send((query, data)) {
+                Ok(()) => return,
+                Err(mpsc::error::SendError(message)) => message,
+            },
+            None => (query, data),
+        };
+
+        let (sender, messages) = mpsc::unbounded_channel();
+        let _ = sender.send(message);
+        *connection = Some(sender);
+
+        let config = self.state.lock().unwrap().engine.config().clone();
+        #[cfg(any(feature = "https", feature = "quic"))]
+        if multiplex::is_multiplexed(config.transport()) {
+            tokio::spawn(run_multiplexed(self.clone(), config, messages));
+            return;
+        }
+
+        tokio::spawn(run_connection(self.clone(), config, messages));
+    }
+
+    /// Forgets the connection if its task is over, a new one is opened for the next message then.
+    fn forget_connection(&self) {
+        let mut connection = self.connection.lock().unwrap();
+        if connection.as_ref().is_some_and(|sender| sender.is_closed()) {
+            *connection = None;
+        }
+    }
+
+    /// The messages still queued are sent again by the engine, along with the others in flight.
+    fn connection_closed(&self, mut messages: Messages) {
+        messages.close();
+        self.forget_connection();
+        self.state.lock().unwrap().engine.handle_stream_closed(Instant::now());
+        self.changed.notify_one();
+    }
+
+    /// The connection is closed unless queries still wait for their responses over it.
+    fn idle(&self, messages: &mut Messages) -> Idle {
+        if self.state.lock().unwrap().engine.pending_streams() > 0 {
+            return Idle::KeepOpen;
+        }
+
+        // Unless a message came in the meantime, nothing is left to send again.
+        messages.close();
+        if messages.try_recv().is_err() {
+            self.forget_connection();
+            return Idle::Close;
+        }
+
+        return Idle::Reopen;
+    }
+}
+
+trait AsyncStream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<S: AsyncRead + AsyncWrite + Send + Unpin> AsyncStream for S {}
+
+/// Connection to the configured server over TCP or, with `Transport::Tls`, over TLS.
+async fn connect(config: &Config) -> Result<Box<dyn AsyncStream>, ResolveError> {
+    let stream = TcpStream::connect(config.server()).await.map_err(|_| ResolveError::TransportFailed)?;
+    let _ = stream.set_nodelay(true);
+
+    let tls = match config.transport() {
+        Transport::Udp => return Ok(Box::new(stream)),
+        Transport::Tls(tls) => tls,
+        #[cfg(feature = "https")]
+        Transport::Https(_) => return Err(ResolveError::TransportFailed),
+        #[cfg(feature = "quic")]
+        Transport::Quic(_) => return Err(ResolveError::TransportFailed),
+    };
+
+    let connector = TlsConnector::from(tls.client_config(Some(DOT_ALPN))?);
+    let stream = connector.connect(tls.server_name()?, stream).await.map_err(|_| ResolveError::TransportFailed)?;
+    return Ok(Box::new(stream));
+}
+
+/// Runs the connection to the server: writes the messages coming from `messages`, hands the responses
+/// to the engine, and closes the connection once it is idle. The queries left without their responses
+/// when the server closes it are sent again over a new one.
+async fn run_connection(shared: Arc<Shared>, config: Config, mut messages: Messages) {
+    let stream = match tokio::time::timeout(config.timeout(), connect(&config)).await {
+        Ok(Ok(stream)) => stream,
+        _ => {
+            shared.connection_closed(messages);
+            return;
+        }
+    };
+
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let last_used = Mutex::new(Instant::now());
+
+    let reading = async {
+        loop {
+            let mut message = match reader.read_u16().await {
+                Ok(length) => vec![0; length as usize],
+                Err(_) => return,
+            };
+
+            if reader.read_exact(&mut message).await.is_err() {
+                return;
+            }
+
+            *last_used.lock().unwrap() = Instant::now();
+            shared.state.lock().unwrap().engine.handle_message(&message, Instant::now());
+            shared.changed.notify_one();
+        }
+    };
+    tokio::pin!(reading);
+
+    loop {
+        let idle_timeout = shared.state.lock().unwrap().engine.idle_timeout();
+        let idle_deadline = *last_used.lock().unwrap() + idle_timeout;
+
+        tokio::select! {
+            _ = &mut reading => break,
+            message = messages.recv() => {
+                let data = match message {
+                    Some((_, data)) => data,
+                    None => break,
+                };
+
+                let mut framed = (data.len() as u16).to_be_bytes().to_vec();
+                framed.extend_from_slice(&data);
+                if writer.write_all(&framed).await.is_err() || writer.flush().await.is_err() {
+                    break;
+                }
+
+                *last_used.lock().unwrap() = Instant::now();
+            }
+            _ = tokio::time::sleep_until(idle_deadline.into()) => match shared.idle(&mut messages) {
+                Idle::KeepOpen => *last_used.lock().unwrap() = Instant::now(),
+                Idle::Close => return,
+                Idle::Reopen => break,
+            },
+        }
+    }
+
+    shared.connection_closed(messages);
+}
+
+/// Runs the HTTP/2 or QUIC connection to the server: every message goes on a stream of its own, the responses
+/// come in any order. The connection is closed once it is idle, and reopened if the server closes it.
+#[cfg(any(feature = "https", feature = "quic"))]
+async fn run_multiplexed(shared: Arc<Shared>, config: Config, mut messages: Messages) {
+    let connection = match tokio::time::timeout(config.timeout(), multiplex::Connection::connect(&config)).await {
+        Ok(Ok(connection)) => connection,
+        _ => {
+            shared.connection_closed(messages);
+            return;
+        }
+    };
+
+    let mut last_used = Instant::now();
+    loop {
+        let idle_timeout = shared.state.lock().unwrap().engine.idle_timeout();
+        tokio::select! {
+            message = messages.recv() => {
+                let (query, data) = match message {
+                    Some(message) => message,
+                    None => break,
+                };
+
+                last_used = Instant::now();
+                tokio::spawn(exchange(shared.clone(), connection.clone(), query, data));
+            }
+            _ = connection.closed() => break,
+            _ = tokio::time::sleep_until((last_used + idle_timeout).into()) => match shared.idle(&mut messages) {
+                Idle::KeepOpen => last_used = Instant::now(),
+                Idle::Close => return,
+                Idle::Reopen => break,
+            },
+        }
+    }
+
+    shared.connection_closed(messages);
+}
+
+/// Hands the response to the query sent over HTTPS or QUIC to the engine. Queries that failed along with
+/// the connection are sent again by `run_multiplexed`, the others fail.
+#[cfg(any(feature = "https", feature = "quic"))]
+async fn exchange(shared: Arc<Shared>, connection: multiplex::Connection, query: QueryId, data: Vec<u8>) {
+    let result = connection.clone().exchange(data).await;
+    {
+        let mut state = shared.state.lock().unwrap();
+        match result {
+            Ok(response) => state.engine.handle_message(&response, Instant::now()),
+            Err(_) if connection.is_closed() => return,
+            Err(err) => state.engine.fail(query, err),
+        }
+    }
+
+    shared.changed.notify_one();
+}
+
+/// Drives the timers of the engine, the responses over UDP are read by the lookups themselves.
+async fn receive(shared: Arc<Shared>) {
+    loop {
+        let deadline = shared.state.lock().unwrap().engine.next_timeout();
+        let timer = async {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                None => future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            _ = timer => shared.state.lock().unwrap().engine.handle_timeout(Instant::now()),
+            _ = shared.changed.notified() => (),
+        }
+
+        let messages = shared.state.lock().unwrap().take_actions();
+        shared.send(messages).await;
+    }
+}
+
+/// Non-blocking socket connected to the server from a random port.
+fn bind(config: &Config) -> Result<UdpSocket, ResolveError> {
+    let socket = connect_udp(config.server(), config.random())?;
+    socket.set_nonblocking(true).map_err(|_| ResolveError::TransportFailed)?;
+    return UdpSocket::from_std(socket).map_err(|_| ResolveError::TransportFailed);
+}
+
+/// Forgets the query and closes its socket when the lookup finishes or is dropped.
+struct Outstanding<'a> {
+    shared: &'a Shared,
+    query: QueryId,
+}
+
+impl Drop for Outstanding<'_> {
+    fn drop(&mut self) {
+        self.shared.sockets.lock().unwrap().remove(&self.query);
+        let mut state = self.shared.state.lock().unwrap();
+        state.waiters.remove(&self.query);
+        state.engine.cancel(self.query);
+    }
+}
+
+/// Stops the receiving task along with the last handle of the resolver.
+struct Receiver(JoinHandle<()>);
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Resolver for Tokio, cheap to clone: clones share the queries in flight, the connection and the cache.
+///
+/// Every lookup over UDP sends its query, and the retransmissions of it, from a socket of its own
+/// bound to a random port and closed once it completes, so concurrent lookups take as many ports.
+/// The responses are matched to their queries by `Engine`. A lookup dropped before it completes
+/// is cancelled, so `tokio::time::timeout` can bound it tighter than the engine does. DNSSEC
+/// validation is not done here, a config asking for it is rejected.
+#[derive(Clone)]
+pub struct AsyncResolver {
+    shared: Arc<Shared>,
+    _receiver: Arc<Receiver>,
+}
+
+impl AsyncResolver {
+    /// Must be called within a Tokio runtime, which runs the receiving task.
+    pub fn new() -> Result<Self, ResolveError> {
+        return Self::with_config(Config::new());
+    }
+
+    /// Must be called within a Tokio runtime, which runs the receiving task. Fails with
+    /// `ResolveError::TransportFailed` if the config asks for DNSSEC validation, which the answers would go without.
+    pub fn with_config(config: Config) -> Result<Self, ResolveError> {
+        if config.dnssec_validation() {
+            return Err(ResolveError::TransportFailed);
+        }
+
+        let state = State {
+            engine: Engine::new(config),
+            waiters: Default::default(),
+        };
+
+        let shared = Arc::new(Shared {
+            sockets: Default::default(),
+            state: Mutex::new(state),
+            changed: Notify::new(),
+            connection: Default::default(),
+            cache: Default::default(),
+        });
+
+        let receiver = Receiver(tokio::spawn(receive(shared.clone())));
+        return Ok(Self {
+            shared,
+            _receiver: Arc::new(receiver),
+        });
+    }
+
+    pub fn config(&self) -> Config {
+        return self.shared.state.lock().unwrap().engine.config().clone();
+    }
+
+    /// Number of queries waiting for their responses.
+    pub fn pending(&self) -> usize {
+        return self.shared.state.lock().unwrap().engine.pending();
+    }
+
+    async fn exchange(&self, qtype: QType, host: &str) -> Result<DnsResponse, ResolveError> {
+        let (sender, mut receiver) = oneshot::channel();
+        let (query, messages, config) = {
+            let mut state = self.shared.state.lock().unwrap();
+            let query = state.engine.query(qtype, host, false, Instant::now());
+            state.waiters.insert(query, sender);
+            (query, state.take_actions(), state.engine.config().clone())
+        };
+
+        let _outstanding = Outstanding {
+            shared: &self.shared,
+            query,
+        };
+
+        // Queries over a connection to the server need no socket.
+        let socket = if messages.iter().any(|action| matches!(action, Action::Send(_, _))) {
+            Some(Arc::new(bind(&config)?))
+        } else {
+            None
+        };
+
+        if let Some(socket) = &socket {
+            self.shared.sockets.lock().unwrap().insert(query, socket.clone());
+        }
+
+        self.shared.changed.notify_one();
+        self.shared.send(messages).await;
+
+        let socket = match socket {
+            Some(socket) => socket,
+            None => return receiver.await.unwrap_or(Err(ResolveError::TransportFailed)),
+        };
+
+        let mut buffer = vec![0; 65_535];
+        loop {
+            tokio::select! {
+                result = &mut receiver => return result.unwrap_or(Err(ResolveError::TransportFailed)),
+                received = socket.recv_from(&mut buffer) => {
+                    // Errors such as unreachable ports are reported by the timeout of the query.
+                    if let Ok((size, source)) = received {
+                        let messages = {
+                            let mut state = self.shared.state.lock().unwrap();
+                            state.engine.handle_datagram(source, &buffer[.. size], Instant::now());
+                            state.take_actions()
+                        };
+
+                        self.shared.changed.notify_one();
+                        self.shared.send(messages).await;
+                    }
+                }
+            }
+        }
+    }
+
+    async fn get_records(&self, qtype: QType, host: &str) -> RecordsResult {
+        let response = self.exchange(qtype, host).await?;
+        check_rcode(&response)?;
+
+        let mut records = answer_records(&response);
+        if let QType::SRV = qtype {
+            sort_by_priority(&mut records);
+        }
+
+        return Ok(records);
+    }
+
+    pub async fn get_srv_records(&self, host: &str) -> RecordsResult {
+        return self.get_records(QType::SRV, host).await;
+    }
+
+    pub async fn get_a_records(&self, host: &str) -> RecordsResult {
+        return self.get_records(QType::A, host).await;
+    }
+
+    /// IPv6 addresses of the host, as `RecordVariant::A` records.
+    pub async fn get_aaaa_records(&self, host: &str) -> RecordsResult {
+        return self.get_records(QType::AAAA, host).await;
+    }
+
+    fn cached(&self, host: &str) -> Option<ResolveResult> {
+        let subnet = self.config().client_subnet().copied();
+        let cache = self.shared.cache.lock().unwrap();
+        let records = cache.get(host)?;
+
+        let now = Instant::now();
+        if records.iter().any(|record| record.is_outdated(now) || !is_applicable(record, subnet.as_ref())) {
+            return None;
+        }
+
+        return Some(ResolveResult::new(records));
+    }
+
+    /// Addresses of the host from the cache shared by the clones, looked up if they are missing or outdated.
+    pub async fn resolve_host(&self, host: &str) -> Result<ResolveResult, ResolveError> {
+        if let Some(result) = self.cached(host) {
+            return Ok(result);
+        }
+
+        let response = self.exchange(QType::A, host).await?;
+        check_rcode(&response)?;
+
+        let records = cached_records(host, &Answer::new(response, Security::Indeterminate));
+        let result = ResolveResult::new(&records);
+        self.shared.cache.lock().unwrap().insert(host.to_string(), records);
+
+        return Ok(result);
+    }
+}
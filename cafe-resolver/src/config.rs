@@ -0,0 +1,212 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use data_encoding::HEXUPPER;
+
+use cafe_dns::{ClientSubnet, QClass, ResourceRecord, Type};
+
+#[cfg(feature = "https")]
+use crate::https::HttpsConfig;
+use crate::random::{Random, SharedRandom, SystemRandom};
+use crate::tls::TlsConfig;
+
+/// How the queries get to the server.
+#[derive(Debug, Clone)]
+pub enum Transport {
+    /// UDP, and TCP for the answers that come truncated or for all of them with `Config::set_force_tcp`.
+    Udp,
+    /// DNS over TLS (RFC 7858), servers listen on port 853 for it.
+    Tls(TlsConfig),
+    /// DNS over HTTPS (RFC 8484) to the resolver of the URI template, at the address of the server.
+    #[cfg(feature = "https")]
+    Https(HttpsConfig),
+    /// DNS over QUIC (RFC 9250), servers listen on UDP port 853 for it.
+    #[cfg(feature = "quic")]
+    Quic(TlsConfig),
+}
+
+impl Transport {
+    /// Settings of TLS the messages are protected with, `None` over UDP and TCP.
+    pub fn tls(&self) -> Option<&TlsConfig> {
+        match self {
+            Transport::Udp => None,
+            Transport::Tls(tls) => Some(tls),
+            #[cfg(feature = "https")]
+            Transport::Https(https) => Some(https.tls()),
+            #[cfg(feature = "quic")]
+            Transport::Quic(tls) => Some(tls),
+        }
+    }
+
+    /// Whether nobody on the path can see or change the messages.
+    pub fn is_encrypted(&self) -> bool {
+        self.tls().is_some()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Upstream recursive server all the queries are sent to.
+    server: SocketAddr,
+    /// Transport the queries go to `server` over, it comes along whenever the server is changed.
+    transport: Transport,
+    /// EDNS Client Subnet (RFC 7871) attached to every query when set.
+    /// `ClientSubnet::opt_out()` asks the server not to use the client's address at all.
+    client_subnet: Option<ClientSubnet>,
+    /// Whether answers are validated with DNSSEC, see `Resolver::lookup`.
+    dnssec_validation: bool,
+    /// DS or DNSKEY records the chains of trust start from, the root KSKs by default.
+    trust_anchors: Vec<ResourceRecord>,
+    /// Time the first attempt of a query waits for the response before the query is sent again.
+    /// Every retransmission waits twice as long as the previous one, plus a random jitter.
+    attempt_timeout: Duration,
+    /// Time a query may take over all its attempts, it fails with `ResolveError::Timeout` then.
+    timeout: Duration,
+    /// Whether the letters of query names are sent in random case, which the responses have
+    /// to repeat (draft-vixie-dnsext-dns0x20). Servers that don't are queried without it.
+    case_randomization: bool,
+    /// Whether the queries go over TCP right away, rather than only once UDP answers come truncated.
+    force_tcp: bool,
+    /// Time a connection to the server is kept open without queries, for the following ones
+    /// to reuse it. Servers may ask for less with edns-tcp-keepalive (RFC 7828).
+    idle_timeout: Duration,
+    /// Source of message IDs, source ports and jitter, shared by the copies of the config.
+    random: SharedRandom,
+}
+
+/// DS records of the root zone KSKs published by IANA.
+fn root_trust_anchors() -> Vec<ResourceRecord> {
+    let anchors = [
+        (20326, "E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D"),
+        (38696, "683D2D0ACB8C9B712A1948B27F741219298D0A450D612C483AF444A4C0FB2B16"),
+    ];
+
+    anchors
+        .iter()
+        .map(|(key_tag, digest)| {
+            let ds = Type::DS {
+                key_tag: *key_tag,
+                algorithm: 8,
+                digest_type: 2,
+                digest: HEXUPPER.decode(digest.as_bytes()).unwrap(),
+            };
+
+            ResourceRecord::new("", QClass::IN as u16, 0, ds)
+        })
+        .collect()
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self {
+            server: SocketAddr::from(([8, 8, 8, 8], 53)),
+            transport: Transport::Udp,
+            client_subnet: None,
+            dnssec_validation: false,
+            trust_anchors: root_trust_anchors(),
+            attempt_timeout: Duration::from_secs(1),
+            timeout: Duration::from_secs(5),
+            case_randomization: false,
+            force_tcp: false,
+            idle_timeout: Duration::from_secs(10),
+            random: SharedRandom::new(SystemRandom::new()),
+        }
+    }
+
+    pub fn server(&self) -> SocketAddr {
+        self.server
+    }
+
+    /// Changes the server, keeping the transport; `set_server_with_transport` changes both.
+    pub fn set_server(&mut self, server: SocketAddr) {
+        self.server = server
+    }
+
+    pub fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    pub fn set_server_with_transport(&mut self, server: SocketAddr, transport: Transport) {
+        self.server = server;
+        self.transport = transport
+    }
+
+    pub fn client_subnet(&self) -> Option<&ClientSubnet> {
+        self.client_subnet.as_ref()
+    }
+
+    pub fn set_client_subnet(&mut self, subnet: Option<ClientSubnet>) {
+        self.client_subnet = subnet
+    }
+
+    pub fn dnssec_validation(&self) -> bool {
+        self.dnssec_validation
+    }
+
+    pub fn set_dnssec_validation(&mut self, value: bool) {
+        self.dnssec_validation = value
+    }
+
+    pub fn trust_anchors(&self) -> &[ResourceRecord] {
+        &self.trust_anchors
+    }
+
+    pub fn set_trust_anchors(&mut self, anchors: Vec<ResourceRecord>) {
+        self.trust_anchors = anchors
+    }
+
+    pub fn attempt_timeout(&self) -> Duration {
+        self.attempt_timeout
+    }
+
+    pub fn set_attempt_timeout(&mut self, value: Duration) {
+        self.attempt_timeout = value
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn set_timeout(&mut self, value: Duration) {
+        self.timeout = value
+    }
+
+    pub fn case_randomization(&self) -> bool {
+        self.case_randomization
+    }
+
+    pub fn set_case_randomization(&mut self, value: bool) {
+        self.case_randomization = value
+    }
+
+    pub fn force_tcp(&self) -> bool {
+        self.force_tcp
+    }
+
+    pub fn set_force_tcp(&mut self, value: bool) {
+        self.force_tcp = value
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    pub fn set_idle_timeout(&mut self, value: Duration) {
+        self.idle_timeout = value
+    }
+
+    pub(crate) fn random(&self) -> &SharedRandom {
+        &self.random
+    }
+
+    /// Replaces `SystemRandom`, to make the queries predictable in tests.
+    pub fn set_random<R: Random + 'static>(&mut self, random: R) {
+        self.random = SharedRandom::new(random)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
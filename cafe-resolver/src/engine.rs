@@ -0,0 +1,458 @@
+//! Query logic separated from sockets and clocks, shared by all the resolvers.
+//!
+//! `Engine` is fed with received messages and the current time, and tells what has to be
+//! sent and which queries finished through `Action`s. Drivers own the transport: the blocking
+//! `Resolver`, the non-blocking `EventResolver`, or anything else able to carry datagrams
+//! and, for the truncated answers, messages over a connection to the server.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use cafe_common::stream::Output as OutputStream;
+use cafe_dns::{Edns, EdnsOption, QClass, QType, Request as DnsRequest, Response as DnsResponse};
+
+use crate::random::SharedRandom;
+use crate::{Config, ResolveError, TlsConfig, Transport};
+
+/// Handle of a query started by `Engine::query`, unique for the engine.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct QueryId(u64);
+
+/// What the driver has to do on behalf of the engine.
+// Actions are taken as soon as they are made, boxing the response would only add an allocation.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum Action {
+    /// Datagram to send to the configured server.
+    Send(QueryId, Vec<u8>),
+    /// Message to send over a connection to the configured server, without the length prefix.
+    /// The response goes to `Engine::handle_message`.
+    SendStream(QueryId, Vec<u8>),
+    /// The query is over, with the response regardless of its response code or with the failure.
+    Finished(QueryId, Result<DnsResponse, ResolveError>),
+}
+
+#[derive(Debug)]
+struct Query {
+    id: QueryId,
+    /// Question the response has to repeat, the name without the trailing dot.
+    qname: String,
+    qtype: QType,
+    /// Whether the DNSSEC records were asked for.
+    validate: bool,
+    /// Message sent again on retransmissions and reconnections.
+    data: Vec<u8>,
+    /// The datagram with the name as it was given when its case is randomized, `None` otherwise.
+    plain_data: Option<Vec<u8>>,
+    /// Number of responses that came with the question in another case.
+    case_mismatches: u32,
+    /// Number of times the datagram was sent.
+    attempts: u32,
+    /// Whether the query went over a connection, it isn't retransmitted then.
+    stream: bool,
+    /// Number of connections the query was sent over.
+    stream_sends: u32,
+    /// Time of the next retransmission.
+    retry: Instant,
+    /// Time the query fails at, whatever the number of attempts.
+    deadline: Instant,
+}
+
+impl Query {
+    /// Checks that the response repeats the question of the query, the name in any case.
+    fn is_answered_by(&self, response: &DnsResponse) -> bool {
+        if !response.header().is_response() {
+            return false;
+        }
+
+        return match response.questions() {
+            [question] => {
+                question.host_name().eq_ignore_ascii_case(&self.qname)
+                    && question.qtype() as u16 == self.qtype as u16
+                    && question.qclass() as u16 == QClass::IN as u16
+            }
+            _ => false,
+        };
+    }
+
+    fn next_timeout(&self) -> Instant {
+        self.retry.min(self.deadline)
+    }
+}
+
+/// End of the question name of the encoded message, which is never compressed.
+fn name_end(data: &[u8]) -> Option<usize> {
+    let mut offset = 12;
+    loop {
+        let length = *data.get(offset)? as usize;
+        offset += 1 + length;
+        if length == 0 {
+            return Some(offset);
+        }
+    }
+}
+
+/// Flips the case of the letters of the question name at random, one bit of the generator per letter.
+fn randomize_case(data: &mut [u8], random: &SharedRandom) {
+    let name = match name_end(data) {
+        Some(end) => &mut data[12 .. end],
+        None => return,
+    };
+
+    let mut bits = vec![0; name.len() / 8 + 1];
+    random.fill(&mut bits);
+    // Length octets never exceed 63, so only the characters of the labels are letters.
+    for (i, byte) in name.iter_mut().enumerate().filter(|(_, byte)| byte.is_ascii_alphabetic()) {
+        if bits[i / 8] & (1 << (i % 8)) != 0 {
+            *byte ^= 0x20;
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Engine {
+    config: Config,
+    /// Set once the server has answered with the case of a question changed too many times,
+    /// the names are not randomized for it until then.
+    case_ignored_until: Option<Instant>,
+    /// Idle timeout of the connections the server announced with edns-tcp-keepalive.
+    keepalive: Option<Duration>,
+    query_count: u64,
+    /// Outstanding queries by their message IDs.
+    queries: BTreeMap<u16, Query>,
+    actions: VecDeque<Action>,
+}
+
+/// Connections a query may be sent over before it fails, so that a server closing them
+/// right away doesn't keep the query going until its deadline.
+const MAX_STREAM_SENDS: u32 = 3;
+
+/// Responses with the question in another case a query takes before it goes with the name as
+/// it was given. Fewer are taken for spoofed ones, sent by somebody who doesn't know the case.
+const MAX_CASE_MISMATCHES: u32 = 3;
+
+/// Time the names are not randomized for once the server failed to repeat their case, after that
+/// they are again, in case the mismatches were spoofed or the server changed.
+const CASE_FALLBACK_TIME: Duration = Duration::from_secs(600);
+
+/// Block size the encrypted queries are padded to.
+const PADDING_BLOCK: usize = 128;
+
+/// Query for `host` to the configured server: recursion desired, OPT with the client subnet
+/// and the DNSSEC bits if `validate` is set. Queries sent over a TCP or TLS connection ask the server
+/// to keep it open (RFC 7828), which must not be done over UDP, and encrypted ones are padded
+/// if the TLS config says so.
+fn encode_query(config: &Config, id: u16, qtype: QType, host: &str, validate: bool, stream: bool) -> Vec<u8> {
+    let mut request = DnsRequest::new(id);
+    request.header_mut().set_rd(true);
+    // Validation is done here, so the server shouldn't drop the data it finds bogus.
+    request.header_mut().set_cd(validate);
+    request.add_question(host, qtype, QClass::IN);
+
+    // OPT is always attached, otherwise servers won't send extended errors back.
+    let mut edns = Edns::new();
+    edns.set_dnssec_ok(validate);
+    if let Some(subnet) = config.client_subnet() {
+        edns.add_option(EdnsOption::ClientSubnet(*subnet));
+    }
+
+    if stream && matches!(config.transport(), Transport::Udp | Transport::Tls(_)) {
+        edns.add_option(EdnsOption::TcpKeepalive(None));
+    }
+    request.set_edns(Some(edns.clone()));
+
+    let mut buffer = Vec::with_capacity(512);
+    request.encode(&mut OutputStream::new(&mut buffer));
+
+    // RFC 8467, section 4.1: the length of the query is padded to a multiple of 128 octets,
+    // counting the four octets of the code and the length of the option.
+    if stream && config.transport().tls().is_some_and(TlsConfig::padding) {
+        let length = (PADDING_BLOCK - (buffer.len() + 4) % PADDING_BLOCK) % PADDING_BLOCK;
+        edns.add_option(EdnsOption::Padding(length as u16));
+        request.set_edns(Some(edns));
+
+        buffer.clear();
+        request.encode(&mut OutputStream::new(&mut buffer));
+    }
+
+    return buffer;
+}
+
+/// RFC 7871, section 7.3: a response with the subnet that doesn't match
+/// the one of the query must be dropped.
+fn client_subnet_matches(config: &Config, response: &DnsResponse) -> bool {
+    let returned = response.edns().and_then(|edns| edns.client_subnet());
+    return match (config.client_subnet(), returned) {
+        (Some(sent), Some(returned)) => {
+            sent.family() == returned.family()
+                && sent.source_prefix() == returned.source_prefix()
+                && sent.address() == returned.address()
+        }
+        _ => true,
+    };
+}
+
+/// Time the attempt number `attempt` (counting from 1) waits: `base` doubled with every
+/// retransmission, plus up to a quarter of it at random, so that clients don't retry in lockstep.
+fn backoff(base: Duration, attempt: u32, random: &SharedRandom) -> Duration {
+    let interval = base.saturating_mul(1 << (attempt - 1).min(16));
+    let quarter = interval.as_nanos() as u64 / 4;
+    let jitter = match quarter {
+        0 => 0,
+        _ => random.next_u64() % quarter,
+    };
+
+    return interval + Duration::from_nanos(jitter);
+}
+
+impl Engine {
+    pub fn new(config: Config) -> Self {
+        return Self {
+            config,
+            case_ignored_until: None,
+            keepalive: None,
+            query_count: 0,
+            queries: Default::default(),
+            actions: Default::default(),
+        };
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn config_mut(&mut self) -> &mut Config {
+        &mut self.config
+    }
+
+    /// Number of queries waiting for their responses.
+    pub fn pending(&self) -> usize {
+        self.queries.len()
+    }
+
+    /// Random message ID not used by any outstanding query, for the messages sent past the engine too.
+    pub fn next_id(&mut self) -> u16 {
+        loop {
+            let id = self.config.random().next_u16();
+            if !self.queries.contains_key(&id) {
+                return id;
+            }
+        }
+    }
+
+    /// Starts the query, the datagram to send comes with the next action. With `validate`
+    /// the DNSSEC records are asked for, validating them is up to the driver.
+    pub fn query(&mut self, qtype: QType, host: &str, validate: bool, now: Instant) -> QueryId {
+        self.query_count += 1;
+        let query = QueryId(self.query_count);
+
+        if self.queries.len() > u16::MAX as usize {
+            self.actions.push_back(Action::Finished(query, Err(ResolveError::TransportFailed)));
+            return query;
+        }
+
+        let id = self.next_id();
+        let stream = self.config.force_tcp() || self.config.transport().is_encrypted();
+        let mut data = encode_query(&self.config, id, qtype, host, validate, stream);
+        let mut plain_data = None;
+        // Nobody off the path can inject anything into a connection, there is nothing to add.
+        let case_ignored = self.case_ignored_until.is_some_and(|until| now < until);
+        if self.config.case_randomization() && !case_ignored && !stream {
+            plain_data = Some(data.clone());
+            randomize_case(&mut data, self.config.random());
+        }
+
+        let deadline = now + self.config.timeout();
+        let pending = Query {
+            id: query,
+            qname: host.trim_end_matches('.').to_string(),
+            qtype,
+            validate,
+            data: data.clone(),
+            plain_data,
+            case_mismatches: 0,
+            attempts: 1,
+            stream,
+            stream_sends: stream as u32,
+            retry: match stream {
+                true => deadline,
+                false => now + backoff(self.config.attempt_timeout(), 1, self.config.random()),
+            },
+            deadline,
+        };
+
+        self.queries.insert(id, pending);
+        self.actions.push_back(match stream {
+            true => Action::SendStream(query, data),
+            false => Action::Send(query, data),
+        });
+
+        return query;
+    }
+
+    /// Forgets the query, no action is produced for it afterwards.
+    pub fn cancel(&mut self, query: QueryId) {
+        self.queries.retain(|_, pending| pending.id != query);
+        self.actions.retain(|action| match action {
+            Action::Send(id, _) | Action::SendStream(id, _) | Action::Finished(id, _) => *id != query,
+        });
+    }
+
+    /// Takes the datagram received from `source`. Those that don't come from the configured
+    /// server or don't answer any outstanding query are ignored, the query keeps waiting then.
+    ///
+    /// With the case of the name randomized the question has to be repeated exactly. A response
+    /// with it in another case is dropped as a spoofed one and the query is sent again. Once that
+    /// happens `MAX_CASE_MISMATCHES` times, the server is taken for one ignoring the case: the query
+    /// goes with the name as it was given, and so do the following ones for `CASE_FALLBACK_TIME`.
+    /// Responses come with the question name restored to the one of the query in any case.
+    ///
+    /// A truncated response makes the query go again over a connection, see `Action::SendStream`.
+    pub fn handle_datagram(&mut self, source: SocketAddr, data: &[u8], now: Instant) {
+        if source == self.config.server() {
+            self.handle_response(false, data, now);
+        }
+    }
+
+    /// Takes the message received over a connection to the server, the length prefix stripped.
+    /// Responses may come in any order, they are matched by their IDs.
+    pub fn handle_message(&mut self, data: &[u8], now: Instant) {
+        self.handle_response(true, data, now);
+    }
+
+    /// Tells that the connection to the server is closed. The queries still waiting for their
+    /// responses over it are sent again, over a new one, unless they were sent too many times already.
+    pub fn handle_stream_closed(&mut self, _now: Instant) {
+        let mut failed = Vec::new();
+        for (id, query) in self.queries.iter_mut().filter(|(_, query)| query.stream) {
+            if query.stream_sends >= MAX_STREAM_SENDS {
+                failed.push(*id);
+            } else {
+                query.stream_sends += 1;
+                self.actions.push_back(Action::SendStream(query.id, query.data.clone()));
+            }
+        }
+
+        for id in failed {
+            if let Some(query) = self.queries.remove(&id) {
+                self.actions.push_back(Action::Finished(query.id, Err(ResolveError::TransportFailed)));
+            }
+        }
+    }
+
+    /// Number of queries waiting for their responses over a connection.
+    pub fn pending_streams(&self) -> usize {
+        self.queries.values().filter(|query| query.stream).count()
+    }
+
+    /// Time a connection to the server may stay open without queries: `Config::idle_timeout`,
+    /// or less if the server asked for that with edns-tcp-keepalive.
+    pub fn idle_timeout(&self) -> Duration {
+        let idle_timeout = self.config.idle_timeout();
+        return self.keepalive.map_or(idle_timeout, |keepalive| keepalive.min(idle_timeout));
+    }
+
+    /// Finishes the query with `error`, for the failures of the transport only the driver sees.
+    pub fn fail(&mut self, query: QueryId, error: ResolveError) {
+        let before = self.queries.len();
+        self.queries.retain(|_, pending| pending.id != query);
+        if self.queries.len() != before {
+            self.actions.push_back(Action::Finished(query, Err(error)));
+        }
+    }
+
+    fn handle_response(&mut self, stream: bool, data: &[u8], now: Instant) {
+        if data.len() < 2 {
+            return;
+        }
+
+        // Datagrams still coming for the queries moved over to a connection are late retransmissions.
+        let id = u16::from_be_bytes([data[0], data[1]]);
+        let mut response = match (self.queries.get(&id), DnsResponse::decode(data)) {
+            (Some(query), Some(response)) if query.stream == stream && query.is_answered_by(&response) => response,
+            _ => return,
+        };
+
+        // Dropped like a spoofed one, the query waits for the genuine response.
+        if !client_subnet_matches(&self.config, &response) {
+            return;
+        }
+
+        let query = self.queries.get_mut(&id).unwrap();
+        let end = name_end(&query.data).unwrap();
+        if query.plain_data.is_some() && data.get(12 .. end) != Some(&query.data[12 .. end]) {
+            query.case_mismatches += 1;
+            if query.case_mismatches >= MAX_CASE_MISMATCHES {
+                self.case_ignored_until = Some(now + CASE_FALLBACK_TIME);
+                query.data = query.plain_data.take().unwrap();
+            }
+
+            self.actions.push_back(Action::Send(query.id, query.data.clone()));
+            return;
+        }
+
+        // Names compressed to the question, as most of the answer usually is, get its case too.
+        let asked = query.plain_data.as_ref().unwrap_or(&query.data);
+        if data.get(12 .. end).is_some_and(|name| name != &asked[12 .. end]) {
+            let mut restored = data.to_vec();
+            restored[12 .. end].copy_from_slice(&asked[12 .. end]);
+            response = match DnsResponse::decode(&restored) {
+                Some(response) => response,
+                None => return,
+            };
+        }
+
+        if let (true, Some(timeout)) = (stream, response.edns().and_then(|edns| edns.tcp_keepalive())) {
+            self.keepalive = Some(Duration::from_millis(u64::from(timeout) * 100));
+        }
+
+        let query = self.queries.get_mut(&id).unwrap();
+        if response.header().tc() && !stream {
+            query.data = encode_query(&self.config, id, query.qtype, &query.qname, query.validate, true);
+            query.plain_data = None;
+            query.stream = true;
+            query.stream_sends = 1;
+            query.retry = query.deadline;
+            self.actions.push_back(Action::SendStream(query.id, query.data.clone()));
+            return;
+        }
+
+        let query = self.queries.remove(&id).unwrap();
+        self.actions.push_back(Action::Finished(query.id, Ok(response)));
+    }
+
+    /// Fails the queries that had no response by their deadlines with `ResolveError::Timeout`,
+    /// and sends the others again once their attempts time out.
+    pub fn handle_timeout(&mut self, now: Instant) {
+        let attempt_timeout = self.config.attempt_timeout();
+        let random = self.config.random().clone();
+        let mut expired = Vec::new();
+        for (id, query) in self.queries.iter_mut() {
+            if query.deadline <= now {
+                expired.push(*id);
+            } else if query.retry <= now {
+                query.attempts += 1;
+                query.retry = now + backoff(attempt_timeout, query.attempts, &random);
+                self.actions.push_back(Action::Send(query.id, query.data.clone()));
+            }
+        }
+
+        for id in expired {
+            if let Some(query) = self.queries.remove(&id) {
+                self.actions.push_back(Action::Finished(query.id, Err(ResolveError::Timeout)));
+            }
+        }
+    }
+
+    /// Time `handle_timeout` has to be called at if nothing is received before,
+    /// `None` if there are no outstanding queries.
+    pub fn next_timeout(&self) -> Option<Instant> {
+        return self.queries.values().map(Query::next_timeout).min();
+    }
+
+    /// Next thing for the driver to do, in the order they come up.
+    pub fn poll_action(&mut self) -> Option<Action> {
+        return self.actions.pop_front();
+    }
+}
@@ -0,0 +1,419 @@
+use std::collections::BTreeMap;
+use std::io::{self, ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+
+use cafe_dns::{QType, Response as DnsResponse};
+use mio::net::{TcpStream, UdpSocket};
+use mio::{Events, Interest, Poll, Token};
+use rustls::ClientConnection;
+
+use crate::engine::{Action, Engine, QueryId};
+use crate::tcp::{self, MessageReader};
+use crate::{answer_records, check_rcode, connect_udp, sort_by_priority};
+use crate::{Config, RecordsResult, ResolveError, Transport};
+#[cfg(any(feature = "https", feature = "quic"))]
+use crate::multiplex;
+
+/// Registration of the UDP socket with the poller.
+const UDP: Token = Token(0);
+/// Registration of the connection to the server.
+const STREAM: Token = Token(1);
+
+/// Invoked once with the outcome of a lookup.
+pub type Callback = Box<dyn FnOnce(RecordsResult)>;
+
+/// Connection to the server over TCP or TLS, read and written without blocking by `process_events`.
+struct Connection {
+    socket: TcpStream,
+    /// TLS session over the socket with `Transport::Tls`.
+    tls: Option<Box<ClientConnection>>,
+    /// Messages the socket didn't take yet, over plain TCP. Over TLS the session keeps them.
+    outgoing: Vec<u8>,
+    reader: MessageReader,
+    last_used: Instant,
+}
+
+impl Connection {
+    /// Starts connecting to the server, the messages sent in the meantime go once it is established.
+    fn open(config: &Config, poll: &Poll) -> Result<Self, ResolveError> {
+        let tls = match config.transport() {
+            Transport::Tls(tls) => Some(Box::new(tcp::tls_connection(tls)?)),
+            _ => None,
+        };
+
+        let mut socket = TcpStream::connect(config.server()).map_err(|_| ResolveError::TransportFailed)?;
+        poll.registry()
+            .register(&mut socket, STREAM, Interest::READABLE | Interest::WRITABLE)
+            .map_err(|_| ResolveError::TransportFailed)?;
+
+        return Ok(Self {
+            socket,
+            tls,
+            outgoing: Vec::new(),
+            reader: MessageReader::default(),
+            last_used: Instant::now(),
+        });
+    }
+
+    /// Queues the message with its length prefix and writes as much as the socket takes.
+    fn send(&mut self, data: &[u8]) -> Result<(), ResolveError> {
+        if data.len() > u16::MAX as usize {
+            return Err(ResolveError::TransportFailed);
+        }
+
+        let mut framed = (data.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(data);
+        match &mut self.tls {
+            Some(tls) => tls.writer().write_all(&framed).map_err(|_| ResolveError::TransportFailed)?,
+            None => self.outgoing.extend_from_slice(&framed),
+        }
+
+        self.last_used = Instant::now();
+        return self.flush();
+    }
+
+    /// Writes what is queued until the socket would block.
+    fn flush(&mut self) -> Result<(), ResolveError> {
+        loop {
+            let result = match &mut self.tls {
+                Some(tls) if tls.wants_write() => tls.write_tls(&mut self.socket),
+                Some(_) => return Ok(()),
+                None if self.outgoing.is_empty() => return Ok(()),
+                None => self.socket.write(&self.outgoing).inspect(|&size| {
+                    self.outgoing.drain(.. size);
+                }),
+            };
+
+            match result {
+                Ok(0) => return Err(ResolveError::TransportFailed),
+                Ok(_) => (),
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) if is_pending(&err) => return Ok(()),
+                Err(_) => return Err(ResolveError::TransportFailed),
+            }
+        }
+    }
+
+    /// Messages received so far, along with whether the connection is over.
+    fn receive(&mut self) -> (Vec<Vec<u8>>, bool) {
+        let mut messages = Vec::new();
+        loop {
+            let mut reader = std::mem::take(&mut self.reader);
+            let result = reader.read_message(self);
+            self.reader = reader;
+            match result {
+                Ok(Some(message)) => messages.push(message),
+                // Nothing more to read for now.
+                Err(ResolveError::Timeout) => break,
+                Ok(None) | Err(_) => return (messages, true),
+            }
+        }
+
+        if !messages.is_empty() {
+            self.last_used = Instant::now();
+        }
+
+        // Reading may leave records to send over TLS, the handshake goes on that way.
+        let closed = self.flush().is_err();
+        return (messages, closed);
+    }
+
+    fn close(mut self, poll: &Poll) {
+        if let Some(tls) = &mut self.tls {
+            tls.send_close_notify();
+            let _ = self.flush();
+        }
+
+        let _ = poll.registry().deregister(&mut self.socket);
+    }
+}
+
+/// Reads what came of the messages, decrypted with TLS.
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let tls = match &mut self.tls {
+            Some(tls) => tls,
+            None => return self.socket.read(buf),
+        };
+
+        loop {
+            match tls.reader().read(buf) {
+                Err(err) if err.kind() == ErrorKind::WouldBlock => (),
+                result => return result,
+            }
+
+            if tls.read_tls(&mut self.socket)? == 0 {
+                return Ok(0);
+            }
+
+            tls.process_new_packets().map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+        }
+    }
+}
+
+/// Whether the socket can't take or give anything now, still connecting or with its buffers full or empty.
+fn is_pending(err: &io::Error) -> bool {
+    return err.kind() == ErrorKind::WouldBlock || err.kind() == ErrorKind::NotConnected;
+}
+
+/// Resolver that never blocks, for hosts running their own event loop.
+///
+/// Lookups are sent right away and complete from `process_events`, which should be called
+/// whenever the descriptor (see `AsRawFd`) becomes readable and once `next_timeout` elapses.
+/// DNSSEC validation is not done here, a config asking for it is rejected.
+///
+/// The socket is bound to a random port and replaced by a new one whenever a lookup starts with
+/// no other awaiting a datagram, so consecutive lookups leave from different ports. Lookups started
+/// while others are outstanding share the port of the first, which a steady load may keep for long.
+/// The descriptor is the one of a poller (epoll or kqueue) the sockets are registered with, it stays
+/// the same for the lifetime of the resolver and may be registered with the event loop once.
+///
+/// Queries that go over TCP, because the answer came truncated or TCP is forced, or over TLS
+/// share a non-blocking connection, registered with the poller too. It is closed by `process_events`
+/// once it is idle for `Engine::idle_timeout`. HTTPS and QUIC are not supported here.
+pub struct EventResolver {
+    engine: Engine,
+    poll: Poll,
+    events: Events,
+    socket: UdpSocket,
+    /// A lookup was sent over the socket, the next one goes from a new one.
+    socket_used: bool,
+    buffer: Vec<u8>,
+    callbacks: BTreeMap<QueryId, (QType, Callback)>,
+    /// Other lookups that finished while one was started, their callbacks are invoked by `process_events`.
+    finished: Vec<(QueryId, Result<DnsResponse, ResolveError>)>,
+    connection: Option<Connection>,
+}
+
+/// Non-blocking socket connected to the server from a random port, registered with the poller.
+fn bind(config: &Config, poll: &Poll) -> Result<UdpSocket, ResolveError> {
+    let socket = connect_udp(config.server(), config.random())?;
+    socket.set_nonblocking(true).map_err(|_| ResolveError::TransportFailed)?;
+    let mut socket = UdpSocket::from_std(socket);
+    poll.registry().register(&mut socket, UDP, Interest::READABLE).map_err(|_| ResolveError::TransportFailed)?;
+    return Ok(socket);
+}
+
+fn records(qtype: QType, response: DnsResponse) -> RecordsResult {
+    check_rcode(&response)?;
+
+    let mut records = answer_records(&response);
+    if let QType::SRV = qtype {
+        sort_by_priority(&mut records);
+    }
+
+    return Ok(records);
+}
+
+impl EventResolver {
+    pub fn new() -> Result<Self, ResolveError> {
+        return Self::with_config(Config::new());
+    }
+
+    /// Fails with `ResolveError::TransportFailed` if the transport of the config is not supported,
+    /// or if it asks for DNSSEC validation, which the answers would go without.
+    pub fn with_config(config: Config) -> Result<Self, ResolveError> {
+        if config.dnssec_validation() {
+            return Err(ResolveError::TransportFailed);
+        }
+
+        #[cfg(any(feature = "https", feature = "quic"))]
+        if multiplex::is_multiplexed(config.transport()) {
+            return Err(ResolveError::TransportFailed);
+        }
+
+        let poll = Poll::new().map_err(|_| ResolveError::TransportFailed)?;
+        return Ok(Self {
+            socket: bind(&config, &poll)?,
+            poll,
+            events: Events::with_capacity(16),
+            socket_used: false,
+            engine: Engine::new(config),
+            buffer: vec![0; 65_535],
+            callbacks: Default::default(),
+            finished: Vec::new(),
+            connection: None,
+        });
+    }
+
+    pub fn config(&self) -> &Config {
+        self.engine.config()
+    }
+
+    /// Number of lookups waiting for their responses.
+    pub fn pending(&self) -> usize {
+        self.callbacks.len()
+    }
+
+    /// Starts the lookup, `callback` gets the answer later unless sending fails right away.
+    fn query(&mut self, qtype: QType, host: &str, callback: Callback) -> Result<(), ResolveError> {
+        // Datagrams still coming to the old socket are late or spoofed.
+        if self.socket_used && self.engine.pending() == self.engine.pending_streams() {
+            let socket = bind(self.engine.config(), &self.poll)?;
+            let _ = self.poll.registry().deregister(&mut self.socket);
+            self.socket = socket;
+        }
+
+        self.socket_used = true;
+        let query = self.engine.query(qtype, host, false, Instant::now());
+        while let Some(action) = self.engine.poll_action() {
+            match action {
+                Action::Send(id, data) if id == query => match self.socket.send(&data) {
+                    Ok(size) if size == data.len() => (),
+                    _ => {
+                        self.engine.cancel(query);
+                        return Err(ResolveError::TransportFailed);
+                    }
+                },
+                Action::Finished(id, Err(err)) if id == query => return Err(err),
+                // Callbacks of the lookups are not invoked from within another one.
+                Action::Finished(id, result) if id != query => self.finished.push((id, result)),
+                action => {
+                    self.take_action(action);
+                }
+            }
+        }
+
+        self.callbacks.insert(query, (qtype, callback));
+        return Ok(());
+    }
+
+    /// Sends the message over the connection to the server, the response comes to `process_events`.
+    fn send_stream(&mut self, data: Vec<u8>) {
+        if self.connection.is_none() {
+            self.connection = Connection::open(self.engine.config(), &self.poll).ok();
+        }
+
+        let sent = match &mut self.connection {
+            Some(connection) => connection.send(&data).is_ok(),
+            None => false,
+        };
+
+        if !sent {
+            self.close_stream();
+            self.engine.handle_stream_closed(Instant::now());
+        }
+    }
+
+    fn close_stream(&mut self) {
+        if let Some(connection) = self.connection.take() {
+            connection.close(&self.poll);
+        }
+    }
+
+    /// Takes what came over the connection, reopening it for the queries it was closed under.
+    fn process_stream(&mut self) {
+        let connection = match &mut self.connection {
+            Some(connection) => connection,
+            None => return,
+        };
+
+        let (messages, closed) = connection.receive();
+        let idle = Instant::now().saturating_duration_since(connection.last_used) >= self.engine.idle_timeout();
+        for message in messages {
+            self.engine.handle_message(&message, Instant::now());
+        }
+
+        if closed {
+            self.close_stream();
+            self.engine.handle_stream_closed(Instant::now());
+        } else if idle && self.engine.pending_streams() == 0 {
+            self.close_stream();
+        }
+    }
+
+    pub fn get_srv_records<F>(&mut self, host: &str, callback: F) -> Result<(), ResolveError>
+    where
+        F: FnOnce(RecordsResult) + 'static,
+    {
+        return self.query(QType::SRV, host, Box::new(callback));
+    }
+
+    pub fn get_a_records<F>(&mut self, host: &str, callback: F) -> Result<(), ResolveError>
+    where
+        F: FnOnce(RecordsResult) + 'static,
+    {
+        return self.query(QType::A, host, Box::new(callback));
+    }
+
+    /// IPv6 addresses of the host, as `RecordVariant::A` records.
+    pub fn get_aaaa_records<F>(&mut self, host: &str, callback: F) -> Result<(), ResolveError>
+    where
+        F: FnOnce(RecordsResult) + 'static,
+    {
+        return self.query(QType::AAAA, host, Box::new(callback));
+    }
+
+    /// Reads all the responses received so far and fails the lookups that timed out,
+    /// invoking the callbacks of the completed ones. Returns the number of them.
+    pub fn process_events(&mut self) -> usize {
+        // The readiness is cleared, the sockets are read until they have nothing more anyway.
+        let _ = self.poll.poll(&mut self.events, Some(Duration::from_secs(0)));
+
+        let mut completed = 0;
+        loop {
+            match self.socket.recv_from(&mut self.buffer) {
+                Ok((size, source)) => self.engine.handle_datagram(source, &self.buffer[.. size], Instant::now()),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                // The server is unreachable, nothing is going to be answered.
+                Err(_) => {
+                    completed += self.callbacks.len();
+                    for (query, (_, callback)) in std::mem::take(&mut self.callbacks) {
+                        self.engine.cancel(query);
+                        callback(Err(ResolveError::TransportFailed));
+                    }
+
+                    break;
+                }
+            }
+        }
+
+        for (query, result) in std::mem::take(&mut self.finished) {
+            completed += self.take_action(Action::Finished(query, result));
+        }
+
+        self.process_stream();
+        self.engine.handle_timeout(Instant::now());
+        while let Some(action) = self.engine.poll_action() {
+            completed += self.take_action(action);
+        }
+
+        return completed;
+    }
+
+    /// Sends the message or completes the lookup, returns the number of lookups completed.
+    fn take_action(&mut self, action: Action) -> usize {
+        match action {
+            // Lost datagrams are up to the engine to notice.
+            Action::Send(_, data) => drop(self.socket.send(&data)),
+            Action::SendStream(_, data) => self.send_stream(data),
+            Action::Finished(query, result) => {
+                if let Some((qtype, callback)) = self.callbacks.remove(&query) {
+                    callback(result.and_then(|response| records(qtype, response)));
+                    return 1;
+                }
+            }
+        }
+
+        return 0;
+    }
+
+    /// Time left until `process_events` has to be called even if nothing is received,
+    /// `None` if there are no pending lookups.
+    pub fn next_timeout(&self) -> Option<Duration> {
+        if !self.finished.is_empty() {
+            return Some(Duration::from_secs(0));
+        }
+
+        return Some(self.engine.next_timeout()?.saturating_duration_since(Instant::now()));
+    }
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::AsRawFd for EventResolver {
+    /// Poller to watch for readability, the same for the lifetime of the resolver.
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        return self.poll.as_raw_fd();
+    }
+}
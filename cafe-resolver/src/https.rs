@@ -0,0 +1,315 @@
+//! DNS over HTTPS (RFC 8484): messages exchanged over the streams of an HTTP/2 connection.
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use data_encoding::BASE64URL_NOPAD;
+use h2::client::SendRequest;
+use http::header::{ACCEPT, AGE, CACHE_CONTROL, CONTENT_TYPE};
+use http::{HeaderMap, Method, Request};
+use tokio::net::TcpStream;
+use tokio::sync::watch;
+use tokio_rustls::TlsConnector;
+
+use crate::{Config, ResolveError, TlsConfig, Transport};
+
+/// ALPN protocol identifier of HTTP/2.
+const H2_ALPN: &[u8] = b"h2";
+
+const DNS_MESSAGE: &str = "application/dns-message";
+
+/// Type of the OPT pseudo-record, its TTL field holds the extended flags.
+const OPT_TYPE: u16 = 41;
+
+/// How the queries go to the server, RFC 8484, section 4.1.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HttpsMethod {
+    /// The query is encoded in base64url as the `dns` variable of the URI, which HTTP caches can key on.
+    Get,
+    /// The query is the body of the request, the URI has no variables.
+    Post,
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpsConfig {
+    /// URI template (RFC 6570) of the resolver, e.g. `https://dns.example/dns-query{?dns}`.
+    template: String,
+    method: HttpsMethod,
+    /// Authentication of the server, by the host of the template unless changed.
+    tls: TlsConfig,
+}
+
+/// Host of the authority of `https://authority/...`, without the port and the brackets of IPv6 addresses.
+fn template_host(template: &str) -> Option<&str> {
+    let rest = template.strip_prefix("https://")?;
+    let authority = &rest[.. rest.find(['/', '?', '{']).unwrap_or(rest.len())];
+    let host = match authority.strip_prefix('[') {
+        Some(address) => &address[.. address.find(']')?],
+        None => authority.split(':').next()?,
+    };
+
+    return match host.is_empty() {
+        true => None,
+        false => Some(host),
+    };
+}
+
+impl HttpsConfig {
+    /// `None` unless the template is an https URI with the `dns` variable in a `{?dns}` or `{&dns}`
+    /// expression, the only ones expanded.
+    pub fn new(template: &str) -> Option<Self> {
+        let host = template_host(template)?;
+        let expressions = template.matches('{').count();
+        if expressions != 1 || !(template.contains("{?dns}") || template.contains("{&dns}")) {
+            return None;
+        }
+
+        return Some(Self {
+            template: template.to_string(),
+            method: HttpsMethod::Post,
+            tls: TlsConfig::new(host),
+        });
+    }
+
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    pub fn method(&self) -> HttpsMethod {
+        self.method
+    }
+
+    pub fn set_method(&mut self, value: HttpsMethod) {
+        self.method = value
+    }
+
+    pub fn tls(&self) -> &TlsConfig {
+        &self.tls
+    }
+
+    /// For trust anchors, SPKI pins or padding, see `TlsConfig`.
+    pub fn tls_mut(&mut self) -> &mut TlsConfig {
+        &mut self.tls
+    }
+
+    /// The template with the `dns` variable set or, with `None`, left out.
+    fn uri(&self, dns: Option<&str>) -> String {
+        let (expression, separator) = match self.template.contains("{?dns}") {
+            true => ("{?dns}", '?'),
+            false => ("{&dns}", '&'),
+        };
+
+        let value = dns.map(|dns| format!("{}dns={}", separator, dns)).unwrap_or_default();
+        return self.template.replace(expression, &value);
+    }
+}
+
+/// Freshness lifetime left of the response (RFC 7234, section 4.2): `max-age` of Cache-Control
+/// less the Age, `None` without `max-age`.
+fn freshness(headers: &HeaderMap) -> Option<u32> {
+    let max_age = headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|directive| directive.trim().strip_prefix("max-age=")?.parse::<u32>().ok())?;
+
+    let age = headers.get(AGE).and_then(|value| value.to_str().ok()?.parse::<u32>().ok()).unwrap_or(0);
+    return Some(max_age.saturating_sub(age));
+}
+
+/// Offset past the name at `offset`, compressed or not.
+fn skip_name(message: &[u8], mut offset: usize) -> Option<usize> {
+    loop {
+        match *message.get(offset)? {
+            0 => return Some(offset + 1),
+            length if length & 0xc0 == 0xc0 => return Some(offset + 2),
+            length => offset += 1 + length as usize,
+        }
+    }
+}
+
+/// Lowers the TTLs of the records of the message to `limit`, so that they aren't kept
+/// for longer than the HTTP response is fresh (RFC 8484, section 5.1).
+fn limit_ttls(message: &mut [u8], limit: u32) -> Option<()> {
+    let count = |offset: usize| Some(u16::from_be_bytes([*message.get(offset)?, *message.get(offset + 1)?]) as usize);
+    let questions = count(4)?;
+    let records = count(6)? + count(8)? + count(10)?;
+
+    let mut offset = 12;
+    for _ in 0 .. questions {
+        offset = skip_name(message, offset)? + 4;
+    }
+
+    for _ in 0 .. records {
+        offset = skip_name(message, offset)?;
+        let fields = message.get_mut(offset .. offset + 10)?;
+        let rtype = u16::from_be_bytes([fields[0], fields[1]]);
+        let ttl = u32::from_be_bytes([fields[4], fields[5], fields[6], fields[7]]);
+        if rtype != OPT_TYPE && ttl > limit {
+            fields[4 .. 8].copy_from_slice(&limit.to_be_bytes());
+        }
+
+        offset += 10 + u16::from_be_bytes([fields[8], fields[9]]) as usize;
+    }
+
+    return Some(());
+}
+
+/// HTTP/2 connection to the server, cheap to clone: clones send their requests over the same one.
+#[derive(Debug, Clone)]
+pub(crate) struct Connection {
+    sender: SendRequest<Bytes>,
+    config: Arc<HttpsConfig>,
+    /// Set once the connection is over, by its task or by a request that found it broken.
+    closed: Arc<watch::Sender<bool>>,
+}
+
+impl Connection {
+    /// Connects to the configured server, the task driving the connection is spawned on the current runtime.
+    pub(crate) async fn connect(config: &Config) -> Result<Self, ResolveError> {
+        let https = match config.transport() {
+            Transport::Https(https) => https,
+            _ => return Err(ResolveError::TransportFailed),
+        };
+
+        let stream = TcpStream::connect(config.server()).await.map_err(|_| ResolveError::TransportFailed)?;
+        let _ = stream.set_nodelay(true);
+
+        let connector = TlsConnector::from(https.tls.client_config(Some(H2_ALPN))?);
+        let stream = connector.connect(https.tls.server_name()?, stream).await.map_err(|_| ResolveError::TransportFailed)?;
+        let (sender, connection) = h2::client::handshake(stream).await.map_err(|_| ResolveError::TransportFailed)?;
+
+        let closed = Arc::new(watch::Sender::new(false));
+        let connection_closed = closed.clone();
+        tokio::spawn(async move {
+            let _ = connection.await;
+            connection_closed.send_replace(true);
+        });
+
+        return Ok(Self {
+            sender,
+            config: Arc::new(https.clone()),
+            closed,
+        });
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        return *self.closed.borrow();
+    }
+
+    /// Waits until the connection is over.
+    pub(crate) async fn closed(&self) {
+        let _ = self.closed.subscribe().wait_for(|closed| *closed).await;
+    }
+
+    /// Sends the query in a request of its own and returns the response, its ID restored.
+    /// The ID goes as zero over HTTP for the caches (RFC 8484, section 4.1). Fails with
+    /// `ResolveError::TransportFailed`, and the connection is closed then if it is broken,
+    /// or with `ResolveError::DecodeFailed` if the server didn't answer with a DNS message.
+    pub(crate) async fn exchange(mut self, mut message: Vec<u8>) -> Result<Vec<u8>, ResolveError> {
+        if message.len() < 12 {
+            return Err(ResolveError::TransportFailed);
+        }
+
+        let id = [message[0], message[1]];
+        message[.. 2].copy_from_slice(&[0, 0]);
+
+        let request = Request::builder().header(ACCEPT, DNS_MESSAGE);
+        let (request, body) = match self.config.method {
+            HttpsMethod::Get => {
+                let dns = BASE64URL_NOPAD.encode(&message);
+                (request.method(Method::GET).uri(self.config.uri(Some(&dns))), None)
+            }
+            HttpsMethod::Post => {
+                let request = request.method(Method::POST).uri(self.config.uri(None)).header(CONTENT_TYPE, DNS_MESSAGE);
+                (request, Some(Bytes::from(message)))
+            }
+        };
+
+        let request = request.body(()).map_err(|_| ResolveError::TransportFailed)?;
+        let result = self.send(request, body).await;
+        if let Err(err) = &result {
+            if err.is_io() || err.is_go_away() {
+                self.closed.send_replace(true);
+            }
+        }
+
+        let (status, headers, mut response) = result.map_err(|_| ResolveError::TransportFailed)?;
+        let is_message = headers.get(CONTENT_TYPE).is_some_and(|value| value.as_bytes() == DNS_MESSAGE.as_bytes());
+        if !status.is_success() || !is_message || response.len() < 12 {
+            return Err(ResolveError::DecodeFailed);
+        }
+
+        if let Some(limit) = freshness(&headers) {
+            limit_ttls(&mut response, limit);
+        }
+
+        response[.. 2].copy_from_slice(&id);
+        return Ok(response);
+    }
+
+    async fn send(
+        &mut self,
+        request: Request<()>,
+        body: Option<Bytes>,
+    ) -> Result<(http::StatusCode, HeaderMap, Vec<u8>), h2::Error> {
+        let mut sender = self.sender.clone().ready().await?;
+        let (response, mut stream) = sender.send_request(request, body.is_none())?;
+        if let Some(body) = body {
+            stream.send_data(body, true)?;
+        }
+
+        let (parts, mut body) = response.await?.into_parts();
+        let mut data = Vec::new();
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk?;
+            let _ = body.flow_control().release_capacity(chunk.len());
+            // Nothing longer is a DNS message, the response is cut short to fail decoding.
+            if data.len() + chunk.len() > u16::MAX as usize {
+                break;
+            }
+
+            data.extend_from_slice(&chunk);
+        }
+
+        return Ok((parts.status, parts.headers, data));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates() {
+        let config = HttpsConfig::new("https://dns.example/dns-query{?dns}").unwrap();
+        assert_eq!(config.tls().name(), "dns.example");
+        assert_eq!(config.uri(Some("AAAB")), "https://dns.example/dns-query?dns=AAAB");
+        assert_eq!(config.uri(None), "https://dns.example/dns-query");
+
+        let config = HttpsConfig::new("https://[2001:db8::1]:8443/q?ct{&dns}").unwrap();
+        assert_eq!(config.tls().name(), "2001:db8::1");
+        assert_eq!(config.uri(Some("AAAB")), "https://[2001:db8::1]:8443/q?ct&dns=AAAB");
+
+        assert!(HttpsConfig::new("http://dns.example/dns-query{?dns}").is_none());
+        assert!(HttpsConfig::new("https://dns.example/dns-query").is_none());
+        assert!(HttpsConfig::new("https://dns.example/{path}{?dns}").is_none());
+    }
+
+    #[test]
+    fn ttls_limited() {
+        // Question for "a." IN A, an A record compressed to it and OPT.
+        let mut message = vec![0, 0, 0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 1, 1, b'a', 0, 0, 1, 0, 1];
+        message.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1, 0, 0, 1, 0, 0, 4, 192, 0, 2, 1]);
+        message.extend_from_slice(&[0, 0, 41, 0x10, 0, 0, 0, 0x80, 0, 0, 0]);
+
+        limit_ttls(&mut message, 60).unwrap();
+        assert_eq!(message[25 .. 29], 60u32.to_be_bytes());
+        assert_eq!(message[40 .. 44], [0, 0, 0x80, 0]);
+
+        limit_ttls(&mut message, 300).unwrap();
+        assert_eq!(message[25 .. 29], 60u32.to_be_bytes());
+        assert!(limit_ttls(&mut message[.. 30], 0).is_none());
+    }
+}
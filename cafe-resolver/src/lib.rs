@@ -1,22 +1,60 @@
+#[cfg(feature = "tokio")]
+pub mod async_resolver;
+pub mod config;
+pub mod engine;
+pub mod event;
+#[cfg(feature = "https")]
+pub mod https;
+#[cfg(any(feature = "https", feature = "quic"))]
+mod multiplex;
+#[cfg(feature = "quic")]
+mod quic;
+pub mod random;
 pub mod resolve_result;
+pub mod tls;
+pub mod transfer;
+mod tcp;
+mod validator;
 
-pub use self::resolve_result::{Record as ResolveRecord, Result as ResolveResult};
+#[cfg(feature = "tokio")]
+pub use self::async_resolver::AsyncResolver;
+pub use self::config::{Config, Transport};
+pub use self::engine::{Action, Engine, QueryId};
+pub use self::event::EventResolver;
+#[cfg(feature = "https")]
+pub use self::https::{HttpsConfig, HttpsMethod};
+pub use self::random::{Random, SystemRandom};
+pub use self::resolve_result::{Answer, Record as ResolveRecord, Result as ResolveResult};
+pub use self::tls::TlsConfig;
+pub use self::transfer::{Difference, Ixfr, Transfer};
+pub use self::validator::Security;
 
 use std::collections::BTreeMap;
 use std::fmt;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
 use std::time::{Duration, Instant};
 
+use self::random::SharedRandom;
+use self::tcp::{MessageReader, Stream};
+use self::validator::ZoneKeys;
+
 use cafe_common::stream::Output as OutputStream;
-use cafe_dns::{QClass, QType, Request as DnsRequest, Response as DnsResponse, ResponseCode, Type};
+use cafe_dns::tsig::{self, Key as TsigKey, Signer as TsigSigner, TsigError, Verifier as TsigVerifier};
+use cafe_dns::{
+    ClientSubnet, ExtendedError, QClass, QType, Request as DnsRequest, ResourceRecord, Response as DnsResponse,
+    ResponseCode, Type, Update as DnsUpdate,
+};
 
 #[derive(Debug)]
 pub enum RecordVariant {
     A {
+        name: String,
         ip: IpAddr,
         ttl: u32,
     },
     SRV {
+        name: String,
         target: String,
         port: u16,
         priority: u16,
@@ -28,21 +66,38 @@ pub enum RecordVariant {
 type RecordsResult = Result<Vec<RecordVariant>, ResolveError>;
 
 impl fmt::Display for RecordVariant {
+    /// The record as a line of a master file.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            RecordVariant::A { ip, ttl } => write!(f, "{}; ttl: {}", ip, ttl),
+        let record = match self {
+            RecordVariant::A { name, ip, ttl } => {
+                let rdata = match ip {
+                    IpAddr::V4(ip) => Type::A { ip: *ip },
+                    IpAddr::V6(ip) => Type::AAAA { ip: *ip },
+                };
+
+                ResourceRecord::new(name, QClass::IN as u16, *ttl, rdata)
+            }
             RecordVariant::SRV {
+                name,
                 target,
                 port,
                 priority,
                 weight,
                 ttl,
-            } => write!(
-                f,
-                "{}:{}; priority: {}; weight: {}; ttl: {}",
-                target, port, priority, weight, ttl
+            } => ResourceRecord::new(
+                name,
+                QClass::IN as u16,
+                *ttl,
+                Type::SRV {
+                    priority: *priority,
+                    weight: *weight,
+                    port: *port,
+                    target: target.to_string(),
+                },
             ),
-        }
+        };
+
+        return write!(f, "{}", record);
     }
 }
 
@@ -50,42 +105,219 @@ impl fmt::Display for RecordVariant {
 pub enum ResolveError {
     TransportFailed,
     DecodeFailed,
-    DnsError(ResponseCode),
+    /// Response code of the failed query along with Extended DNS Errors (RFC 8914)
+    /// explaining it, if the server provided any.
+    DnsError(ResponseCode, Vec<ExtendedError>),
+    /// The response failed TSIG verification or the server rejected the signature of the request.
+    TsigFailed(TsigError),
+    /// DNSSEC validation of the answer failed.
+    Bogus,
+    /// No response came in time, see `Config::timeout`.
+    Timeout,
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::TransportFailed => write!(f, "transport failed"),
+            ResolveError::DecodeFailed => write!(f, "unable to decode response"),
+            ResolveError::DnsError(rcode, errors) => {
+                write!(f, "{:?}", rcode)?;
+                for error in errors {
+                    write!(f, "; {}", error)?;
+                }
+
+                Ok(())
+            }
+            ResolveError::TsigFailed(err) => write!(f, "TSIG verification failed: {}", err),
+            ResolveError::Bogus => write!(f, "DNSSEC validation failed"),
+            ResolveError::Timeout => write!(f, "timed out"),
+        }
+    }
+}
+
+/// Address and SRV records of the answer section.
+fn answer_records(response: &DnsResponse) -> Vec<RecordVariant> {
+    let mut result = Vec::new();
+    for answer in response.answers() {
+        let ttl = answer.ttl();
+        match answer.ttype() {
+            Type::A { ip } => result.push(RecordVariant::A {
+                name: answer.name().to_string(),
+                ip: IpAddr::V4(*ip),
+                ttl,
+            }),
+            Type::AAAA { ip } => result.push(RecordVariant::A {
+                name: answer.name().to_string(),
+                ip: IpAddr::V6(*ip),
+                ttl,
+            }),
+            Type::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => result.push(RecordVariant::SRV {
+                name: answer.name().to_string(),
+                target: target.to_string(),
+                port: *port,
+                priority: *priority,
+                weight: *weight,
+                ttl,
+            }),
+            _ => (),
+        }
+    }
+
+    return result;
+}
+
+fn sort_by_priority(records: &mut [RecordVariant]) {
+    records.sort_unstable_by(|a, b| match (a, b) {
+        (
+            RecordVariant::SRV {
+                name: _,
+                target: _,
+                port: _,
+                priority: p1,
+                weight: _,
+                ttl: _,
+            },
+            RecordVariant::SRV {
+                name: _,
+                target: _,
+                port: _,
+                priority: p2,
+                weight: _,
+                ttl: _,
+            },
+        ) => return p1.cmp(&p2),
+        _ => return std::cmp::Ordering::Equal,
+    });
+}
+
+/// Checks whether the cached record was given for the client subnet `subnet`.
+fn is_applicable(record: &ResolveRecord, subnet: Option<&ClientSubnet>) -> bool {
+    match (record.client_subnet(), subnet) {
+        (None, _) => true,
+        (Some(scope), Some(subnet)) => scope.matches(&subnet.address()),
+        (Some(scope), None) => scope.scope_prefix() == 0,
+    }
+}
+
+/// Addresses of the answer to the A query for `host` as they are kept in the cache.
+fn cached_records(host: &str, answer: &Answer) -> Vec<ResolveRecord> {
+    let response = answer.response();
+    let subnet: Option<ClientSubnet> = response.edns().and_then(|edns| edns.client_subnet()).copied();
+
+    let mut records = Vec::new();
+    let now = Instant::now();
+    for record in response.answers() {
+        match record.ttype() {
+            Type::A { ip } => {
+                let time_to_die = now + Duration::new(record.ttl().into(), 0);
+                let mut record = ResolveRecord::new(host, IpAddr::V4(*ip), None, time_to_die);
+                record.set_client_subnet(subnet);
+                record.set_security(answer.security());
+                records.push(record);
+            }
+            _ => (),
+        }
+    }
+
+    return records;
+}
+
+fn check_rcode(response: &DnsResponse) -> Result<(), ResolveError> {
+    if response.header().rcode() == ResponseCode::NoError {
+        return Ok(());
+    }
+
+    let errors = match response.edns() {
+        Some(edns) => edns.extended_errors().cloned().collect(),
+        None => Vec::new(),
+    };
+
+    return Err(ResolveError::DnsError(response.header().rcode(), errors));
+}
+
+/// Attempts to bind a random source port before leaving the choice to the system.
+const PORT_ATTEMPTS: usize = 16;
+
+/// UDP socket of the address family of `server` bound to a random port out of 1024-65535.
+fn bind_udp(server: SocketAddr, random: &SharedRandom) -> Result<UdpSocket, ResolveError> {
+    let ip = match server {
+        SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+
+    let random_ports = (0 .. PORT_ATTEMPTS).map(|_| 1024 + random.next_u16() % (u16::MAX - 1023));
+    return match random_ports.chain(Some(0)).find_map(|port| UdpSocket::bind(SocketAddr::new(ip, port)).ok()) {
+        None => Err(ResolveError::TransportFailed),
+        Some(s) => Ok(s),
+    };
+}
+
+/// UDP socket bound to a random port out of 1024-65535 and connected to `server`.
+fn connect_udp(server: SocketAddr, random: &SharedRandom) -> Result<UdpSocket, ResolveError> {
+    let socket = bind_udp(server, random)?;
+    match socket.connect(&server) {
+        Err(_) => return Err(ResolveError::TransportFailed),
+        _ => (),
+    };
+
+    return Ok(socket);
 }
 
+/// Blocking resolver, drives `Engine` over a UDP socket per lookup
+/// and a TCP, TLS, HTTPS or QUIC connection kept between lookups.
 #[derive(Debug)]
 pub struct Resolver {
-    id_count: u16,
+    engine: Engine,
     buffer: [u8; 65_535],
+    /// Connection to the server with what was read of the next message and the time it was last used.
+    connection: Option<(Stream, MessageReader, Instant)>,
+    /// HTTP/2 or QUIC connection to the server with the runtime it goes on, and the time it was last used.
+    #[cfg(any(feature = "https", feature = "quic"))]
+    multiplexed: Option<(tokio::runtime::Runtime, multiplex::Connection, Instant)>,
     cache: BTreeMap<String, Vec<ResolveRecord>>,
+    /// Keys of the zones met on the way from the trust anchors, until they expire.
+    zones: BTreeMap<String, (ZoneKeys, Instant)>,
 }
 
 impl Resolver {
     pub fn new() -> Self {
+        return Self::with_config(Config::new());
+    }
+
+    pub fn with_config(config: Config) -> Self {
         return Self {
-            id_count: 0,
+            engine: Engine::new(config),
             buffer: [0; 65_535],
+            connection: None,
+            #[cfg(any(feature = "https", feature = "quic"))]
+            multiplexed: None,
             cache: Default::default(),
+            zones: Default::default(),
         };
     }
 
-    fn connect_to_server(&mut self) -> Result<UdpSocket, ResolveError> {
-        let laddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::from([0; 4])), 0);
-        let socket = match UdpSocket::bind(laddr) {
-            Err(_) => return Err(ResolveError::TransportFailed),
-            Ok(s) => s,
-        };
+    pub fn config(&self) -> &Config {
+        self.engine.config()
+    }
 
-        let raddr = SocketAddr::from(([8, 8, 8, 8], 53));
-        match socket.connect(&raddr) {
-            Err(_) => return Err(ResolveError::TransportFailed),
-            _ => (),
-        };
+    pub fn config_mut(&mut self) -> &mut Config {
+        self.engine.config_mut()
+    }
 
-        return Ok(socket);
+    fn connect_to_server(&mut self) -> Result<UdpSocket, ResolveError> {
+        return connect_udp(self.config().server(), self.config().random());
     }
 
-    fn get_response(&mut self, socket: &UdpSocket, buf: &mut [u8]) -> Result<(), ResolveError> {
+    /// Sends the message and waits for the response with the ID `id` from the server,
+    /// other datagrams are ignored.
+    fn get_response(&mut self, buf: &mut [u8], id: u16) -> Result<usize, ResolveError> {
+        let socket = self.connect_to_server()?;
         match socket.send(&buf) {
             Err(_) => return Err(ResolveError::TransportFailed),
             Ok(size) => {
@@ -95,94 +327,315 @@ impl Resolver {
             }
         };
 
-        match socket.recv(&mut self.buffer[..]) {
-            Err(_) => return Err(ResolveError::TransportFailed),
-            _ => (),
-        };
+        let deadline = Instant::now() + self.config().timeout();
+        loop {
+            // Zero would mean no timeout at all.
+            let timeout = deadline.saturating_duration_since(Instant::now()).max(Duration::from_millis(1));
+            if socket.set_read_timeout(Some(timeout)).is_err() {
+                return Err(ResolveError::TransportFailed);
+            }
 
-        return Ok(());
+            match socket.recv_from(&mut self.buffer[..]) {
+                Err(err) if err.kind() == ErrorKind::WouldBlock || err.kind() == ErrorKind::TimedOut => {
+                    return Err(ResolveError::Timeout)
+                }
+                Err(_) => return Err(ResolveError::TransportFailed),
+                Ok((size, source)) => {
+                    let is_response = size >= 12 && self.buffer[2] & 0x80 != 0;
+                    if source == self.config().server() && is_response && self.buffer[.. 2] == id.to_be_bytes() {
+                        return Ok(size);
+                    }
+                }
+            };
+        }
     }
 
-    fn get_records(&mut self, socket: &UdpSocket, qtype: QType, host: &str) -> RecordsResult {
-        self.id_count = self.id_count.wrapping_add(1);
+    /// Runs the query through the engine until it finishes.
+    fn exchange(&mut self, qtype: QType, host: &str) -> Result<DnsResponse, ResolveError> {
+        let validate = self.config().dnssec_validation();
+        let query = self.engine.query(qtype, host, validate, Instant::now());
+        let result = self.drive(query);
+        if result.is_err() {
+            self.engine.cancel(query);
+        }
 
-        let mut request = DnsRequest::new(self.id_count);
-        request.header_mut().set_rd(true);
-        request.add_question(&host, qtype, QClass::IN);
+        return result;
+    }
 
-        let mut buffer = Vec::with_capacity(512);
-        let mut stream = OutputStream::new(&mut buffer);
-        request.encode(&mut stream);
+    /// Sends the message over the connection to the server, opening a new one if there is none
+    /// or it was idle for too long. A connection that failed is reported to the engine.
+    fn send_stream(&mut self, data: &[u8]) -> Result<(), ResolveError> {
+        let now = Instant::now();
+        if let Some((_, _, last_used)) = &self.connection {
+            if now.saturating_duration_since(*last_used) >= self.engine.idle_timeout() {
+                self.connection = None;
+            }
+        }
 
-        match self.get_response(&socket, &mut buffer) {
-            Err(_) => return Err(ResolveError::TransportFailed),
-            _ => (),
+        if self.connection.is_none() {
+            let deadline = self.engine.next_timeout().unwrap_or(now);
+            let timeout = deadline.saturating_duration_since(now).max(Duration::from_millis(1));
+            let stream = Stream::connect(self.config(), timeout)?;
+            self.connection = Some((stream, MessageReader::default(), now));
         }
 
-        let response = match DnsResponse::decode(&self.buffer) {
-            None => return Err(ResolveError::DecodeFailed),
-            Some(response) => response,
-        };
+        let (stream, _, last_used) = self.connection.as_mut().unwrap();
+        *last_used = now;
+        if tcp::write_message(stream, data).is_err() {
+            self.connection = None;
+            self.engine.handle_stream_closed(now);
+        }
+
+        return Ok(());
+    }
 
-        if response.header().rcode() != ResponseCode::NoError {
-            return Err(ResolveError::DnsError(response.header().rcode()));
+    /// Sends the query over the HTTP/2 or QUIC connection to the server, opening a new one if there
+    /// is none, it is closed or idle for too long, and hands the response to the engine.
+    #[cfg(any(feature = "https", feature = "quic"))]
+    fn exchange_multiplexed(&mut self, query: QueryId, data: Vec<u8>) -> Result<(), ResolveError> {
+        let now = Instant::now();
+        if let Some((_, connection, last_used)) = &self.multiplexed {
+            if connection.is_closed() || now.saturating_duration_since(*last_used) >= self.engine.idle_timeout() {
+                self.multiplexed = None;
+            }
         }
 
-        let mut result = Vec::new();
-        for answer in response.answers() {
-            let ttl = answer.ttl();
-            match answer.ttype() {
-                Type::A { ip } => result.push(RecordVariant::A {
-                    ip: IpAddr::V4(*ip),
-                    ttl,
-                }),
-                Type::SRV {
-                    priority,
-                    weight,
-                    port,
-                    target,
-                } => result.push(RecordVariant::SRV {
-                    target: target.to_string(),
-                    port: *port,
-                    priority: *priority,
-                    weight: *weight,
-                    ttl,
-                }),
+        let deadline = self.engine.next_timeout().unwrap_or(now);
+        let timeout = deadline.saturating_duration_since(now).max(Duration::from_millis(1));
+        if self.multiplexed.is_none() {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(|_| ResolveError::TransportFailed)?;
+
+            let config = self.config();
+            let connection = match runtime.block_on(async { tokio::time::timeout(timeout, multiplex::Connection::connect(config)).await }) {
+                Ok(connection) => connection?,
+                Err(_) => return Err(ResolveError::Timeout),
+            };
+
+            self.multiplexed = Some((runtime, connection, now));
+        }
+
+        let (runtime, connection, last_used) = self.multiplexed.as_mut().unwrap();
+        *last_used = now;
+        let exchange = connection.clone().exchange(data);
+        match runtime.block_on(async { tokio::time::timeout(timeout, exchange).await }) {
+            Ok(Ok(response)) => self.engine.handle_message(&response, Instant::now()),
+            Ok(Err(_)) if connection.is_closed() => {
+                self.multiplexed = None;
+                self.engine.handle_stream_closed(Instant::now());
             }
+            Ok(Err(err)) => self.engine.fail(query, err),
+            Err(_) => self.engine.handle_timeout(Instant::now()),
         }
 
-        return Ok(result);
+        return Ok(());
+    }
+
+    /// Carries the messages of the query over a UDP socket of its own and, if the answer comes
+    /// truncated, TCP is forced or the transport is encrypted, over the connection.
+    fn drive(&mut self, query: QueryId) -> Result<DnsResponse, ResolveError> {
+        let mut socket = None;
+        loop {
+            while let Some(action) = self.engine.poll_action() {
+                match action {
+                    Action::Send(_, data) => {
+                        if socket.is_none() {
+                            socket = Some(self.connect_to_server()?);
+                        }
+
+                        match socket.as_ref().unwrap().send(&data) {
+                            Ok(size) if size == data.len() => (),
+                            _ => return Err(ResolveError::TransportFailed),
+                        }
+                    }
+                    #[cfg(any(feature = "https", feature = "quic"))]
+                    Action::SendStream(id, data) if multiplex::is_multiplexed(self.config().transport()) => {
+                        self.exchange_multiplexed(id, data)?
+                    }
+                    Action::SendStream(_, data) => self.send_stream(&data)?,
+                    Action::Finished(id, result) if id == query => return result,
+                    Action::Finished(_, _) => (),
+                }
+            }
+
+            let deadline = match self.engine.next_timeout() {
+                Some(deadline) => deadline,
+                None => return Err(ResolveError::TransportFailed),
+            };
+
+            // Zero would mean no timeout at all.
+            let timeout = deadline.saturating_duration_since(Instant::now()).max(Duration::from_millis(1));
+            if let (Some((stream, reader, last_used)), true) = (&mut self.connection, self.engine.pending_streams() > 0) {
+                stream.set_read_timeout(Some(timeout))?;
+                match reader.read_message(stream) {
+                    Ok(Some(message)) => {
+                        *last_used = Instant::now();
+                        self.engine.handle_message(&message, Instant::now());
+                    }
+                    Err(ResolveError::Timeout) => self.engine.handle_timeout(Instant::now()),
+                    // Closed by the server, maybe as idle before the query got to it.
+                    Ok(None) | Err(_) => {
+                        self.connection = None;
+                        self.engine.handle_stream_closed(Instant::now());
+                    }
+                }
+
+                continue;
+            }
+
+            let socket = match &socket {
+                Some(socket) => socket,
+                None => return Err(ResolveError::TransportFailed),
+            };
+
+            socket.set_read_timeout(Some(timeout)).map_err(|_| ResolveError::TransportFailed)?;
+            match socket.recv_from(&mut self.buffer[..]) {
+                Ok((size, source)) => self.engine.handle_datagram(source, &self.buffer[.. size], Instant::now()),
+                Err(err) if err.kind() == ErrorKind::WouldBlock || err.kind() == ErrorKind::TimedOut => {
+                    self.engine.handle_timeout(Instant::now())
+                }
+                Err(_) => return Err(ResolveError::TransportFailed),
+            }
+        }
+    }
+
+    /// Sends the query and validates the response if DNSSEC validation is enabled.
+    fn lookup_with(&mut self, qtype: QType, host: &str) -> Result<Answer, ResolveError> {
+        let response = self.exchange(qtype, host)?;
+        let security = match self.config().dnssec_validation() {
+            true => self.validate(host, qtype, &response)?,
+            false => Security::Indeterminate,
+        };
+
+        return Ok(Answer::new(response, security));
+    }
+
+    /// Answer to the query with its security status, which is `Security::Indeterminate`
+    /// unless DNSSEC validation is enabled. Neither error response codes nor bogus
+    /// answers are treated as errors here.
+    pub fn lookup(&mut self, host: &str, qtype: QType) -> Result<Answer, ResolveError> {
+        return self.lookup_with(qtype, host);
+    }
+
+    fn query(&mut self, qtype: QType, host: &str) -> Result<Answer, ResolveError> {
+        let answer = self.lookup_with(qtype, host)?;
+        check_rcode(answer.response())?;
+        if answer.security() == Security::Bogus {
+            return Err(ResolveError::Bogus);
+        }
+
+        return Ok(answer);
+    }
+
+    fn get_records(&mut self, qtype: QType, host: &str) -> RecordsResult {
+        let answer = self.query(qtype, host)?;
+        return Ok(answer_records(answer.response()));
     }
 
     pub fn get_srv_records(&mut self, host: &str) -> RecordsResult {
-        let socket = self.connect_to_server()?;
-        let mut records = self.get_records(&socket, QType::SRV, host)?;
-        records.sort_unstable_by(|a, b| match (a, b) {
-            (
-                RecordVariant::SRV {
-                    target: _,
-                    port: _,
-                    priority: p1,
-                    weight: _,
-                    ttl: _,
-                },
-                RecordVariant::SRV {
-                    target: _,
-                    port: _,
-                    priority: p2,
-                    weight: _,
-                    ttl: _,
-                },
-            ) => return p1.cmp(&p2),
-            _ => return std::cmp::Ordering::Equal,
-        });
+        let mut records = self.get_records(QType::SRV, host)?;
+        sort_by_priority(&mut records);
 
         return Ok(records);
     }
 
     pub fn get_a_records(&mut self, host: &str) -> RecordsResult {
-        let socket = self.connect_to_server()?;
-        return self.get_records(&socket, QType::A, host);
+        return self.get_records(QType::A, host);
+    }
+
+    /// IPv6 addresses of the host, as `RecordVariant::A` records.
+    pub fn get_aaaa_records(&mut self, host: &str) -> RecordsResult {
+        return self.get_records(QType::AAAA, host);
+    }
+
+    /// Sends the dynamic update (RFC 2136) to the configured server, which should be
+    /// the primary one of the zone. The update is signed with TSIG when `key` is given,
+    /// and the response must be signed with the same key then.
+    pub fn update(&mut self, update: &mut DnsUpdate, key: Option<&TsigKey>) -> Result<(), ResolveError> {
+        let id = self.engine.next_id();
+        update.header_mut().set_id(id);
+
+        let mut buffer = Vec::with_capacity(512);
+        let mut stream = OutputStream::new(&mut buffer);
+        update.encode(&mut stream);
+
+        let mut signer = key.map(TsigSigner::new);
+        if let Some(signer) = signer.as_mut() {
+            signer.sign(&mut buffer, tsig::current_time()).map_err(ResolveError::TsigFailed)?;
+        }
+
+        let size = self.get_response(&mut buffer, id)?;
+
+        if let (Some(key), Some(signer)) = (key, signer.as_ref()) {
+            let mut verifier = TsigVerifier::for_response(key, signer.mac().unwrap_or_default());
+            verifier
+                .verify(&self.buffer[..size], tsig::current_time())
+                .map_err(ResolveError::TsigFailed)?;
+        }
+
+        let response = match DnsResponse::decode(&self.buffer[..size]) {
+            None => return Err(ResolveError::DecodeFailed),
+            Some(response) => response,
+        };
+
+        check_rcode(&response)?;
+
+        return Ok(());
+    }
+
+    /// Starts full zone transfer (RFC 5936) of `zone` from the configured server over TCP.
+    /// The request is signed with TSIG when `key` is given, and so must be the answer then.
+    pub fn axfr<'a>(&mut self, zone: &str, key: Option<&'a TsigKey>) -> Result<Transfer<'a>, ResolveError> {
+        return self.transfer(zone, QType::AXFR, None, key);
+    }
+
+    /// Starts incremental zone transfer (RFC 1995) of `zone` from the version `serial`,
+    /// see `Transfer::changes` to get the differences.
+    pub fn ixfr<'a>(&mut self, zone: &str, serial: u32, key: Option<&'a TsigKey>) -> Result<Transfer<'a>, ResolveError> {
+        // Only the serial of the SOA in the authority section is looked at by servers.
+        let soa = Type::SOA {
+            mname: String::new(),
+            rname: String::new(),
+            serial,
+            refresh: 0,
+            retry: 0,
+            expire: 0,
+            minimum: 0,
+        };
+
+        return self.transfer(zone, QType::IXFR, Some(ResourceRecord::new(zone, QClass::IN as u16, 0, soa)), key);
+    }
+
+    fn transfer<'a>(
+        &mut self,
+        zone: &str,
+        qtype: QType,
+        authority: Option<ResourceRecord>,
+        key: Option<&'a TsigKey>,
+    ) -> Result<Transfer<'a>, ResolveError> {
+        let mut request = DnsRequest::new(self.engine.next_id());
+        request.add_question(zone, qtype, QClass::IN);
+        if let Some(authority) = authority {
+            request.add_authority(authority);
+        }
+
+        let mut buffer = Vec::with_capacity(512);
+        let mut stream = OutputStream::new(&mut buffer);
+        request.encode(&mut stream);
+
+        let key = match key {
+            Some(key) => {
+                let mut signer = TsigSigner::new(key);
+                signer.sign(&mut buffer, tsig::current_time()).map_err(ResolveError::TsigFailed)?;
+                Some((key, signer.mac().unwrap_or_default().to_vec()))
+            }
+            None => None,
+        };
+
+        let key = key.as_ref().map(|(key, mac)| (*key, mac.as_slice()));
+        return Transfer::start(self.config().server(), self.config().timeout(), &request, &buffer, key);
     }
 
     fn need_to_update_records(&mut self, host: &str) -> bool {
@@ -191,7 +644,7 @@ impl Resolver {
             Some(rs) => {
                 let now = Instant::now();
                 for r in rs {
-                    if r.is_outdated(now) {
+                    if r.is_outdated(now) || !self.is_applicable(r) {
                         return true;
                     }
                 }
@@ -205,22 +658,14 @@ impl Resolver {
         };
     }
 
+    fn is_applicable(&self, record: &ResolveRecord) -> bool {
+        return is_applicable(record, self.config().client_subnet());
+    }
+
     pub fn resolve_host(&mut self, host: &str) -> Result<ResolveResult, ResolveError> {
         if self.need_to_update_records(host) {
-            let records = self.get_a_records(host)?;
-            let entry = self.cache.get_mut(host).unwrap();
-            entry.clear();
-
-            let now = Instant::now();
-            for r in records {
-                match r {
-                    RecordVariant::A { ip, ttl } => {
-                        let time_to_die = now + Duration::new(ttl.into(), 0);
-                        entry.push(ResolveRecord::new(host, ip, None, time_to_die));
-                    }
-                    _ => (),
-                }
-            }
+            let answer = self.query(QType::A, host)?;
+            *self.cache.get_mut(host).unwrap() = cached_records(host, &answer);
         }
 
         let entry = self.cache.get_mut(host).unwrap();
@@ -1,40 +1,498 @@
-use cafe_dns::QType;
-use cafe_resolver::Resolver;
+use cafe_dns::dnssec::{Algorithm, Denial, SigningKey, ZoneSigner};
+use cafe_dns::tsig::Key as TsigKey;
+use cafe_dns::{zone, ClientSubnet, QType, Type, Update};
+use cafe_resolver::{Config, Resolver, TlsConfig, Transport};
+#[cfg(feature = "https")]
+use cafe_resolver::{HttpsConfig, HttpsMethod};
 
+use std::fs::File;
+use std::io::{self, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::process::exit;
 
+use data_encoding::{BASE64, HEXLOWER_PERMISSIVE};
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
 #[structopt()]
 pub struct Args {
     #[structopt(short, long)]
-    host: String,
+    host: Option<String>,
 
     #[structopt(short = "t", long, default_value = "A")]
     qtype: String,
+
+    /// EDNS Client Subnet sent to the server, e.g. 192.0.2.0/24; a zero prefix opts out.
+    #[structopt(long)]
+    client_subnet: Option<String>,
+
+    /// Server to send queries to, as ip or ip:port.
+    #[structopt(short, long)]
+    server: Option<String>,
+
+    /// Sends queries over TLS (RFC 7858) to port 853 by default, authenticating the server by this name.
+    #[structopt(long)]
+    tls: Option<String>,
+
+    /// Sends queries over HTTPS (RFC 8484) to port 443 by default, to the resolver of this URI template,
+    /// e.g. https://dns.example/dns-query{?dns}.
+    #[cfg(feature = "https")]
+    #[structopt(long)]
+    https: Option<String>,
+
+    /// Sends HTTPS queries with GET rather than POST.
+    #[cfg(feature = "https")]
+    #[structopt(long)]
+    get: bool,
+
+    /// Sends queries over QUIC (RFC 9250) to port 853 by default, authenticating the server by this name.
+    #[cfg(feature = "quic")]
+    #[structopt(long)]
+    quic: Option<String>,
+
+    /// Base64 SHA-256 digest of SubjectPublicKeyInfo the certificate of the TLS server must have.
+    #[structopt(long)]
+    spki_pin: Vec<String>,
+
+    /// Sends query names in random case (0x20), falling back if the server doesn't keep it.
+    #[structopt(long)]
+    randomize_case: bool,
+
+    /// Sends queries over TCP rather than UDP.
+    #[structopt(long)]
+    tcp: bool,
+
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Sends a dynamic update (RFC 2136) for a zone.
+    Update {
+        zone: String,
+
+        /// BIND key file used to sign the update with TSIG.
+        #[structopt(short, long)]
+        key_file: Option<PathBuf>,
+
+        /// Record to add, as "name ttl TYPE data".
+        #[structopt(long)]
+        add: Vec<String>,
+
+        /// Records to delete, as "name", "name TYPE" or "name TYPE data".
+        #[structopt(long)]
+        delete: Vec<String>,
+
+        /// Name that must not exist for the update to apply.
+        #[structopt(long)]
+        absent: Vec<String>,
+
+        /// Name that must exist for the update to apply.
+        #[structopt(long)]
+        present: Vec<String>,
+    },
+    /// Transfers a whole zone (RFC 5936) and writes it as a master file.
+    Axfr {
+        zone: String,
+
+        /// BIND key file used to sign the transfer with TSIG.
+        #[structopt(short, long)]
+        key_file: Option<PathBuf>,
+
+        /// File to write the zone to instead of the standard output.
+        #[structopt(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Signs a zone read from a master file with DNSSEC.
+    SignZone {
+        /// Master file of the zone.
+        zone_file: PathBuf,
+
+        /// BIND key file (.key or .private) to sign with; a KSK and a ZSK are generated if none is given.
+        #[structopt(short, long)]
+        key: Vec<PathBuf>,
+
+        /// Algorithm of generated keys: ED25519 or ECDSAP256SHA256.
+        #[structopt(short, long, default_value = "ED25519")]
+        algorithm: String,
+
+        /// Directory generated keys are saved to.
+        #[structopt(long, default_value = ".")]
+        key_directory: PathBuf,
+
+        /// Proves denial of existence with NSEC3 instead of NSEC.
+        #[structopt(long)]
+        nsec3: bool,
+
+        /// NSEC3 salt in hex, "-" for none.
+        #[structopt(long, default_value = "-")]
+        salt: String,
+
+        /// Additional NSEC3 hash iterations.
+        #[structopt(long, default_value = "0")]
+        iterations: u16,
+
+        /// Leaves delegations without DS records out of the NSEC3 chain.
+        #[structopt(long)]
+        opt_out: bool,
+
+        /// File to write the signed zone to instead of the standard output.
+        #[structopt(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    exit(1)
+}
+
+fn parse_server(server: &str, port: u16) -> Option<SocketAddr> {
+    if let Ok(addr) = server.parse::<SocketAddr>() {
+        return Some(addr);
+    }
+
+    return server.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, port));
+}
+
+fn parse_spki_pin(pin: &str) -> Option<[u8; 32]> {
+    let digest = BASE64.decode(pin.as_bytes()).ok()?;
+    if digest.len() != 32 {
+        return None;
+    }
+
+    let mut pin = [0; 32];
+    pin.copy_from_slice(&digest);
+    return Some(pin);
+}
+
+/// Transport selected by the arguments, with the port of the server for it.
+fn transport(args: &Args) -> (Transport, u16) {
+    let pins: Vec<[u8; 32]> = args
+        .spki_pin
+        .iter()
+        .map(|pin| parse_spki_pin(pin).unwrap_or_else(|| fail(format!("Invalid SPKI pin: {}", pin))))
+        .collect();
+
+    #[cfg(feature = "https")]
+    if let Some(template) = &args.https {
+        let mut https = HttpsConfig::new(template).unwrap_or_else(|| fail(format!("Invalid URI template: {}", template)));
+        if args.get {
+            https.set_method(HttpsMethod::Get);
+        }
+
+        for pin in &pins {
+            https.tls_mut().add_spki_pin(*pin);
+        }
+
+        return (Transport::Https(https), 443);
+    }
+
+    #[cfg(feature = "quic")]
+    if let Some(name) = &args.quic {
+        let mut tls = TlsConfig::new(name);
+        for pin in &pins {
+            tls.add_spki_pin(*pin);
+        }
+
+        return (Transport::Quic(tls), 853);
+    }
+
+    return match &args.tls {
+        Some(name) => {
+            let mut tls = TlsConfig::new(name);
+            for pin in &pins {
+                tls.add_spki_pin(*pin);
+            }
+
+            (Transport::Tls(tls), 853)
+        }
+        None => (Transport::Udp, 53),
+    };
+}
+
+fn parse_qtype(qtype: &str) -> Option<QType> {
+    return qtype.parse().ok();
+}
+
+fn parse_rdata(qtype: QType, data: &[&str]) -> Option<Type> {
+    return match (qtype, data) {
+        (QType::A, [ip]) => Some(Type::A { ip: ip.parse().ok()? }),
+        (QType::AAAA, [ip]) => Some(Type::AAAA { ip: ip.parse().ok()? }),
+        (QType::SRV, [priority, weight, port, target]) => Some(Type::SRV {
+            priority: priority.parse().ok()?,
+            weight: weight.parse().ok()?,
+            port: port.parse().ok()?,
+            target: target.trim_end_matches('.').to_string(),
+        }),
+        _ => None,
+    };
+}
+
+fn add_record(update: &mut Update, record: &str) -> Option<()> {
+    let fields: Vec<&str> = record.split_whitespace().collect();
+    if fields.len() < 4 {
+        return None;
+    }
+
+    let ttl = fields[1].parse().ok()?;
+    let data = parse_rdata(parse_qtype(fields[2])?, &fields[3..])?;
+    update.add(fields[0], ttl, data);
+
+    return Some(());
+}
+
+fn delete_record(update: &mut Update, record: &str) -> Option<()> {
+    let fields: Vec<&str> = record.split_whitespace().collect();
+    match fields.as_slice() {
+        [name] => update.delete_name(name),
+        [name, qtype] => update.delete_rrset(name, parse_qtype(qtype)?),
+        [name, qtype, data @ ..] => update.delete_record(name, parse_rdata(parse_qtype(qtype)?, data)?),
+        [] => return None,
+    }
+
+    return Some(());
+}
+
+/// The first key of a BIND key file.
+fn load_key(path: &Path) -> TsigKey {
+    match TsigKey::load_bind(path) {
+        Ok(mut keys) if !keys.is_empty() => keys.remove(0),
+        Ok(_) => fail(format!("No keys in {}", path.display())),
+        Err(err) => fail(format!("Cannot load {}: {}", path.display(), err)),
+    }
+}
+
+fn create_output(output: Option<PathBuf>) -> Box<dyn Write> {
+    return match output {
+        Some(path) => match File::create(&path) {
+            Ok(file) => Box::new(io::BufWriter::new(file)),
+            Err(err) => fail(format!("Cannot create {}: {}", path.display(), err)),
+        },
+        None => Box::new(io::stdout()),
+    };
+}
+
+fn transfer_zone(resolver: &mut Resolver, zone: &str, key_file: Option<PathBuf>, output: Option<PathBuf>) {
+    let key = key_file.map(|path| load_key(&path));
+    let mut output = create_output(output);
+
+    let transfer = match resolver.axfr(zone, key.as_ref()) {
+        Ok(transfer) => transfer,
+        Err(err) => fail(format!("Error occured: {}", err)),
+    };
+
+    for record in transfer {
+        let written = match record {
+            Ok(record) => writeln!(output, "{}", record),
+            Err(err) => fail(format!("Error occured: {}", err)),
+        };
+
+        if let Err(err) = written {
+            fail(format!("Cannot write zone: {}", err));
+        }
+    }
+
+    if let Err(err) = output.flush() {
+        fail(format!("Cannot write zone: {}", err));
+    }
+}
+
+fn parse_algorithm(name: &str) -> Option<Algorithm> {
+    return match name.to_ascii_uppercase().as_str() {
+        "ED25519" => Some(Algorithm::Ed25519),
+        "ECDSAP256SHA256" => Some(Algorithm::EcdsaP256Sha256),
+        _ => None,
+    };
+}
+
+/// Generates a KSK and a ZSK of `zone` and saves them into `directory`.
+fn generate_keys(zone: &str, algorithm: &str, directory: &Path) -> Vec<SigningKey> {
+    let algorithm = match parse_algorithm(algorithm) {
+        Some(algorithm) => algorithm,
+        None => fail(format!("Unsupported algorithm: {}", algorithm)),
+    };
+
+    let mut keys = Vec::new();
+    for ksk in &[true, false] {
+        let key = match SigningKey::generate(zone, algorithm, *ksk) {
+            Ok(key) => key,
+            Err(err) => fail(format!("Cannot generate key: {}", err)),
+        };
+
+        match key.save_bind(directory) {
+            Ok(path) => eprintln!("Generated {}", path.display()),
+            Err(err) => fail(format!("Cannot save key: {}", err)),
+        }
+
+        keys.push(key);
+    }
+
+    return keys;
+}
+
+fn sign_zone(
+    zone_file: &Path,
+    key_files: &[PathBuf],
+    algorithm: &str,
+    key_directory: &Path,
+    denial: Denial,
+    output: Option<PathBuf>,
+) {
+    let records = match zone::load(zone_file) {
+        Ok(records) => records,
+        Err(err) => fail(format!("Cannot load {}: {}", zone_file.display(), err)),
+    };
+
+    let keys = match key_files.is_empty() {
+        true => match records.iter().find(|record| record.ttype().code() == QType::SOA as u16) {
+            Some(soa) => generate_keys(soa.name(), algorithm, key_directory),
+            None => fail(format!("No SOA record in {}", zone_file.display())),
+        },
+        false => key_files
+            .iter()
+            .map(|path| match SigningKey::load_bind(path) {
+                Ok(key) => key,
+                Err(err) => fail(format!("Cannot load {}: {}", path.display(), err)),
+            })
+            .collect(),
+    };
+
+    let mut signer = ZoneSigner::new(keys);
+    signer.set_denial(denial);
+    let signed = match signer.sign(&records) {
+        Ok(signed) => signed,
+        Err(err) => fail(format!("Cannot sign {}: {}", zone_file.display(), err)),
+    };
+
+    let mut output = create_output(output);
+    for record in signed {
+        if let Err(err) = writeln!(output, "{}", record) {
+            fail(format!("Cannot write zone: {}", err));
+        }
+    }
+
+    if let Err(err) = output.flush() {
+        fail(format!("Cannot write zone: {}", err));
+    }
+}
+
+fn send_update(
+    resolver: &mut Resolver,
+    zone: &str,
+    key_file: Option<PathBuf>,
+    add: &[String],
+    delete: &[String],
+    absent: &[String],
+    present: &[String],
+) {
+    let key = key_file.map(|path| load_key(&path));
+
+    let mut update = Update::new(0, zone);
+    for name in absent {
+        update.require_name_absent(name);
+    }
+
+    for name in present {
+        update.require_name_in_use(name);
+    }
+
+    for record in delete {
+        if delete_record(&mut update, record).is_none() {
+            fail(format!("Invalid record to delete: {}", record));
+        }
+    }
+
+    for record in add {
+        if add_record(&mut update, record).is_none() {
+            fail(format!("Invalid record to add: {}", record));
+        }
+    }
+
+    match resolver.update(&mut update, key.as_ref()) {
+        Err(err) => fail(format!("Error occured: {}", err)),
+        Ok(()) => println!("Update of {} succeeded", zone),
+    }
 }
 
 fn main() {
     let args = Args::from_args();
-    let qtype = match args.qtype.as_str() {
-        "A" => QType::A,
-        "SRV" => QType::SRV,
-        _ => {
-            eprintln!("Unsupported question type: {}", args.qtype);
-            exit(1)
+
+    let mut config = Config::new();
+    if let Some(subnet) = &args.client_subnet {
+        match subnet.parse::<ClientSubnet>() {
+            Ok(subnet) => config.set_client_subnet(Some(subnet)),
+            Err(_) => fail(format!("Invalid client subnet: {}", subnet)),
         }
+    }
+
+    let (transport, port) = transport(&args);
+    let server = match &args.server {
+        Some(server) => match parse_server(server, port) {
+            Some(server) => server,
+            None => fail(format!("Invalid server address: {}", server)),
+        },
+        None => SocketAddr::new(config.server().ip(), port),
     };
 
-    let mut resolver = Resolver::new();
-    let result = match qtype {
-        QType::A => resolver.get_a_records(&args.host),
-        QType::SRV => resolver.get_srv_records(&args.host),
+    config.set_server_with_transport(server, transport);
+
+    config.set_case_randomization(args.randomize_case);
+    config.set_force_tcp(args.tcp);
+    let mut resolver = Resolver::with_config(config);
+    match args.command {
+        Some(Command::Update { zone, key_file, add, delete, absent, present }) => {
+            send_update(&mut resolver, &zone, key_file, &add, &delete, &absent, &present);
+            return;
+        }
+        Some(Command::Axfr { zone, key_file, output }) => {
+            transfer_zone(&mut resolver, &zone, key_file, output);
+            return;
+        }
+        Some(Command::SignZone {
+            zone_file,
+            key,
+            algorithm,
+            key_directory,
+            nsec3,
+            salt,
+            iterations,
+            opt_out,
+            output,
+        }) => {
+            let denial = match nsec3 {
+                true => match salt.as_str() {
+                    "-" => Denial::Nsec3 { iterations, salt: Vec::new(), opt_out },
+                    _ => match HEXLOWER_PERMISSIVE.decode(salt.as_bytes()) {
+                        Ok(salt) => Denial::Nsec3 { iterations, salt, opt_out },
+                        Err(_) => fail(format!("Invalid salt: {}", salt)),
+                    },
+                },
+                false => Denial::Nsec,
+            };
+
+            sign_zone(&zone_file, &key, &algorithm, &key_directory, denial, output);
+            return;
+        }
+        None => (),
+    }
+
+    let host = match args.host {
+        Some(host) => host,
+        None => fail("Host to resolve is required, see --help".to_string()),
+    };
+    let result = match parse_qtype(&args.qtype) {
+        Some(QType::A) => resolver.get_a_records(&host),
+        Some(QType::AAAA) => resolver.get_aaaa_records(&host),
+        Some(QType::SRV) => resolver.get_srv_records(&host),
+        _ => fail(format!("Unsupported question type: {}", args.qtype)),
     };
 
     match result {
         Err(err) => {
-            println!("Error occured: {:?}", err);
+            println!("Error occured: {}", err);
             exit(1)
         }
         Ok(rs) => {
@@ -0,0 +1,73 @@
+//! Connections that carry every message on a stream of its own, so that a slow response
+//! holds up no other: HTTP/2 for DNS over HTTPS and QUIC for DNS over QUIC.
+
+#[cfg(feature = "https")]
+use crate::https;
+#[cfg(feature = "quic")]
+use crate::quic;
+use crate::{Config, ResolveError, Transport};
+
+/// Whether the messages go over a multiplexed connection with the transport, rather than
+/// over a UDP socket or a connection that carries them one after another.
+pub(crate) fn is_multiplexed(transport: &Transport) -> bool {
+    match transport {
+        #[cfg(feature = "https")]
+        Transport::Https(_) => true,
+        #[cfg(feature = "quic")]
+        Transport::Quic(_) => true,
+        _ => false,
+    }
+}
+
+/// Connection to the server over the transport of the config, cheap to clone: clones send
+/// their messages over the same one.
+#[derive(Debug, Clone)]
+pub(crate) enum Connection {
+    #[cfg(feature = "https")]
+    Https(https::Connection),
+    #[cfg(feature = "quic")]
+    Quic(quic::Connection),
+}
+
+impl Connection {
+    /// Connects to the configured server, the tasks driving the connection are spawned on the current runtime.
+    pub(crate) async fn connect(config: &Config) -> Result<Self, ResolveError> {
+        return match config.transport() {
+            #[cfg(feature = "https")]
+            Transport::Https(_) => Ok(Connection::Https(https::Connection::connect(config).await?)),
+            #[cfg(feature = "quic")]
+            Transport::Quic(_) => Ok(Connection::Quic(quic::Connection::connect(config).await?)),
+            _ => Err(ResolveError::TransportFailed),
+        };
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        match self {
+            #[cfg(feature = "https")]
+            Connection::Https(connection) => connection.is_closed(),
+            #[cfg(feature = "quic")]
+            Connection::Quic(connection) => connection.is_closed(),
+        }
+    }
+
+    /// Waits until the connection is over.
+    pub(crate) async fn closed(&self) {
+        match self {
+            #[cfg(feature = "https")]
+            Connection::Https(connection) => connection.closed().await,
+            #[cfg(feature = "quic")]
+            Connection::Quic(connection) => connection.closed().await,
+        }
+    }
+
+    /// Sends the message on a stream of its own and returns the response. When it fails,
+    /// the connection is closed if it is broken, and the message should go again over a new one.
+    pub(crate) async fn exchange(self, message: Vec<u8>) -> Result<Vec<u8>, ResolveError> {
+        match self {
+            #[cfg(feature = "https")]
+            Connection::Https(connection) => connection.exchange(message).await,
+            #[cfg(feature = "quic")]
+            Connection::Quic(connection) => connection.exchange(message).await,
+        }
+    }
+}
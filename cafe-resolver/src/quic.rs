@@ -0,0 +1,168 @@
+//! DNS over QUIC (RFC 9250): every message exchanged over a bidirectional stream of its own.
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use quinn::crypto::rustls::QuicClientConfig;
+use quinn::{ClientConfig, ConnectionError, Endpoint, EndpointConfig, ReadError, ReadToEndError, TokioRuntime, WriteError};
+use tokio::sync::watch;
+
+use crate::{bind_udp, Config, ResolveError, Transport};
+
+/// ALPN protocol identifier of DNS over QUIC.
+const DOQ_ALPN: &[u8] = b"doq";
+
+/// Operation codes of the messages that may be replayed, and so sent in 0-RTT data (RFC 9250, section 4.5).
+const OPCODE_QUERY: u8 = 0;
+const OPCODE_NOTIFY: u8 = 4;
+
+/// Why an exchange over a stream failed.
+enum StreamError {
+    /// The server didn't take the 0-RTT data, the message has to go again now that the handshake is over.
+    ZeroRttRejected,
+    Failed(ResolveError),
+}
+
+impl From<ConnectionError> for StreamError {
+    fn from(_: ConnectionError) -> Self {
+        StreamError::Failed(ResolveError::TransportFailed)
+    }
+}
+
+impl From<WriteError> for StreamError {
+    fn from(err: WriteError) -> Self {
+        match err {
+            WriteError::ZeroRttRejected => StreamError::ZeroRttRejected,
+            _ => StreamError::Failed(ResolveError::TransportFailed),
+        }
+    }
+}
+
+impl From<ReadToEndError> for StreamError {
+    fn from(err: ReadToEndError) -> Self {
+        match err {
+            ReadToEndError::Read(ReadError::ZeroRttRejected) => StreamError::ZeroRttRejected,
+            _ => StreamError::Failed(ResolveError::TransportFailed),
+        }
+    }
+}
+
+/// QUIC connection to the server, cheap to clone: clones open their streams on the same one.
+#[derive(Debug, Clone)]
+pub(crate) struct Connection {
+    connection: quinn::Connection,
+    /// Set once the handshake is over, until then the streams go in 0-RTT data.
+    established: watch::Receiver<bool>,
+    /// Drives the connection along with the others of the endpoint.
+    _endpoint: Endpoint,
+}
+
+impl Connection {
+    /// Connects to the configured server from a random port. If a TLS session with the server
+    /// is resumed, the connection is usable right away and the first messages go in 0-RTT data.
+    pub(crate) async fn connect(config: &Config) -> Result<Self, ResolveError> {
+        let tls = match config.transport() {
+            Transport::Quic(tls) => tls,
+            _ => return Err(ResolveError::TransportFailed),
+        };
+
+        let mut client_config = (*tls.client_config(Some(DOQ_ALPN))?).clone();
+        client_config.enable_early_data = true;
+        let client_config = QuicClientConfig::try_from(client_config).map_err(|_| ResolveError::TransportFailed)?;
+
+        let socket = bind_udp(config.server(), config.random())?;
+        let mut endpoint = Endpoint::new(EndpointConfig::default(), None, socket, Arc::new(TokioRuntime))
+            .map_err(|_| ResolveError::TransportFailed)?;
+        endpoint.set_default_client_config(ClientConfig::new(Arc::new(client_config)));
+
+        let connecting = endpoint.connect(config.server(), tls.name()).map_err(|_| ResolveError::TransportFailed)?;
+        let (established, handshake) = watch::channel(false);
+        let connection = match connecting.into_0rtt() {
+            Ok((connection, accepted)) => {
+                tokio::spawn(async move {
+                    let _ = accepted.await;
+                    established.send_replace(true);
+                });
+                connection
+            }
+            Err(connecting) => {
+                let connection = connecting.await.map_err(|_| ResolveError::TransportFailed)?;
+                established.send_replace(true);
+                connection
+            }
+        };
+
+        return Ok(Self {
+            connection,
+            established: handshake,
+            _endpoint: endpoint,
+        });
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        return self.connection.close_reason().is_some();
+    }
+
+    /// Waits until the connection is over.
+    pub(crate) async fn closed(&self) {
+        let _ = self.connection.closed().await;
+    }
+
+    /// Waits until the handshake is over, or the connection failed on the way.
+    async fn established(&self) {
+        let _ = self.established.clone().wait_for(|established| *established).await;
+    }
+
+    /// Sends the message over a new stream and returns the response, its ID restored. The ID goes
+    /// as zero (RFC 9250, section 4.2.1). Fails with `ResolveError::TransportFailed` if the stream
+    /// or the connection failed, or with `ResolveError::DecodeFailed` if the response is malformed.
+    pub(crate) async fn exchange(self, mut message: Vec<u8>) -> Result<Vec<u8>, ResolveError> {
+        if message.len() < 12 || message.len() > u16::MAX as usize {
+            return Err(ResolveError::TransportFailed);
+        }
+
+        let id = [message[0], message[1]];
+        message[.. 2].copy_from_slice(&[0, 0]);
+
+        // Anything else could do harm if an attacker replayed it, it waits for the handshake.
+        let opcode = (message[2] >> 3) & 0x0f;
+        if opcode != OPCODE_QUERY && opcode != OPCODE_NOTIFY {
+            self.established().await;
+        }
+
+        let mut framed = (message.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(&message);
+        let result = match self.send(&framed).await {
+            Err(StreamError::ZeroRttRejected) => {
+                self.established().await;
+                self.send(&framed).await
+            }
+            result => result,
+        };
+
+        let mut response = match result {
+            Ok(response) => response,
+            Err(StreamError::ZeroRttRejected) => return Err(ResolveError::TransportFailed),
+            Err(StreamError::Failed(err)) => return Err(err),
+        };
+
+        // The stream carries a single message, its length has to match.
+        if response.len() < 14 || u16::from_be_bytes([response[0], response[1]]) as usize != response.len() - 2 {
+            return Err(ResolveError::DecodeFailed);
+        }
+
+        response.drain(.. 2);
+        response[.. 2].copy_from_slice(&id);
+        return Ok(response);
+    }
+
+    /// Writes the framed message to a new stream, closes the sending side of it and reads
+    /// whatever comes back until the server closes it too.
+    async fn send(&self, framed: &[u8]) -> Result<Vec<u8>, StreamError> {
+        let (mut send, mut recv) = self.connection.open_bi().await?;
+        send.write_all(framed).await?;
+        let _ = send.finish();
+
+        return Ok(recv.read_to_end(2 + u16::MAX as usize).await?);
+    }
+}
@@ -0,0 +1,68 @@
+//! Random numbers for message IDs, source ports and retransmission jitter.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use ring::rand::{SecureRandom, SystemRandom as RingRandom};
+
+/// Source of the random numbers of the resolvers, see `Config::set_random`.
+/// Message IDs and source ports are what off-path attackers have to guess to spoof answers,
+/// so anything but `SystemRandom` is only good for tests.
+pub trait Random: Send {
+    fn fill(&mut self, dest: &mut [u8]);
+}
+
+/// Cryptographically secure generator of the operating system, the default one.
+#[derive(Debug)]
+pub struct SystemRandom(RingRandom);
+
+impl SystemRandom {
+    pub fn new() -> Self {
+        Self(RingRandom::new())
+    }
+}
+
+impl Default for SystemRandom {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Random for SystemRandom {
+    fn fill(&mut self, dest: &mut [u8]) {
+        // Nothing sensible is left to do without the system source.
+        self.0.fill(dest).expect("system random source failed");
+    }
+}
+
+/// Generator shared by the copies of a config.
+#[derive(Clone)]
+pub(crate) struct SharedRandom(Arc<Mutex<Box<dyn Random>>>);
+
+impl SharedRandom {
+    pub(crate) fn new<R: Random + 'static>(random: R) -> Self {
+        Self(Arc::new(Mutex::new(Box::new(random))))
+    }
+
+    pub(crate) fn fill(&self, dest: &mut [u8]) {
+        self.0.lock().unwrap().fill(dest)
+    }
+
+    pub(crate) fn next_u16(&self) -> u16 {
+        let mut bytes = [0; 2];
+        self.0.lock().unwrap().fill(&mut bytes);
+        u16::from_be_bytes(bytes)
+    }
+
+    pub(crate) fn next_u64(&self) -> u64 {
+        let mut bytes = [0; 8];
+        self.0.lock().unwrap().fill(&mut bytes);
+        u64::from_be_bytes(bytes)
+    }
+}
+
+impl fmt::Debug for SharedRandom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedRandom")
+    }
+}
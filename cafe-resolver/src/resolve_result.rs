@@ -2,12 +2,19 @@ use std::fmt;
 use std::net::IpAddr;
 use std::time::Instant;
 
+use cafe_dns::{ClientSubnet, Response as DnsResponse};
+
+use crate::Security;
+
 #[derive(Debug, Clone)]
 pub struct Record {
     host: String,
     ip: IpAddr,
     port: Option<u16>,
     time_to_die: Instant,
+    /// Client subnet and its scope the record was given for, if the server supports ECS.
+    client_subnet: Option<ClientSubnet>,
+    security: Security,
 }
 
 impl Record {
@@ -17,9 +24,37 @@ impl Record {
             ip,
             port,
             time_to_die,
+            client_subnet: None,
+            security: Security::Indeterminate,
         }
     }
 
+    pub fn ip(&self) -> IpAddr {
+        self.ip
+    }
+
+    pub fn client_subnet(&self) -> Option<&ClientSubnet> {
+        self.client_subnet.as_ref()
+    }
+
+    pub fn set_client_subnet(&mut self, subnet: Option<ClientSubnet>) {
+        self.client_subnet = subnet
+    }
+
+    pub fn security(&self) -> Security {
+        self.security
+    }
+
+    pub fn set_security(&mut self, security: Security) {
+        self.security = security
+    }
+
+    /// Number of leftmost bits of the client subnet the record is valid for,
+    /// zero means the record is applicable to any client.
+    pub fn scope_prefix(&self) -> Option<u8> {
+        self.client_subnet.map(|subnet| subnet.scope_prefix())
+    }
+
     pub fn is_outdated(&self, time: Instant) -> bool {
         time >= self.time_to_die
     }
@@ -60,3 +95,24 @@ impl<'a> IntoIterator for &'a Result {
         self.state.iter()
     }
 }
+
+/// Response to a query along with its DNSSEC security status.
+#[derive(Debug)]
+pub struct Answer {
+    response: DnsResponse,
+    security: Security,
+}
+
+impl Answer {
+    pub fn new(response: DnsResponse, security: Security) -> Self {
+        Self { response, security }
+    }
+
+    pub fn response(&self) -> &DnsResponse {
+        &self.response
+    }
+
+    pub fn security(&self) -> Security {
+        self.security
+    }
+}
@@ -0,0 +1,164 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use rustls::{ClientConnection, StreamOwned};
+
+use crate::{Config, ResolveError, TlsConfig, Transport};
+
+/// ALPN protocol identifier of DNS over TLS.
+pub(crate) const DOT_ALPN: &[u8] = b"dot";
+
+/// Read timeouts of the stream are reported as `ResolveError::Timeout`.
+fn read_error(err: io::Error) -> ResolveError {
+    match err.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => ResolveError::Timeout,
+        _ => ResolveError::TransportFailed,
+    }
+}
+
+/// Connection to `server`, failing with `ResolveError::Timeout` if it takes longer than `timeout`.
+pub(crate) fn connect(server: SocketAddr, timeout: Duration) -> Result<TcpStream, ResolveError> {
+    let stream = TcpStream::connect_timeout(&server, timeout).map_err(read_error)?;
+    // Queries are single small writes, there is nothing to coalesce.
+    let _ = stream.set_nodelay(true);
+
+    return Ok(stream);
+}
+
+/// TLS session with the server of `tls`, the handshake is up to the caller.
+pub(crate) fn tls_connection(tls: &TlsConfig) -> Result<ClientConnection, ResolveError> {
+    let client_config = tls.client_config(Some(DOT_ALPN))?;
+    return ClientConnection::new(client_config, tls.server_name()?).map_err(|_| ResolveError::TransportFailed);
+}
+
+/// Connection to the configured server over TCP or, with `Transport::Tls`, over TLS.
+#[derive(Debug)]
+pub(crate) enum Stream {
+    Tcp(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+impl Stream {
+    /// Connects to the server and completes the TLS handshake, if any, within `timeout`.
+    pub(crate) fn connect(config: &Config, timeout: Duration) -> Result<Self, ResolveError> {
+        let tls = match config.transport() {
+            Transport::Udp => return Ok(Stream::Tcp(connect(config.server(), timeout)?)),
+            Transport::Tls(tls) => tls,
+            #[cfg(feature = "https")]
+            Transport::Https(_) => return Err(ResolveError::TransportFailed),
+            #[cfg(feature = "quic")]
+            Transport::Quic(_) => return Err(ResolveError::TransportFailed),
+        };
+
+        let mut stream = StreamOwned::new(tls_connection(tls)?, connect(config.server(), timeout)?);
+
+        // Failures of the handshake, such as a certificate that doesn't validate, end the connection.
+        stream.sock.set_read_timeout(Some(timeout)).map_err(|_| ResolveError::TransportFailed)?;
+        stream.sock.set_write_timeout(Some(timeout)).map_err(|_| ResolveError::TransportFailed)?;
+        while stream.conn.is_handshaking() {
+            stream.conn.complete_io(&mut stream.sock).map_err(read_error)?;
+        }
+
+        return Ok(Stream::Tls(Box::new(stream)));
+    }
+
+    fn socket(&self) -> &TcpStream {
+        match self {
+            Stream::Tcp(stream) => stream,
+            Stream::Tls(stream) => &stream.sock,
+        }
+    }
+
+    pub(crate) fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), ResolveError> {
+        return self.socket().set_read_timeout(timeout).map_err(|_| ResolveError::TransportFailed);
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            Stream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            Stream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            Stream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Sends a message prefixed with its two octet length (RFC 1035, section 4.2.2).
+pub(crate) fn write_message<S: Write>(stream: &mut S, message: &[u8]) -> Result<(), ResolveError> {
+    if message.len() > u16::MAX as usize {
+        return Err(ResolveError::TransportFailed);
+    }
+
+    let mut data = Vec::with_capacity(message.len() + 2);
+    data.extend_from_slice(&(message.len() as u16).to_be_bytes());
+    data.extend_from_slice(message);
+
+    stream.write_all(&data).map_err(|_| ResolveError::TransportFailed)?;
+    return stream.flush().map_err(|_| ResolveError::TransportFailed);
+}
+
+/// Receives the next length-prefixed message, `None` if the peer closed the connection
+/// in between messages.
+pub(crate) fn read_message<S: Read>(stream: &mut S) -> Result<Option<Vec<u8>>, ResolveError> {
+    let mut length = [0; 2];
+    match stream.read(&mut length[.. 1]) {
+        Err(err) => return Err(read_error(err)),
+        Ok(0) => return Ok(None),
+        Ok(_) => (),
+    }
+
+    stream.read_exact(&mut length[1 ..]).map_err(read_error)?;
+    let mut message = vec![0; u16::from_be_bytes(length) as usize];
+    stream.read_exact(&mut message).map_err(read_error)?;
+
+    return Ok(Some(message));
+}
+
+/// Splits what is read from a connection into messages. Unlike `read_message`, a read timing out
+/// in the middle of a message leaves the connection usable: what was received of it is kept
+/// for the next read.
+#[derive(Debug, Default)]
+pub(crate) struct MessageReader {
+    buffer: Vec<u8>,
+}
+
+impl MessageReader {
+    /// The next message once it is complete, `None` if the peer closed the connection.
+    pub(crate) fn read_message<S: Read>(&mut self, stream: &mut S) -> Result<Option<Vec<u8>>, ResolveError> {
+        loop {
+            if let [high, low, ..] = self.buffer[..] {
+                let end = 2 + u16::from_be_bytes([high, low]) as usize;
+                if self.buffer.len() >= end {
+                    let message = self.buffer[2 .. end].to_vec();
+                    self.buffer.drain(.. end);
+                    return Ok(Some(message));
+                }
+            }
+
+            let mut chunk = [0; 4096];
+            match stream.read(&mut chunk) {
+                Ok(0) => return Ok(None),
+                Ok(size) => self.buffer.extend_from_slice(&chunk[.. size]),
+                Err(err) if err.kind() == ErrorKind::Interrupted => (),
+                Err(err) => return Err(read_error(err)),
+            }
+        }
+    }
+}
@@ -0,0 +1,247 @@
+//! TLS settings of the encrypted transports, DNS over TLS (RFC 7858) first of all.
+
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+
+use ring::digest;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::ring as provider;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+
+use crate::ResolveError;
+
+/// How the server is authenticated and what the queries sent to it look like.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    /// Authentication domain name: sent in SNI and matched against the certificate of the server.
+    name: String,
+    /// DER-encoded certificates of the trust anchors, the ones of webpki-roots if there are none.
+    roots: Vec<Vec<u8>>,
+    /// SHA-256 digests of SubjectPublicKeyInfo, the certificate of the server has to match one of them
+    /// on top of the usual validation (RFC 7858, section 4.2). The other certificates the server sends
+    /// are left out: nothing ties them to the path that was validated.
+    spki_pins: Vec<[u8; 32]>,
+    /// Whether queries are padded to blocks of 128 octets (RFC 8467, section 4.1).
+    padding: bool,
+    /// Built on the first connection and shared by the copies, so that TLS sessions are resumed.
+    client_config: Arc<Mutex<Option<Arc<ClientConfig>>>>,
+}
+
+impl TlsConfig {
+    pub fn new(name: &str) -> Self {
+        return Self {
+            name: name.to_string(),
+            roots: Vec::new(),
+            spki_pins: Vec::new(),
+            padding: true,
+            client_config: Default::default(),
+        };
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn roots(&self) -> &[Vec<u8>] {
+        &self.roots
+    }
+
+    /// Trusts the DER-encoded certificate instead of the webpki-roots ones.
+    pub fn add_root_certificate(&mut self, der: &[u8]) {
+        self.roots.push(der.to_vec());
+        self.client_config = Default::default();
+    }
+
+    pub fn spki_pins(&self) -> &[[u8; 32]] {
+        &self.spki_pins
+    }
+
+    /// Adds SHA-256 digest of SubjectPublicKeyInfo to the pin set, see `spki_pin`.
+    pub fn add_spki_pin(&mut self, pin: [u8; 32]) {
+        self.spki_pins.push(pin);
+        self.client_config = Default::default();
+    }
+
+    pub fn padding(&self) -> bool {
+        self.padding
+    }
+
+    pub fn set_padding(&mut self, value: bool) {
+        self.padding = value
+    }
+
+    pub(crate) fn server_name(&self) -> Result<ServerName<'static>, ResolveError> {
+        return ServerName::try_from(self.name.clone()).map_err(|_| ResolveError::TransportFailed);
+    }
+
+    /// Client configuration with the ALPN protocol `alpn`, if any.
+    pub(crate) fn client_config(&self, alpn: Option<&[u8]>) -> Result<Arc<ClientConfig>, ResolveError> {
+        let mut cached = self.client_config.lock().unwrap();
+        let config = match &*cached {
+            Some(config) => config.clone(),
+            None => {
+                let config = Arc::new(self.build_client_config()?);
+                *cached = Some(config.clone());
+                config
+            }
+        };
+
+        return match alpn {
+            None => Ok(config),
+            Some(protocol) => {
+                let mut config = (*config).clone();
+                config.alpn_protocols = vec![protocol.to_vec()];
+                Ok(Arc::new(config))
+            }
+        };
+    }
+
+    fn build_client_config(&self) -> Result<ClientConfig, ResolveError> {
+        let provider = Arc::new(provider::default_provider());
+
+        let mut roots = RootCertStore::empty();
+        if self.roots.is_empty() {
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        }
+
+        for root in &self.roots {
+            roots.add(CertificateDer::from(root.clone())).map_err(|_| ResolveError::TransportFailed)?;
+        }
+
+        let verifier = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+            .build()
+            .map_err(|_| ResolveError::TransportFailed)?;
+
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|_| ResolveError::TransportFailed)?;
+
+        let config = match self.spki_pins.is_empty() {
+            true => builder.with_webpki_verifier(verifier).with_no_client_auth(),
+            false => {
+                let verifier = PinnedVerifier {
+                    inner: verifier,
+                    pins: self.spki_pins.clone(),
+                };
+
+                builder.dangerous().with_custom_certificate_verifier(Arc::new(verifier)).with_no_client_auth()
+            }
+        };
+
+        return Ok(config);
+    }
+}
+
+/// SHA-256 digest of SubjectPublicKeyInfo of the DER-encoded certificate, for `TlsConfig::add_spki_pin`.
+pub fn spki_pin(certificate: &[u8]) -> Option<[u8; 32]> {
+    let spki = subject_public_key_info(certificate)?;
+    let mut pin = [0; 32];
+    pin.copy_from_slice(digest::digest(&digest::SHA256, spki).as_ref());
+
+    return Some(pin);
+}
+
+/// Splits the DER element with `tag` off the start of `data`, returning
+/// its contents, the whole element and the rest of `data`.
+fn der_element(data: &[u8], tag: u8) -> Option<(&[u8], &[u8], &[u8])> {
+    if *data.first()? != tag {
+        return None;
+    }
+
+    let (length, header) = match *data.get(1)? {
+        length if length < 0x80 => (length as usize, 2),
+        0x81 => (*data.get(2)? as usize, 3),
+        0x82 => (u16::from_be_bytes([*data.get(2)?, *data.get(3)?]) as usize, 4),
+        0x83 => (u32::from_be_bytes([0, *data.get(2)?, *data.get(3)?, *data.get(4)?]) as usize, 5),
+        _ => return None,
+    };
+
+    let end = header + length;
+    let contents = data.get(header .. end)?;
+    return Some((contents, &data[.. end], &data[end ..]));
+}
+
+/// SubjectPublicKeyInfo of the certificate, RFC 5280, section 4.1:
+/// Certificate ::= SEQUENCE { TBSCertificate ::= SEQUENCE { [0] version OPTIONAL,
+/// serialNumber, signature, issuer, validity, subject, subjectPublicKeyInfo, ... }, ... }
+fn subject_public_key_info(certificate: &[u8]) -> Option<&[u8]> {
+    const SEQUENCE: u8 = 0x30;
+
+    let (certificate, _, _) = der_element(certificate, SEQUENCE)?;
+    let (mut fields, _, _) = der_element(certificate, SEQUENCE)?;
+    if let Some((_, _, rest)) = der_element(fields, 0xa0) {
+        fields = rest;
+    }
+
+    for tag in [0x02, SEQUENCE, SEQUENCE, SEQUENCE, SEQUENCE].iter() {
+        fields = der_element(fields, *tag)?.2;
+    }
+
+    return Some(der_element(fields, SEQUENCE)?.1);
+}
+
+/// Validates the chain as usual, then requires the end-entity certificate to match a pin.
+#[derive(Debug)]
+struct PinnedVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    pins: Vec<[u8; 32]>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        let pinned = spki_pin(end_entity).is_some_and(|pin| self.pins.contains(&pin));
+
+        return match pinned {
+            true => Ok(verified),
+            false => Err(rustls::Error::General("no SPKI pin matched".to_string())),
+        };
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        certificate: &CertificateDer<'_>,
+        signature: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        return self.inner.verify_tls12_signature(message, certificate, signature);
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        certificate: &CertificateDer<'_>,
+        signature: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        return self.inner.verify_tls13_signature(message, certificate, signature);
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        return self.inner.supported_verify_schemes();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn der_lengths() {
+        assert_eq!(der_element(&[0x30, 0x01, 0xff, 0x00], 0x30), Some((&[0xff][..], &[0x30, 0x01, 0xff][..], &[0x00][..])));
+        assert!(der_element(&[0x30, 0x02, 0xff], 0x30).is_none());
+        assert!(der_element(&[0x02, 0x01, 0xff], 0x30).is_none());
+
+        let mut long = vec![0x04, 0x82, 0x01, 0x00];
+        long.extend_from_slice(&[0; 256]);
+        assert_eq!(der_element(&long, 0x04).unwrap().0.len(), 256);
+    }
+}
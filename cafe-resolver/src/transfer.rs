@@ -0,0 +1,316 @@
+use std::collections::VecDeque;
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use cafe_dns::tsig::{self, Key as TsigKey, Verifier as TsigVerifier};
+use cafe_dns::{QType, Request as DnsRequest, ResourceRecord, Response as DnsResponse, Type};
+
+use crate::{check_rcode, tcp, ResolveError};
+
+fn soa_serial(record: &ResourceRecord) -> Option<u32> {
+    match record.ttype() {
+        Type::SOA { serial, .. } => Some(*serial),
+        _ => None,
+    }
+}
+
+/// Records of a zone transfer as they arrive from the server over TCP.
+///
+/// An AXFR stream (RFC 5936) yields the SOA followed by the rest of the zone, the SOA
+/// closing the transfer is not repeated. An IXFR stream (RFC 1995) yields the current SOA
+/// followed either by the whole zone or by the difference sequences, each made of the old
+/// SOA, the deleted records, the new SOA and the added records; use `changes` to get them
+/// structured. With TSIG, records of unsigned messages are held back until the next signed
+/// message verifies. The stream ends after the first error, reading from the server times out
+/// after `Config::timeout`.
+pub struct Transfer<'a> {
+    connection: TcpStream,
+    id: u16,
+    zone: String,
+    qtype: QType,
+    verifier: Option<TsigVerifier<'a>>,
+    pending: VecDeque<ResourceRecord>,
+    /// Records of the unsigned messages since the last signed one.
+    unverified: Vec<ResourceRecord>,
+    /// Serial of the SOA the transfer started with.
+    serial: Option<u32>,
+    /// Number of records received so far, the closing SOA included.
+    received: usize,
+    /// IXFR stream made of difference sequences rather than the whole zone.
+    incremental: bool,
+    /// Whether the next SOA of an incremental stream starts additions rather than deletions.
+    adding: bool,
+    done: bool,
+}
+
+/// Outcome of an incremental zone transfer.
+#[derive(Debug)]
+pub enum Ixfr {
+    /// The zone has not changed since the serial of the request, here is its SOA.
+    UpToDate(ResourceRecord),
+    /// The server sent the whole zone instead of the differences, starting with SOA.
+    Zone(Vec<ResourceRecord>),
+    /// Changes to apply in order to get from the serial of the request to the current one.
+    Differences(Vec<Difference>),
+}
+
+/// A single version step of an incremental zone transfer.
+#[derive(Debug)]
+pub struct Difference {
+    from: ResourceRecord,
+    to: ResourceRecord,
+    deleted: Vec<ResourceRecord>,
+    added: Vec<ResourceRecord>,
+}
+
+impl Difference {
+    /// SOA of the version the difference applies to.
+    pub fn from(&self) -> &ResourceRecord {
+        &self.from
+    }
+
+    /// SOA of the version the difference leads to.
+    pub fn to(&self) -> &ResourceRecord {
+        &self.to
+    }
+
+    pub fn deleted(&self) -> &[ResourceRecord] {
+        &self.deleted
+    }
+
+    pub fn added(&self) -> &[ResourceRecord] {
+        &self.added
+    }
+}
+
+impl<'a> Transfer<'a> {
+    /// Sends `request` encoded as `message` and prepares to read the answer. The answer
+    /// is verified with the key when given along with the MAC of the signed request.
+    /// Connecting and each read from the server fail after `timeout`.
+    pub(crate) fn start(
+        server: SocketAddr,
+        timeout: Duration,
+        request: &DnsRequest,
+        message: &[u8],
+        key: Option<(&'a TsigKey, &[u8])>,
+    ) -> Result<Self, ResolveError> {
+        let question = match request.questions().first() {
+            Some(question) => question,
+            None => return Err(ResolveError::DecodeFailed),
+        };
+
+        let mut connection = tcp::connect(server, timeout)?;
+        connection.set_read_timeout(Some(timeout)).map_err(|_| ResolveError::TransportFailed)?;
+        connection.set_write_timeout(Some(timeout)).map_err(|_| ResolveError::TransportFailed)?;
+        tcp::write_message(&mut connection, message)?;
+
+        return Ok(Self {
+            connection,
+            id: request.header().id(),
+            // Names of the records come without the trailing dot.
+            zone: question.host_name().trim_end_matches('.').to_string(),
+            qtype: question.qtype(),
+            verifier: key.map(|(key, request_mac)| TsigVerifier::for_response(key, request_mac)),
+            pending: VecDeque::new(),
+            unverified: Vec::new(),
+            serial: None,
+            received: 0,
+            incremental: false,
+            adding: false,
+            done: false,
+        });
+    }
+
+    /// Reads the rest of an IXFR stream and splits it into the difference sequences.
+    pub fn changes(mut self) -> Result<Ixfr, ResolveError> {
+        let mut records = Vec::new();
+        for record in &mut self {
+            records.push(record?);
+        }
+
+        let mut records = records.into_iter();
+        let current = match records.next() {
+            Some(soa) => soa,
+            None => return Err(ResolveError::DecodeFailed),
+        };
+
+        if !self.incremental {
+            if self.received == 1 {
+                return Ok(Ixfr::UpToDate(current));
+            }
+
+            let mut zone = vec![current];
+            zone.extend(records);
+            return Ok(Ixfr::Zone(zone));
+        }
+
+        let mut differences: Vec<Difference> = Vec::new();
+        let mut deleting = false;
+        for record in records {
+            let is_soa = soa_serial(&record).is_some() && record.name().eq_ignore_ascii_case(&self.zone);
+            match (is_soa, deleting, differences.last_mut()) {
+                (true, false, _) => {
+                    differences.push(Difference {
+                        from: record.clone(),
+                        to: record,
+                        deleted: Vec::new(),
+                        added: Vec::new(),
+                    });
+                    deleting = true;
+                }
+                (true, true, Some(difference)) => {
+                    difference.to = record;
+                    deleting = false;
+                }
+                (false, true, Some(difference)) => difference.deleted.push(record),
+                (false, false, Some(difference)) => difference.added.push(record),
+                _ => return Err(ResolveError::DecodeFailed),
+            }
+        }
+
+        return Ok(Ixfr::Differences(differences));
+    }
+
+    fn receive(&mut self) -> Result<(), ResolveError> {
+        let message = match tcp::read_message(&mut self.connection)? {
+            Some(message) => message,
+            None => return Err(ResolveError::TransportFailed),
+        };
+
+        let response = match DnsResponse::decode(&message) {
+            Some(response) => response,
+            None => return Err(ResolveError::DecodeFailed),
+        };
+
+        if response.id() != self.id || !response.header().is_response() {
+            return Err(ResolveError::DecodeFailed);
+        }
+
+        check_rcode(&response)?;
+
+        // RFC 5936, section 2.2: messages after the first one may omit the question.
+        match response.questions().first() {
+            Some(question) if !question.host_name().eq_ignore_ascii_case(&self.zone) => {
+                return Err(ResolveError::DecodeFailed)
+            }
+            None if self.received == 0 => return Err(ResolveError::DecodeFailed),
+            _ => (),
+        }
+
+        let signed = match self.verifier.as_mut() {
+            Some(verifier) => verifier
+                .verify(&message, tsig::current_time())
+                .map_err(ResolveError::TsigFailed)?
+                .is_some(),
+            None => true,
+        };
+
+        // RFC 8945, section 5.3.1: unsigned messages are covered by the MAC of the next signed one,
+        // their records can't be trusted before it verifies.
+        if signed {
+            self.pending.extend(self.unverified.drain(..));
+        }
+
+        let start = self.pending.len();
+        for record in response.answers() {
+            self.accept(record.clone())?;
+        }
+
+        if !signed {
+            self.unverified.extend(self.pending.drain(start ..));
+        }
+
+        // RFC 1995, section 4: a single SOA tells the client its version is current.
+        if let QType::IXFR = self.qtype {
+            if self.received == 1 {
+                self.done = true;
+            }
+        }
+
+        if self.done {
+            if let Some(verifier) = self.verifier.as_ref() {
+                verifier.finish().map_err(ResolveError::TsigFailed)?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn accept(&mut self, record: ResourceRecord) -> Result<(), ResolveError> {
+        if self.done {
+            return Err(ResolveError::DecodeFailed);
+        }
+
+        self.received += 1;
+        let serial = match record.name().eq_ignore_ascii_case(&self.zone) {
+            true => soa_serial(&record),
+            false => None,
+        };
+
+        let first = match self.serial {
+            Some(first) => first,
+            None => {
+                if serial.is_none() {
+                    return Err(ResolveError::DecodeFailed);
+                }
+
+                self.serial = serial;
+                self.pending.push_back(record);
+                return Ok(());
+            }
+        };
+
+        if let (QType::IXFR, 2, Some(serial)) = (self.qtype, self.received, serial) {
+            if serial != first {
+                self.incremental = true;
+                self.adding = true;
+                self.pending.push_back(record);
+                return Ok(());
+            }
+        }
+
+        if self.incremental && serial.is_some() {
+            if self.adding {
+                self.adding = false;
+                self.pending.push_back(record);
+                return Ok(());
+            }
+
+            if serial != Some(first) {
+                self.adding = true;
+                self.pending.push_back(record);
+                return Ok(());
+            }
+        }
+
+        match serial {
+            Some(_) => self.done = true,
+            None => self.pending.push_back(record),
+        }
+
+        return Ok(());
+    }
+}
+
+impl<'a> Iterator for Transfer<'a> {
+    type Item = Result<ResourceRecord, ResolveError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.pending.pop_front() {
+                return Some(Ok(record));
+            }
+
+            if self.done {
+                return None;
+            }
+
+            if let Err(err) = self.receive() {
+                self.done = true;
+                self.pending.clear();
+                self.unverified.clear();
+                return Some(Err(err));
+            }
+        }
+    }
+}
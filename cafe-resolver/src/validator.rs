@@ -0,0 +1,719 @@
+//! DNSSEC validation of responses (RFC 4035, section 5) along the chain of trust
+//! from the configured trust anchors.
+
+use std::convert::TryFrom;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use data_encoding::BASE32HEX_NOPAD;
+
+use cafe_dns::dnssec::{self, Algorithm, DNSKEY_PROTOCOL, ZONE_KEY_FLAG};
+use cafe_dns::rrset::{self, RRset};
+use cafe_dns::{QType, ResourceRecord, Response as DnsResponse, ResponseCode, Type};
+
+use crate::{ResolveError, Resolver};
+
+const NS_TYPE: u16 = 2;
+const SOA_TYPE: u16 = QType::SOA as u16;
+const CNAME_TYPE: u16 = 5;
+const DS_TYPE: u16 = QType::DS as u16;
+const DNAME_TYPE: u16 = 39;
+const RRSIG_TYPE: u16 = QType::RRSIG as u16;
+const NSEC_TYPE: u16 = QType::NSEC as u16;
+const DNSKEY_TYPE: u16 = QType::DNSKEY as u16;
+const NSEC3_TYPE: u16 = QType::NSEC3 as u16;
+
+/// NSEC3 flag of spans that may contain unsigned delegations (RFC 5155, section 3.1.2.1).
+const OPT_OUT_FLAG: u8 = 0x01;
+/// The only NSEC3 hash algorithm, SHA-1.
+const NSEC3_SHA1: u8 = 1;
+
+/// How long a zone that is not secure is remembered as such.
+const NEGATIVE_CACHE_TIME: Duration = Duration::from_secs(300);
+
+/// Security status of an answer (RFC 4033, section 5).
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Security {
+    /// Every RRset and the denial of existence are validated up to a trust anchor.
+    Secure,
+    /// The answer is proven to come from beneath an unsigned delegation.
+    Insecure,
+    /// Signatures or proofs are missing, expired or wrong.
+    Bogus,
+    /// Validation is disabled or no trust anchor covers the name.
+    Indeterminate,
+}
+
+impl Security {
+    /// Status of an answer made of two parts with the statuses `self` and `other`.
+    fn and(self, other: Security) -> Security {
+        let rank = |security| match security {
+            Security::Secure => 0,
+            Security::Insecure => 1,
+            Security::Indeterminate => 2,
+            Security::Bogus => 3,
+        };
+
+        match rank(self) >= rank(other) {
+            true => self,
+            false => other,
+        }
+    }
+}
+
+/// What is known about the keys of a zone.
+#[derive(Debug, Clone)]
+pub(crate) enum ZoneKeys {
+    /// Validated zone keys.
+    Secure(Vec<Type>),
+    /// The zone is beneath an unsigned delegation or signed with unsupported algorithms only.
+    Insecure,
+    /// The name is proven not to be a zone apex.
+    NotZone,
+    Bogus,
+    Indeterminate,
+}
+
+/// Outcome of checking the proof of non-existence of a name or of its type.
+#[derive(Debug, PartialEq)]
+enum Denial {
+    NxDomain,
+    /// The name exists with the listed types only.
+    NoData(Vec<u16>),
+    /// The name may be an unsigned delegation in an opt-out span.
+    OptOut,
+    Insecure,
+    Bogus,
+    Indeterminate,
+}
+
+/// RRset along with the signatures covering it.
+struct Signed {
+    rrset: RRset,
+    signatures: Vec<Type>,
+}
+
+fn group(records: &[ResourceRecord]) -> Vec<Signed> {
+    let (rrsigs, rrsets): (Vec<RRset>, Vec<RRset>) = rrset::group(records)
+        .into_iter()
+        .partition(|rrset| rrset.code() == RRSIG_TYPE);
+
+    return rrsets
+        .into_iter()
+        .map(|rrset| {
+            let signatures = rrsigs
+                .iter()
+                .filter(|rrsig| rrsig.name().eq_ignore_ascii_case(rrset.name()))
+                .flat_map(|rrsig| rrsig.rdatas())
+                .filter(|rrsig| matches!(rrsig, Type::RRSIG { type_covered, .. } if *type_covered == rrset.code()))
+                .cloned()
+                .collect();
+
+            Signed { rrset, signatures }
+        })
+        .collect();
+}
+
+fn labels(name: &str) -> Vec<&str> {
+    return name.split('.').filter(|label| !label.is_empty()).collect();
+}
+
+/// Whether `name` is `zone` itself or a name beneath it.
+fn is_subdomain(name: &str, zone: &str) -> bool {
+    let name = labels(name);
+    let zone = labels(zone);
+    return name.len() >= zone.len()
+        && name[name.len() - zone.len() ..]
+            .iter()
+            .zip(&zone)
+            .all(|(left, right)| left.eq_ignore_ascii_case(right));
+}
+
+/// The longest name both `left` and `right` are beneath.
+fn common_ancestor(left: &str, right: &str) -> String {
+    let left = labels(left);
+    let right = labels(right);
+    let common = left
+        .iter()
+        .rev()
+        .zip(right.iter().rev())
+        .take_while(|(left, right)| left.eq_ignore_ascii_case(right))
+        .count();
+
+    return left[left.len() - common ..].join(".");
+}
+
+fn parent(name: &str) -> String {
+    return labels(name).iter().skip(1).cloned().collect::<Vec<&str>>().join(".");
+}
+
+fn wildcard(name: &str) -> String {
+    return match name.is_empty() {
+        true => "*".to_string(),
+        false => format!("*.{}", name),
+    };
+}
+
+/// Whether the NSEC owned by `owner` with the next name `next` proves `name` does not exist.
+fn covers(owner: &str, next: &str, name: &str) -> bool {
+    let after_owner = dnssec::compare_names(owner, name).is_lt();
+    return match dnssec::compare_names(owner, next).is_lt() {
+        true => after_owner && dnssec::compare_names(name, next).is_lt(),
+        // The last NSEC of the zone points back to its apex.
+        false => after_owner,
+    };
+}
+
+/// Whether the NSEC3 span from `owner` to `next` hashes contains `hash`.
+fn covers_hash(owner: &[u8], next: &[u8], hash: &[u8]) -> bool {
+    return match owner < next {
+        true => owner < hash && hash < next,
+        false => owner < hash || hash < next,
+    };
+}
+
+fn now() -> u32 {
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as u32)
+        .unwrap_or(0);
+}
+
+/// Whether the signature validity period includes `now`, in serial number arithmetic.
+fn is_current(inception: u32, expiration: u32, now: u32) -> bool {
+    return (now.wrapping_sub(inception) as i32) >= 0 && (expiration.wrapping_sub(now) as i32) >= 0;
+}
+
+fn is_supported_anchor(anchor: &Type) -> bool {
+    return match anchor {
+        Type::DS { algorithm, digest_type, .. } => {
+            Algorithm::try_from(*algorithm).is_ok() && matches!(digest_type, 1 | 2 | 4)
+        }
+        Type::DNSKEY { algorithm, .. } => Algorithm::try_from(*algorithm).is_ok(),
+        _ => false,
+    };
+}
+
+/// Whether the DS or DNSKEY `anchor` authenticates the `key` of `zone`.
+fn anchor_matches(zone: &str, anchor: &Type, key: &Type) -> bool {
+    return match (anchor, key) {
+        (Type::DS { key_tag, algorithm, digest_type, digest }, Type::DNSKEY { algorithm: key_algorithm, .. }) => {
+            key.key_tag() == Some(*key_tag)
+                && algorithm == key_algorithm
+                && dnssec::ds_digest(zone, key, *digest_type).as_ref() == Some(digest)
+        }
+        (Type::DNSKEY { .. }, Type::DNSKEY { .. }) => anchor.rdata() == key.rdata(),
+        _ => false,
+    };
+}
+
+/// Whether the zone key `key` may have made the signature with `algorithm` and `key_tag`.
+fn key_matches(key: &Type, algorithm: u8, key_tag: u16) -> bool {
+    return match key {
+        Type::DNSKEY { flags, protocol, algorithm: key_algorithm, .. } => {
+            flags & ZONE_KEY_FLAG != 0
+                && *protocol == DNSKEY_PROTOCOL
+                && *key_algorithm == algorithm
+                && key.key_tag() == Some(key_tag)
+        }
+        _ => false,
+    };
+}
+
+/// Verifies one of the `signatures` of `set` made by `signer` with one of `keys`,
+/// returns the number of labels of the RRSIG that made it.
+fn verify_rrset(set: &Signed, signatures: &[Type], signer: &str, keys: &[Type], now: u32) -> Option<u8> {
+    for rrsig in signatures {
+        let (algorithm, labels, expiration, inception, key_tag, signer_name, signature) = match rrsig {
+            Type::RRSIG { algorithm, labels, expiration, inception, key_tag, signer_name, signature, .. } => {
+                (*algorithm, *labels, *expiration, *inception, *key_tag, signer_name, signature)
+            }
+            _ => continue,
+        };
+
+        if !signer_name.eq_ignore_ascii_case(signer) || !is_current(inception, expiration, now) {
+            continue;
+        }
+
+        let data = match dnssec::signed_data(rrsig, &set.rrset.records()) {
+            Some(data) => data,
+            None => continue,
+        };
+
+        let verified = keys
+            .iter()
+            .filter(|key| key_matches(key, algorithm, key_tag))
+            .any(|key| dnssec::verify(key, &data, signature));
+        if verified {
+            return Some(labels);
+        }
+    }
+
+    return None;
+}
+
+/// Whether the NSEC types are those of the parent side of a zone cut.
+fn is_delegation(types: &[u16]) -> bool {
+    return types.contains(&NS_TYPE) && !types.contains(&SOA_TYPE);
+}
+
+fn nsec_denial(qname: &str, qtype: u16, nsecs: &[(&str, &str, &[u16])]) -> Denial {
+    if let Some((_, _, types)) = nsecs.iter().find(|(owner, _, _)| owner.eq_ignore_ascii_case(qname)) {
+        if types.contains(&qtype) || types.contains(&CNAME_TYPE) {
+            return Denial::Bogus;
+        }
+
+        // The rest of the records of a delegation point are in the child zone.
+        if is_delegation(types) && qtype != DS_TYPE {
+            return Denial::Bogus;
+        }
+
+        return Denial::NoData(types.to_vec());
+    }
+
+    let (owner, next) = match nsecs.iter().find(|(owner, next, _)| covers(owner, next, qname)) {
+        Some((owner, next, _)) => (*owner, *next),
+        None => return Denial::Bogus,
+    };
+
+    // RFC 6840, section 4.1: an NSEC at a zone cut or a DNAME above the name proves nothing
+    // about the names beneath, they belong to another zone.
+    let ancestor = nsecs.iter().any(|(owner, _, types)| {
+        is_subdomain(qname, owner)
+            && !owner.eq_ignore_ascii_case(qname)
+            && (is_delegation(types) || types.contains(&DNAME_TYPE))
+    });
+    if ancestor {
+        return Denial::Bogus;
+    }
+
+    // An empty non-terminal exists but owns no records, the next name is beneath it.
+    if is_subdomain(next, qname) {
+        return Denial::NoData(Vec::new());
+    }
+
+    let from_owner = common_ancestor(qname, owner);
+    let from_next = common_ancestor(qname, next);
+    let encloser = match labels(&from_owner).len() >= labels(&from_next).len() {
+        true => from_owner,
+        false => from_next,
+    };
+
+    let wildcard = wildcard(&encloser);
+    return match nsecs.iter().any(|(owner, next, _)| covers(owner, next, &wildcard)) {
+        true => Denial::NxDomain,
+        false => Denial::Bogus,
+    };
+}
+
+/// NSEC3 record decoded for the proofs: hash of its owner, the zone and RDATA.
+struct Nsec3<'r> {
+    hash: Vec<u8>,
+    zone: String,
+    flags: u8,
+    next: &'r [u8],
+    types: &'r [u16],
+}
+
+fn nsec3_denial(qname: &str, qtype: u16, records: &[ResourceRecord]) -> Denial {
+    let mut nsec3s = Vec::new();
+    let mut parameters = None;
+    for record in records {
+        if let Type::NSEC3 { hash_algorithm, flags, iterations, salt, next_hashed_owner, types } = record.ttype() {
+            // RFC 5155, section 8.1: records with unknown hash algorithms are ignored.
+            if *hash_algorithm != NSEC3_SHA1 {
+                continue;
+            }
+
+            let owner = labels(record.name());
+            let hash = match owner.first().and_then(|label| BASE32HEX_NOPAD.decode(label.to_ascii_uppercase().as_bytes()).ok()) {
+                Some(hash) => hash,
+                None => return Denial::Bogus,
+            };
+
+            parameters = Some((salt, *iterations));
+            nsec3s.push(Nsec3 {
+                hash,
+                zone: owner[1 ..].join("."),
+                flags: *flags,
+                next: next_hashed_owner,
+                types,
+            });
+        }
+    }
+
+    let (salt, iterations) = match parameters {
+        Some(parameters) => parameters,
+        None => return Denial::Insecure,
+    };
+
+    let zone = nsec3s[0].zone.clone();
+    let hash = |name: &str| dnssec::nsec3_hash(name, salt, iterations);
+    let matching = |name: &str| {
+        let hash = hash(name);
+        nsec3s.iter().find(|nsec3| nsec3.hash == hash)
+    };
+    let covering = |name: &str| {
+        let hash = hash(name);
+        nsec3s.iter().find(|nsec3| covers_hash(&nsec3.hash, nsec3.next, &hash))
+    };
+
+    if let Some(nsec3) = matching(qname) {
+        if nsec3.types.contains(&qtype) || nsec3.types.contains(&CNAME_TYPE) {
+            return Denial::Bogus;
+        }
+
+        return Denial::NoData(nsec3.types.to_vec());
+    }
+
+    // RFC 5155, section 8.3: the closest encloser exists, the next closer name does not.
+    let mut next_closer = qname.to_string();
+    let mut encloser = parent(qname);
+    loop {
+        if !is_subdomain(&encloser, &zone) || labels(&next_closer).is_empty() {
+            return Denial::Bogus;
+        }
+
+        if matching(&encloser).is_some() {
+            break;
+        }
+
+        next_closer = encloser.clone();
+        encloser = parent(&encloser);
+    }
+
+    let cover = match covering(&next_closer) {
+        Some(cover) => cover,
+        None => return Denial::Bogus,
+    };
+
+    if cover.flags & OPT_OUT_FLAG != 0 {
+        return Denial::OptOut;
+    }
+
+    return match covering(&wildcard(&encloser)) {
+        Some(_) => Denial::NxDomain,
+        None => Denial::Bogus,
+    };
+}
+
+impl Resolver {
+    /// Validates `response` to the query of `qtype` for `qname`.
+    pub(crate) fn validate(
+        &mut self,
+        qname: &str,
+        qtype: QType,
+        response: &DnsResponse,
+    ) -> Result<Security, ResolveError> {
+        if self.config().trust_anchors().is_empty() {
+            return Ok(Security::Indeterminate);
+        }
+
+        let rcode = response.header().rcode();
+        let sets = group(response.answers());
+        if sets.is_empty() || rcode != ResponseCode::NoError {
+            let denial = self.validate_denial(qname, qtype as u16, response)?;
+            return Ok(match (rcode, denial) {
+                (ResponseCode::NameError, Denial::NxDomain) => Security::Secure,
+                (ResponseCode::NoError, Denial::NoData(_)) => Security::Secure,
+                (_, Denial::OptOut) | (_, Denial::Insecure) => Security::Insecure,
+                (_, Denial::Indeterminate) => Security::Indeterminate,
+                _ => Security::Bogus,
+            });
+        }
+
+        if !sets.iter().any(|set| set.rrset.name().eq_ignore_ascii_case(qname)) {
+            return Ok(Security::Bogus);
+        }
+
+        let mut security = Security::Secure;
+        for set in &sets {
+            let (status, labels) = self.validate_rrset(set)?;
+            security = security.and(status);
+
+            // RFC 4035, section 5.3.4: an answer synthesized from a wildcard requires
+            // the proof that the name itself does not exist.
+            if let (Security::Secure, Some(labels)) = (status, labels) {
+                if (labels as usize) < dnssec::label_count(set.rrset.name()) {
+                    security = security.and(self.validate_expansion(set.rrset.name(), labels, response)?);
+                }
+            }
+        }
+
+        return Ok(security);
+    }
+
+    /// Validates the RRset, returns its status along with the number of labels of the RRSIG
+    /// that verified it.
+    fn validate_rrset(&mut self, set: &Signed) -> Result<(Security, Option<u8>), ResolveError> {
+        if set.signatures.is_empty() {
+            return Ok((self.prove_insecure(set.rrset.name())?, None));
+        }
+
+        let now = now();
+        let mut security = Security::Bogus;
+        for rrsig in &set.signatures {
+            let signer = match rrsig {
+                Type::RRSIG { signer_name, .. } if is_subdomain(set.rrset.name(), signer_name) => signer_name,
+                _ => continue,
+            };
+
+            match self.zone_keys(signer)? {
+                ZoneKeys::Secure(keys) => {
+                    if let Some(labels) = verify_rrset(set, &set.signatures, signer, &keys, now) {
+                        return Ok((Security::Secure, Some(labels)));
+                    }
+                }
+                ZoneKeys::Insecure => return Ok((Security::Insecure, None)),
+                ZoneKeys::Indeterminate => security = Security::Indeterminate,
+                ZoneKeys::NotZone | ZoneKeys::Bogus => (),
+            }
+        }
+
+        return Ok((security, None));
+    }
+
+    /// Checks that the name `owner` an answer was synthesized for from the wildcard
+    /// with `labels` labels does not exist.
+    fn validate_expansion(
+        &mut self,
+        owner: &str,
+        labels: u8,
+        response: &DnsResponse,
+    ) -> Result<Security, ResolveError> {
+        let mut security = Security::Secure;
+        let sets = group(response.authorities());
+        for set in sets.iter().filter(|set| set.rrset.code() == NSEC_TYPE || set.rrset.code() == NSEC3_TYPE) {
+            security = security.and(self.validate_rrset(set)?.0);
+        }
+
+        let records: Vec<ResourceRecord> = sets.into_iter().flat_map(|set| set.rrset.records()).collect();
+        let nsec_proof = records.iter().any(|record| match record.ttype() {
+            Type::NSEC { next_domain_name, .. } => covers(record.name(), next_domain_name, owner),
+            _ => false,
+        });
+
+        let owner_labels = self::labels(owner);
+        let next_closer = owner_labels[owner_labels.len() - labels as usize - 1 ..].join(".");
+        let nsec3_proof = records.iter().any(|record| match record.ttype() {
+            Type::NSEC3 { salt, iterations, next_hashed_owner, .. } => {
+                let hash = dnssec::nsec3_hash(&next_closer, salt, *iterations);
+                let owner_hash = self::labels(record.name())
+                    .first()
+                    .and_then(|label| BASE32HEX_NOPAD.decode(label.to_ascii_uppercase().as_bytes()).ok());
+                owner_hash.is_some_and(|owner_hash| covers_hash(&owner_hash, next_hashed_owner, &hash))
+            }
+            _ => false,
+        });
+
+        return Ok(match nsec_proof || nsec3_proof {
+            true => security,
+            false => Security::Bogus,
+        });
+    }
+
+    /// Validates the authority section proving there is no `qtype` at `qname`.
+    fn validate_denial(&mut self, qname: &str, qtype: u16, response: &DnsResponse) -> Result<Denial, ResolveError> {
+        let sets = group(response.authorities());
+        if sets.is_empty() {
+            return Ok(match self.prove_insecure(qname)? {
+                Security::Insecure => Denial::Insecure,
+                Security::Indeterminate => Denial::Indeterminate,
+                _ => Denial::Bogus,
+            });
+        }
+
+        let mut security = Security::Secure;
+        let mut proofs = Vec::new();
+        for set in sets.iter().filter(|set| [SOA_TYPE, NSEC_TYPE, NSEC3_TYPE].contains(&set.rrset.code())) {
+            security = security.and(self.validate_rrset(set)?.0);
+            if set.rrset.code() != SOA_TYPE {
+                proofs.extend(set.rrset.records());
+            }
+        }
+
+        match security {
+            Security::Secure => (),
+            Security::Insecure => return Ok(Denial::Insecure),
+            Security::Indeterminate => return Ok(Denial::Indeterminate),
+            Security::Bogus => return Ok(Denial::Bogus),
+        }
+
+        let nsecs: Vec<(&str, &str, &[u16])> = proofs
+            .iter()
+            .filter_map(|record| match record.ttype() {
+                Type::NSEC { next_domain_name, types } => Some((record.name(), next_domain_name.as_str(), types.as_slice())),
+                _ => None,
+            })
+            .collect();
+
+        if !nsecs.is_empty() {
+            return Ok(nsec_denial(qname, qtype, &nsecs));
+        }
+
+        if proofs.is_empty() {
+            return Ok(Denial::Bogus);
+        }
+
+        return Ok(nsec3_denial(qname, qtype, &proofs));
+    }
+
+    /// Looks for an unsigned delegation above `name` walking down from the root,
+    /// unsigned data found elsewhere is bogus.
+    fn prove_insecure(&mut self, name: &str) -> Result<Security, ResolveError> {
+        let labels = labels(name);
+        for i in (0 ..= labels.len()).rev() {
+            let candidate = labels[i ..].join(".");
+            match self.zone_keys(&candidate)? {
+                ZoneKeys::Secure(_) | ZoneKeys::NotZone => (),
+                ZoneKeys::Insecure => return Ok(Security::Insecure),
+                ZoneKeys::Indeterminate => return Ok(Security::Indeterminate),
+                ZoneKeys::Bogus => return Ok(Security::Bogus),
+            }
+        }
+
+        return Ok(Security::Bogus);
+    }
+
+    /// Keys of `zone` validated with the trust anchors or the DS records of the parent zone.
+    fn zone_keys(&mut self, zone: &str) -> Result<ZoneKeys, ResolveError> {
+        let zone = zone.to_ascii_lowercase();
+        let now = Instant::now();
+        if let Some((keys, valid_until)) = self.zones.get(&zone) {
+            if now < *valid_until {
+                return Ok(keys.clone());
+            }
+        }
+
+        // Lookups made while the keys are being fetched must not come back to the zone.
+        self.zones.insert(zone.clone(), (ZoneKeys::Bogus, now + NEGATIVE_CACHE_TIME));
+        let (keys, ttl) = match self.fetch_zone_keys(&zone) {
+            Ok(fetched) => fetched,
+            Err(err) => {
+                self.zones.remove(&zone);
+                return Err(err);
+            }
+        };
+
+        self.zones.insert(zone, (keys.clone(), now + ttl));
+        return Ok(keys);
+    }
+
+    fn fetch_zone_keys(&mut self, zone: &str) -> Result<(ZoneKeys, Duration), ResolveError> {
+        let anchors: Vec<Type> = self
+            .config()
+            .trust_anchors()
+            .iter()
+            .filter(|anchor| anchor.name().eq_ignore_ascii_case(zone))
+            .map(|anchor| anchor.ttype().clone())
+            .collect();
+
+        let anchors = match (anchors.is_empty(), zone.is_empty()) {
+            (false, _) => anchors,
+            (true, true) => return Ok((ZoneKeys::Indeterminate, NEGATIVE_CACHE_TIME)),
+            (true, false) => {
+                let response = self.exchange(QType::DS, zone)?;
+                let sets = group(response.answers());
+                let found = sets
+                    .iter()
+                    .find(|set| set.rrset.code() == DS_TYPE && set.rrset.name().eq_ignore_ascii_case(zone));
+                let set = match found {
+                    Some(set) => set,
+                    None => {
+                        let keys = match self.validate_denial(zone, DS_TYPE, &response)? {
+                            Denial::NoData(types) if types.contains(&SOA_TYPE) => ZoneKeys::Bogus,
+                            Denial::NoData(types) if types.contains(&NS_TYPE) => ZoneKeys::Insecure,
+                            Denial::NoData(_) | Denial::NxDomain => ZoneKeys::NotZone,
+                            Denial::OptOut | Denial::Insecure => ZoneKeys::Insecure,
+                            Denial::Indeterminate => ZoneKeys::Indeterminate,
+                            Denial::Bogus => ZoneKeys::Bogus,
+                        };
+
+                        return Ok((keys, NEGATIVE_CACHE_TIME));
+                    }
+                };
+
+                match self.validate_rrset(set)?.0 {
+                    Security::Secure => set.rrset.rdatas().to_vec(),
+                    Security::Insecure => return Ok((ZoneKeys::Insecure, NEGATIVE_CACHE_TIME)),
+                    Security::Indeterminate => return Ok((ZoneKeys::Indeterminate, NEGATIVE_CACHE_TIME)),
+                    Security::Bogus => return Ok((ZoneKeys::Bogus, NEGATIVE_CACHE_TIME)),
+                }
+            }
+        };
+
+        // RFC 4035, section 5.2: a zone signed with unsupported algorithms only is insecure.
+        let anchors: Vec<Type> = anchors.into_iter().filter(is_supported_anchor).collect();
+        if anchors.is_empty() {
+            return Ok((ZoneKeys::Insecure, NEGATIVE_CACHE_TIME));
+        }
+
+        let response = self.exchange(QType::DNSKEY, zone)?;
+        let sets = group(response.answers());
+        let found = sets
+            .iter()
+            .find(|set| set.rrset.code() == DNSKEY_TYPE && set.rrset.name().eq_ignore_ascii_case(zone));
+        let set = match found {
+            Some(set) => set,
+            None => return Ok((ZoneKeys::Bogus, NEGATIVE_CACHE_TIME)),
+        };
+
+        let keys: Vec<Type> = set.rrset.rdatas().to_vec();
+        let entry_keys: Vec<Type> = keys
+            .iter()
+            .filter(|key| anchors.iter().any(|anchor| anchor_matches(zone, anchor, key)))
+            .cloned()
+            .collect();
+
+        if verify_rrset(set, &set.signatures, zone, &entry_keys, now()).is_none() {
+            return Ok((ZoneKeys::Bogus, NEGATIVE_CACHE_TIME));
+        }
+
+        let ttl = Duration::from_secs(set.rrset.ttl().into());
+        return Ok((ZoneKeys::Secure(keys), ttl));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nsec_coverage() {
+        assert!(covers("alfa.example.com", "host.example.com", "b.example.com"));
+        assert!(covers("alfa.example.com", "host.example.com", "x.b.example.com"));
+        assert!(!covers("alfa.example.com", "host.example.com", "host.example.com"));
+        assert!(!covers("alfa.example.com", "host.example.com", "alfa.example.com"));
+        assert!(covers("www.example.com", "example.com", "xyz.example.com"));
+        assert!(!covers("www.example.com", "example.com", "a.example.com"));
+    }
+
+    #[test]
+    fn names() {
+        assert!(is_subdomain("www.Example.com", "example.COM"));
+        assert!(is_subdomain("example.com", ""));
+        assert!(!is_subdomain("example.com", "www.example.com"));
+        assert!(!is_subdomain("badexample.com", "example.com"));
+
+        assert_eq!(common_ancestor("a.b.example.com", "c.b.Example.com"), "b.example.com");
+        assert_eq!(common_ancestor("example.com", "example.org"), "");
+        assert_eq!(parent("www.example.com"), "example.com");
+        assert_eq!(parent("com"), "");
+        assert_eq!(wildcard(""), "*");
+    }
+
+    #[test]
+    fn security_of_parts() {
+        assert_eq!(Security::Secure.and(Security::Insecure), Security::Insecure);
+        assert_eq!(Security::Bogus.and(Security::Insecure), Security::Bogus);
+        assert_eq!(Security::Secure.and(Security::Secure), Security::Secure);
+        assert_eq!(Security::Indeterminate.and(Security::Insecure), Security::Indeterminate);
+    }
+
+    #[test]
+    fn signature_validity() {
+        assert!(is_current(100, 200, 150));
+        assert!(!is_current(100, 200, 250));
+        assert!(!is_current(100, 200, 50));
+        assert!(is_current(u32::MAX - 10, 10, 5));
+    }
+}
@@ -0,0 +1,92 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use cafe_dns::{QType, Response as DnsResponse};
+use cafe_resolver::{AsyncResolver, Config, RecordVariant, ResolveError};
+
+/// Answers A queries for jabber.ru and SRV queries, NXDOMAIN for missing.jabber.ru
+/// and nothing at all for silent.jabber.ru. Responses to A queries are delayed by 100 ms.
+fn spawn_server() -> (AsyncResolver, Arc<AtomicUsize>) {
+    let queries = Arc::new(AtomicUsize::new(0));
+    let counter = queries.clone();
+
+    let server = common::spawn_udp_server(move |query| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        let decoded = DnsResponse::decode(query).unwrap();
+        let question = &decoded.questions()[0];
+        match (question.host_name(), question.qtype()) {
+            ("silent.jabber.ru", _) => None,
+            ("missing.jabber.ru", _) => Some(common::reply(query, 3, &[])),
+            ("jabber.ru", QType::A) => {
+                thread::sleep(Duration::from_millis(100));
+                Some(common::reply(query, 0, &[common::a_record([192, 0, 2, 1], 3600)]))
+            }
+            _ => Some(common::reply(query, 0, &[])),
+        }
+    });
+
+    let mut config = Config::new();
+    config.set_server(server);
+    (AsyncResolver::with_config(config).unwrap(), queries)
+}
+
+#[tokio::test]
+async fn concurrent_lookups() {
+    let (resolver, _) = spawn_server();
+
+    let (addresses, services, missing) = tokio::join!(
+        resolver.get_a_records("jabber.ru"),
+        resolver.get_srv_records("_xmpp-client._tcp.jabber.ru"),
+        resolver.get_a_records("missing.jabber.ru"),
+    );
+
+    match addresses.unwrap().as_slice() {
+        [RecordVariant::A { ip, ttl: 3600, .. }] => assert_eq!(ip.to_string(), "192.0.2.1"),
+        other => panic!("Unexpected records: {:?}", other),
+    }
+
+    assert!(services.unwrap().is_empty());
+    assert!(matches!(missing, Err(ResolveError::DnsError(_, _))));
+    assert_eq!(resolver.pending(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn cache_shared_across_tasks() {
+    let (resolver, queries) = spawn_server();
+
+    let clone = resolver.clone();
+    let first = tokio::spawn(async move { clone.resolve_host("jabber.ru").await.unwrap().into_iter().count() });
+    assert_eq!(first.await.unwrap(), 1);
+
+    let clone = resolver.clone();
+    let second = tokio::spawn(async move { clone.resolve_host("jabber.ru").await.unwrap().into_iter().count() });
+    assert_eq!(second.await.unwrap(), 1);
+
+    assert_eq!(queries.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn cancelled_on_drop() {
+    let (resolver, queries) = spawn_server();
+
+    let lookup = tokio::time::timeout(Duration::from_millis(200), resolver.get_a_records("silent.jabber.ru"));
+    assert!(lookup.await.is_err());
+    assert_eq!(resolver.pending(), 0);
+    assert_eq!(queries.load(Ordering::SeqCst), 1);
+
+    // The resolver keeps working after that.
+    assert_eq!(resolver.get_a_records("jabber.ru").await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn rejects_dnssec_validation() {
+    let mut config = Config::new();
+    config.set_dnssec_validation(true);
+    assert!(matches!(AsyncResolver::with_config(config), Err(ResolveError::TransportFailed)));
+}
@@ -0,0 +1,140 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use cafe_dns::{QType, Response as DnsResponse};
+use cafe_resolver::{Action, Config, Engine, QueryId, RecordVariant, Resolver};
+
+fn sent(engine: &mut Engine, query: QueryId) -> Vec<u8> {
+    match engine.poll_action() {
+        Some(Action::Send(id, data)) if id == query => data,
+        other => panic!("Unexpected action: {:?}", other),
+    }
+}
+
+fn question_name(data: &[u8]) -> String {
+    DnsResponse::decode(data).unwrap().questions()[0].host_name().to_string()
+}
+
+#[test]
+fn exact_case_required() {
+    let mut config = Config::new();
+    config.set_case_randomization(true);
+    let mut engine = Engine::new(config);
+    let server = engine.config().server();
+    let now = Instant::now();
+
+    // The chance of 29 letters coming out all in lowercase is negligible.
+    let host = "xmpp-client.abcdefghijklmnopqrstuvwxyz.ru";
+    let query = engine.query(QType::A, host, false, now);
+    let data = sent(&mut engine, query);
+    let sent_name = question_name(&data);
+    assert_ne!(sent_name, host);
+    assert!(sent_name.eq_ignore_ascii_case(host));
+
+    let answer = common::reply(&data, 0, &[common::a_record([192, 0, 2, 1], 60)]);
+    engine.handle_datagram(server, &answer, now);
+
+    // The question and the answer compressed to it come with the name as it was asked.
+    match engine.poll_action() {
+        Some(Action::Finished(id, Ok(response))) if id == query => {
+            assert_eq!(response.questions()[0].host_name(), host);
+            assert_eq!(response.answers()[0].name(), host);
+        }
+        other => panic!("Unexpected action: {:?}", other),
+    }
+}
+
+/// Response to the query with the question name lowercased, as servers ignoring the case send.
+fn lowercased_reply(data: &[u8]) -> Vec<u8> {
+    let mut response = common::reply(data, 0, &[]);
+    let end = 12 + question_name(data).len() + 2;
+    response[12 .. end].make_ascii_lowercase();
+    response
+}
+
+#[test]
+fn spoofed_case_dropped() {
+    let mut config = Config::new();
+    config.set_case_randomization(true);
+    let mut engine = Engine::new(config);
+    let server = engine.config().server();
+    let now = Instant::now();
+
+    let host = "abcdefghijklmnopqrstuvwxyz.ru";
+    let query = engine.query(QType::A, host, false, now);
+    let data = sent(&mut engine, query);
+
+    // A single response in another case is taken for a spoofed one, the query goes again as it was.
+    engine.handle_datagram(server, &lowercased_reply(&data), now);
+    assert_eq!(sent(&mut engine, query), data);
+    assert_eq!(engine.pending(), 1);
+
+    engine.handle_datagram(server, &common::reply(&data, 0, &[]), now);
+    assert!(matches!(engine.poll_action(), Some(Action::Finished(id, Ok(_))) if id == query));
+
+    // The following queries are still randomized.
+    let next = engine.query(QType::A, host, false, now);
+    assert_ne!(question_name(&sent(&mut engine, next)), host);
+}
+
+#[test]
+fn fallback_without_case() {
+    let mut config = Config::new();
+    config.set_case_randomization(true);
+    let mut engine = Engine::new(config);
+    let server = engine.config().server();
+    let now = Instant::now();
+
+    let host = "abcdefghijklmnopqrstuvwxyz.ru";
+    let query = engine.query(QType::A, host, false, now);
+    let data = sent(&mut engine, query);
+
+    // The server lowercases the question every time, so the query ends up going with the name unchanged.
+    engine.handle_datagram(server, &lowercased_reply(&data), now);
+    assert_eq!(sent(&mut engine, query), data);
+    engine.handle_datagram(server, &lowercased_reply(&data), now);
+    assert_eq!(sent(&mut engine, query), data);
+    engine.handle_datagram(server, &lowercased_reply(&data), now);
+    let retry = sent(&mut engine, query);
+    assert_eq!(question_name(&retry), host);
+    assert_eq!(retry[.. 2], data[.. 2]);
+
+    engine.handle_datagram(server, &common::reply(&retry, 0, &[]), now);
+    assert!(matches!(engine.poll_action(), Some(Action::Finished(id, Ok(_))) if id == query));
+
+    // The following queries aren't randomized for a while.
+    let next = engine.query(QType::A, host, false, now + Duration::from_secs(60));
+    assert_eq!(question_name(&sent(&mut engine, next)), host);
+    engine.cancel(next);
+
+    let later = engine.query(QType::A, host, false, now + Duration::from_secs(601));
+    assert_ne!(question_name(&sent(&mut engine, later)), host);
+}
+
+#[test]
+fn resolver_with_case_ignoring_server() {
+    let queries = Arc::new(AtomicUsize::new(0));
+    let counter = queries.clone();
+    let server = common::spawn_udp_server(move |query| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        let mut response = common::reply(query, 0, &[common::a_record([192, 0, 2, 1], 60)]);
+        let end = 12 + question_name(query).len() + 2;
+        response[12 .. end].make_ascii_uppercase();
+        Some(response)
+    });
+
+    let mut config = Config::new();
+    config.set_server(server);
+    config.set_case_randomization(true);
+    let mut resolver = Resolver::with_config(config);
+
+    match resolver.get_a_records("jabber.ru").unwrap().as_slice() {
+        [RecordVariant::A { name, .. }] => assert_eq!(name, "jabber.ru"),
+        other => panic!("Unexpected records: {:?}", other),
+    }
+
+    assert_eq!(queries.load(Ordering::SeqCst), 4);
+}
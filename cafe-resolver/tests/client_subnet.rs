@@ -0,0 +1,93 @@
+mod common;
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use cafe_dns::{ClientSubnet, Edns, EdnsOption, Response as DnsResponse};
+use cafe_resolver::{Config, Resolver};
+
+/// Answers every A query with 192.0.2.1 and echoes the client subnet with the scope /16.
+fn spawn_server() -> (SocketAddr, Arc<AtomicUsize>) {
+    let queries = Arc::new(AtomicUsize::new(0));
+    let counter = queries.clone();
+
+    let addr = common::spawn_udp_server(move |query| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        let mut response = common::reply(query, 0, &[common::a_record([192, 0, 2, 1], 3600)]);
+
+        let decoded = DnsResponse::decode(query).unwrap();
+        if let Some(subnet) = decoded.edns().and_then(|edns| edns.client_subnet()) {
+            let mut subnet = *subnet;
+            subnet.set_scope_prefix(16);
+
+            let mut edns = Edns::new();
+            edns.add_option(EdnsOption::ClientSubnet(subnet));
+            common::append_edns(&mut response, &edns);
+        }
+
+        Some(response)
+    });
+
+    (addr, queries)
+}
+
+#[test]
+fn resolve_with_client_subnet() {
+    let (server, queries) = spawn_server();
+    let mut config = Config::new();
+    config.set_server(server);
+    config.set_client_subnet(Some("192.0.2.0/24".parse().unwrap()));
+
+    let mut resolver = Resolver::with_config(config);
+    let result = resolver.resolve_host("www.example.com").unwrap();
+    let records: Vec<_> = result.into_iter().collect();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].ip(), "192.0.2.1".parse::<IpAddr>().unwrap());
+    assert_eq!(records[0].scope_prefix(), Some(16));
+    assert_eq!(records[0].client_subnet().unwrap().source_prefix(), 24);
+    assert_eq!(queries.load(Ordering::SeqCst), 1);
+
+    // The same /16 is covered by the cached answer.
+    resolver.config_mut().set_client_subnet(Some("192.0.3.0/24".parse().unwrap()));
+    resolver.resolve_host("www.example.com").unwrap();
+    assert_eq!(queries.load(Ordering::SeqCst), 1);
+
+    // Another subnet must not reuse the cached answer.
+    resolver.config_mut().set_client_subnet(Some("198.51.100.0/24".parse().unwrap()));
+    resolver.resolve_host("www.example.com").unwrap();
+    assert_eq!(queries.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn resolve_with_opt_out() {
+    let (server, _) = spawn_server();
+    let mut config = Config::new();
+    config.set_server(server);
+    config.set_client_subnet(Some(ClientSubnet::opt_out()));
+
+    let mut resolver = Resolver::with_config(config);
+    let result = resolver.resolve_host("www.example.com").unwrap();
+    let records: Vec<_> = result.into_iter().collect();
+    assert_eq!(records.len(), 1);
+
+    let subnet = records[0].client_subnet().unwrap();
+    assert_eq!(subnet.source_prefix(), 0);
+    assert!(subnet.matches(&"203.0.113.1".parse().unwrap()));
+}
+
+#[test]
+fn resolve_without_client_subnet() {
+    let (server, _) = spawn_server();
+    let mut config = Config::new();
+    config.set_server(server);
+
+    let mut resolver = Resolver::with_config(config);
+    let records = resolver.get_a_records("www.example.com").unwrap();
+    assert_eq!(records.len(), 1);
+
+    let result = resolver.resolve_host("www.example.com").unwrap();
+    for record in &result {
+        assert!(record.client_subnet().is_none());
+    }
+}
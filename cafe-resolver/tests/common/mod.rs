@@ -0,0 +1,131 @@
+#![allow(dead_code)]
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, UdpSocket};
+use std::thread;
+use std::time::Duration;
+
+use cafe_common::stream::Output as OutputStream;
+use cafe_dns::{Edns, Response as DnsResponse};
+
+/// Serves UDP queries on a loopback port with `handler` until no query arrives for a few seconds.
+/// Nothing is sent back if the handler returns `None`.
+pub fn spawn_udp_server<F>(handler: F) -> SocketAddr
+where
+    F: Fn(&[u8]) -> Option<Vec<u8>> + Send + 'static,
+{
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+    serve_udp(socket, handler);
+    addr
+}
+
+/// Serves a single length-prefixed query per TCP connection on a loopback port, answering
+/// with the messages `handler` returns and closing the connection afterwards.
+pub fn spawn_tcp_server<F>(handler: F) -> SocketAddr
+where
+    F: Fn(&[u8]) -> Vec<Vec<u8>> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    serve_tcp(listener, handler);
+    addr
+}
+
+/// Serves both UDP and TCP on the same loopback port, as `spawn_udp_server` and `spawn_tcp_server` do.
+pub fn spawn_server<U, T>(udp_handler: U, tcp_handler: T) -> SocketAddr
+where
+    U: Fn(&[u8]) -> Option<Vec<u8>> + Send + 'static,
+    T: Fn(&[u8]) -> Vec<Vec<u8>> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    serve_udp(UdpSocket::bind(addr).unwrap(), udp_handler);
+    serve_tcp(listener, tcp_handler);
+    addr
+}
+
+fn serve_udp<F>(socket: UdpSocket, handler: F)
+where
+    F: Fn(&[u8]) -> Option<Vec<u8>> + Send + 'static,
+{
+    socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    thread::spawn(move || {
+        let mut buffer = [0; 65_535];
+        while let Ok((size, peer)) = socket.recv_from(&mut buffer) {
+            if let Some(response) = handler(&buffer[.. size]) {
+                let _ = socket.send_to(&response, peer);
+            }
+        }
+    });
+}
+
+fn serve_tcp<F>(listener: TcpListener, handler: F)
+where
+    F: Fn(&[u8]) -> Vec<Vec<u8>> + Send + 'static,
+{
+    thread::spawn(move || {
+        for connection in listener.incoming() {
+            let mut connection = match connection {
+                Ok(connection) => connection,
+                Err(_) => break,
+            };
+
+            let mut length = [0; 2];
+            if connection.read_exact(&mut length).is_err() {
+                continue;
+            }
+
+            let mut query = vec![0; u16::from_be_bytes(length) as usize];
+            if connection.read_exact(&mut query).is_err() {
+                continue;
+            }
+
+            for message in handler(&query) {
+                let _ = connection.write_all(&(message.len() as u16).to_be_bytes());
+                let _ = connection.write_all(&message);
+            }
+        }
+    });
+}
+
+/// Response to `query` made of its header and question section with `rcode`
+/// and `answers` given as encoded resource records.
+pub fn reply(query: &[u8], rcode: u8, answers: &[Vec<u8>]) -> Vec<u8> {
+    let decoded = DnsResponse::decode(query).unwrap();
+    let qname = decoded.questions()[0].host_name();
+    let name_length = if qname.is_empty() { 1 } else { qname.len() + 2 };
+    let question_end = 12 + name_length + 4;
+
+    let mut response = query[.. question_end].to_vec();
+    response[2] = 0x80 | (query[2] & 0x01);
+    response[3] = 0x80 | rcode;
+    response[6 .. 8].copy_from_slice(&(answers.len() as u16).to_be_bytes());
+    response[8 .. 12].copy_from_slice(&[0, 0, 0, 0]);
+    for answer in answers {
+        response.extend_from_slice(answer);
+    }
+
+    response
+}
+
+/// Sets the TC bit of the response.
+pub fn truncate(response: &mut [u8]) {
+    response[2] |= 0x02;
+}
+
+/// Appends OPT pseudo-record to the additional section of `response`.
+pub fn append_edns(response: &mut Vec<u8>, edns: &Edns) {
+    edns.encode(&mut OutputStream::new(response));
+    let arcount = u16::from_be_bytes([response[10], response[11]]) + 1;
+    response[10 .. 12].copy_from_slice(&arcount.to_be_bytes());
+}
+
+/// A record owned by the query name (compressed pointer to the question).
+pub fn a_record(ip: [u8; 4], ttl: u32) -> Vec<u8> {
+    let mut record = vec![0xc0, 0x0c, 0, 1, 0, 1];
+    record.extend_from_slice(&ttl.to_be_bytes());
+    record.extend_from_slice(&[0, 4]);
+    record.extend_from_slice(&ip);
+    record
+}
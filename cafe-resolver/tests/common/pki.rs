@@ -0,0 +1,55 @@
+//! Certificates for the TLS servers of the tests, kept apart from `common` for the crates without rustls.
+
+use std::sync::Arc;
+
+use rcgen::{BasicConstraints, CertificateParams, CertifiedIssuer, IsCa, KeyPair};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use rustls::ServerConfig;
+
+/// Certificate of the server for "dns.example", issued by a CA of its own.
+pub struct Pki {
+    pub ca: Vec<u8>,
+    pub certificate: Vec<u8>,
+    pub key: KeyPair,
+}
+
+pub fn pki() -> Pki {
+    let mut params = CertificateParams::new(Vec::new()).unwrap();
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca = CertifiedIssuer::self_signed(params, KeyPair::generate().unwrap()).unwrap();
+
+    let key = KeyPair::generate().unwrap();
+    let params = CertificateParams::new(vec!["dns.example".to_string()]).unwrap();
+    let certificate = params.signed_by(&key, &ca).unwrap();
+
+    Pki {
+        ca: ca.der().to_vec(),
+        certificate: certificate.der().to_vec(),
+        key,
+    }
+}
+
+/// TLS config of a server with the certificate of `pki`, speaking the ALPN protocol `alpn`.
+pub fn tls_server_config(pki: &Pki, alpn: &[u8]) -> Arc<ServerConfig> {
+    tls_server_config_with_chain(pki, &[], alpn)
+}
+
+/// Same as `tls_server_config`, with the DER-encoded `intermediates` sent after the certificate.
+pub fn tls_server_config_with_chain(pki: &Pki, intermediates: &[Vec<u8>], alpn: &[u8]) -> Arc<ServerConfig> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(pki.key.serialize_der()));
+    let chain = Some(&pki.certificate)
+        .into_iter()
+        .chain(intermediates)
+        .map(|certificate| CertificateDer::from(certificate.clone()))
+        .collect();
+    let mut config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(chain, key)
+        .unwrap();
+
+    config.alpn_protocols = vec![alpn.to_vec()];
+    Arc::new(config)
+}
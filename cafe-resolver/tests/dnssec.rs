@@ -0,0 +1,467 @@
+mod common;
+
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use data_encoding::{BASE32HEX_NOPAD, BASE64};
+use ring::rand::SystemRandom;
+use ring::rsa::PublicKeyComponents;
+use ring::signature::{
+    EcdsaKeyPair, EcdsaSigningAlgorithm, Ed25519KeyPair, KeyPair, RsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING,
+    ECDSA_P384_SHA384_FIXED_SIGNING, RSA_PKCS1_SHA256,
+};
+
+use cafe_common::stream::Output as OutputStream;
+use cafe_dns::dnssec::{self, DNSKEY_PROTOCOL, SECURE_ENTRY_POINT_FLAG, ZONE_KEY_FLAG};
+use cafe_dns::{QClass, QType, ResourceRecord, Response as DnsResponse, ResponseCode, Type};
+use cafe_resolver::{Config, ResolveError, Resolver, Security};
+
+/// 2048-bit RSA key of the ru zone, RSAPrivateKey in DER.
+const RU_KEY: &[&str] = &[
+    "MIIEowIBAAKCAQEAtMQL9QgUP7bYjDRZJpLevX1fz9dQ3FXegry87cpK79+0eiHNkRSb/cK7+7vzYv+kItJCSDSoKwJMGxNv",
+    "a+LgWXMVIhsX33loe7ivLYRHCVRdJt3OhoUm/g5UTZboqXsaUDSDYXokFqSYZSjqSGI8GMyEEFTgiBKXmUJXVbholEcDfZHz",
+    "Ajo2ZbTg8LMV7LG+FoH8Q4KlbL4U+tzXooVIseCLzQ4AqsfPuOJtwudIgbjZLLAZbx2O1aEmqjmHSo6wb1TNlqsWJcSNyA7i",
+    "deKzRRXOBWw0ayZkxhVjrZ5sOkbfHSI8kVLwM/avQCvhQt0Ye0zKltGmDmqRa34lSGBLOwIDAQABAoIBAAwWghAwKp56HS/+",
+    "/ikwsBluzV3qMZuSWG9YrK+gE/PHe1uhcAwwGyBUidqTHnrzymeTtN/8OXSq0ikLWyuVP9hrdDHUKvwEM+L2HizGiDONAy+q",
+    "rHldZRafIa8QlN1kgIV1hYWas9gZyKEXI9eUTh2Aa6Jl8p/W0nFquMlgLAoiOsaJVIcloZSL5Sr+Jbur9uYP10HbYYkXeUQz",
+    "fmsrdTL6Ta8lEcCFCR+yDr3DSzwpsLIdFS40ZILrdc8GLwwUmDorLJVU5xYOmwp4+EN2aIQq341MIMkQoRqZNCPQJ5P7Y6k5",
+    "Me5n5A0mBw/aDDiWObWlYMnYzEG+epf6TUUqVdECgYEA3Yxr3/PQH58NSAhLzS96YFeDw9ofHNkfphCPch/vNvIQV6IYd45F",
+    "0lLkTX7ItBNu63TqR4vagETP88T4R6puDOt1PMExPK02SrpStojqH0jm97CGOcHmPYhQkqLJ/K/X2umNLJKG6Q2PGErm9mS0",
+    "MMTwUpMeE5z3yOR2PPVo/NECgYEA0OAdZWvtGT+LT/SBhzHFKZSocyKzGY/otxNhqMOyLJgmYmfXT/Kqm8ZdjJsJo3IeiP5+",
+    "+b0Vgqy9pVvk+q5uQM8d0g2N62gK4f51iPu9YGamMZgBdUpVJK/c5gMSn7l1ffWOCzMPD4e3zyjXrcI9rul/Z3/Tk7JVLUPj",
+    "0oIhGksCgYEAr9SbZPFO81m7I7kZ1+3fdQjf1ZeOa8nSeWzZO90mcHgrCjHmszQiK+uHsA14YpqtxIdUzJWtQ3HZjQF9fvs/",
+    "Ple9Awvc9OgPl71Kmf4NueiVDm12Ce7euWUvdUFnh3lTmjUTyxU7p0kQkVoY9eI0tCQJC7AhBbXlXfYb9QNcYJECgYBILqr5",
+    "+aoeJ4+FOgHpLpHsJKtx3nP2SX0qh1AgekCAmLPrHeeSgVHkM1Dw3kMecLmMriZDN8zPM9L16iPfXZhMXUb46CY7S+E26DDF",
+    "/fkb711PljAq0F1MKFo2QqmU+QpZVRqZVDPIMiabOIoQSif24kpdAhej2uUEl4mLndzCsQKBgBA/Ge6ycMt1dgSNy8IL2nm0",
+    "975wuRNmt7A0CvPkvb883Bbitzlkv+yXjLppZOdbFpFjVshqk1k9ZFDKY6MoY/KLHqjmSAI7zINVrrsXlNvcqwIbHy2PReo9",
+    "jMG5uiu0VhVywXmnixvAJ8MiqsTJAqblZuxtaud2Td0/xvtEx/hX",
+];
+
+const TTL: u32 = 3600;
+const NSEC3_SALT: &[u8] = &[0xaa, 0xbb, 0xcc, 0xdd];
+const NSEC3_ITERATIONS: u16 = 1;
+
+enum Signer {
+    Ed25519(Ed25519KeyPair),
+    Ecdsa(EcdsaKeyPair),
+    Rsa(RsaKeyPair),
+}
+
+/// The only key of a zone, used as both the KSK and the ZSK.
+struct ZoneKey {
+    zone: &'static str,
+    signer: Signer,
+    dnskey: Type,
+}
+
+impl ZoneKey {
+    fn ed25519(zone: &'static str) -> Self {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let public_key = pair.public_key().as_ref().to_vec();
+        Self::new(zone, 15, public_key, Signer::Ed25519(pair))
+    }
+
+    fn ecdsa(zone: &'static str, algorithm: u8, signing: &'static EcdsaSigningAlgorithm) -> Self {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(signing, &rng).unwrap();
+        let pair = EcdsaKeyPair::from_pkcs8(signing, pkcs8.as_ref(), &rng).unwrap();
+        // The uncompressed point without the leading 0x04 (RFC 6605, section 4).
+        let public_key = pair.public_key().as_ref()[1 ..].to_vec();
+        Self::new(zone, algorithm, public_key, Signer::Ecdsa(pair))
+    }
+
+    fn rsa(zone: &'static str) -> Self {
+        let pair = RsaKeyPair::from_der(&BASE64.decode(RU_KEY.concat().as_bytes()).unwrap()).unwrap();
+        let components = PublicKeyComponents::<Vec<u8>>::from(pair.public());
+        // RFC 3110, section 2: exponent length, exponent and modulus.
+        let mut public_key = vec![components.e.len() as u8];
+        public_key.extend_from_slice(&components.e);
+        public_key.extend_from_slice(&components.n);
+        Self::new(zone, 8, public_key, Signer::Rsa(pair))
+    }
+
+    fn new(zone: &'static str, algorithm: u8, public_key: Vec<u8>, signer: Signer) -> Self {
+        let dnskey = Type::DNSKEY {
+            flags: ZONE_KEY_FLAG | SECURE_ENTRY_POINT_FLAG,
+            protocol: DNSKEY_PROTOCOL,
+            algorithm,
+            public_key,
+        };
+
+        Self { zone, signer, dnskey }
+    }
+
+    fn record(&self) -> ResourceRecord {
+        record(self.zone, self.dnskey.clone())
+    }
+
+    fn ds(&self) -> ResourceRecord {
+        let ds = Type::DS {
+            key_tag: self.dnskey.key_tag().unwrap(),
+            algorithm: self.algorithm(),
+            digest_type: 2,
+            digest: dnssec::ds_digest(self.zone, &self.dnskey, 2).unwrap(),
+        };
+
+        record(self.zone, ds)
+    }
+
+    fn algorithm(&self) -> u8 {
+        match self.dnskey {
+            Type::DNSKEY { algorithm, .. } => algorithm,
+            _ => unreachable!(),
+        }
+    }
+
+    /// The records of `rrset` followed by their signature valid for a day.
+    fn sign(&self, rrset: Vec<ResourceRecord>) -> Vec<ResourceRecord> {
+        let owner = rrset[0].name().to_string();
+        let wildcard = owner.starts_with("*.") as usize;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32;
+        let mut rrsig = Type::RRSIG {
+            type_covered: rrset[0].ttype().code(),
+            algorithm: self.algorithm(),
+            labels: (dnssec::label_count(&owner) - wildcard) as u8,
+            original_ttl: TTL,
+            expiration: now + 86_400,
+            inception: now - 3600,
+            key_tag: self.dnskey.key_tag().unwrap(),
+            signer_name: self.zone.to_string(),
+            signature: Vec::new(),
+        };
+
+        let data = dnssec::signed_data(&rrsig, &rrset).unwrap();
+        let rng = SystemRandom::new();
+        let signed = match &self.signer {
+            Signer::Ed25519(pair) => pair.sign(&data).as_ref().to_vec(),
+            Signer::Ecdsa(pair) => pair.sign(&rng, &data).unwrap().as_ref().to_vec(),
+            Signer::Rsa(pair) => {
+                let mut signature = vec![0; pair.public().modulus_len()];
+                pair.sign(&RSA_PKCS1_SHA256, &rng, &data, &mut signature).unwrap();
+                signature
+            }
+        };
+
+        if let Type::RRSIG { signature, .. } = &mut rrsig {
+            *signature = signed;
+        }
+
+        let mut result = rrset;
+        result.push(record(&owner, rrsig));
+        result
+    }
+
+    fn soa(&self) -> Vec<ResourceRecord> {
+        let soa = Type::SOA {
+            mname: format!("ns.{}", self.zone),
+            rname: format!("hostmaster.{}", self.zone),
+            serial: 1,
+            refresh: 3600,
+            retry: 600,
+            expire: 86_400,
+            minimum: TTL,
+        };
+
+        self.sign(vec![record(self.zone, soa)])
+    }
+
+    fn nsec(&self, owner: &str, next: &str, types: &[u16]) -> Vec<ResourceRecord> {
+        let nsec = Type::NSEC {
+            next_domain_name: next.to_string(),
+            types: types.to_vec(),
+        };
+
+        self.sign(vec![record(owner, nsec)])
+    }
+}
+
+fn record(owner: &str, rdata: Type) -> ResourceRecord {
+    ResourceRecord::new(owner, QClass::IN as u16, TTL, rdata)
+}
+
+fn a(owner: &str, ip: [u8; 4]) -> ResourceRecord {
+    record(owner, Type::A { ip: Ipv4Addr::from(ip) })
+}
+
+/// Answers of the server keyed by the query name and type: response code, answer
+/// and authority sections.
+type Answers = BTreeMap<(String, u16), (u8, Vec<ResourceRecord>, Vec<ResourceRecord>)>;
+
+fn add(answers: &mut Answers, qname: &str, qtype: QType, rcode: u8, answer: Vec<ResourceRecord>, authority: Vec<ResourceRecord>) {
+    answers.insert((qname.to_string(), qtype as u16), (rcode, answer, authority));
+}
+
+/// NSEC3 chain of the nsec3.ru zone made of its apex and host.nsec3.ru.
+fn nsec3_chain(key: &ZoneKey) -> Vec<Vec<ResourceRecord>> {
+    let mut hashes: Vec<(Vec<u8>, Vec<u16>)> = vec![
+        (dnssec::nsec3_hash("nsec3.ru", NSEC3_SALT, NSEC3_ITERATIONS), vec![2, 6, 46, 48, 51]),
+        (dnssec::nsec3_hash("host.nsec3.ru", NSEC3_SALT, NSEC3_ITERATIONS), vec![1, 46]),
+    ];
+    hashes.sort();
+
+    (0 .. hashes.len())
+        .map(|i| {
+            let (hash, types) = &hashes[i];
+            let nsec3 = Type::NSEC3 {
+                hash_algorithm: 1,
+                flags: 0,
+                iterations: NSEC3_ITERATIONS,
+                salt: NSEC3_SALT.to_vec(),
+                next_hashed_owner: hashes[(i + 1) % hashes.len()].0.clone(),
+                types: types.clone(),
+            };
+
+            let owner = format!("{}.nsec3.ru", BASE32HEX_NOPAD.encode(hash).to_ascii_lowercase());
+            key.sign(vec![record(&owner, nsec3)])
+        })
+        .collect()
+}
+
+/// Answers of a recursive server for the signed tree made of the root (Ed25519),
+/// ru (RSA/SHA-256), jabber.ru (ECDSA P-256 with NSEC), nsec3.ru (ECDSA P-384 with NSEC3)
+/// and the unsigned delegation insecure.ru.
+fn signed_tree(root: &ZoneKey, tampered: bool) -> Answers {
+    let ru = ZoneKey::rsa("ru");
+    let jabber = ZoneKey::ecdsa("jabber.ru", 13, &ECDSA_P256_SHA256_FIXED_SIGNING);
+    let nsec3 = ZoneKey::ecdsa("nsec3.ru", 14, &ECDSA_P384_SHA384_FIXED_SIGNING);
+
+    let mut answers = Answers::new();
+    add(&mut answers, "", QType::DNSKEY, 0, root.sign(vec![root.record()]), vec![]);
+    add(&mut answers, "ru", QType::DS, 0, root.sign(vec![ru.ds()]), vec![]);
+
+    add(&mut answers, "ru", QType::DNSKEY, 0, ru.sign(vec![ru.record()]), vec![]);
+    add(&mut answers, "jabber.ru", QType::DS, 0, ru.sign(vec![jabber.ds()]), vec![]);
+    add(&mut answers, "nsec3.ru", QType::DS, 0, ru.sign(vec![nsec3.ds()]), vec![]);
+    let insecure_nsec = ru.nsec("insecure.ru", "jabber.ru", &[2, 46, 47]);
+    add(&mut answers, "insecure.ru", QType::DS, 0, vec![], [ru.soa(), insecure_nsec].concat());
+    add(&mut answers, "host.insecure.ru", QType::A, 0, vec![a("host.insecure.ru", [192, 0, 2, 3])], vec![]);
+
+    add(&mut answers, "jabber.ru", QType::DNSKEY, 0, jabber.sign(vec![jabber.record()]), vec![]);
+    let mut www = jabber.sign(vec![a("www.jabber.ru", [192, 0, 2, 1])]);
+    if tampered {
+        www[0] = a("www.jabber.ru", [203, 0, 113, 1]);
+    }
+    add(&mut answers, "www.jabber.ru", QType::A, 0, www.clone(), vec![]);
+
+    // Names in RDATA come in another case than they were signed in, which canonical form undoes.
+    let mut alias = jabber.sign(vec![record("alias.jabber.ru", Type::CNAME { cname: "www.jabber.ru".to_string() })]);
+    alias[0] = record("alias.jabber.ru", Type::CNAME { cname: "WWW.Jabber.ru".to_string() });
+    add(&mut answers, "alias.jabber.ru", QType::A, 0, [alias, www].concat(), vec![]);
+
+    let mut ns = jabber.sign(vec![record("jabber.ru", Type::NS { nsdname: "ns.jabber.ru".to_string() })]);
+    ns[0] = record("jabber.ru", Type::NS { nsdname: "NS.JABBER.RU".to_string() });
+    let mut mx = jabber.sign(vec![record("jabber.ru", Type::MX { preference: 10, exchange: "xmpp.jabber.ru".to_string() })]);
+    mx[0] = record("jabber.ru", Type::MX { preference: 10, exchange: "Xmpp.Jabber.ru".to_string() });
+    add(&mut answers, "jabber.ru", QType::ANY, 0, [jabber.soa(), ns, mx].concat(), vec![]);
+
+    let www_nsec = jabber.nsec("www.jabber.ru", "jabber.ru", &[1, 46, 47]);
+    add(&mut answers, "www.jabber.ru", QType::SRV, 0, vec![], [jabber.soa(), www_nsec].concat());
+    let apex_nsec = jabber.nsec("jabber.ru", "*.wild.jabber.ru", &[1, 2, 6, 46, 47, 48]);
+    add(&mut answers, "nope.jabber.ru", QType::A, 3, vec![], [jabber.soa(), apex_nsec].concat());
+
+    let mut expanded = jabber.sign(vec![a("*.wild.jabber.ru", [192, 0, 2, 2])]);
+    for record in expanded.iter_mut() {
+        *record = ResourceRecord::new("x.wild.jabber.ru", record.class(), record.ttl(), record.ttype().clone());
+    }
+    let proof = match tampered {
+        true => vec![],
+        false => jabber.nsec("*.wild.jabber.ru", "www.jabber.ru", &[1, 46, 47]),
+    };
+    add(&mut answers, "x.wild.jabber.ru", QType::A, 0, expanded, proof);
+
+    add(&mut answers, "nsec3.ru", QType::DNSKEY, 0, nsec3.sign(vec![nsec3.record()]), vec![]);
+    add(&mut answers, "host.nsec3.ru", QType::A, 0, nsec3.sign(vec![a("host.nsec3.ru", [192, 0, 2, 4])]), vec![]);
+    let chain = nsec3_chain(&nsec3);
+    add(&mut answers, "missing.nsec3.ru", QType::A, 3, vec![], [vec![nsec3.soa()], chain.clone()].concat().concat());
+    add(&mut answers, "host.nsec3.ru", QType::SRV, 0, vec![], [vec![nsec3.soa()], chain].concat().concat());
+
+    answers
+}
+
+fn encode(record: &ResourceRecord) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    record.encode(&mut OutputStream::new(&mut buffer));
+    buffer
+}
+
+/// Serves `answers`, anything else fails with SERVFAIL.
+fn spawn_server(answers: Answers) -> SocketAddr {
+    common::spawn_udp_server(move |query| {
+        let decoded = DnsResponse::decode(query)?;
+        let question = &decoded.questions()[0];
+        let key = (question.host_name().to_ascii_lowercase(), question.qtype() as u16);
+        let (rcode, answer, authority) = match answers.get(&key) {
+            Some(found) => found,
+            None => return Some(common::reply(query, 2, &[])),
+        };
+
+        let mut response = common::reply(query, *rcode, &answer.iter().map(encode).collect::<Vec<_>>());
+        for record in authority {
+            response.extend_from_slice(&encode(record));
+        }
+        response[8 .. 10].copy_from_slice(&(authority.len() as u16).to_be_bytes());
+
+        Some(response)
+    })
+}
+
+fn resolver(tampered: bool) -> Resolver {
+    let root = ZoneKey::ed25519("");
+    let mut config = Config::new();
+    config.set_server(spawn_server(signed_tree(&root, tampered)));
+    config.set_dnssec_validation(true);
+    config.set_trust_anchors(vec![root.record()]);
+    Resolver::with_config(config)
+}
+
+#[test]
+fn secure_answers() {
+    let mut resolver = resolver(false);
+
+    let answer = resolver.lookup("www.jabber.ru", QType::A).unwrap();
+    assert_eq!(answer.security(), Security::Secure);
+    assert_eq!(answer.response().answers().len(), 2);
+
+    let answer = resolver.lookup("host.nsec3.ru", QType::A).unwrap();
+    assert_eq!(answer.security(), Security::Secure);
+
+    let records: Vec<_> = resolver.resolve_host("www.jabber.ru").unwrap().into_iter().collect();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].ip(), IpAddr::from([192, 0, 2, 1]));
+    assert_eq!(records[0].security(), Security::Secure);
+}
+
+#[test]
+fn signed_names_in_rdata() {
+    let mut resolver = resolver(false);
+
+    let answer = resolver.lookup("alias.jabber.ru", QType::A).unwrap();
+    assert_eq!(answer.security(), Security::Secure);
+    assert_eq!(answer.response().answers().len(), 4);
+
+    let answer = resolver.lookup("jabber.ru", QType::ANY).unwrap();
+    assert_eq!(answer.security(), Security::Secure);
+    assert_eq!(answer.response().answers().len(), 6);
+}
+
+#[test]
+fn tampered_answer() {
+    let mut resolver = resolver(true);
+
+    let answer = resolver.lookup("www.jabber.ru", QType::A).unwrap();
+    assert_eq!(answer.security(), Security::Bogus);
+
+    match resolver.resolve_host("www.jabber.ru") {
+        Err(ResolveError::Bogus) => (),
+        other => panic!("unexpected result {:?}", other.map(|records| records.into_iter().count())),
+    }
+}
+
+#[test]
+fn insecure_delegation() {
+    let mut resolver = resolver(false);
+
+    let answer = resolver.lookup("host.insecure.ru", QType::A).unwrap();
+    assert_eq!(answer.security(), Security::Insecure);
+}
+
+#[test]
+fn wildcard_expansion() {
+    let answer = resolver(false).lookup("x.wild.jabber.ru", QType::A).unwrap();
+    assert_eq!(answer.security(), Security::Secure);
+
+    // Without the NSEC proving x.wild.jabber.ru itself doesn't exist.
+    let answer = resolver(true).lookup("x.wild.jabber.ru", QType::A).unwrap();
+    assert_eq!(answer.security(), Security::Bogus);
+}
+
+#[test]
+fn nsec_denial() {
+    let mut resolver = resolver(false);
+
+    let answer = resolver.lookup("nope.jabber.ru", QType::A).unwrap();
+    assert_eq!(answer.response().header().rcode(), ResponseCode::NameError);
+    assert_eq!(answer.security(), Security::Secure);
+
+    let answer = resolver.lookup("www.jabber.ru", QType::SRV).unwrap();
+    assert_eq!(answer.security(), Security::Secure);
+}
+
+#[test]
+fn nsec3_denial() {
+    let mut resolver = resolver(false);
+
+    let answer = resolver.lookup("missing.nsec3.ru", QType::A).unwrap();
+    assert_eq!(answer.response().header().rcode(), ResponseCode::NameError);
+    assert_eq!(answer.security(), Security::Secure);
+
+    let answer = resolver.lookup("host.nsec3.ru", QType::SRV).unwrap();
+    assert_eq!(answer.security(), Security::Secure);
+}
+
+#[test]
+fn unproven_denial() {
+    // The A record exists, so the NSEC of www.jabber.ru can't prove there is none.
+    let root = ZoneKey::ed25519("");
+    let mut answers = signed_tree(&root, false);
+    let nodata = answers[&("www.jabber.ru".to_string(), QType::SRV as u16)].clone();
+    answers.insert(("www.jabber.ru".to_string(), QType::A as u16), nodata);
+
+    let mut config = Config::new();
+    config.set_server(spawn_server(answers));
+    config.set_dnssec_validation(true);
+    config.set_trust_anchors(vec![root.record()]);
+
+    let answer = Resolver::with_config(config).lookup("www.jabber.ru", QType::A).unwrap();
+    assert_eq!(answer.security(), Security::Bogus);
+}
+
+#[test]
+fn replayed_delegation_nsec() {
+    // The NSEC of ru at the cut of insecure.ru says nothing about the names of the child zone.
+    let root = ZoneKey::ed25519("");
+    let mut answers = signed_tree(&root, false);
+    let (_, _, proof) = answers[&("insecure.ru".to_string(), QType::DS as u16)].clone();
+    add(&mut answers, "nope.insecure.ru", QType::A, 3, vec![], proof.clone());
+    add(&mut answers, "insecure.ru", QType::A, 0, vec![], proof);
+
+    let mut config = Config::new();
+    config.set_server(spawn_server(answers));
+    config.set_dnssec_validation(true);
+    config.set_trust_anchors(vec![root.record()]);
+    let mut resolver = Resolver::with_config(config);
+
+    let answer = resolver.lookup("nope.insecure.ru", QType::A).unwrap();
+    assert_eq!(answer.security(), Security::Bogus);
+
+    let answer = resolver.lookup("insecure.ru", QType::A).unwrap();
+    assert_eq!(answer.security(), Security::Bogus);
+
+    // Only the absence of DS at the cut is proven.
+    let answer = resolver.lookup("insecure.ru", QType::DS).unwrap();
+    assert_eq!(answer.security(), Security::Secure);
+}
+
+#[test]
+fn untrusted_root_key() {
+    let mut config = resolver(false).config().clone();
+    config.set_trust_anchors(vec![ZoneKey::ed25519("").ds()]);
+
+    let answer = Resolver::with_config(config).lookup("www.jabber.ru", QType::A).unwrap();
+    assert_eq!(answer.security(), Security::Bogus);
+}
+
+#[test]
+fn validation_disabled() {
+    let mut config = resolver(false).config().clone();
+    config.set_dnssec_validation(false);
+    let mut resolver = Resolver::with_config(config);
+
+    let answer = resolver.lookup("www.jabber.ru", QType::A).unwrap();
+    assert_eq!(answer.security(), Security::Indeterminate);
+
+    let records: Vec<_> = resolver.resolve_host("www.jabber.ru").unwrap().into_iter().collect();
+    assert_eq!(records[0].security(), Security::Indeterminate);
+}
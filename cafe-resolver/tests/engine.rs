@@ -0,0 +1,246 @@
+mod common;
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use cafe_dns::{Edns, EdnsOption, QType, Response as DnsResponse};
+use cafe_resolver::{Action, Config, Engine, QueryId, ResolveError};
+
+/// Takes the datagram the engine wants to send for `query`.
+fn sent(engine: &mut Engine, query: QueryId) -> Vec<u8> {
+    match engine.poll_action() {
+        Some(Action::Send(id, data)) if id == query => data,
+        other => panic!("Unexpected action: {:?}", other),
+    }
+}
+
+fn finished(engine: &mut Engine) -> (QueryId, Result<DnsResponse, ResolveError>) {
+    match engine.poll_action() {
+        Some(Action::Finished(id, result)) => (id, result),
+        other => panic!("Unexpected action: {:?}", other),
+    }
+}
+
+#[test]
+fn in_memory_exchange() {
+    let mut engine = Engine::new(Config::new());
+    let server = engine.config().server();
+    let now = Instant::now();
+
+    let first = engine.query(QType::A, "jabber.ru", false, now);
+    let second = engine.query(QType::SRV, "_xmpp-client._tcp.jabber.ru", false, now);
+    assert_ne!(first, second);
+
+    let first_query = sent(&mut engine, first);
+    let second_query = sent(&mut engine, second);
+    assert!(engine.poll_action().is_none());
+    assert_eq!(engine.pending(), 2);
+
+    let decoded = DnsResponse::decode(&second_query).unwrap();
+    assert_eq!(decoded.questions()[0].host_name(), "_xmpp-client._tcp.jabber.ru");
+
+    // Responses may come in any order, and strangers are ignored.
+    engine.handle_datagram(server, &common::reply(&second_query, 3, &[]), now);
+    engine.handle_datagram(server, &[0xff, 0xff, 0x81, 0x80], now);
+    engine.handle_datagram(server, &common::reply(&first_query, 0, &[common::a_record([192, 0, 2, 1], 60)]), now);
+
+    match finished(&mut engine) {
+        (id, Ok(response)) if id == second => assert_eq!(response.header().rcode() as u8, 3),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    match finished(&mut engine) {
+        (id, Ok(response)) if id == first => assert_eq!(response.answers().len(), 1),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    assert!(engine.poll_action().is_none());
+    assert_eq!(engine.pending(), 0);
+    assert!(engine.next_timeout().is_none());
+}
+
+#[test]
+fn retransmission_and_timeout() {
+    let mut config = Config::new();
+    config.set_attempt_timeout(Duration::from_millis(100));
+    config.set_timeout(Duration::from_secs(1));
+    let mut engine = Engine::new(config);
+    let server = engine.config().server();
+    let now = Instant::now();
+
+    let lost = engine.query(QType::A, "jabber.ru", false, now);
+    let cancelled = engine.query(QType::A, "jabber.ru", false, now + Duration::from_millis(10));
+    let lost_query = sent(&mut engine, lost);
+    engine.cancel(cancelled);
+    assert!(engine.poll_action().is_none());
+
+    // The first attempt waits 100 ms, jitter aside.
+    let retry = engine.next_timeout().unwrap();
+    assert!(retry >= now + Duration::from_millis(100) && retry <= now + Duration::from_millis(125));
+    engine.handle_timeout(retry - Duration::from_millis(1));
+    assert!(engine.poll_action().is_none());
+
+    engine.handle_timeout(retry);
+    assert_eq!(sent(&mut engine, lost), lost_query);
+
+    // The second one twice as long.
+    let next = engine.next_timeout().unwrap();
+    assert!(next >= retry + Duration::from_millis(200) && next <= retry + Duration::from_millis(250));
+
+    engine.handle_timeout(now + Duration::from_secs(1));
+    assert!(matches!(finished(&mut engine), (id, Err(ResolveError::Timeout)) if id == lost));
+
+    // Too late, the query is gone.
+    engine.handle_datagram(server, &common::reply(&lost_query, 0, &[]), now + Duration::from_secs(1));
+    assert!(engine.poll_action().is_none());
+    assert_eq!(engine.pending(), 0);
+}
+
+#[test]
+fn client_subnet_mismatch() {
+    let mut config = Config::new();
+    config.set_client_subnet(Some("192.0.2.0/24".parse().unwrap()));
+    let mut engine = Engine::new(config);
+    let server = engine.config().server();
+    let now = Instant::now();
+
+    let query = engine.query(QType::A, "jabber.ru", false, now);
+    let data = sent(&mut engine, query);
+
+    let answer = |subnet: &str| {
+        let mut response = common::reply(&data, 0, &[common::a_record([192, 0, 2, 1], 60)]);
+        let mut edns = Edns::new();
+        edns.add_option(EdnsOption::ClientSubnet(subnet.parse().unwrap()));
+        common::append_edns(&mut response, &edns);
+        response
+    };
+
+    // Dropped, the query still waits for its response.
+    engine.handle_datagram(server, &answer("198.51.100.0/24"), now);
+    assert!(engine.poll_action().is_none());
+    assert_eq!(engine.pending(), 1);
+
+    engine.handle_datagram(server, &answer("192.0.2.0/24"), now);
+    assert!(matches!(finished(&mut engine), (id, Ok(response)) if id == query && response.answers().len() == 1));
+}
+
+#[test]
+fn mismatched_responses_ignored() {
+    let mut engine = Engine::new(Config::new());
+    let server = engine.config().server();
+    let now = Instant::now();
+
+    let query = engine.query(QType::A, "Jabber.RU.", false, now);
+    let data = sent(&mut engine, query);
+    let answer = common::reply(&data, 0, &[common::a_record([192, 0, 2, 1], 60)]);
+
+    let mut other_id = answer.clone();
+    other_id[1] ^= 0xff;
+    let mut not_response = answer.clone();
+    not_response[2] &= 0x7f;
+    let mut other_type = answer.clone();
+    other_type[12 + 11] = 28;
+    let mut other_name = answer.clone();
+    other_name[13] = b'g';
+
+    let stranger: SocketAddr = "192.0.2.53:53".parse().unwrap();
+    engine.handle_datagram(stranger, &answer, now);
+    for datagram in [other_id, not_response, other_type, other_name, answer[.. 20].to_vec()].iter() {
+        engine.handle_datagram(server, datagram, now);
+    }
+
+    assert!(engine.poll_action().is_none());
+    assert_eq!(engine.pending(), 1);
+
+    // The question is matched regardless of case.
+    let mut answer = answer;
+    answer[13 .. 19].copy_from_slice(b"JaBbEr");
+    engine.handle_datagram(server, &answer, now);
+    assert!(matches!(finished(&mut engine), (id, Ok(_)) if id == query));
+}
+
+#[test]
+fn truncated_response_moved_to_stream() {
+    let mut engine = Engine::new(Config::new());
+    let server = engine.config().server();
+    let now = Instant::now();
+
+    let query = engine.query(QType::A, "jabber.ru", false, now);
+    let data = sent(&mut engine, query);
+    let mut truncated = common::reply(&data, 0, &[]);
+    common::truncate(&mut truncated);
+    engine.handle_datagram(server, &truncated, now);
+
+    match engine.poll_action() {
+        // The same query, with edns-tcp-keepalive added.
+        Some(Action::SendStream(id, message)) if id == query => {
+            assert_eq!(message[.. 2], data[.. 2]);
+            let edns = DnsResponse::decode(&message).unwrap().edns().cloned().unwrap();
+            assert!(edns.options().contains(&EdnsOption::TcpKeepalive(None)));
+        }
+        other => panic!("Unexpected action: {:?}", other),
+    }
+
+    // Not retransmitted over UDP anymore, and late datagrams are ignored.
+    engine.handle_timeout(now + Duration::from_secs(2));
+    engine.handle_datagram(server, &common::reply(&data, 0, &[]), now);
+    assert!(engine.poll_action().is_none());
+
+    engine.handle_message(&common::reply(&data, 0, &[common::a_record([192, 0, 2, 1], 60)]), now);
+    match finished(&mut engine) {
+        (id, Ok(response)) if id == query => assert_eq!(response.answers().len(), 1),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn stream_closed() {
+    let mut config = Config::new();
+    config.set_force_tcp(true);
+    let mut engine = Engine::new(config);
+    let now = Instant::now();
+
+    let query = engine.query(QType::A, "jabber.ru", false, now);
+    let data = match engine.poll_action() {
+        Some(Action::SendStream(id, data)) if id == query => data,
+        other => panic!("Unexpected action: {:?}", other),
+    };
+
+    // Sent again over two more connections, then given up on.
+    for _ in 0 .. 2 {
+        engine.handle_stream_closed(now);
+        assert!(matches!(engine.poll_action(), Some(Action::SendStream(id, ref again)) if id == query && *again == data));
+    }
+
+    engine.handle_stream_closed(now);
+    assert!(matches!(finished(&mut engine), (id, Err(ResolveError::TransportFailed)) if id == query));
+    assert_eq!(engine.pending_streams(), 0);
+}
+
+#[test]
+fn keepalive_shortens_idle_timeout() {
+    let mut config = Config::new();
+    config.set_force_tcp(true);
+    config.set_idle_timeout(Duration::from_secs(10));
+    let mut engine = Engine::new(config);
+    let now = Instant::now();
+
+    let query = engine.query(QType::A, "jabber.ru", false, now);
+    let data = match engine.poll_action() {
+        Some(Action::SendStream(_, data)) => data,
+        other => panic!("Unexpected action: {:?}", other),
+    };
+
+    let edns = DnsResponse::decode(&data).unwrap().edns().cloned().unwrap();
+    assert!(edns.options().contains(&EdnsOption::TcpKeepalive(None)));
+    assert_eq!(engine.idle_timeout(), Duration::from_secs(10));
+
+    let mut response = common::reply(&data, 0, &[]);
+    let mut edns = Edns::new();
+    edns.add_option(EdnsOption::TcpKeepalive(Some(25)));
+    common::append_edns(&mut response, &edns);
+    engine.handle_message(&response, now);
+
+    assert!(matches!(finished(&mut engine), (id, Ok(_)) if id == query));
+    assert_eq!(engine.idle_timeout(), Duration::from_millis(2500));
+}
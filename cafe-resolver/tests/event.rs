@@ -0,0 +1,116 @@
+mod common;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cafe_dns::{QType, Response as DnsResponse};
+use cafe_resolver::{Config, EventResolver, RecordVariant, ResolveError};
+
+/// Answers A queries for jabber.ru after `delay`, NXDOMAIN for the rest.
+fn resolver_for(delay: Duration) -> EventResolver {
+    let mut config = Config::new();
+    config.set_server(common::spawn_udp_server(move |query| {
+        thread::sleep(delay);
+        let decoded = DnsResponse::decode(query).unwrap();
+        let question = &decoded.questions()[0];
+        match (question.host_name(), question.qtype()) {
+            ("jabber.ru", QType::A) => Some(common::reply(query, 0, &[common::a_record([192, 0, 2, 1], 60)])),
+            _ => Some(common::reply(query, 3, &[])),
+        }
+    }));
+
+    EventResolver::with_config(config).unwrap()
+}
+
+/// Stands for the event loop of the host, without waiting on the socket.
+fn run(resolver: &mut EventResolver) -> usize {
+    let started = Instant::now();
+    let mut completed = 0;
+    while resolver.pending() > 0 {
+        assert!(started.elapsed() < Duration::from_secs(3), "lookups did not complete");
+        let timeout = resolver.next_timeout().unwrap();
+        thread::sleep(timeout.min(Duration::from_millis(5)));
+        completed += resolver.process_events();
+    }
+
+    completed
+}
+
+#[test]
+fn concurrent_lookups() {
+    let mut resolver = resolver_for(Duration::from_millis(0));
+    let results = Rc::new(RefCell::new(Vec::new()));
+
+    let sink = results.clone();
+    resolver
+        .get_a_records("jabber.ru", move |result| sink.borrow_mut().push(("jabber.ru", result)))
+        .unwrap();
+    let sink = results.clone();
+    resolver
+        .get_a_records("missing.jabber.ru", move |result| sink.borrow_mut().push(("missing.jabber.ru", result)))
+        .unwrap();
+
+    assert_eq!(resolver.pending(), 2);
+    assert!(resolver.next_timeout().unwrap() <= Duration::from_secs(5));
+    assert_eq!(run(&mut resolver), 2);
+    assert!(resolver.next_timeout().is_none());
+
+    let mut results = results.borrow_mut();
+    results.sort_by_key(|(host, _)| *host);
+    match &results[0] {
+        ("jabber.ru", Ok(records)) => match records.as_slice() {
+            [RecordVariant::A { ip, ttl: 60, .. }] => assert_eq!(ip.to_string(), "192.0.2.1"),
+            other => panic!("Unexpected records: {:?}", other),
+        },
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    assert!(matches!(results[1], ("missing.jabber.ru", Err(ResolveError::DnsError(_, _)))));
+}
+
+#[test]
+fn nothing_received_yet() {
+    let mut resolver = resolver_for(Duration::from_millis(200));
+    let done = Rc::new(RefCell::new(false));
+
+    let flag = done.clone();
+    resolver.get_a_records("jabber.ru", move |result| *flag.borrow_mut() = result.is_ok()).unwrap();
+
+    assert_eq!(resolver.process_events(), 0);
+    assert!(!*done.borrow());
+    assert_eq!(run(&mut resolver), 1);
+    assert!(*done.borrow());
+}
+
+#[test]
+fn rejects_dnssec_validation() {
+    let mut config = Config::new();
+    config.set_dnssec_validation(true);
+    assert!(matches!(EventResolver::with_config(config), Err(ResolveError::TransportFailed)));
+}
+
+#[test]
+fn lookups_started_while_connections_fail() {
+    // Every connection is closed a moment after the first query, so each lookup takes three of them and fails.
+    let mut config = Config::new();
+    config.set_server(common::spawn_tcp_server(|_| {
+        thread::sleep(Duration::from_millis(50));
+        Vec::new()
+    }));
+    config.set_force_tcp(true);
+    config.set_timeout(Duration::from_secs(10));
+    let mut resolver = EventResolver::with_config(config).unwrap();
+    let results = Rc::new(RefCell::new(Vec::new()));
+
+    // Each lookup finds the connection of the previous ones closed, the last one uses up the first three.
+    for host in ["a.jabber.ru", "b.jabber.ru", "c.jabber.ru", "d.jabber.ru", "e.jabber.ru"].iter() {
+        let sink = results.clone();
+        resolver.get_a_records(host, move |result| sink.borrow_mut().push(result)).unwrap();
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    assert_eq!(run(&mut resolver), 5);
+    assert!(results.borrow().iter().all(|result| matches!(result, Err(ResolveError::TransportFailed))));
+}
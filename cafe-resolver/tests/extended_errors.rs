@@ -0,0 +1,62 @@
+mod common;
+
+use cafe_dns::{Edns, EdnsOption, ExtendedError, ResponseCode};
+use cafe_resolver::{Config, ResolveError, Resolver};
+
+fn resolver_for(handler: fn(&[u8]) -> Option<Vec<u8>>) -> Resolver {
+    let mut config = Config::new();
+    config.set_server(common::spawn_udp_server(handler));
+    Resolver::with_config(config)
+}
+
+#[test]
+fn server_failure_with_extended_errors() {
+    let mut resolver = resolver_for(|query| {
+        let mut response = common::reply(query, 2, &[]);
+        let mut edns = Edns::new();
+        edns.add_option(EdnsOption::ExtendedError(ExtendedError::new(6, "signature expired for jabber.ru")));
+        edns.add_option(EdnsOption::ExtendedError(ExtendedError::new(22, "")));
+        common::append_edns(&mut response, &edns);
+        Some(response)
+    });
+
+    match resolver.get_a_records("jabber.ru") {
+        Err(ResolveError::DnsError(rcode, errors)) => {
+            assert_eq!(rcode, ResponseCode::ServerFailure);
+            assert_eq!(errors.len(), 2);
+            assert_eq!(errors[0].info_code(), 6);
+            assert_eq!(errors[0].extra_text(), "signature expired for jabber.ru");
+            assert_eq!(errors[1].info_code(), 22);
+
+            let message = ResolveError::DnsError(rcode, errors).to_string();
+            assert_eq!(message, "ServerFailure; DNSSEC Bogus (6): signature expired for jabber.ru; No Reachable Authority (22)");
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn server_failure_without_edns() {
+    let mut resolver = resolver_for(|query| Some(common::reply(query, 2, &[])));
+
+    match resolver.get_a_records("jabber.ru") {
+        Err(ResolveError::DnsError(rcode, errors)) => {
+            assert_eq!(rcode, ResponseCode::ServerFailure);
+            assert!(errors.is_empty());
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn query_carries_opt() {
+    let mut resolver = resolver_for(|query| {
+        let decoded = cafe_dns::Response::decode(query).unwrap();
+        match decoded.edns() {
+            Some(_) => Some(common::reply(query, 0, &[common::a_record([192, 0, 2, 1], 60)])),
+            None => Some(common::reply(query, 1, &[])),
+        }
+    });
+
+    assert_eq!(resolver.get_a_records("jabber.ru").unwrap().len(), 1);
+}
@@ -0,0 +1,192 @@
+#![cfg(feature = "https")]
+
+mod common;
+#[path = "common/pki.rs"]
+mod pki;
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use bytes::Bytes;
+use data_encoding::BASE64URL_NOPAD;
+use http::{Method, Request, Response, StatusCode};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+
+use cafe_resolver::{
+    AsyncResolver, Config, EventResolver, HttpsConfig, HttpsMethod, RecordVariant, ResolveError, Resolver, Transport,
+};
+
+use pki::Pki;
+
+#[derive(Default)]
+struct Counters {
+    connections: AtomicUsize,
+    /// Methods of the requests in the order they came.
+    methods: Mutex<Vec<Method>>,
+    /// Queries that came with an ID other than zero or without padding.
+    malformed: AtomicUsize,
+}
+
+/// Query of the request: the `dns` parameter of GET or the body of POST.
+async fn read_query(request: Request<h2::RecvStream>) -> Option<Vec<u8>> {
+    if request.method() == Method::GET {
+        let dns = request.uri().query()?.split('&').find_map(|pair| pair.strip_prefix("dns="))?;
+        return BASE64URL_NOPAD.decode(dns.as_bytes()).ok();
+    }
+
+    let mut body = request.into_body();
+    let mut query = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.ok()?;
+        let _ = body.flow_control().release_capacity(chunk.len());
+        query.extend_from_slice(&chunk);
+    }
+
+    Some(query)
+}
+
+/// Answers requests to /dns-query with an A record of TTL 300, fresh for 60 seconds and
+/// 10 seconds old, and the others with 404.
+async fn serve(listener: TcpListener, acceptor: TlsAcceptor, counters: Arc<Counters>) {
+    loop {
+        let (stream, _) = listener.accept().await.unwrap();
+        counters.connections.fetch_add(1, Ordering::SeqCst);
+        let (acceptor, counters) = (acceptor.clone(), counters.clone());
+
+        tokio::spawn(async move {
+            let stream = acceptor.accept(stream).await.ok()?;
+            let mut connection = h2::server::handshake(stream).await.ok()?;
+            while let Some(Ok((request, mut respond))) = connection.accept().await {
+                let counters = counters.clone();
+                tokio::spawn(async move {
+                    counters.methods.lock().unwrap().push(request.method().clone());
+                    if request.uri().path() != "/dns-query" {
+                        let response = Response::builder().status(StatusCode::NOT_FOUND).body(()).unwrap();
+                        let _ = respond.send_response(response, true);
+                        return;
+                    }
+
+                    let query = read_query(request).await.unwrap();
+                    if query[.. 2] != [0, 0] || !query.len().is_multiple_of(128) {
+                        counters.malformed.fetch_add(1, Ordering::SeqCst);
+                    }
+
+                    let answer = common::reply(&query, 0, &[common::a_record([192, 0, 2, 1], 300)]);
+                    let response = Response::builder()
+                        .header("content-type", "application/dns-message")
+                        .header("cache-control", "public, max-age=60")
+                        .header("age", "10")
+                        .body(())
+                        .unwrap();
+
+                    let mut stream = respond.send_response(response, false).unwrap();
+                    let _ = stream.send_data(Bytes::from(answer), true);
+                });
+            }
+
+            Some(())
+        });
+    }
+}
+
+fn spawn_https_server(pki: &Pki) -> (SocketAddr, Arc<Counters>) {
+    let acceptor = TlsAcceptor::from(pki::tls_server_config(pki, b"h2"));
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    listener.set_nonblocking(true).unwrap();
+
+    let counters = Arc::new(Counters::default());
+    let server_counters = counters.clone();
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async { serve(TcpListener::from_std(listener).unwrap(), acceptor, server_counters).await });
+    });
+
+    (addr, counters)
+}
+
+fn https_config(server: SocketAddr, pki: &Pki, template: &str, method: HttpsMethod) -> Config {
+    let mut https = HttpsConfig::new(template).unwrap();
+    https.set_method(method);
+    https.tls_mut().add_root_certificate(&pki.ca);
+
+    let mut config = Config::new();
+    config.set_server_with_transport(server, Transport::Https(https));
+    config.set_timeout(Duration::from_secs(2));
+    config
+}
+
+fn ttls(records: &[RecordVariant]) -> Vec<u32> {
+    records
+        .iter()
+        .map(|record| match record {
+            RecordVariant::A { ttl, .. } | RecordVariant::SRV { ttl, .. } => *ttl,
+        })
+        .collect()
+}
+
+#[test]
+fn post_requests() {
+    let pki = pki::pki();
+    let (server, counters) = spawn_https_server(&pki);
+    let config = https_config(server, &pki, "https://dns.example/dns-query{?dns}", HttpsMethod::Post);
+    let mut resolver = Resolver::with_config(config);
+
+    for host in ["jabber.ru", "jabber.org", "xmpp.org"].iter() {
+        // TTLs are limited by max-age less the age of the response.
+        assert_eq!(ttls(&resolver.get_a_records(host).unwrap()), vec![50]);
+    }
+
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 1);
+    assert_eq!(*counters.methods.lock().unwrap(), vec![Method::POST; 3]);
+    assert_eq!(counters.malformed.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn get_requests() {
+    let pki = pki::pki();
+    let (server, counters) = spawn_https_server(&pki);
+    let config = https_config(server, &pki, "https://dns.example/dns-query{?dns}", HttpsMethod::Get);
+    let mut resolver = Resolver::with_config(config);
+
+    assert_eq!(ttls(&resolver.get_a_records("jabber.ru").unwrap()), vec![50]);
+    assert_eq!(*counters.methods.lock().unwrap(), vec![Method::GET]);
+    assert_eq!(counters.malformed.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn error_status_fails_query() {
+    let pki = pki::pki();
+    let (server, counters) = spawn_https_server(&pki);
+    let config = https_config(server, &pki, "https://dns.example/resolve{?dns}", HttpsMethod::Get);
+    let mut resolver = Resolver::with_config(config);
+
+    assert!(matches!(resolver.get_a_records("jabber.ru"), Err(ResolveError::DecodeFailed)));
+    assert_eq!(counters.methods.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn event_resolver_rejects_https() {
+    let pki = pki::pki();
+    let config = https_config(SocketAddr::from(([127, 0, 0, 1], 443)), &pki, "https://dns.example/q{?dns}", HttpsMethod::Post);
+    assert!(EventResolver::with_config(config).is_err());
+}
+
+#[tokio::test]
+async fn async_resolver_over_https() {
+    let pki = pki::pki();
+    let (server, counters) = spawn_https_server(&pki);
+    let config = https_config(server, &pki, "https://dns.example/dns-query?ct{&dns}", HttpsMethod::Get);
+    let resolver = AsyncResolver::with_config(config).unwrap();
+
+    let (first, second) = tokio::join!(resolver.get_a_records("jabber.ru"), resolver.get_a_records("jabber.org"));
+    assert_eq!(ttls(&first.unwrap()), vec![50]);
+    assert_eq!(ttls(&second.unwrap()), vec![50]);
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 1);
+    assert_eq!(counters.malformed.load(Ordering::SeqCst), 0);
+    assert_eq!(resolver.pending(), 0);
+}
@@ -0,0 +1,150 @@
+#![cfg(feature = "quic")]
+
+mod common;
+#[path = "common/pki.rs"]
+mod pki;
+
+use std::convert::TryFrom;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use quinn::crypto::rustls::QuicServerConfig;
+use quinn::{Endpoint, EndpointConfig, ServerConfig, TokioRuntime};
+
+use cafe_resolver::{AsyncResolver, Config, EventResolver, Resolver, TlsConfig, Transport};
+
+use pki::Pki;
+
+#[derive(Default)]
+struct Counters {
+    connections: AtomicUsize,
+    queries: AtomicUsize,
+    /// Queries that came in 0-RTT data, before the handshake was over.
+    early: AtomicUsize,
+    /// Queries that came with an ID other than zero, without padding or with a wrong length.
+    malformed: AtomicUsize,
+}
+
+/// Answers the query of every stream with an A record and closes the stream.
+async fn serve(endpoint: Endpoint, counters: Arc<Counters>) {
+    while let Some(incoming) = endpoint.accept().await {
+        counters.connections.fetch_add(1, Ordering::SeqCst);
+        let counters = counters.clone();
+
+        tokio::spawn(async move {
+            let (connection, _) = incoming.accept().ok()?.into_0rtt().ok()?;
+            while let Ok((mut send, mut recv)) = connection.accept_bi().await {
+                let counters = counters.clone();
+                tokio::spawn(async move {
+                    if recv.is_0rtt() {
+                        counters.early.fetch_add(1, Ordering::SeqCst);
+                    }
+
+                    let framed = recv.read_to_end(2 + u16::MAX as usize).await.unwrap();
+                    let query = &framed[2 ..];
+                    counters.queries.fetch_add(1, Ordering::SeqCst);
+                    if framed[.. 2] != (query.len() as u16).to_be_bytes() || query[.. 2] != [0, 0] || !query.len().is_multiple_of(128) {
+                        counters.malformed.fetch_add(1, Ordering::SeqCst);
+                    }
+
+                    let response = common::reply(query, 0, &[common::a_record([192, 0, 2, 1], 60)]);
+                    let mut message = (response.len() as u16).to_be_bytes().to_vec();
+                    message.extend_from_slice(&response);
+                    let _ = send.write_all(&message).await;
+                    let _ = send.finish();
+                });
+            }
+
+            Some(())
+        });
+    }
+}
+
+fn spawn_quic_server(pki: &Pki) -> (SocketAddr, Arc<Counters>) {
+    let mut tls = (*pki::tls_server_config(pki, b"doq")).clone();
+    tls.max_early_data_size = u32::MAX;
+    let config = ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(tls).unwrap()));
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+    let counters = Arc::new(Counters::default());
+    let server_counters = counters.clone();
+    thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let endpoint = Endpoint::new(EndpointConfig::default(), Some(config), socket, Arc::new(TokioRuntime)).unwrap();
+            serve(endpoint, server_counters).await
+        });
+    });
+
+    (addr, counters)
+}
+
+fn quic_config(server: SocketAddr, pki: &Pki) -> Config {
+    let mut tls = TlsConfig::new("dns.example");
+    tls.add_root_certificate(&pki.ca);
+
+    let mut config = Config::new();
+    config.set_server_with_transport(server, Transport::Quic(tls));
+    config.set_timeout(Duration::from_secs(2));
+    config
+}
+
+#[test]
+fn stream_per_query() {
+    let pki = pki::pki();
+    let (server, counters) = spawn_quic_server(&pki);
+    let mut resolver = Resolver::with_config(quic_config(server, &pki));
+
+    for host in ["jabber.ru", "jabber.org", "xmpp.org"].iter() {
+        assert_eq!(resolver.get_a_records(host).unwrap().len(), 1);
+    }
+
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 1);
+    assert_eq!(counters.queries.load(Ordering::SeqCst), 3);
+    assert_eq!(counters.malformed.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn resumed_in_0rtt() {
+    let pki = pki::pki();
+    let (server, counters) = spawn_quic_server(&pki);
+    let mut config = quic_config(server, &pki);
+    config.set_idle_timeout(Duration::from_millis(100));
+    let mut resolver = Resolver::with_config(config);
+
+    assert_eq!(resolver.get_a_records("jabber.ru").unwrap().len(), 1);
+    assert_eq!(counters.early.load(Ordering::SeqCst), 0);
+
+    // The connection is idle for too long, the next one resumes the session.
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(resolver.get_a_records("jabber.org").unwrap().len(), 1);
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 2);
+    assert_eq!(counters.early.load(Ordering::SeqCst), 1);
+    assert_eq!(counters.malformed.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn event_resolver_rejects_quic() {
+    let pki = pki::pki();
+    let config = quic_config(SocketAddr::from(([127, 0, 0, 1], 853)), &pki);
+    assert!(EventResolver::with_config(config).is_err());
+}
+
+#[tokio::test]
+async fn async_resolver_over_quic() {
+    let pki = pki::pki();
+    let (server, counters) = spawn_quic_server(&pki);
+    let resolver = AsyncResolver::with_config(quic_config(server, &pki)).unwrap();
+
+    let (first, second) = tokio::join!(resolver.get_a_records("jabber.ru"), resolver.get_a_records("jabber.org"));
+    assert_eq!(first.unwrap().len(), 1);
+    assert_eq!(second.unwrap().len(), 1);
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 1);
+    assert_eq!(counters.queries.load(Ordering::SeqCst), 2);
+    assert_eq!(counters.malformed.load(Ordering::SeqCst), 0);
+    assert_eq!(resolver.pending(), 0);
+}
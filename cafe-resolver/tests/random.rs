@@ -0,0 +1,133 @@
+mod common;
+
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cafe_dns::QType;
+#[cfg(feature = "tokio")]
+use cafe_resolver::AsyncResolver;
+use cafe_resolver::{Action, Config, Engine, EventResolver, Random, Resolver};
+
+/// Generator going through the given bytes, over and over.
+struct Sequence(Vec<u8>, usize);
+
+impl Random for Sequence {
+    fn fill(&mut self, dest: &mut [u8]) {
+        for byte in dest.iter_mut() {
+            *byte = self.0[self.1 % self.0.len()];
+            self.1 += 1;
+        }
+    }
+}
+
+/// Answers every query, reporting its message ID and the address it came from.
+fn spawn_reporting_server() -> (SocketAddr, mpsc::Receiver<(u16, SocketAddr)>) {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let addr = socket.local_addr().unwrap();
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let mut buffer = [0; 512];
+        while let Ok((size, peer)) = socket.recv_from(&mut buffer) {
+            let query = &buffer[.. size];
+            let _ = sender.send((u16::from_be_bytes([query[0], query[1]]), peer));
+            let _ = socket.send_to(&common::reply(query, 0, &[common::a_record([192, 0, 2, 1], 60)]), peer);
+        }
+    });
+
+    (addr, receiver)
+}
+
+#[test]
+fn deterministic_ids() {
+    let mut config = Config::new();
+    config.set_random(Sequence(vec![0x12, 0x34], 0));
+    let mut engine = Engine::new(config);
+
+    let query = engine.query(QType::A, "jabber.ru", false, Instant::now());
+    match engine.poll_action() {
+        Some(Action::Send(id, data)) if id == query => assert_eq!(&data[.. 2], &[0x12, 0x34]),
+        other => panic!("Unexpected action: {:?}", other),
+    }
+
+    // An ID in use is not given out again, the generator is asked once more. The query
+    // takes two bytes for the ID and eight for the jitter.
+    let mut bytes = vec![0x12, 0x34];
+    bytes.extend_from_slice(&[0; 8]);
+    bytes.extend_from_slice(&[0x12, 0x34, 0x56, 0x78]);
+    let mut config = Config::new();
+    config.set_random(Sequence(bytes, 0));
+    let mut engine = Engine::new(config);
+    engine.query(QType::A, "jabber.ru", false, Instant::now());
+    assert_eq!(engine.next_id(), 0x5678);
+}
+
+#[test]
+fn fresh_port_and_id_per_query() {
+    let (server, queries) = spawn_reporting_server();
+    let mut config = Config::new();
+    config.set_server(server);
+    let mut resolver = Resolver::with_config(config);
+
+    let mut seen = Vec::new();
+    for _ in 0 .. 4 {
+        assert_eq!(resolver.get_a_records("jabber.ru").unwrap().len(), 1);
+        let (id, source) = queries.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(source.port() >= 1024);
+        seen.push((id, source.port()));
+    }
+
+    // Four draws out of 2^16 and 64512 values coinciding means no randomness at all.
+    assert!(seen.iter().any(|&(id, _)| id != seen[0].0));
+    assert!(seen.iter().any(|&(_, port)| port != seen[0].1));
+}
+
+#[test]
+fn event_resolver_rebinds_between_lookups() {
+    let (server, queries) = spawn_reporting_server();
+    let mut config = Config::new();
+    config.set_server(server);
+    let mut resolver = EventResolver::with_config(config).unwrap();
+
+    let mut ports = Vec::new();
+    for _ in 0 .. 2 {
+        resolver.get_a_records("jabber.ru", |result| assert_eq!(result.unwrap().len(), 1)).unwrap();
+        let started = Instant::now();
+        while resolver.pending() > 0 {
+            assert!(started.elapsed() < Duration::from_secs(3), "lookup did not complete");
+            thread::sleep(Duration::from_millis(5));
+            resolver.process_events();
+        }
+
+        ports.push(queries.recv_timeout(Duration::from_secs(1)).unwrap().1.port());
+    }
+
+    // A lookup started while another is outstanding goes from the same socket.
+    resolver.get_a_records("jabber.ru", |_| ()).unwrap();
+    resolver.get_a_records("jabber.org", |_| ()).unwrap();
+    let first = queries.recv_timeout(Duration::from_secs(1)).unwrap().1.port();
+    let second = queries.recv_timeout(Duration::from_secs(1)).unwrap().1.port();
+
+    assert_ne!(ports[0], ports[1]);
+    assert_eq!(first, second);
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn async_resolver_port_per_lookup() {
+    let (server, queries) = spawn_reporting_server();
+    let mut config = Config::new();
+    config.set_server(server);
+    let resolver = AsyncResolver::with_config(config).unwrap();
+
+    assert_eq!(resolver.get_a_records("jabber.ru").await.unwrap().len(), 1);
+    assert_eq!(resolver.get_a_records("jabber.ru").await.unwrap().len(), 1);
+    let first = queries.recv_timeout(Duration::from_secs(1)).unwrap().1;
+    let second = queries.recv_timeout(Duration::from_secs(1)).unwrap().1;
+
+    assert_ne!(first.port(), second.port());
+    assert_eq!(resolver.pending(), 0);
+}
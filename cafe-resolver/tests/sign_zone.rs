@@ -0,0 +1,61 @@
+use std::fs;
+use std::process::Command;
+
+use cafe_dns::{zone, Type};
+
+const ZONE: &str = "\
+$ORIGIN jabber.ru.
+$TTL 3600
+@                   SOA ns1 hostmaster 1 7200 3600 1209600 300
+                    NS  ns1
+                    MX  10 @
+ns1                 A   192.0.2.1
+xmpp                CNAME @
+insecure            NS  ns.insecure
+ns.insecure         A   192.0.2.2   ; glue
+";
+
+#[test]
+fn signs_master_file() {
+    let directory = std::env::temp_dir().join(format!("cafe-resolver-sign-{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    let zone_file = directory.join("jabber.ru.zone");
+    let signed_file = directory.join("jabber.ru.zone.signed");
+    fs::write(&zone_file, ZONE).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_cafe-resolver"))
+        .arg("sign-zone")
+        .arg(&zone_file)
+        .arg("--key-directory")
+        .arg(&directory)
+        .arg("--output")
+        .arg(&signed_file)
+        .status()
+        .unwrap();
+    let signed = zone::load(&signed_file);
+    let keys = fs::read_dir(&directory)
+        .unwrap()
+        .filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().starts_with("Kjabber.ru."))
+        .count();
+    fs::remove_dir_all(&directory).unwrap();
+
+    assert!(status.success());
+    assert_eq!(keys, 4);
+
+    let signed = signed.unwrap();
+    let covered: Vec<(&str, u16)> = signed
+        .iter()
+        .filter_map(|record| match record.ttype() {
+            Type::RRSIG { type_covered, .. } => Some((record.name(), *type_covered)),
+            _ => None,
+        })
+        .collect();
+    assert!(covered.contains(&("jabber.ru", 2)) && covered.contains(&("jabber.ru", 15)) && covered.contains(&("xmpp.jabber.ru", 5)));
+    assert!(!covered.contains(&("insecure.jabber.ru", 2)) && !covered.iter().any(|(name, _)| *name == "ns.insecure.jabber.ru"));
+
+    let delegation = signed
+        .iter()
+        .find(|record| record.name() == "insecure.jabber.ru" && record.ttype().code() == 47)
+        .unwrap();
+    assert_eq!(delegation.ttype(), &Type::NSEC { next_domain_name: "ns1.jabber.ru".to_string(), types: vec![2, 46, 47] });
+}
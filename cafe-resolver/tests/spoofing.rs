@@ -0,0 +1,55 @@
+mod common;
+
+use std::net::{SocketAddr, UdpSocket};
+use std::thread;
+use std::time::Duration;
+
+use cafe_resolver::{Config, RecordVariant, Resolver};
+
+/// Answers the first query with a burst of datagrams that must all be ignored,
+/// followed by the genuine answer with 192.0.2.1.
+fn spawn_spoofing_server() -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let off_path = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let addr = socket.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let mut buffer = [0; 512];
+        let (size, peer) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(_) => return,
+        };
+
+        let query = &buffer[.. size];
+        let forged = common::reply(query, 0, &[common::a_record([198, 51, 100, 66], 86400)]);
+
+        let mut other_id = forged.clone();
+        other_id[0] ^= 0x5a;
+        let mut not_response = forged.clone();
+        not_response[2] &= 0x7f;
+        let mut other_name = forged.clone();
+        other_name[13] = b'x';
+
+        let _ = off_path.send_to(&forged, peer);
+        for datagram in [other_id, not_response, other_name, forged[.. 7].to_vec()].iter() {
+            let _ = socket.send_to(datagram, peer);
+        }
+
+        let _ = socket.send_to(&common::reply(query, 0, &[common::a_record([192, 0, 2, 1], 60)]), peer);
+    });
+
+    addr
+}
+
+#[test]
+fn forged_responses_ignored() {
+    let mut config = Config::new();
+    config.set_server(spawn_spoofing_server());
+    let mut resolver = Resolver::with_config(config);
+
+    match resolver.get_a_records("jabber.ru").unwrap().as_slice() {
+        [RecordVariant::A { ip, ttl: 60, .. }] => assert_eq!(ip.to_string(), "192.0.2.1"),
+        other => panic!("Unexpected records: {:?}", other),
+    }
+}
@@ -0,0 +1,302 @@
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use cafe_dns::{Edns, EdnsOption, Response as DnsResponse};
+use cafe_resolver::{Config, EventResolver, Resolver};
+
+/// Answers over UDP with the first of three A records and TC set,
+/// and with all of them over TCP. Returns the counters of the queries of both.
+fn spawn_truncating_server() -> (SocketAddr, Arc<AtomicUsize>, Arc<AtomicUsize>) {
+    let udp_queries = Arc::new(AtomicUsize::new(0));
+    let tcp_queries = Arc::new(AtomicUsize::new(0));
+    let (udp_counter, tcp_counter) = (udp_queries.clone(), tcp_queries.clone());
+
+    let server = common::spawn_server(
+        move |query| {
+            udp_counter.fetch_add(1, Ordering::SeqCst);
+            let mut response = common::reply(query, 0, &[common::a_record([192, 0, 2, 1], 60)]);
+            common::truncate(&mut response);
+            Some(response)
+        },
+        move |query| {
+            tcp_counter.fetch_add(1, Ordering::SeqCst);
+            let answers: Vec<_> = (1 ..= 3).map(|i| common::a_record([192, 0, 2, i], 60)).collect();
+            vec![common::reply(query, 0, &answers)]
+        },
+    );
+
+    (server, udp_queries, tcp_queries)
+}
+
+#[derive(Default)]
+struct Counters {
+    connections: AtomicUsize,
+    queries: AtomicUsize,
+    /// Queries that came without edns-tcp-keepalive.
+    without_keepalive: AtomicUsize,
+}
+
+fn read_query(connection: &mut TcpStream) -> Option<Vec<u8>> {
+    let mut length = [0; 2];
+    connection.read_exact(&mut length).ok()?;
+    let mut query = vec![0; u16::from_be_bytes(length) as usize];
+    connection.read_exact(&mut query).ok()?;
+    Some(query)
+}
+
+/// Keeps TCP connections open, answering the queries that come close together in reverse order,
+/// with `keepalive` announced in the responses. The first connection is closed without answering
+/// if `drop_first` is set.
+fn spawn_persistent_server(keepalive: u16, drop_first: bool) -> (SocketAddr, Arc<Counters>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let counters = Arc::new(Counters::default());
+    let server_counters = counters.clone();
+
+    thread::spawn(move || {
+        for connection in listener.incoming() {
+            let mut connection = match connection {
+                Ok(connection) => connection,
+                Err(_) => break,
+            };
+
+            let counters = server_counters.clone();
+            let number = counters.connections.fetch_add(1, Ordering::SeqCst);
+            thread::spawn(move || loop {
+                connection.set_read_timeout(None).unwrap();
+                let mut queries = match read_query(&mut connection) {
+                    Some(query) => vec![query],
+                    None => return,
+                };
+
+                connection.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+                while let Some(query) = read_query(&mut connection) {
+                    queries.push(query);
+                }
+
+                counters.queries.fetch_add(queries.len(), Ordering::SeqCst);
+                if number == 0 && drop_first {
+                    return;
+                }
+
+                for query in queries.iter().rev() {
+                    let edns = DnsResponse::decode(query).unwrap().edns().cloned().unwrap();
+                    if !edns.options().contains(&EdnsOption::TcpKeepalive(None)) {
+                        counters.without_keepalive.fetch_add(1, Ordering::SeqCst);
+                    }
+
+                    let mut response = common::reply(query, 0, &[common::a_record([192, 0, 2, 1], 60)]);
+                    let mut edns = Edns::new();
+                    edns.add_option(EdnsOption::TcpKeepalive(Some(keepalive)));
+                    common::append_edns(&mut response, &edns);
+
+                    let mut message = (response.len() as u16).to_be_bytes().to_vec();
+                    message.extend_from_slice(&response);
+                    if connection.write_all(&message).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+
+    (addr, counters)
+}
+
+fn tcp_config(server: SocketAddr) -> Config {
+    let mut config = Config::new();
+    config.set_server(server);
+    config.set_force_tcp(true);
+    config
+}
+
+#[test]
+fn truncated_answer_retried_over_tcp() {
+    let (server, udp_queries, tcp_queries) = spawn_truncating_server();
+    let mut config = Config::new();
+    config.set_server(server);
+    let mut resolver = Resolver::with_config(config);
+
+    assert_eq!(resolver.get_a_records("jabber.ru").unwrap().len(), 3);
+    assert_eq!(udp_queries.load(Ordering::SeqCst), 1);
+    assert_eq!(tcp_queries.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn forced_tcp() {
+    let (server, udp_queries, tcp_queries) = spawn_truncating_server();
+    let mut config = Config::new();
+    config.set_server(server);
+    config.set_force_tcp(true);
+    let mut resolver = Resolver::with_config(config);
+
+    assert_eq!(resolver.get_a_records("jabber.ru").unwrap().len(), 3);
+    assert_eq!(resolver.get_a_records("jabber.org").unwrap().len(), 3);
+    assert_eq!(udp_queries.load(Ordering::SeqCst), 0);
+    assert_eq!(tcp_queries.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn event_resolver_retries_over_tcp() {
+    let (server, _, tcp_queries) = spawn_truncating_server();
+    let mut config = Config::new();
+    config.set_server(server);
+    let mut resolver = EventResolver::with_config(config).unwrap();
+
+    let found = Arc::new(AtomicUsize::new(0));
+    let counter = found.clone();
+    resolver
+        .get_a_records("jabber.ru", move |result| counter.store(result.unwrap().len(), Ordering::SeqCst))
+        .unwrap();
+
+    // A host loop would wait on the socket for that long.
+    while let Some(timeout) = resolver.next_timeout() {
+        thread::sleep(timeout.min(Duration::from_millis(10)));
+        resolver.process_events();
+    }
+
+    assert_eq!(found.load(Ordering::SeqCst), 3);
+    assert_eq!(tcp_queries.load(Ordering::SeqCst), 1);
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn async_resolver_retries_over_tcp() {
+    let (server, _, tcp_queries) = spawn_truncating_server();
+    let mut config = Config::new();
+    config.set_server(server);
+    let resolver = cafe_resolver::AsyncResolver::with_config(config).unwrap();
+
+    let (first, second) = tokio::join!(resolver.get_a_records("jabber.ru"), resolver.get_a_records("jabber.org"));
+    assert_eq!(first.unwrap().len(), 3);
+    assert_eq!(second.unwrap().len(), 3);
+    assert_eq!(tcp_queries.load(Ordering::SeqCst), 2);
+    assert_eq!(resolver.pending(), 0);
+}
+
+#[test]
+fn connection_reused() {
+    let (server, counters) = spawn_persistent_server(100, false);
+    let mut resolver = Resolver::with_config(tcp_config(server));
+
+    for host in ["jabber.ru", "jabber.org", "xmpp.org"].iter() {
+        assert_eq!(resolver.get_a_records(host).unwrap().len(), 1);
+    }
+
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 1);
+    assert_eq!(counters.queries.load(Ordering::SeqCst), 3);
+    assert_eq!(counters.without_keepalive.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn reconnected_when_closed() {
+    let (server, counters) = spawn_persistent_server(100, true);
+    let mut resolver = Resolver::with_config(tcp_config(server));
+
+    assert_eq!(resolver.get_a_records("jabber.ru").unwrap().len(), 1);
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 2);
+    assert_eq!(counters.queries.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn idle_connection_closed() {
+    // The server asks for 100 ms, less than the configured timeout.
+    let (server, counters) = spawn_persistent_server(1, false);
+    let mut resolver = Resolver::with_config(tcp_config(server));
+
+    assert_eq!(resolver.get_a_records("jabber.ru").unwrap().len(), 1);
+    assert_eq!(resolver.get_a_records("jabber.org").unwrap().len(), 1);
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 1);
+
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(resolver.get_a_records("xmpp.org").unwrap().len(), 1);
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn event_resolver_pipelines() {
+    let (server, counters) = spawn_persistent_server(100, true);
+    let mut resolver = EventResolver::with_config(tcp_config(server)).unwrap();
+
+    let found = Arc::new(AtomicUsize::new(0));
+    for host in ["jabber.ru", "jabber.org", "xmpp.org"].iter() {
+        let counter = found.clone();
+        resolver
+            .get_a_records(host, move |result| {
+                counter.fetch_add(result.unwrap().len(), Ordering::SeqCst);
+            })
+            .unwrap();
+    }
+
+    while let Some(timeout) = resolver.next_timeout() {
+        thread::sleep(timeout.min(Duration::from_millis(10)));
+        resolver.process_events();
+    }
+
+    assert_eq!(found.load(Ordering::SeqCst), 3);
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn event_resolver_woken_by_connection() {
+    use std::os::unix::io::AsRawFd;
+    use std::time::Instant;
+
+    use mio::unix::SourceFd;
+    use mio::{Events, Interest, Poll, Token};
+
+    let (server, _) = spawn_persistent_server(100, false);
+    let mut resolver = EventResolver::with_config(tcp_config(server)).unwrap();
+    let found = Arc::new(AtomicUsize::new(0));
+    let counter = found.clone();
+    resolver.get_a_records("jabber.ru", move |result| counter.store(result.unwrap().len(), Ordering::SeqCst)).unwrap();
+
+    // Nothing but the descriptor tells when the response comes over the connection.
+    assert!(resolver.next_timeout().unwrap() > Duration::from_secs(1));
+
+    // The host loop registers the descriptor once and sleeps until it is readable.
+    let mut poll = Poll::new().unwrap();
+    let descriptor = resolver.as_raw_fd();
+    poll.registry().register(&mut SourceFd(&descriptor), Token(0), Interest::READABLE).unwrap();
+    let mut events = Events::with_capacity(4);
+    let started = Instant::now();
+    while let Some(timeout) = resolver.next_timeout() {
+        poll.poll(&mut events, Some(timeout)).unwrap();
+        resolver.process_events();
+    }
+
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(found.load(Ordering::SeqCst), 1);
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn async_resolver_pipelines() {
+    let (server, counters) = spawn_persistent_server(100, true);
+    let resolver = cafe_resolver::AsyncResolver::with_config(tcp_config(server)).unwrap();
+
+    let lookups: Vec<_> = (0 .. 10)
+        .map(|i| {
+            let resolver = resolver.clone();
+            tokio::spawn(async move { resolver.get_a_records(&format!("host{}.jabber.ru", i)).await })
+        })
+        .collect();
+
+    for lookup in lookups {
+        assert_eq!(lookup.await.unwrap().unwrap().len(), 1);
+    }
+
+    // The first connection is dropped by the server, the second one carries everything.
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 2);
+    assert_eq!(resolver.pending(), 0);
+
+    assert_eq!(resolver.get_a_records("jabber.ru").await.unwrap().len(), 1);
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cafe_resolver::{Config, EventResolver, ResolveError, Resolver};
+
+/// Drops the first `dropped` queries it receives and answers the rest,
+/// recording the message IDs of all of them.
+fn spawn_dropping_server(dropped: usize) -> (SocketAddr, Arc<Mutex<Vec<u16>>>) {
+    let ids = Arc::new(Mutex::new(Vec::new()));
+    let seen = ids.clone();
+
+    let addr = common::spawn_udp_server(move |query| {
+        let mut seen = seen.lock().unwrap();
+        seen.push(u16::from_be_bytes([query[0], query[1]]));
+        match seen.len() > dropped {
+            true => Some(common::reply(query, 0, &[common::a_record([192, 0, 2, 1], 60)])),
+            false => None,
+        }
+    });
+
+    (addr, ids)
+}
+
+fn config(server: SocketAddr, timeout: Duration) -> Config {
+    let mut config = Config::new();
+    config.set_server(server);
+    config.set_attempt_timeout(Duration::from_millis(50));
+    config.set_timeout(timeout);
+    config
+}
+
+#[test]
+fn retransmitted_until_answered() {
+    let (server, ids) = spawn_dropping_server(2);
+    let mut resolver = Resolver::with_config(config(server, Duration::from_secs(3)));
+
+    let records = resolver.get_a_records("jabber.ru").unwrap();
+    assert_eq!(records.len(), 1);
+
+    // Retransmissions repeat the query as it was.
+    let ids = ids.lock().unwrap();
+    assert_eq!(ids.len(), 3);
+    assert_eq!(ids.iter().collect::<BTreeSet<_>>().len(), 1);
+}
+
+#[test]
+fn overall_timeout() {
+    let (server, ids) = spawn_dropping_server(usize::MAX);
+    let mut resolver = Resolver::with_config(config(server, Duration::from_millis(400)));
+
+    let started = Instant::now();
+    assert!(matches!(resolver.get_a_records("jabber.ru"), Err(ResolveError::Timeout)));
+    let elapsed = started.elapsed();
+    assert!(elapsed >= Duration::from_millis(400) && elapsed < Duration::from_secs(2), "{:?}", elapsed);
+
+    // 50, 100 and 200 ms attempts fit, with some jitter.
+    let attempts = ids.lock().unwrap().len();
+    assert!((3 ..= 4).contains(&attempts), "{} attempts", attempts);
+    assert_eq!(ResolveError::Timeout.to_string(), "timed out");
+}
+
+#[test]
+fn event_resolver_retransmits() {
+    let (server, ids) = spawn_dropping_server(1);
+    let mut resolver = EventResolver::with_config(config(server, Duration::from_secs(3))).unwrap();
+
+    let result = Rc::new(RefCell::new(None));
+    let sink = result.clone();
+    resolver.get_a_records("jabber.ru", move |records| *sink.borrow_mut() = Some(records)).unwrap();
+
+    let started = Instant::now();
+    while resolver.pending() > 0 {
+        assert!(started.elapsed() < Duration::from_secs(2), "lookup did not complete");
+        thread::sleep(resolver.next_timeout().unwrap().min(Duration::from_millis(5)));
+        resolver.process_events();
+    }
+
+    assert_eq!(result.borrow_mut().take().unwrap().unwrap().len(), 1);
+    assert_eq!(ids.lock().unwrap().len(), 2);
+}
@@ -0,0 +1,229 @@
+mod common;
+#[path = "common/pki.rs"]
+mod pki;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rcgen::PublicKeyData;
+use ring::digest;
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+
+use cafe_dns::{EdnsOption, Response as DnsResponse};
+use cafe_resolver::tls::spki_pin;
+use cafe_resolver::{Config, EventResolver, Resolver, TlsConfig, Transport};
+
+use pki::Pki;
+
+#[derive(Default)]
+struct Counters {
+    connections: AtomicUsize,
+    queries: AtomicUsize,
+    /// Queries that came without padding to a multiple of 128 octets.
+    unpadded: AtomicUsize,
+    /// Server names the clients asked for in SNI.
+    names: Mutex<Vec<String>>,
+}
+
+fn spawn_tls_server(pki: &Pki) -> (SocketAddr, Arc<Counters>) {
+    spawn_tls_server_with_config(pki::tls_server_config(pki, b"dot"))
+}
+
+/// Answers the queries over TLS with an A record, keeping the connections open.
+fn spawn_tls_server_with_config(config: Arc<ServerConfig>) -> (SocketAddr, Arc<Counters>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let counters = Arc::new(Counters::default());
+    let server_counters = counters.clone();
+
+    thread::spawn(move || {
+        for connection in listener.incoming() {
+            let connection = match connection {
+                Ok(connection) => connection,
+                Err(_) => break,
+            };
+
+            let counters = server_counters.clone();
+            counters.connections.fetch_add(1, Ordering::SeqCst);
+            let mut stream = StreamOwned::new(ServerConnection::new(config.clone()).unwrap(), connection);
+            thread::spawn(move || loop {
+                let mut length = [0; 2];
+                if stream.read_exact(&mut length).is_err() {
+                    return;
+                }
+
+                if counters.queries.fetch_add(1, Ordering::SeqCst) == 0 {
+                    let name = stream.conn.server_name().unwrap_or_default().to_string();
+                    counters.names.lock().unwrap().push(name);
+                }
+
+                let mut query = vec![0; u16::from_be_bytes(length) as usize];
+                if stream.read_exact(&mut query).is_err() {
+                    return;
+                }
+
+                let edns = DnsResponse::decode(&query).unwrap().edns().cloned().unwrap();
+                let padded = edns.options().iter().any(|option| matches!(option, EdnsOption::Padding(_)));
+                if !padded || !query.len().is_multiple_of(128) {
+                    counters.unpadded.fetch_add(1, Ordering::SeqCst);
+                }
+
+                let response = common::reply(&query, 0, &[common::a_record([192, 0, 2, 1], 60)]);
+                let mut message = (response.len() as u16).to_be_bytes().to_vec();
+                message.extend_from_slice(&response);
+                if stream.write_all(&message).and_then(|_| stream.flush()).is_err() {
+                    return;
+                }
+            });
+        }
+    });
+
+    (addr, counters)
+}
+
+fn tls_config(server: SocketAddr, pki: &Pki, name: &str) -> (Config, TlsConfig) {
+    let mut tls = TlsConfig::new(name);
+    tls.add_root_certificate(&pki.ca);
+
+    let mut config = Config::new();
+    config.set_server_with_transport(server, Transport::Tls(tls.clone()));
+    config.set_timeout(Duration::from_secs(2));
+    (config, tls)
+}
+
+#[test]
+fn connection_reused() {
+    let pki = pki::pki();
+    let (server, counters) = spawn_tls_server(&pki);
+    let (config, _) = tls_config(server, &pki, "dns.example");
+    let mut resolver = Resolver::with_config(config);
+
+    for host in ["jabber.ru", "jabber.org", "xmpp.org"].iter() {
+        assert_eq!(resolver.get_a_records(host).unwrap().len(), 1);
+    }
+
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 1);
+    assert_eq!(counters.queries.load(Ordering::SeqCst), 3);
+    assert_eq!(counters.unpadded.load(Ordering::SeqCst), 0);
+    assert_eq!(*counters.names.lock().unwrap(), vec!["dns.example".to_string()]);
+}
+
+#[test]
+fn server_changed_over_tls() {
+    let pki = pki::pki();
+    let (server, counters) = spawn_tls_server(&pki);
+    let (mut config, _) = tls_config(SocketAddr::from(([127, 0, 0, 1], 1)), &pki, "dns.example");
+    config.set_server(server);
+    assert!(matches!(config.transport(), Transport::Tls(_)));
+
+    let mut resolver = Resolver::with_config(config);
+    assert_eq!(resolver.get_a_records("jabber.ru").unwrap().len(), 1);
+    assert_eq!(counters.queries.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn padding_disabled() {
+    let pki = pki::pki();
+    let (server, counters) = spawn_tls_server(&pki);
+    let (mut config, mut tls) = tls_config(server, &pki, "dns.example");
+    tls.set_padding(false);
+    config.set_server_with_transport(server, Transport::Tls(tls));
+    let mut resolver = Resolver::with_config(config);
+
+    assert_eq!(resolver.get_a_records("jabber.ru").unwrap().len(), 1);
+    assert_eq!(counters.unpadded.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn wrong_name_rejected() {
+    let pki = pki::pki();
+    let (server, counters) = spawn_tls_server(&pki);
+    let (config, _) = tls_config(server, &pki, "other.example");
+    let mut resolver = Resolver::with_config(config);
+
+    assert!(resolver.get_a_records("jabber.ru").is_err());
+    assert_eq!(counters.queries.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn spki_pins() {
+    let pki = pki::pki();
+    let pin = spki_pin(&pki.certificate).unwrap();
+    assert_eq!(pin[..], *digest::digest(&digest::SHA256, &pki.key.subject_public_key_info()).as_ref());
+
+    let (server, counters) = spawn_tls_server(&pki);
+    let (mut config, mut tls) = tls_config(server, &pki, "dns.example");
+    tls.add_spki_pin([0; 32]);
+    config.set_server_with_transport(server, Transport::Tls(tls.clone()));
+    let mut resolver = Resolver::with_config(config.clone());
+    assert!(resolver.get_a_records("jabber.ru").is_err());
+    assert_eq!(counters.queries.load(Ordering::SeqCst), 0);
+
+    tls.add_spki_pin(pin);
+    config.set_server_with_transport(server, Transport::Tls(tls));
+    let mut resolver = Resolver::with_config(config);
+    assert_eq!(resolver.get_a_records("jabber.ru").unwrap().len(), 1);
+    assert_eq!(counters.queries.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn spki_pin_of_extra_certificate_ignored() {
+    // The server sends the pinned certificate along with its own, outside the validated path.
+    let (pki, pinned) = (pki::pki(), pki::pki());
+    let server_config = pki::tls_server_config_with_chain(&pki, std::slice::from_ref(&pinned.certificate), b"dot");
+    let (server, counters) = spawn_tls_server_with_config(server_config);
+    let (mut config, mut tls) = tls_config(server, &pki, "dns.example");
+    tls.add_spki_pin(spki_pin(&pinned.certificate).unwrap());
+    config.set_server_with_transport(server, Transport::Tls(tls));
+
+    let mut resolver = Resolver::with_config(config);
+    assert!(resolver.get_a_records("jabber.ru").is_err());
+    assert_eq!(counters.queries.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn event_resolver_over_tls() {
+    let pki = pki::pki();
+    let (server, counters) = spawn_tls_server(&pki);
+    let (config, _) = tls_config(server, &pki, "dns.example");
+    let mut resolver = EventResolver::with_config(config).unwrap();
+
+    let found = Arc::new(AtomicUsize::new(0));
+    for host in ["jabber.ru", "jabber.org"].iter() {
+        let counter = found.clone();
+        resolver
+            .get_a_records(host, move |result| {
+                counter.fetch_add(result.unwrap().len(), Ordering::SeqCst);
+            })
+            .unwrap();
+    }
+
+    while let Some(timeout) = resolver.next_timeout() {
+        thread::sleep(timeout.min(Duration::from_millis(10)));
+        resolver.process_events();
+    }
+
+    assert_eq!(found.load(Ordering::SeqCst), 2);
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 1);
+    assert_eq!(counters.unpadded.load(Ordering::SeqCst), 0);
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn async_resolver_over_tls() {
+    let pki = pki::pki();
+    let (server, counters) = spawn_tls_server(&pki);
+    let (config, _) = tls_config(server, &pki, "dns.example");
+    let resolver = cafe_resolver::AsyncResolver::with_config(config).unwrap();
+
+    let (first, second) = tokio::join!(resolver.get_a_records("jabber.ru"), resolver.get_a_records("jabber.org"));
+    assert_eq!(first.unwrap().len(), 1);
+    assert_eq!(second.unwrap().len(), 1);
+    assert_eq!(resolver.get_a_records("xmpp.org").await.unwrap().len(), 1);
+    assert_eq!(counters.connections.load(Ordering::SeqCst), 1);
+    assert_eq!(counters.queries.load(Ordering::SeqCst), 3);
+}
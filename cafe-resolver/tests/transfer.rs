@@ -0,0 +1,362 @@
+mod common;
+
+use std::net::{Ipv4Addr, TcpListener};
+use std::time::{Duration, Instant};
+
+use cafe_common::stream::Output as OutputStream;
+use cafe_dns::tsig::{self, Algorithm, Key, Signer, TsigError, Verifier};
+use cafe_dns::{QType, ResourceRecord, Response as DnsResponse, ResponseCode, Type};
+use cafe_resolver::{Config, Ixfr, ResolveError, Resolver};
+
+const SECRET: &[u8] = b"secret shared with the primary server";
+
+fn soa(serial: u32) -> ResourceRecord {
+    let soa = Type::SOA {
+        mname: "ns1.jabber.ru".to_string(),
+        rname: "hostmaster.jabber.ru".to_string(),
+        serial,
+        refresh: 7200,
+        retry: 3600,
+        expire: 1209600,
+        minimum: 300,
+    };
+
+    ResourceRecord::new("jabber.ru", 1, 3600, soa)
+}
+
+fn a(name: &str, ip: [u8; 4]) -> ResourceRecord {
+    ResourceRecord::new(name, 1, 300, Type::A { ip: Ipv4Addr::from(ip) })
+}
+
+fn srv(name: &str, port: u16, target: &str) -> ResourceRecord {
+    let srv = Type::SRV {
+        priority: 0,
+        weight: 5,
+        port,
+        target: target.to_string(),
+    };
+
+    ResourceRecord::new(name, 1, 300, srv)
+}
+
+fn zone() -> Vec<ResourceRecord> {
+    vec![
+        soa(3),
+        ResourceRecord::new("jabber.ru", 1, 3600, Type::NS { nsdname: "ns1.jabber.ru".to_string() }),
+        a("ns1.jabber.ru", [192, 0, 2, 1]),
+        a("jabber.ru", [192, 0, 2, 10]),
+        srv("_xmpp-client._tcp.jabber.ru", 5222, "jabber.ru"),
+        srv("_xmpp-server._tcp.jabber.ru", 5269, "jabber.ru"),
+    ]
+}
+
+/// Response message to `query` carrying `records`, without the question if `question` is unset.
+fn message(query: &[u8], rcode: u8, records: &[ResourceRecord], question: bool) -> Vec<u8> {
+    let answers: Vec<Vec<u8>> = records
+        .iter()
+        .map(|record| {
+            let mut data = Vec::new();
+            record.encode(&mut OutputStream::new(&mut data));
+            data
+        })
+        .collect();
+
+    let mut message = common::reply(query, rcode, &answers);
+    if !question {
+        let question_end = 12 + "jabber.ru".len() + 2 + 4;
+        message.drain(12 .. question_end);
+        message[4 .. 6].copy_from_slice(&[0, 0]);
+    }
+
+    message
+}
+
+fn resolver_for<F>(handler: F) -> Resolver
+where
+    F: Fn(&[u8]) -> Vec<Vec<u8>> + Send + 'static,
+{
+    let mut config = Config::new();
+    config.set_server(common::spawn_tcp_server(handler));
+    Resolver::with_config(config)
+}
+
+fn key(secret: &[u8]) -> Key {
+    Key::new("transfer.jabber.ru", Algorithm::HmacSha256, secret)
+}
+
+#[test]
+fn axfr_in_several_messages() {
+    let mut resolver = resolver_for(|query| {
+        let request = DnsResponse::decode(query).unwrap();
+        assert!(matches!(request.questions()[0].qtype(), QType::AXFR));
+
+        let zone = zone();
+        vec![
+            message(query, 0, &zone[.. 2], true),
+            message(query, 0, &zone[2 .. 5], false),
+            message(query, 0, &[zone[5].clone(), soa(3)], false),
+        ]
+    });
+
+    let records: Vec<ResourceRecord> = resolver.axfr("jabber.ru", None).unwrap().map(Result::unwrap).collect();
+    assert_eq!(records, zone());
+
+    let lines: Vec<String> = records.iter().map(ResourceRecord::to_string).collect();
+    assert_eq!(lines[0], "jabber.ru.\t3600\tIN\tSOA\tns1.jabber.ru. hostmaster.jabber.ru. 3 7200 3600 1209600 300");
+    assert_eq!(lines[1], "jabber.ru.\t3600\tIN\tNS\tns1.jabber.ru.");
+    assert_eq!(lines[2], "ns1.jabber.ru.\t300\tIN\tA\t192.0.2.1");
+    assert_eq!(lines[4], "_xmpp-client._tcp.jabber.ru.\t300\tIN\tSRV\t0 5 5222 jabber.ru.");
+}
+
+#[test]
+fn axfr_signed() {
+    let mut resolver = resolver_for(|query| {
+        let server_key = key(SECRET);
+        let tsig = Verifier::new(&server_key).verify(query, tsig::current_time()).unwrap().unwrap();
+        let mut signer = Signer::for_response(&server_key, tsig.mac());
+
+        let zone = zone();
+        let mut messages = vec![
+            message(query, 0, &zone[.. 2], true),
+            message(query, 0, &zone[2 .. 5], false),
+            message(query, 0, &[zone[5].clone(), soa(3)], false),
+        ];
+
+        signer.sign(&mut messages[0], tsig::current_time()).unwrap();
+        signer.add_unsigned(&messages[1]).unwrap();
+        signer.sign(&mut messages[2], tsig::current_time()).unwrap();
+        messages
+    });
+
+    let client_key = key(SECRET);
+    let transfer = resolver.axfr("jabber.ru", Some(&client_key)).unwrap();
+    let records: Result<Vec<ResourceRecord>, ResolveError> = transfer.collect();
+    assert_eq!(records.unwrap(), zone());
+}
+
+#[test]
+fn axfr_with_unsigned_end() {
+    let mut resolver = resolver_for(|query| {
+        let server_key = key(SECRET);
+        let tsig = Verifier::new(&server_key).verify(query, tsig::current_time()).unwrap().unwrap();
+        let mut signer = Signer::for_response(&server_key, tsig.mac());
+
+        let zone = zone();
+        let mut messages = vec![message(query, 0, &zone[.. 2], true), message(query, 0, &[soa(3)], false)];
+        signer.sign(&mut messages[0], tsig::current_time()).unwrap();
+        messages
+    });
+
+    let client_key = key(SECRET);
+    let records: Vec<_> = resolver.axfr("jabber.ru", Some(&client_key)).unwrap().collect();
+    assert_eq!(records.len(), 3);
+    assert!(records[.. 2].iter().all(Result::is_ok));
+    match &records[2] {
+        Err(ResolveError::TsigFailed(TsigError::Unsigned)) => (),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn axfr_with_forged_unsigned_message() {
+    let mut resolver = resolver_for(|query| {
+        let server_key = key(SECRET);
+        let tsig = Verifier::new(&server_key).verify(query, tsig::current_time()).unwrap().unwrap();
+        let mut signer = Signer::for_response(&server_key, tsig.mac());
+
+        let zone = zone();
+        let mut messages = vec![
+            message(query, 0, &zone[.. 2], true),
+            message(query, 0, &zone[2 .. 5], false),
+            message(query, 0, &[zone[5].clone(), soa(3)], false),
+        ];
+
+        // The MAC of the last message doesn't cover the one inserted before it.
+        signer.sign(&mut messages[0], tsig::current_time()).unwrap();
+        signer.sign(&mut messages[2], tsig::current_time()).unwrap();
+        messages
+    });
+
+    let client_key = key(SECRET);
+    let records: Vec<_> = resolver.axfr("jabber.ru", Some(&client_key)).unwrap().collect();
+    assert_eq!(records.len(), 3);
+    assert!(records[.. 2].iter().all(Result::is_ok));
+    match &records[2] {
+        Err(ResolveError::TsigFailed(TsigError::BadSig)) => (),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn axfr_timeout() {
+    // Connections are accepted by the kernel but nothing is ever answered.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut config = Config::new();
+    config.set_server(listener.local_addr().unwrap());
+    config.set_timeout(Duration::from_millis(200));
+    let mut resolver = Resolver::with_config(config);
+
+    let started = Instant::now();
+    let mut transfer = resolver.axfr("jabber.ru", None).unwrap();
+    assert!(matches!(transfer.next(), Some(Err(ResolveError::Timeout))));
+    assert!(transfer.next().is_none());
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn axfr_refused() {
+    let mut resolver = resolver_for(|query| vec![message(query, 5, &[], true)]);
+
+    let mut transfer = resolver.axfr("jabber.ru", None).unwrap();
+    match transfer.next() {
+        Some(Err(ResolveError::DnsError(rcode, _))) => assert_eq!(rcode, ResponseCode::Refused),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+
+    assert!(transfer.next().is_none());
+}
+
+#[test]
+fn transfer_of_absolute_zone_name() {
+    let mut resolver = resolver_for(|query| vec![message(query, 0, &[zone(), vec![soa(3)]].concat(), true)]);
+    let records: Vec<ResourceRecord> = resolver.axfr("jabber.ru.", None).unwrap().map(Result::unwrap).collect();
+    assert_eq!(records, zone());
+
+    let mut resolver = resolver_for(ixfr_server);
+    let differences = match resolver.ixfr("jabber.ru.", 1, None).unwrap().changes().unwrap() {
+        Ixfr::Differences(differences) => differences,
+        other => panic!("Unexpected result: {:?}", other),
+    };
+
+    assert_eq!(differences.len(), 2);
+}
+
+#[test]
+fn axfr_with_compressed_names() {
+    let mut resolver = resolver_for(|query| {
+        let encoded = |record: &ResourceRecord| {
+            let mut data = Vec::new();
+            record.encode(&mut OutputStream::new(&mut data));
+            data
+        };
+
+        // Names point back to jabber.ru of the question at offset 12.
+        let record = |owner: &[u8], code: u16, rdata: &[u8]| {
+            let mut data = owner.to_vec();
+            data.extend_from_slice(&code.to_be_bytes());
+            data.extend_from_slice(&[0, 1, 0, 0, 0x0e, 0x10]);
+            data.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            data.extend_from_slice(rdata);
+            data
+        };
+
+        vec![common::reply(query, 0, &[
+            encoded(&soa(3)),
+            record(&[0xc0, 0x0c], 2, b"\x03ns1\xc0\x0c"),
+            record(b"\x04xmpp\xc0\x0c", 5, &[0xc0, 0x0c]),
+            record(&[0xc0, 0x0c], 15, b"\x00\x0a\x04xmpp\xc0\x0c"),
+            record(&[0xc0, 0x0c], 7, b"\x04mail\xc0\x0c"),
+            encoded(&soa(3)),
+        ])]
+    });
+
+    let records: Vec<ResourceRecord> = resolver.axfr("jabber.ru", None).unwrap().map(Result::unwrap).collect();
+    assert_eq!(records.len(), 5);
+    assert_eq!(records[1].ttype(), &Type::NS { nsdname: "ns1.jabber.ru".to_string() });
+    assert_eq!(records[2].name(), "xmpp.jabber.ru");
+    assert_eq!(records[2].ttype(), &Type::CNAME { cname: "jabber.ru".to_string() });
+    assert_eq!(records[3].ttype(), &Type::MX { preference: 10, exchange: "xmpp.jabber.ru".to_string() });
+    assert_eq!(records[4].ttype(), &Type::Unknown { code: 7, data: b"\x04mail\x06jabber\x02ru\x00".to_vec() });
+}
+
+#[test]
+fn axfr_interrupted() {
+    let mut resolver = resolver_for(|query| vec![message(query, 0, &zone()[.. 3], true)]);
+
+    let records: Vec<_> = resolver.axfr("jabber.ru", None).unwrap().collect();
+    assert_eq!(records.len(), 4);
+    assert!(matches!(records[3], Err(ResolveError::TransportFailed)));
+}
+
+#[test]
+fn axfr_without_soa() {
+    let mut resolver = resolver_for(|query| vec![message(query, 0, &zone()[1 ..], true)]);
+
+    let records: Vec<_> = resolver.axfr("jabber.ru", None).unwrap().collect();
+    assert!(matches!(records[..], [Err(ResolveError::DecodeFailed)]));
+}
+
+/// Server at version 3 of the zone with the history of RFC 1995 style changes.
+fn ixfr_server(query: &[u8]) -> Vec<Vec<u8>> {
+    let request = DnsResponse::decode(query).unwrap();
+    assert!(matches!(request.questions()[0].qtype(), QType::IXFR));
+
+    let serial = match request.authorities()[0].ttype() {
+        Type::SOA { serial, .. } => *serial,
+        other => panic!("Unexpected authority: {:?}", other),
+    };
+
+    match serial {
+        1 => vec![
+            message(
+                query,
+                0,
+                &[soa(3), soa(1), a("jabber.ru", [192, 0, 2, 9]), soa(2), a("jabber.ru", [192, 0, 2, 10])],
+                true,
+            ),
+            message(
+                query,
+                0,
+                &[soa(2), soa(3), srv("_xmpp-server._tcp.jabber.ru", 5269, "jabber.ru"), soa(3)],
+                false,
+            ),
+        ],
+        3 => vec![message(query, 0, &[soa(3)], true)],
+        _ => {
+            let mut zone = zone();
+            zone.push(soa(3));
+            vec![message(query, 0, &zone, true)]
+        }
+    }
+}
+
+#[test]
+fn ixfr_differences() {
+    let mut resolver = resolver_for(ixfr_server);
+
+    let differences = match resolver.ixfr("jabber.ru", 1, None).unwrap().changes().unwrap() {
+        Ixfr::Differences(differences) => differences,
+        other => panic!("Unexpected result: {:?}", other),
+    };
+
+    assert_eq!(differences.len(), 2);
+    assert_eq!(differences[0].from(), &soa(1));
+    assert_eq!(differences[0].to(), &soa(2));
+    assert_eq!(differences[0].deleted(), &[a("jabber.ru", [192, 0, 2, 9])]);
+    assert_eq!(differences[0].added(), &[a("jabber.ru", [192, 0, 2, 10])]);
+
+    assert_eq!(differences[1].from(), &soa(2));
+    assert_eq!(differences[1].to(), &soa(3));
+    assert!(differences[1].deleted().is_empty());
+    assert_eq!(differences[1].added(), &[srv("_xmpp-server._tcp.jabber.ru", 5269, "jabber.ru")]);
+}
+
+#[test]
+fn ixfr_up_to_date() {
+    let mut resolver = resolver_for(ixfr_server);
+
+    match resolver.ixfr("jabber.ru", 3, None).unwrap().changes().unwrap() {
+        Ixfr::UpToDate(current) => assert_eq!(current, soa(3)),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn ixfr_whole_zone() {
+    let mut resolver = resolver_for(ixfr_server);
+
+    match resolver.ixfr("jabber.ru", 0, None).unwrap().changes().unwrap() {
+        Ixfr::Zone(records) => assert_eq!(records, zone()),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use std::net::Ipv4Addr;
+
+use cafe_dns::tsig::{self, Algorithm, Key, Signer, TsigError, Verifier};
+use cafe_dns::{QType, Response as DnsResponse, ResponseCode, Type, Update};
+use cafe_resolver::{Config, ResolveError, Resolver};
+
+const SECRET: &[u8] = b"secret shared with the primary server";
+
+fn key(secret: &[u8]) -> Key {
+    Key::new("update.jabber.ru", Algorithm::HmacSha256, secret)
+}
+
+/// Primary server of jabber.ru: checks the update is signed with the shared key if signed
+/// at all and answers with `rcode`, signing the response with `secret`.
+fn resolver_for(secret: &'static [u8], rcode: u8) -> Resolver {
+    let server = common::spawn_udp_server(move |message| {
+        let update = DnsResponse::decode(message)?;
+        if update.header().opcode() != 5 || update.questions()[0].host_name() != "jabber.ru" {
+            return Some(common::reply(message, 1, &[]));
+        }
+
+        let mut response = common::reply(message, rcode, &[]);
+        response[2] = 0x80 | (5 << 3);
+
+        if update.tsig().is_some() {
+            let tsig = match Verifier::new(&key(SECRET)).verify(message, tsig::current_time()) {
+                Ok(Some(tsig)) => tsig,
+                _ => return Some(common::reply(message, 9, &[])),
+            };
+
+            Signer::for_response(&key(secret), tsig.mac()).sign(&mut response, tsig::current_time()).unwrap();
+        }
+
+        Some(response)
+    });
+
+    let mut config = Config::new();
+    config.set_server(server);
+    Resolver::with_config(config)
+}
+
+fn component_update() -> Update {
+    let mut update = Update::new(0, "jabber.ru");
+    update.require_name_absent("upload.jabber.ru");
+    update.delete_rrset("_xmpp-server._tcp.upload.jabber.ru", QType::SRV);
+    update.add("upload.jabber.ru", 300, Type::A { ip: Ipv4Addr::new(192, 0, 2, 10) });
+    update
+}
+
+#[test]
+fn signed_update() {
+    let mut resolver = resolver_for(SECRET, 0);
+    let mut update = component_update();
+
+    resolver.update(&mut update, Some(&key(SECRET))).unwrap();
+    assert_ne!(update.header().id(), 0);
+}
+
+#[test]
+fn unsigned_update() {
+    let mut resolver = resolver_for(SECRET, 0);
+    resolver.update(&mut component_update(), None).unwrap();
+}
+
+#[test]
+fn response_signed_with_other_key() {
+    let mut resolver = resolver_for(b"another secret", 0);
+
+    match resolver.update(&mut component_update(), Some(&key(SECRET))) {
+        Err(ResolveError::TsigFailed(err)) => assert_eq!(err, TsigError::BadSig),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn prerequisite_failed() {
+    let mut resolver = resolver_for(SECRET, 6);
+
+    match resolver.update(&mut component_update(), Some(&key(SECRET))) {
+        Err(ResolveError::DnsError(rcode, errors)) => {
+            assert_eq!(rcode, ResponseCode::YXDomain);
+            assert!(errors.is_empty());
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}